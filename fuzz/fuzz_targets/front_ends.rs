@@ -0,0 +1,10 @@
+//! Feeds arbitrary bytes to the lexer and both parsers, which must never panic.
+//!
+//! Run with `cargo fuzz run front_ends` from the repository root.
+
+#![no_main]
+
+use keynes::generator::feed_front_ends;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| feed_front_ends(data));
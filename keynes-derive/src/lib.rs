@@ -0,0 +1,78 @@
+//! Derive macros for `keynes::convert::IntoKeynes` and `keynes::convert::FromKeynes`.
+//!
+//! Structs with named fields convert to and from hashes keyed by field name.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Ident};
+
+#[proc_macro_derive(IntoKeynes)]
+pub fn derive_into_keynes(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_into_keynes(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+#[proc_macro_derive(FromKeynes)]
+pub fn derive_from_keynes(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_from_keynes(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn named_fields(input: &DeriveInput) -> Result<Vec<&Ident>, Error> {
+    match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => Ok(fields.named.iter().filter_map(|field| field.ident.as_ref()).collect()),
+            Fields::Unit => Ok(Vec::new()),
+            Fields::Unnamed(_) => Err(Error::new_spanned(&input.ident, "tuple structs cannot be converted to Keynes hashes")),
+        },
+        _ => Err(Error::new_spanned(&input.ident, "only structs can derive Keynes conversions")),
+    }
+}
+
+fn expand_into_keynes(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let name = &input.ident;
+    let fields = named_fields(input)?;
+    let keys = fields.iter().map(|field| field.to_string()).collect::<Vec<_>>();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::keynes::convert::IntoKeynes for #name #ty_generics #where_clause {
+            fn into_keynes(self) -> ::keynes::Object {
+                let mut pairs = ::std::collections::BTreeMap::new();
+                #(pairs.insert(
+                    ::keynes::object::HashKey::String(#keys.to_string()),
+                    ::keynes::convert::IntoKeynes::into_keynes(self.#fields),
+                );)*
+                ::keynes::Object::Hash(pairs)
+            }
+        }
+    })
+}
+
+fn expand_from_keynes(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let name = &input.ident;
+    let type_name = name.to_string();
+    let fields = named_fields(input)?;
+    let keys = fields.iter().map(|field| field.to_string()).collect::<Vec<_>>();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::keynes::convert::FromKeynes for #name #ty_generics #where_clause {
+            fn from_keynes(object: ::keynes::Object) -> ::std::result::Result<Self, ::keynes::RuntimeError> {
+                let mut pairs = match object {
+                    ::keynes::Object::Hash(pairs) => pairs,
+                    other => return Err(::keynes::convert::mismatch(#type_name, &other)),
+                };
+                Ok(#name {
+                    #(#fields: {
+                        let value = pairs
+                            .remove(&::keynes::object::HashKey::String(#keys.to_string()))
+                            .unwrap_or(::keynes::Object::Null);
+                        ::keynes::convert::FromKeynes::from_keynes(value).map_err(|err| {
+                            ::keynes::RuntimeError::new(format!("field `{}` of {}: {}", #keys, #type_name, err.message))
+                        })?
+                    },)*
+                })
+            }
+        }
+    })
+}
@@ -1,320 +1,544 @@
-use std::fmt::{Debug, Display};
-
-use crate::lexer::Token;
-
-use super::{node::Node, statements::BlockStatement};
-
-use dyn_clone::DynClone;
-
-pub trait Expression: Node + DynClone {
-    fn expression_node(&self);
-    fn as_any(&self) -> &dyn std::any::Any;
-}
-
-dyn_clone::clone_trait_object!(Expression);
-
-#[derive(Debug, Clone, PartialEq)]
-pub struct IntegerLiteral {
-    pub token: Token,
-    pub value: i64,
-}
-
-impl Node for IntegerLiteral {}
-impl Expression for IntegerLiteral {
-    fn expression_node(&self) {}
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
-}
-impl Display for IntegerLiteral {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.value)
-    }
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub struct IdentifierLiteral {
-    pub token: Token,
-}
-
-impl Node for IdentifierLiteral {}
-impl Expression for IdentifierLiteral {
-    fn expression_node(&self) {}
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
-}
-impl Display for IdentifierLiteral {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.token)
-    }
-    
-}
-
-impl From<Token> for IdentifierLiteral {
-    fn from(token: Token) -> Self {
-        match token.clone() {
-            Token::IDENTIFIER(_) => IdentifierLiteral { token },
-            _ => panic!("Invalid identifier token {:?}", token),
-        }
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct PrefixExpression {
-    pub token: Token,
-    pub operator: PrefixOperator,
-    pub right: Box<dyn Expression>,
-}
-
-impl Node for PrefixExpression {}
-impl Expression for PrefixExpression {
-    fn expression_node(&self) {}
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
-}
-impl Display for PrefixExpression {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "({}{})", self.operator, self.right)
-    }
-    
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum PrefixOperator {
-    BANG,
-    MINUS,
-}
-
-impl Display for PrefixOperator {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            PrefixOperator::BANG => write!(f, "!"),
-            PrefixOperator::MINUS => write!(f, "-"),
-        }
-    }
-    
-}
-
-impl From<Token> for PrefixOperator {
-    fn from(token: Token) -> Self {
-        match token {
-            Token::BANG => PrefixOperator::BANG,
-            Token::MINUS => PrefixOperator::MINUS,
-            _ => panic!("Invalid prefix operator token {:?}", token),
-        }
-    }
-}
-
-#[derive(PartialEq, PartialOrd, Debug, Clone)]
-pub enum Precedence {
-    LOWEST,
-    EQUALS      ,// ==
-    LESSGREATER ,// > or <
-    SUM         ,// +
-    PRODUCT     ,// *
-    PREFIX      ,// -X or !X
-    CALL        ,// myFunction(X)
-}
-
-impl From<Token> for Precedence {
-    fn from(token: Token) -> Self {
-        match token {
-            Token::EQUAL => Precedence::EQUALS,
-            Token::NOT_EQUAL => Precedence::EQUALS,
-            Token::GREATER_THAN => Precedence::LESSGREATER,
-            Token::GREATER_THAN_EQUAL => Precedence::LESSGREATER,
-            Token::LESS_THAN => Precedence::LESSGREATER,
-            Token::LESS_THAN_EQUAL => Precedence::LESSGREATER,
-            Token::PLUS => Precedence::SUM,
-            Token::MINUS => Precedence::SUM,
-            Token::MULTIPLY => Precedence::PRODUCT,
-            Token::DIVIDE => Precedence::PRODUCT,
-            Token::LPAREN => Precedence::CALL,
-            _ => Precedence::LOWEST,
-        }
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct InfixExpression {
-    pub token: Token,
-    pub operator: InfixOperator,
-    pub left: Box<dyn Expression>,
-    pub right: Box<dyn Expression>,
-}
-
-impl Node for InfixExpression {}
-impl Expression for InfixExpression {
-    fn expression_node(&self) {}
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
-}
-impl Display for InfixExpression {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "({} {} {})", self.left, self.operator, self.right)
-    }
-    
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum InfixOperator {
-    PLUS,
-    MINUS,
-    MULTIPLY,
-    DIVIDE,
-    EQUAL,
-    NOT_EQUAL,
-    LESS_THAN,
-    LESS_THAN_EQUAL,
-    GREATER_THAN,
-    GREATER_THAN_EQUAL,
-}
-
-impl Display for InfixOperator {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            InfixOperator::PLUS => write!(f, "+"),
-            InfixOperator::MINUS => write!(f, "-"),
-            InfixOperator::MULTIPLY => write!(f, "*"),
-            InfixOperator::DIVIDE => write!(f, "/"),
-            InfixOperator::EQUAL => write!(f, "=="),
-            InfixOperator::NOT_EQUAL => write!(f, "!="),
-            InfixOperator::LESS_THAN => write!(f, "<"),
-            InfixOperator::LESS_THAN_EQUAL => write!(f, "<="),
-            InfixOperator::GREATER_THAN => write!(f, ">"),
-            InfixOperator::GREATER_THAN_EQUAL => write!(f, ">="),
-        }
-    }
-    
-}
-
-impl TryFrom<Token> for InfixOperator {
-    type Error = String;
-    fn try_from(token: Token) -> Result<Self, Self::Error> {
-        match token {
-            Token::PLUS => Ok(InfixOperator::PLUS),
-            Token::MINUS => Ok(InfixOperator::MINUS),
-            Token::MULTIPLY => Ok(InfixOperator::MULTIPLY),
-            Token::DIVIDE => Ok(InfixOperator::DIVIDE),
-            Token::EQUAL => Ok(InfixOperator::EQUAL),
-            Token::NOT_EQUAL => Ok(InfixOperator::NOT_EQUAL),
-            Token::LESS_THAN => Ok(InfixOperator::LESS_THAN),
-            Token::LESS_THAN_EQUAL => Ok(InfixOperator::LESS_THAN_EQUAL),
-            Token::GREATER_THAN => Ok(InfixOperator::GREATER_THAN),
-            Token::GREATER_THAN_EQUAL => Ok(InfixOperator::GREATER_THAN_EQUAL),
-            _ => Err(format!("Invalid infix operator token {:?}", token)),
-        }
-    }
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub struct BooleanLiteral {
-    pub token: Token,
-    pub value: bool,
-}
-
-impl Node for BooleanLiteral {}
-impl Expression for BooleanLiteral {
-    fn expression_node(&self) {}
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
-}
-impl Display for BooleanLiteral {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", if self.value { "true" } else { "false" })
-    }
-    
-}
-
-#[derive(Debug, Clone)]
-pub struct IfExpression {
-    pub token: Token,
-    pub condition: Box<dyn Expression>,
-    pub consequence: BlockStatement,
-    pub alternative: Option<BlockStatement>,
-}
-
-impl Node for IfExpression {}
-impl Expression for IfExpression {
-    fn expression_node(&self) {}
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
-}
-impl Display for IfExpression {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut if_expression = String::new();
-        if_expression.push_str(&format!("{} ", self.token));
-        if_expression.push_str(&format!("{} ", self.condition));
-        if_expression.push_str(&format!("{}", self.consequence));
-        if let Some(alternative) = &self.alternative {
-            if_expression.push_str(&format!(" else {}", alternative));
-        }
-        write!(f, "{}", if_expression)
-    }
-    
-}
-
-#[derive(Debug, Clone)]
-pub struct FunctionLiteral {
-    pub token: Token,
-    pub parameters: Vec<IdentifierLiteral>,
-    pub body: BlockStatement,
-}
-
-impl Node for FunctionLiteral {}
-impl Expression for FunctionLiteral {
-    fn expression_node(&self) {}
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
-}
-impl Display for FunctionLiteral {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut function_literal = String::new();
-        function_literal.push_str(&format!("{}(", self.token));
-        for (i, parameter) in self.parameters.iter().enumerate() {
-            function_literal.push_str(&format!("{}", parameter));
-            if i < self.parameters.len() - 1 {
-                function_literal.push_str(", ");
-            }
-        }
-        function_literal.push_str(&format!(") {}", self.body));
-        write!(f, "{}", function_literal)
-    }
-    
-}
-
-#[derive(Debug, Clone)]
-pub struct CallExpression {
-    pub token: Token,
-    pub function: Box<dyn Expression>,
-    pub arguments: Vec<Box<dyn Expression>>,
-}
-
-impl Node for CallExpression {}
-impl Expression for CallExpression {
-    fn expression_node(&self) {}
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
-}
-impl Display for CallExpression {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut call_expression = String::new();
-        call_expression.push_str(&format!("{}(", self.function));
-        for (i, argument) in self.arguments.iter().enumerate() {
-            call_expression.push_str(&format!("{}", argument));
-            if i < self.arguments.len() - 1 {
-                call_expression.push_str(", ");
-            }
-        }
-        call_expression.push_str(")");
-        write!(f, "{}", call_expression)
-    }
-    
-}
\ No newline at end of file
+use std::fmt::{Debug, Display};
+
+use crate::lexer::{quote_string, Token, Span};
+
+use super::{node::Node, statements::BlockStatement};
+
+use dyn_clone::DynClone;
+
+pub trait Expression: Node + DynClone + Send + Sync {
+    fn expression_node(&self);
+    fn as_any(&self) -> &dyn std::any::Any;
+}
+
+dyn_clone::clone_trait_object!(Expression);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegerLiteral {
+    pub token: Token,
+    pub value: i64,
+}
+
+impl Node for IntegerLiteral {}
+impl Expression for IntegerLiteral {
+    fn expression_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+impl Display for IntegerLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IdentifierLiteral {
+    pub token: Token,
+    pub span: Span,
+}
+
+/// Identifiers are equal when they name the same thing, wherever they are.
+impl PartialEq for IdentifierLiteral {
+    fn eq(&self, other: &Self) -> bool {
+        self.token == other.token
+    }
+}
+
+impl Node for IdentifierLiteral {}
+impl Expression for IdentifierLiteral {
+    fn expression_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+impl Display for IdentifierLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.token)
+    }
+    
+}
+
+impl From<Token> for IdentifierLiteral {
+    fn from(token: Token) -> Self {
+        match token.clone() {
+            Token::IDENTIFIER(_) => IdentifierLiteral { token, span: Span::default() },
+            _ => panic!("Invalid identifier token {:?}", token),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PrefixExpression {
+    pub token: Token,
+    pub operator: PrefixOperator,
+    pub right: Box<dyn Expression>,
+    pub span: Span,
+}
+
+impl Node for PrefixExpression {}
+impl Expression for PrefixExpression {
+    fn expression_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+impl Display for PrefixExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}{})", self.operator, self.right)
+    }
+    
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrefixOperator {
+    BANG,
+    MINUS,
+}
+
+impl Display for PrefixOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrefixOperator::BANG => write!(f, "!"),
+            PrefixOperator::MINUS => write!(f, "-"),
+        }
+    }
+    
+}
+
+impl From<Token> for PrefixOperator {
+    fn from(token: Token) -> Self {
+        match token {
+            Token::BANG => PrefixOperator::BANG,
+            Token::MINUS => PrefixOperator::MINUS,
+            _ => panic!("Invalid prefix operator token {:?}", token),
+        }
+    }
+}
+
+#[derive(PartialEq, PartialOrd, Debug, Clone)]
+pub enum Precedence {
+    LOWEST,
+    EQUALS      ,// ==
+    LESSGREATER ,// > or <
+    SUM         ,// +
+    PRODUCT     ,// *
+    PREFIX      ,// -X or !X
+    CALL        ,// myFunction(X)
+    INDEX       ,// array[index]
+}
+
+impl From<Token> for Precedence {
+    fn from(token: Token) -> Self {
+        match token {
+            Token::EQUAL => Precedence::EQUALS,
+            Token::NOT_EQUAL => Precedence::EQUALS,
+            Token::GREATER_THAN => Precedence::LESSGREATER,
+            Token::GREATER_THAN_EQUAL => Precedence::LESSGREATER,
+            Token::LESS_THAN => Precedence::LESSGREATER,
+            Token::LESS_THAN_EQUAL => Precedence::LESSGREATER,
+            Token::PLUS => Precedence::SUM,
+            Token::MINUS => Precedence::SUM,
+            Token::MULTIPLY => Precedence::PRODUCT,
+            Token::DIVIDE => Precedence::PRODUCT,
+            Token::LPAREN => Precedence::CALL,
+            Token::LBRACKET => Precedence::INDEX,
+            _ => Precedence::LOWEST,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InfixExpression {
+    pub token: Token,
+    pub operator: InfixOperator,
+    pub left: Box<dyn Expression>,
+    pub right: Box<dyn Expression>,
+    pub span: Span,
+}
+
+impl Node for InfixExpression {}
+impl Expression for InfixExpression {
+    fn expression_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+impl Display for InfixExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({} {} {})", self.left, self.operator, self.right)
+    }
+    
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InfixOperator {
+    PLUS,
+    MINUS,
+    MULTIPLY,
+    DIVIDE,
+    EQUAL,
+    NOT_EQUAL,
+    LESS_THAN,
+    LESS_THAN_EQUAL,
+    GREATER_THAN,
+    GREATER_THAN_EQUAL,
+}
+
+impl Display for InfixOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InfixOperator::PLUS => write!(f, "+"),
+            InfixOperator::MINUS => write!(f, "-"),
+            InfixOperator::MULTIPLY => write!(f, "*"),
+            InfixOperator::DIVIDE => write!(f, "/"),
+            InfixOperator::EQUAL => write!(f, "=="),
+            InfixOperator::NOT_EQUAL => write!(f, "!="),
+            InfixOperator::LESS_THAN => write!(f, "<"),
+            InfixOperator::LESS_THAN_EQUAL => write!(f, "<="),
+            InfixOperator::GREATER_THAN => write!(f, ">"),
+            InfixOperator::GREATER_THAN_EQUAL => write!(f, ">="),
+        }
+    }
+    
+}
+
+impl TryFrom<Token> for InfixOperator {
+    type Error = String;
+    fn try_from(token: Token) -> Result<Self, Self::Error> {
+        match token {
+            Token::PLUS => Ok(InfixOperator::PLUS),
+            Token::MINUS => Ok(InfixOperator::MINUS),
+            Token::MULTIPLY => Ok(InfixOperator::MULTIPLY),
+            Token::DIVIDE => Ok(InfixOperator::DIVIDE),
+            Token::EQUAL => Ok(InfixOperator::EQUAL),
+            Token::NOT_EQUAL => Ok(InfixOperator::NOT_EQUAL),
+            Token::LESS_THAN => Ok(InfixOperator::LESS_THAN),
+            Token::LESS_THAN_EQUAL => Ok(InfixOperator::LESS_THAN_EQUAL),
+            Token::GREATER_THAN => Ok(InfixOperator::GREATER_THAN),
+            Token::GREATER_THAN_EQUAL => Ok(InfixOperator::GREATER_THAN_EQUAL),
+            _ => Err(format!("Invalid infix operator token {:?}", token)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BooleanLiteral {
+    pub token: Token,
+    pub value: bool,
+}
+
+impl Node for BooleanLiteral {}
+impl Expression for BooleanLiteral {
+    fn expression_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+impl Display for BooleanLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", if self.value { "true" } else { "false" })
+    }
+    
+}
+
+#[derive(Debug, Clone)]
+pub struct IfExpression {
+    pub token: Token,
+    pub condition: Box<dyn Expression>,
+    pub consequence: BlockStatement,
+    pub alternative: Option<BlockStatement>,
+    pub span: Span,
+}
+
+impl Node for IfExpression {}
+impl Expression for IfExpression {
+    fn expression_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+impl Display for IfExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut if_expression = String::new();
+        if_expression.push_str(&format!("{} ", self.token));
+        if_expression.push_str(&format!("{} ", self.condition));
+        if_expression.push_str(&format!("{}", self.consequence));
+        if let Some(alternative) = &self.alternative {
+            if_expression.push_str(&format!(" else {}", alternative));
+        }
+        write!(f, "{}", if_expression)
+    }
+    
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionLiteral {
+    pub token: Token,
+    /// Whether it is an `async fn`, whose calls give a future of the result.
+    pub is_async: bool,
+    pub parameters: Vec<IdentifierLiteral>,
+    pub body: BlockStatement,
+    pub span: Span,
+}
+
+impl Node for FunctionLiteral {}
+impl Expression for FunctionLiteral {
+    fn expression_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+impl Display for FunctionLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut function_literal = String::new();
+        if self.is_async {
+            function_literal.push_str("async ");
+        }
+        function_literal.push_str(&format!("{}(", self.token));
+        for (i, parameter) in self.parameters.iter().enumerate() {
+            function_literal.push_str(&format!("{}", parameter));
+            if i < self.parameters.len() - 1 {
+                function_literal.push_str(", ");
+            }
+        }
+        function_literal.push_str(&format!(") {}", self.body));
+        write!(f, "{}", function_literal)
+    }
+    
+}
+
+#[derive(Debug, Clone)]
+pub struct CallExpression {
+    pub token: Token,
+    pub function: Box<dyn Expression>,
+    pub arguments: Vec<Box<dyn Expression>>,
+    pub span: Span,
+}
+
+impl Node for CallExpression {}
+impl Expression for CallExpression {
+    fn expression_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+impl Display for CallExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut call_expression = String::new();
+        call_expression.push_str(&format!("{}(", self.function));
+        for (i, argument) in self.arguments.iter().enumerate() {
+            call_expression.push_str(&format!("{}", argument));
+            if i < self.arguments.len() - 1 {
+                call_expression.push_str(", ");
+            }
+        }
+        call_expression.push_str(")");
+        write!(f, "{}", call_expression)
+    }
+    
+}
+
+#[derive(Debug, Clone)]
+pub struct ArrayLiteral {
+    pub token: Token,
+    pub elements: Vec<Box<dyn Expression>>,
+    pub span: Span,
+}
+
+impl Node for ArrayLiteral {}
+impl Expression for ArrayLiteral {
+    fn expression_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+impl Display for ArrayLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let elements = self.elements.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        write!(f, "[{}]", elements.join(", "))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexExpression {
+    pub token: Token,
+    pub left: Box<dyn Expression>,
+    pub index: Box<dyn Expression>,
+    pub span: Span,
+}
+
+impl Node for IndexExpression {}
+impl Expression for IndexExpression {
+    fn expression_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+impl Display for IndexExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}[{}])", self.left, self.index)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PathExpression {
+    pub token: Token,
+    pub module: IdentifierLiteral,
+    pub member: IdentifierLiteral,
+    pub span: Span,
+}
+
+impl Node for PathExpression {}
+impl Expression for PathExpression {
+    fn expression_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+impl Display for PathExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}::{}", self.module, self.member)
+    }
+}
+
+/// `spawn f(x)`: calls `f` in a task of its own, evaluating to the task.
+#[derive(Debug, Clone)]
+pub struct SpawnExpression {
+    pub token: Token,
+    pub call: CallExpression,
+    pub span: Span,
+}
+
+impl Node for SpawnExpression {}
+impl Expression for SpawnExpression {
+    fn expression_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+impl Display for SpawnExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "spawn {}", self.call)
+    }
+}
+
+/// `await future`: waits for the future to resolve, evaluating to its value.
+#[derive(Debug, Clone)]
+pub struct AwaitExpression {
+    pub token: Token,
+    pub value: Box<dyn Expression>,
+    pub span: Span,
+}
+
+impl Node for AwaitExpression {}
+impl Expression for AwaitExpression {
+    fn expression_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+impl Display for AwaitExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(await {})", self.value)
+    }
+}
+
+/// `select { rx as value => body, ... }`: waits until one of the receivers has a value,
+/// then evaluates the body of its arm, the first such arm if several do.
+#[derive(Debug, Clone)]
+pub struct SelectExpression {
+    pub token: Token,
+    pub arms: Vec<SelectArm>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct SelectArm {
+    pub receiver: Box<dyn Expression>,
+    /// Name the value received is bound to in the body, if any.
+    pub binding: Option<IdentifierLiteral>,
+    pub body: BlockStatement,
+}
+
+impl Node for SelectExpression {}
+impl Expression for SelectExpression {
+    fn expression_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+impl Display for SelectExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let arms = self.arms.iter().map(|arm| arm.to_string()).collect::<Vec<_>>();
+        write!(f, "{} {{ {} }}", self.token, arms.join(", "))
+    }
+}
+
+impl Display for SelectArm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.binding {
+            Some(binding) => write!(f, "{} as {} => {}", self.receiver, binding, self.body),
+            None => write!(f, "{} => {}", self.receiver, self.body),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloatLiteral {
+    pub token: Token,
+    pub value: f64,
+}
+
+impl Node for FloatLiteral {}
+impl Expression for FloatLiteral {
+    fn expression_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+impl Display for FloatLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StringLiteral {
+    pub token: Token,
+    pub value: String,
+}
+
+impl Node for StringLiteral {}
+impl Expression for StringLiteral {
+    fn expression_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+impl Display for StringLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", quote_string(&self.value))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HashLiteral {
+    pub token: Token,
+    pub pairs: Vec<(Box<dyn Expression>, Box<dyn Expression>)>,
+    pub span: Span,
+}
+
+impl Node for HashLiteral {}
+impl Expression for HashLiteral {
+    fn expression_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+impl Display for HashLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pairs = self.pairs.iter().map(|(k, v)| format!("{}: {}", k, v)).collect::<Vec<_>>();
+        write!(f, "{{{}}}", pairs.join(", "))
+    }
+}
@@ -1,4 +1,4 @@
-pub mod expressions;
-pub mod statements;
-pub mod program;
+pub mod expressions;
+pub mod statements;
+pub mod program;
 pub mod node;
\ No newline at end of file
@@ -1,4 +1,4 @@
-use std::fmt::{Display, Debug};
-
-
+use std::fmt::{Display, Debug};
+
+
 pub trait Node: Debug + Display {}
\ No newline at end of file
@@ -1,113 +1,260 @@
-use std::fmt::{Debug, Display};
-
-use crate::lexer::Token;
-
-use super::{expressions::{IdentifierLiteral, Expression}, node::Node};
-
-use dyn_clone::DynClone;
-
-
-pub trait Statement: Node + DynClone {
-    fn statement_node(&self);
-    fn as_any(&self) -> &dyn std::any::Any;
-}
-
-dyn_clone::clone_trait_object!(Statement);
-
-#[derive(Debug, Clone)]
-pub struct LetStatement {
-    pub token: Token,
-    pub mutable: bool,
-    pub name: IdentifierLiteral,
-    pub value: Box<dyn Expression>,
-}
-
-impl Node for LetStatement {}
-impl Statement for LetStatement {
-    fn statement_node(&self) {}
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
-}
-
-impl Display for LetStatement {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut let_statement = String::new();
-        let_statement.push_str(&format!("{} ", self.token));
-        if self.mutable {
-            let_statement.push_str("mut ");
-        }
-        let_statement.push_str(&format!("{} = {};", self.name, self.value));
-        write!(f, "{}", let_statement)
-    }
-}
-
-
-#[derive(Debug, Clone)]
-pub struct ExpressionStatement {
-    pub token: Token,
-    pub expression: Box<dyn Expression>,
-}
-
-impl Node for ExpressionStatement {}
-impl Statement for ExpressionStatement {
-    fn statement_node(&self) {}
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
-}
-
-impl Display for ExpressionStatement {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.expression)
-    }
-    
-}
-
-#[derive(Debug, Clone)]
-pub struct ReturnStatement {
-    pub token: Token,
-    pub expression: Box<dyn Expression>,
-}
-
-impl Node for ReturnStatement {}
-impl Statement for ReturnStatement {
-    fn statement_node(&self) {}
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
-}
-
-impl Display for ReturnStatement {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut return_statement = String::new();
-        return_statement.push_str(&format!("{} ", self.token));
-        return_statement.push_str(&format!("{}", self.expression));
-        return_statement.push_str(";");
-        write!(f, "{}", return_statement)
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct BlockStatement {
-    pub token: Token,
-    pub statements: Vec<Box<dyn Statement>>,
-}
-
-impl Node for BlockStatement {}
-impl Statement for BlockStatement {
-    fn statement_node(&self) {}
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
-}
-impl Display for BlockStatement {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut block_statement = String::new();
-        for statement in &self.statements {
-            block_statement.push_str(&format!("{}", statement));
-        }
-        write!(f, "{{ {} }}", block_statement)
-    }
-    
-}
\ No newline at end of file
+use std::fmt::{Debug, Display};
+
+use crate::lexer::{quote_string, Token, Span};
+
+use super::{expressions::{IdentifierLiteral, Expression}, node::Node};
+
+use dyn_clone::DynClone;
+
+
+pub trait Statement: Node + DynClone + Send + Sync {
+    fn statement_node(&self);
+    fn as_any(&self) -> &dyn std::any::Any;
+    /// Source range of the statement, including a trailing semicolon.
+    fn span(&self) -> Span;
+}
+
+dyn_clone::clone_trait_object!(Statement);
+
+#[derive(Debug, Clone)]
+pub struct LetStatement {
+    pub token: Token,
+    pub public: bool,
+    pub mutable: bool,
+    pub name: IdentifierLiteral,
+    pub value: Box<dyn Expression>,
+    pub span: Span,
+}
+
+impl Node for LetStatement {}
+impl Statement for LetStatement {
+    fn statement_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Display for LetStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut let_statement = String::new();
+        if self.public {
+            let_statement.push_str("pub ");
+        }
+        let_statement.push_str(&format!("{} ", self.token));
+        if self.mutable {
+            let_statement.push_str("mut ");
+        }
+        let_statement.push_str(&format!("{} = {};", self.name, self.value));
+        write!(f, "{}", let_statement)
+    }
+}
+
+/// `let (a, b) = value;`: binds the elements of an array to names, in order.
+#[derive(Debug, Clone)]
+pub struct LetTupleStatement {
+    pub token: Token,
+    pub names: Vec<IdentifierLiteral>,
+    pub value: Box<dyn Expression>,
+    pub span: Span,
+}
+
+impl Node for LetTupleStatement {}
+impl Statement for LetTupleStatement {
+    fn statement_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Display for LetTupleStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = self.names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        write!(f, "{} ({}) = {};", self.token, names.join(", "), self.value)
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct ExpressionStatement {
+    pub token: Token,
+    pub expression: Box<dyn Expression>,
+    pub span: Span,
+}
+
+impl Node for ExpressionStatement {}
+impl Statement for ExpressionStatement {
+    fn statement_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Display for ExpressionStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+    
+}
+
+#[derive(Debug, Clone)]
+pub struct ReturnStatement {
+    pub token: Token,
+    pub expression: Box<dyn Expression>,
+    pub span: Span,
+}
+
+impl Node for ReturnStatement {}
+impl Statement for ReturnStatement {
+    fn statement_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Display for ReturnStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut return_statement = String::new();
+        return_statement.push_str(&format!("{} ", self.token));
+        return_statement.push_str(&format!("{}", self.expression));
+        return_statement.push_str(";");
+        write!(f, "{}", return_statement)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockStatement {
+    pub token: Token,
+    pub statements: Vec<Box<dyn Statement>>,
+    pub span: Span,
+}
+
+impl Node for BlockStatement {}
+impl Statement for BlockStatement {
+    fn statement_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+impl Display for BlockStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut block_statement = String::new();
+        for statement in &self.statements {
+            block_statement.push_str(&format!("{}", statement));
+        }
+        write!(f, "{{ {} }}", block_statement)
+    }
+    
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportStatement {
+    pub token: Token,
+    pub path: String,
+    pub alias: IdentifierLiteral,
+    pub span: Span,
+}
+
+impl Node for ImportStatement {}
+impl Statement for ImportStatement {
+    fn statement_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+impl Display for ImportStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {:?} as {};", self.token, self.path, self.alias)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UseStatement {
+    pub token: Token,
+    pub module: IdentifierLiteral,
+    pub names: Vec<IdentifierLiteral>,
+    pub span: Span,
+}
+
+impl Node for UseStatement {}
+impl Statement for UseStatement {
+    fn statement_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+impl Display for UseStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = self.names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        write!(f, "{} {}::{{{}}};", self.token, self.module, names.join(", "))
+    }
+}
+
+/// `test "name" { ... }`, a block run only by `keynes test`.
+#[derive(Debug, Clone)]
+pub struct TestStatement {
+    pub token: Token,
+    pub name: String,
+    pub body: BlockStatement,
+    pub span: Span,
+}
+
+impl Node for TestStatement {}
+impl Statement for TestStatement {
+    fn statement_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+impl Display for TestStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.token, quote_string(&self.name), self.body)
+    }
+}
+
+/// Placeholder for a statement that failed to parse, covering the tokens
+/// the parser skipped to recover.
+#[derive(Debug, Clone)]
+pub struct ErrorStatement {
+    pub token: Token,
+    pub span: Span,
+}
+
+impl Node for ErrorStatement {}
+impl Statement for ErrorStatement {
+    fn statement_node(&self) {}
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+impl Display for ErrorStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<error>")
+    }
+}
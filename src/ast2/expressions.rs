@@ -1,29 +1,66 @@
-use super::statements::BlockStatement;
-
-use super::traits::Expression;
-
-
-#[derive(Debug, Clone)]
-pub struct PrefixExpression {
-    pub operator: String,
-    pub right: Box<dyn Expression>,
-}
-
-impl Expression for PrefixExpression {}
-
-#[derive(Debug, Clone)]
-pub struct InfixExpression {
-    pub operator: String,
-    pub left: Box<dyn Expression>,
-    pub right: Box<dyn Expression>,
-}
-
-impl Expression for InfixExpression {}
-
-#[derive(Debug, Clone)]
-pub struct IfExpression {
-    pub conditions: Vec<(Box<dyn Expression>, BlockStatement)>,
-    pub alternative: Option<BlockStatement>,
-}
-
-impl Expression for IfExpression {}
\ No newline at end of file
+use super::statements::BlockStatement;
+
+use super::literals::IdentifierLiteral;
+use super::traits::Expression;
+
+
+#[derive(Debug, Clone)]
+pub struct PrefixExpression {
+    pub operator: String,
+    pub right: Box<dyn Expression>,
+}
+
+impl Expression for PrefixExpression {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InfixExpression {
+    pub operator: String,
+    pub left: Box<dyn Expression>,
+    pub right: Box<dyn Expression>,
+}
+
+impl Expression for InfixExpression {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IfExpression {
+    pub conditions: Vec<(Box<dyn Expression>, BlockStatement)>,
+    pub alternative: Option<BlockStatement>,
+}
+
+impl Expression for IfExpression {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionLiteral {
+    pub is_async: bool,
+    pub parameters: Vec<IdentifierLiteral>,
+    pub body: BlockStatement,
+}
+
+impl Expression for FunctionLiteral {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AwaitExpression {
+    pub value: Box<dyn Expression>,
+}
+
+impl Expression for AwaitExpression {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
\ No newline at end of file
@@ -1,31 +1,47 @@
-use super::traits::Expression;
-
-#[derive(Debug, Clone)]
-pub struct IdentifierLiteral {
-    pub name: String
-}
-
-impl Expression for IdentifierLiteral {}
-
-#[derive(Debug, Clone)]
-pub struct IntegerLiteral {
-    pub value: String,
-    pub length: String,
-}
-
-impl Expression for IntegerLiteral {}
-
-#[derive(Debug, Clone)]
-pub struct FloatLiteral {
-    pub value: String,
-    pub length: String,
-}
-
-impl Expression for FloatLiteral {}
-
-#[derive(Debug, Clone)]
-pub struct BooleanLiteral {
-    pub value: bool
-}
-
-impl Expression for BooleanLiteral {}
+use super::traits::Expression;
+
+#[derive(Debug, Clone)]
+pub struct IdentifierLiteral {
+    pub name: String
+}
+
+impl Expression for IdentifierLiteral {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IntegerLiteral {
+    pub value: String,
+    pub length: String,
+}
+
+impl Expression for IntegerLiteral {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FloatLiteral {
+    pub value: String,
+    pub length: String,
+}
+
+impl Expression for FloatLiteral {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BooleanLiteral {
+    pub value: bool
+}
+
+impl Expression for BooleanLiteral {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -1,5 +1,5 @@
-pub mod expressions;
-pub mod statements;
-pub mod program;
-pub mod traits;
+pub mod expressions;
+pub mod statements;
+pub mod program;
+pub mod traits;
 pub mod literals;
\ No newline at end of file
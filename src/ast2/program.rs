@@ -1,6 +1,6 @@
-use super::traits::Statement;
-
-#[derive(Debug, Clone)]
-pub struct Program {
-    pub statements: Vec<Box<dyn Statement>>,
+use super::traits::Statement;
+
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub statements: Vec<Box<dyn Statement>>,
 }
\ No newline at end of file
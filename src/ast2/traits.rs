@@ -1,14 +1,14 @@
-use std::fmt::Debug;
-
-use dyn_clone::DynClone;
-
-pub trait Statement: Debug + DynClone {
-    
-}
-
-pub trait Expression: Debug + DynClone  {
-    
-}
-
-dyn_clone::clone_trait_object!(Expression);
+use std::fmt::Debug;
+
+use dyn_clone::DynClone;
+
+pub trait Statement: Debug + DynClone {
+    fn as_any(&self) -> &dyn std::any::Any;
+}
+
+pub trait Expression: Debug + DynClone  {
+    fn as_any(&self) -> &dyn std::any::Any;
+}
+
+dyn_clone::clone_trait_object!(Expression);
 dyn_clone::clone_trait_object!(Statement);
\ No newline at end of file
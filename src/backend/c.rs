@@ -0,0 +1,452 @@
+//! Compiles Keynes programs to portable C, for `keynes build --target c`.
+//!
+//! Values keep the dynamic semantics of the interpreter: they are a tagged union
+//! defined by the runtime in `c_runtime.h`, whose integer widths map to the
+//! `<stdint.h>` types (and `__int128` for `i128`). Every function literal becomes
+//! a C function, and every scope a heap allocated environment struct holding its
+//! variables and a pointer to the enclosing scope, which closures capture.
+
+use std::path::Path;
+
+use crate::{
+    ast::{expressions::*, program::Program, statements::*},
+    builtins,
+    checker::collect_declared,
+    diagnostics::Diagnostic,
+    lexer::Span,
+};
+
+use log::*;
+
+const RUNTIME: &str = include_str!("c_runtime.h");
+
+/// The builtins the runtime implements. Programs using any other builtin are rejected.
+const BUILTINS: [&str; 21] = [
+    "print", "println", "len", "str", "first", "last", "rest", "push", "keys", "values", "abs", "min", "max", "assert",
+    "i8", "i16", "i32", "i64", "i128", "f32", "f64",
+];
+
+/// Lowers `program`, read from `file`, to a C translation unit with a `main` function.
+pub fn compile(file: &str, program: &Program) -> Result<String, Vec<Diagnostic>> {
+    trace!("compile to C: {}", file);
+    let mut compiler = Compiler {
+        file,
+        scopes: Vec::new(),
+        chain: Vec::new(),
+        functions: Vec::new(),
+        out: String::new(),
+        indent: 1,
+        temps: 0,
+        returns_from_main: false,
+        diagnostics: Vec::new(),
+    };
+    let main = compiler.main(program);
+    if !compiler.diagnostics.is_empty() {
+        return Err(compiler.diagnostics);
+    }
+
+    let mut c = format!("{}\n/* Compiled from {} */\n\n", RUNTIME, file.replace("*/", "* /"));
+    for scope in &compiler.scopes {
+        c.push_str(&format!("struct kn_scope_{};\n", scope.id));
+    }
+    for scope in &compiler.scopes {
+        let parent = match scope.parent {
+            Some(parent) => format!("struct kn_scope_{}", parent),
+            None => "void".to_string(),
+        };
+        c.push_str(&format!("\nstruct kn_scope_{} {{\n    {} *parent;\n", scope.id, parent));
+        for (i, name) in scope.names.iter().enumerate() {
+            c.push_str(&format!("    kn_value v{}; /* {} */\n", i, name));
+        }
+        c.push_str("};\n");
+    }
+    c.push('\n');
+    for id in 1..compiler.scopes.len() {
+        c.push_str(&format!("static kn_value kn_fn_{}(kn_closure *self, int argc, kn_value *argv);\n", id));
+    }
+    for function in &compiler.functions {
+        c.push('\n');
+        c.push_str(function);
+    }
+    c.push('\n');
+    c.push_str(&main);
+    Ok(c)
+}
+
+/// Compiles C source to an executable at `output` with the system C compiler, `$CC` or `cc`.
+pub fn build(c_source: &str, output: &Path) -> Result<(), String> {
+    super::cc(c_source, "c", &["-std=c11", "-O2"], &["-lm"], output)
+}
+
+/// The variables of a program or function body. Blocks share the scope they are in.
+struct Scope {
+    id: usize,
+    parent: Option<usize>,
+    names: Vec<String>,
+}
+
+struct Compiler<'a> {
+    file: &'a str,
+    /// Every scope, indexed by id. Scope 0 is the program's, scope `n` that of `kn_fn_n`.
+    scopes: Vec<Scope>,
+    /// Ids of the scopes enclosing the code being compiled, innermost last.
+    chain: Vec<usize>,
+    functions: Vec<String>,
+    /// Body of the C function being compiled.
+    out: String,
+    indent: usize,
+    temps: usize,
+    /// Whether `main` has a top level `return`, which jumps to its end.
+    returns_from_main: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Compiler<'_> {
+    fn line(&mut self, text: &str) {
+        self.out.push_str(&"    ".repeat(self.indent));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// Declares a fresh temporary holding `value`.
+    fn temp(&mut self, value: &str) -> String {
+        self.temps += 1;
+        let temp = format!("t{}", self.temps);
+        self.line(&format!("kn_value {} = {};", temp, value));
+        temp
+    }
+
+    fn unsupported(&mut self, span: Span, what: &str) -> String {
+        self.diagnostics.push(Diagnostic::new(format!("{} not supported by the C backend", what), span));
+        "kn_null()".to_string()
+    }
+
+    fn enter(&mut self, names: Vec<String>) -> usize {
+        let id = self.scopes.len();
+        let mut unique = Vec::new();
+        for name in names {
+            if !unique.contains(&name) {
+                unique.push(name);
+            }
+        }
+        self.scopes.push(Scope {
+            id,
+            parent: self.chain.last().copied(),
+            names: unique,
+        });
+        self.chain.push(id);
+        id
+    }
+
+    fn main(&mut self, program: &Program) -> String {
+        let mut names = Vec::new();
+        collect_declared(&program.statements, &mut names);
+        self.enter(names);
+        self.line("struct kn_scope_0 *scope = kn_alloc(sizeof *scope);");
+        self.line("kn_value result = kn_null();");
+        for statement in &program.statements {
+            let value = self.statement(statement.as_ref(), true);
+            self.line(&format!("result = {};", value));
+        }
+        if self.returns_from_main {
+            self.out.push_str("done:\n");
+        }
+        self.line("return kn_finish(result);");
+        self.chain.pop();
+        format!("int main(void) {{\n{}}}\n", std::mem::take(&mut self.out))
+    }
+
+    /// The C expression for the variable `name`: the innermost enclosing scope that has bound it.
+    fn variable(&mut self, name: &str, span: Span) -> String {
+        let mut slots = Vec::new();
+        for (depth, id) in self.chain.iter().rev().enumerate() {
+            if let Some(index) = self.scopes[*id].names.iter().position(|n| n == name) {
+                slots.push(format!("&scope{}->v{}", "->parent".repeat(depth), index));
+            }
+        }
+        let fallback = if BUILTINS.contains(&name) {
+            format!("kn_builtin_named({})", c_string(name))
+        } else if slots.is_empty() && builtins::get(name).is_some() {
+            return self.unsupported(span, &format!("builtin `{}` is", name));
+        } else {
+            "(kn_value){ .tag = KN_UNDEF }".to_string()
+        };
+        if slots.is_empty() {
+            return fallback;
+        }
+        format!(
+            "kn_lookup((kn_value *[]){{ {} }}, {}, {}, {})",
+            slots.join(", "),
+            slots.len(),
+            c_string(name),
+            fallback
+        )
+    }
+
+    /// Compiles `statement`, returning the C expression for its value. `returns` is whether
+    /// a `return` in it leaves the function, as it does unless the statement is part of an
+    /// `if` whose value is used.
+    fn statement(&mut self, statement: &dyn Statement, returns: bool) -> String {
+        let start = statement.span().start;
+        self.line(&format!("kn_location = {};", c_string(&format!("{}:{}", self.file, start))));
+        let any = statement.as_any();
+        if let Some(statement) = any.downcast_ref::<LetStatement>() {
+            let value = self.expression(statement.value.as_ref());
+            let name = statement.name.to_string();
+            let scope = &self.scopes[*self.chain.last().expect("a scope is open")];
+            let index = scope.names.iter().position(|n| *n == name).expect("let names are declared");
+            self.line(&format!("scope->v{} = kn_named({}, {});", index, value, c_string(&name)));
+            "kn_null()".to_string()
+        } else if let Some(statement) = any.downcast_ref::<ReturnStatement>() {
+            if !returns {
+                return self.unsupported(statement.span, "`return` inside an `if` used as a value is");
+            }
+            let value = self.expression(statement.expression.as_ref());
+            if self.chain.len() == 1 {
+                self.returns_from_main = true;
+                self.line(&format!("result = {};", value));
+                self.line("goto done;");
+            } else {
+                self.line(&format!("return {};", value));
+            }
+            "kn_null()".to_string()
+        } else if let Some(statement) = any.downcast_ref::<ExpressionStatement>() {
+            match statement.expression.as_any().downcast_ref::<IfExpression>() {
+                Some(if_expression) => self.if_expression(if_expression, returns),
+                None => self.expression(statement.expression.as_ref()),
+            }
+        } else if let Some(block) = any.downcast_ref::<BlockStatement>() {
+            self.block(block, returns)
+        } else if any.is::<TestStatement>() {
+            // Tests only run under `keynes test`.
+            "kn_null()".to_string()
+        } else if any.is::<ImportStatement>() || any.is::<UseStatement>() {
+            self.unsupported(statement.span(), "modules are")
+        } else if any.is::<LetTupleStatement>() {
+            self.unsupported(statement.span(), "destructuring is")
+        } else {
+            self.unsupported(statement.span(), &format!("`{}` is", statement))
+        }
+    }
+
+    /// Compiles the statements of `block`, returning the C expression for the value of the last one.
+    fn block(&mut self, block: &BlockStatement, returns: bool) -> String {
+        let mut value = "kn_null()".to_string();
+        for statement in &block.statements {
+            value = self.statement(statement.as_ref(), returns);
+        }
+        value
+    }
+
+    /// Compiles `expression` into statements, returning a temporary or constant holding its value.
+    /// Every value lands in a temporary as soon as it is computed, so evaluation order is kept.
+    fn expression(&mut self, expression: &dyn Expression) -> String {
+        let any = expression.as_any();
+        if let Some(integer) = any.downcast_ref::<IntegerLiteral>() {
+            format!("kn_int(KN_I64, {})", c_i64(integer.value))
+        } else if let Some(float) = any.downcast_ref::<FloatLiteral>() {
+            format!("kn_f64({})", c_double(float.value))
+        } else if let Some(string) = any.downcast_ref::<StringLiteral>() {
+            format!("kn_str({}, {})", c_string(&string.value), string.value.len())
+        } else if let Some(boolean) = any.downcast_ref::<BooleanLiteral>() {
+            format!("kn_bool({})", boolean.value)
+        } else if let Some(identifier) = any.downcast_ref::<IdentifierLiteral>() {
+            let variable = self.variable(&identifier.to_string(), identifier.span);
+            self.temp(&variable)
+        } else if let Some(prefix) = any.downcast_ref::<PrefixExpression>() {
+            let right = self.expression(prefix.right.as_ref());
+            let function = match prefix.operator {
+                PrefixOperator::BANG => "kn_not",
+                PrefixOperator::MINUS => "kn_negate",
+            };
+            self.temp(&format!("{}({})", function, right))
+        } else if let Some(infix) = any.downcast_ref::<InfixExpression>() {
+            let left = self.expression(infix.left.as_ref());
+            let right = self.expression(infix.right.as_ref());
+            self.temp(&format!("kn_binary({}, {}, {})", c_op(&infix.operator), left, right))
+        } else if let Some(if_expression) = any.downcast_ref::<IfExpression>() {
+            self.if_expression(if_expression, false)
+        } else if let Some(function) = any.downcast_ref::<FunctionLiteral>() {
+            if function.is_async {
+                return self.unsupported(function.span, "async functions are");
+            }
+            self.function(function)
+        } else if let Some(call) = any.downcast_ref::<CallExpression>() {
+            if let Some(value) = self.width_literal(call) {
+                return value;
+            }
+            let function = self.expression(call.function.as_ref());
+            let arguments = call.arguments.iter().map(|argument| self.expression(argument.as_ref())).collect::<Vec<_>>();
+            self.temp(&format!("kn_call({}, {}, {})", function, arguments.len(), c_array(&arguments)))
+        } else if let Some(array) = any.downcast_ref::<ArrayLiteral>() {
+            let elements = array.elements.iter().map(|element| self.expression(element.as_ref())).collect::<Vec<_>>();
+            self.temp(&format!("kn_array_of({}, {})", elements.len(), c_array(&elements)))
+        } else if let Some(hash) = any.downcast_ref::<HashLiteral>() {
+            let result = self.temp("kn_hash_new()");
+            for (key, value) in &hash.pairs {
+                let key = self.expression(key.as_ref());
+                self.line(&format!("kn_check_key({});", key));
+                let value = self.expression(value.as_ref());
+                self.line(&format!("kn_hash_set({}, {}, {});", result, key, value));
+            }
+            result
+        } else if let Some(index) = any.downcast_ref::<IndexExpression>() {
+            let left = self.expression(index.left.as_ref());
+            let position = self.expression(index.index.as_ref());
+            self.temp(&format!("kn_index({}, {})", left, position))
+        } else if let Some(spawn) = any.downcast_ref::<SpawnExpression>() {
+            self.unsupported(spawn.span, "tasks are")
+        } else if let Some(select) = any.downcast_ref::<SelectExpression>() {
+            self.unsupported(select.span, "channels are")
+        } else if let Some(await_expression) = any.downcast_ref::<AwaitExpression>() {
+            self.unsupported(await_expression.span, "futures are")
+        } else if let Some(path) = any.downcast_ref::<PathExpression>() {
+            self.unsupported(path.span, "modules are")
+        } else {
+            self.unsupported(Span::default(), &format!("`{}` is", expression))
+        }
+    }
+
+    fn if_expression(&mut self, if_expression: &IfExpression, returns: bool) -> String {
+        let condition = self.expression(if_expression.condition.as_ref());
+        self.temps += 1;
+        let result = format!("t{}", self.temps);
+        self.line(&format!("kn_value {};", result));
+        self.line(&format!("if (kn_truthy({})) {{", condition));
+        self.indent += 1;
+        let value = self.block(&if_expression.consequence, returns);
+        self.line(&format!("{} = {};", result, value));
+        self.indent -= 1;
+        self.line("} else {");
+        self.indent += 1;
+        let value = match &if_expression.alternative {
+            Some(alternative) => self.block(alternative, returns),
+            None => "kn_null()".to_string(),
+        };
+        self.line(&format!("{} = {};", result, value));
+        self.indent -= 1;
+        self.line("}");
+        result
+    }
+
+    /// Compiles the body of `function` to a C function of its own, returning a closure over the current scope.
+    fn function(&mut self, function: &FunctionLiteral) -> String {
+        let mut names = function.parameters.iter().map(|parameter| parameter.to_string()).collect::<Vec<_>>();
+        collect_declared(&function.body.statements, &mut names);
+        let id = self.enter(names);
+
+        let outer = std::mem::take(&mut self.out);
+        let indent = std::mem::replace(&mut self.indent, 1);
+        self.line(&format!("struct kn_scope_{} *scope = kn_alloc(sizeof *scope);", id));
+        self.line("(void)argc;");
+        self.line("scope->parent = self->env;");
+        for (i, parameter) in function.parameters.iter().enumerate() {
+            let index = self.scopes[id].names.iter().position(|n| *n == parameter.to_string()).unwrap();
+            self.line(&format!("scope->v{} = argv[{}];", index, i));
+        }
+        let value = self.block(&function.body, true);
+        self.line(&format!("return {};", value));
+        let body = std::mem::replace(&mut self.out, outer);
+        self.indent = indent;
+        self.chain.pop();
+
+        self.functions.push(format!(
+            "static kn_value kn_fn_{}(kn_closure *self, int argc, kn_value *argv) {{\n{}}}\n",
+            id, body
+        ));
+        self.temp(&format!(
+            "kn_closure_new(kn_fn_{}, scope, {}, {})",
+            id,
+            function.parameters.len(),
+            c_string(&function.to_string())
+        ))
+    }
+
+    /// A call of a width builtin on a literal, as in `i8(100)` or `f32(1.5)`, becomes a constant
+    /// of the matching C type when the value fits.
+    fn width_literal(&mut self, call: &CallExpression) -> Option<String> {
+        let name = call.function.as_any().downcast_ref::<IdentifierLiteral>()?.to_string();
+        if self.chain.iter().any(|id| self.scopes[*id].names.contains(&name)) {
+            return None;
+        }
+        let [argument] = call.arguments.as_slice() else {
+            return None;
+        };
+        let any = argument.as_any();
+        let integer = any.downcast_ref::<IntegerLiteral>().map(|integer| integer.value);
+        let float = any.downcast_ref::<FloatLiteral>().map(|float| float.value);
+        match (name.as_str(), integer, float) {
+            ("i8", Some(value), _) if i8::try_from(value).is_ok() => Some(format!("kn_int(KN_I8, (int8_t){})", value)),
+            ("i16", Some(value), _) if i16::try_from(value).is_ok() => Some(format!("kn_int(KN_I16, (int16_t){})", value)),
+            ("i32", Some(value), _) if i32::try_from(value).is_ok() => Some(format!("kn_int(KN_I32, (int32_t){})", value)),
+            ("i64", Some(value), _) => Some(format!("kn_int(KN_I64, {})", c_i64(value))),
+            ("i128", Some(value), _) => Some(format!("kn_int(KN_I128, (kn_i128){})", c_i64(value))),
+            ("f32", Some(value), _) => Some(format!("kn_f32((float){})", c_double(value as f64))),
+            ("f32", _, Some(value)) => Some(format!("kn_f32((float){})", c_double(value))),
+            ("f64", Some(value), _) => Some(format!("kn_f64({})", c_double(value as f64))),
+            ("f64", _, Some(value)) => Some(format!("kn_f64({})", c_double(value))),
+            _ => None,
+        }
+    }
+}
+
+fn c_op(operator: &InfixOperator) -> &'static str {
+    match operator {
+        InfixOperator::PLUS => "KN_ADD",
+        InfixOperator::MINUS => "KN_SUB",
+        InfixOperator::MULTIPLY => "KN_MUL",
+        InfixOperator::DIVIDE => "KN_DIV",
+        InfixOperator::EQUAL => "KN_EQ",
+        InfixOperator::NOT_EQUAL => "KN_NE",
+        InfixOperator::LESS_THAN => "KN_LT",
+        InfixOperator::LESS_THAN_EQUAL => "KN_LE",
+        InfixOperator::GREATER_THAN => "KN_GT",
+        InfixOperator::GREATER_THAN_EQUAL => "KN_GE",
+    }
+}
+
+/// A C array of `values` that lives as long as the enclosing block, or `NULL` when empty.
+fn c_array(values: &[String]) -> String {
+    match values.is_empty() {
+        true => "NULL".to_string(),
+        false => format!("(kn_value[]){{ {} }}", values.join(", ")),
+    }
+}
+
+fn c_i64(value: i64) -> String {
+    match value {
+        i64::MIN => "INT64_MIN".to_string(),
+        value => format!("INT64_C({})", value),
+    }
+}
+
+fn c_double(value: f64) -> String {
+    if value.is_nan() {
+        "NAN".to_string()
+    } else if value.is_infinite() {
+        format!("{}INFINITY", if value < 0.0 { "-" } else { "" })
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+/// A C string literal of the bytes of `text`. Everything but printable ASCII is written in octal.
+fn c_string(text: &str) -> String {
+    let mut literal = String::from("\"");
+    for byte in text.bytes() {
+        match byte {
+            b'"' | b'\\' | b'?' => {
+                literal.push('\\');
+                literal.push(byte as char);
+            },
+            b' '..=b'~' => literal.push(byte as char),
+            _ => literal.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+#[cfg(test)]
+#[path = "./c_tests.rs"]
+mod tests;
@@ -0,0 +1,970 @@
+/* Runtime for Keynes programs compiled to C by `keynes build --target c`.
+ *
+ * Values behave as in the interpreter, down to the wording of runtime errors.
+ * Memory is never freed: compiled programs are expected to be short lived. */
+
+#include <math.h>
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+typedef __int128 kn_i128;
+
+typedef enum { KN_UNDEF, KN_NULL, KN_INTEGER, KN_FLOAT, KN_BOOLEAN, KN_STRING, KN_ARRAY, KN_HASH, KN_FUNCTION, KN_BUILTIN } kn_tag;
+typedef enum { KN_I8, KN_I16, KN_I32, KN_I64, KN_I128 } kn_int_width;
+typedef enum { KN_F32, KN_F64 } kn_float_width;
+typedef enum { KN_ADD, KN_SUB, KN_MUL, KN_DIV, KN_EQ, KN_NE, KN_LT, KN_LE, KN_GT, KN_GE } kn_op;
+
+typedef struct kn_value kn_value;
+typedef struct kn_closure kn_closure;
+typedef kn_value (*kn_code)(kn_closure *self, int argc, kn_value *argv);
+typedef kn_value (*kn_native)(int argc, kn_value *argv);
+
+typedef struct { size_t len; char *data; } kn_string;
+typedef struct { size_t len; kn_value *items; } kn_array;
+/* Pairs are kept sorted by key, in the order of the interpreter's hash keys. */
+typedef struct { size_t len; kn_value *keys; kn_value *values; } kn_hash;
+typedef struct { const char *name; kn_native function; } kn_builtin;
+
+struct kn_closure {
+    kn_code code;
+    void *env;
+    int arity;
+    /* The binding the function was first assigned to, or NULL. */
+    const char *name;
+    /* How the function prints, e.g. `fn(x) { (x * 2) }`. */
+    const char *source;
+};
+
+struct kn_value {
+    kn_tag tag;
+    union {
+        struct {
+            kn_int_width width;
+            union { int8_t i8; int16_t i16; int32_t i32; int64_t i64; kn_i128 i128; } as;
+        } integer;
+        struct {
+            kn_float_width width;
+            union { float f32; double f64; } as;
+        } flt;
+        bool boolean;
+        kn_string *string;
+        kn_array *array;
+        kn_hash *hash;
+        kn_closure *function;
+        const kn_builtin *builtin;
+    } as;
+};
+
+/* Where the statement being run starts, as `file:line:column`. */
+static const char *kn_location = "";
+
+static void kn_fail(const char *format, ...) {
+    va_list args;
+    fflush(stdout);
+    fputs("error: ", stderr);
+    va_start(args, format);
+    vfprintf(stderr, format, args);
+    va_end(args);
+    fprintf(stderr, "\n --> %s\n", kn_location);
+    exit(1);
+}
+
+static void *kn_alloc(size_t size) {
+    void *memory = calloc(1, size ? size : 1);
+    if (!memory) {
+        kn_fail("out of memory");
+    }
+    return memory;
+}
+
+/* Text buffers */
+
+typedef struct { char *data; size_t len, cap; } kn_buf;
+
+static void kn_buf_put(kn_buf *buf, const char *data, size_t len) {
+    if (buf->len + len + 1 > buf->cap) {
+        buf->cap = (buf->len + len + 1) * 2;
+        buf->data = realloc(buf->data, buf->cap);
+        if (!buf->data) {
+            kn_fail("out of memory");
+        }
+    }
+    memcpy(buf->data + buf->len, data, len);
+    buf->len += len;
+    buf->data[buf->len] = '\0';
+}
+
+static void kn_buf_puts(kn_buf *buf, const char *text) {
+    kn_buf_put(buf, text, strlen(text));
+}
+
+static const char *kn_buf_text(kn_buf *buf) {
+    return buf->data ? buf->data : "";
+}
+
+/* Constructors */
+
+static kn_value kn_null(void) {
+    kn_value value = { .tag = KN_NULL };
+    return value;
+}
+
+static kn_value kn_bool(bool boolean) {
+    kn_value value = { .tag = KN_BOOLEAN };
+    value.as.boolean = boolean;
+    return value;
+}
+
+static const char *kn_int_width_name(kn_int_width width) {
+    static const char *names[] = { "i8", "i16", "i32", "i64", "i128" };
+    return names[width];
+}
+
+static bool kn_int_fits(kn_int_width width, kn_i128 value) {
+    switch (width) {
+    case KN_I8: return value >= INT8_MIN && value <= INT8_MAX;
+    case KN_I16: return value >= INT16_MIN && value <= INT16_MAX;
+    case KN_I32: return value >= INT32_MIN && value <= INT32_MAX;
+    case KN_I64: return value >= INT64_MIN && value <= INT64_MAX;
+    default: return true;
+    }
+}
+
+/* An integer of `width`; `value` must fit. */
+static kn_value kn_int(kn_int_width width, kn_i128 value) {
+    kn_value result = { .tag = KN_INTEGER };
+    result.as.integer.width = width;
+    switch (width) {
+    case KN_I8: result.as.integer.as.i8 = (int8_t)value; break;
+    case KN_I16: result.as.integer.as.i16 = (int16_t)value; break;
+    case KN_I32: result.as.integer.as.i32 = (int32_t)value; break;
+    case KN_I64: result.as.integer.as.i64 = (int64_t)value; break;
+    case KN_I128: result.as.integer.as.i128 = value; break;
+    }
+    return result;
+}
+
+static kn_i128 kn_int_value(kn_value value) {
+    switch (value.as.integer.width) {
+    case KN_I8: return value.as.integer.as.i8;
+    case KN_I16: return value.as.integer.as.i16;
+    case KN_I32: return value.as.integer.as.i32;
+    case KN_I64: return value.as.integer.as.i64;
+    default: return value.as.integer.as.i128;
+    }
+}
+
+static kn_value kn_f32(float number) {
+    kn_value value = { .tag = KN_FLOAT };
+    value.as.flt.width = KN_F32;
+    value.as.flt.as.f32 = number;
+    return value;
+}
+
+static kn_value kn_f64(double number) {
+    kn_value value = { .tag = KN_FLOAT };
+    value.as.flt.width = KN_F64;
+    value.as.flt.as.f64 = number;
+    return value;
+}
+
+static double kn_float_value(kn_value value) {
+    return value.as.flt.width == KN_F32 ? (double)value.as.flt.as.f32 : value.as.flt.as.f64;
+}
+
+/* A float of the same width as `like`, as `Float::with_value`. */
+static kn_value kn_float_like(kn_value like, double number) {
+    return like.as.flt.width == KN_F32 ? kn_f32((float)number) : kn_f64(number);
+}
+
+static kn_value kn_str(const char *data, size_t len) {
+    kn_value value = { .tag = KN_STRING };
+    value.as.string = kn_alloc(sizeof(kn_string));
+    value.as.string->data = kn_alloc(len + 1);
+    memcpy(value.as.string->data, data, len);
+    value.as.string->len = len;
+    return value;
+}
+
+static kn_value kn_array_of(size_t len, const kn_value *items) {
+    kn_value value = { .tag = KN_ARRAY };
+    value.as.array = kn_alloc(sizeof(kn_array));
+    value.as.array->items = kn_alloc(len * sizeof(kn_value));
+    if (items) {
+        memcpy(value.as.array->items, items, len * sizeof(kn_value));
+    }
+    value.as.array->len = len;
+    return value;
+}
+
+static kn_value kn_hash_new(void) {
+    kn_value value = { .tag = KN_HASH };
+    value.as.hash = kn_alloc(sizeof(kn_hash));
+    return value;
+}
+
+static kn_value kn_closure_new(kn_code code, void *env, int arity, const char *source) {
+    kn_value value = { .tag = KN_FUNCTION };
+    value.as.function = kn_alloc(sizeof(kn_closure));
+    value.as.function->code = code;
+    value.as.function->env = env;
+    value.as.function->arity = arity;
+    value.as.function->source = source;
+    return value;
+}
+
+static kn_value kn_builtin_value(const kn_builtin *builtin) {
+    kn_value value = { .tag = KN_BUILTIN };
+    value.as.builtin = builtin;
+    return value;
+}
+
+/* Names an anonymous function after the binding it is first assigned to. */
+static kn_value kn_named(kn_value value, const char *name) {
+    if (value.tag == KN_FUNCTION && !value.as.function->name) {
+        kn_closure *named = kn_alloc(sizeof(kn_closure));
+        *named = *value.as.function;
+        named->name = name;
+        value.as.function = named;
+    }
+    return value;
+}
+
+/* Types */
+
+static const char *kn_type_name(kn_value value) {
+    switch (value.tag) {
+    case KN_NULL: return "NULL";
+    case KN_INTEGER: return "INTEGER";
+    case KN_FLOAT: return "FLOAT";
+    case KN_BOOLEAN: return "BOOLEAN";
+    case KN_STRING: return "STRING";
+    case KN_ARRAY: return "ARRAY";
+    case KN_HASH: return "HASH";
+    case KN_FUNCTION: return "FUNCTION";
+    case KN_BUILTIN: return "BUILTIN";
+    default: return "UNDEFINED";
+    }
+}
+
+static const char *kn_describe_type(kn_value value) {
+    if (value.tag == KN_INTEGER) {
+        return kn_int_width_name(value.as.integer.width);
+    }
+    if (value.tag == KN_FLOAT) {
+        return value.as.flt.width == KN_F32 ? "f32" : "f64";
+    }
+    return kn_type_name(value);
+}
+
+static bool kn_truthy(kn_value value) {
+    return !(value.tag == KN_NULL || (value.tag == KN_BOOLEAN && !value.as.boolean));
+}
+
+/* Display */
+
+static void kn_put_i128(kn_buf *buf, kn_i128 value) {
+    char digits[48];
+    int at = sizeof digits;
+    unsigned __int128 magnitude = value < 0 ? -(unsigned __int128)value : (unsigned __int128)value;
+    digits[--at] = '\0';
+    do {
+        digits[--at] = (char)('0' + (int)(magnitude % 10));
+        magnitude /= 10;
+    } while (magnitude);
+    if (value < 0) {
+        digits[--at] = '-';
+    }
+    kn_buf_puts(buf, digits + at);
+}
+
+/* The shortest decimal digits that read back as `value` at its width, and the
+ * power of ten of the first digit, as Rust prints floats. */
+static void kn_shortest_digits(double value, bool f32, char *digits, int *exponent) {
+    char text[64];
+    for (int precision = 0; precision < 17; precision++) {
+        snprintf(text, sizeof text, "%.*e", precision, value);
+        if (f32 ? strtof(text, NULL) == (float)value : strtod(text, NULL) == value) {
+            break;
+        }
+    }
+    int len = 0;
+    char *at = text;
+    for (; *at && *at != 'e'; at++) {
+        if (*at >= '0' && *at <= '9') {
+            digits[len++] = *at;
+        }
+    }
+    while (len > 1 && digits[len - 1] == '0') {
+        len--;
+    }
+    digits[len] = '\0';
+    *exponent = atoi(at + 1);
+}
+
+/* Formats a float like Rust: `debug` selects `{:?}`, which keeps a `.0` and
+ * switches to exponents for very large and small numbers, over `{}`. */
+static void kn_put_float(kn_buf *buf, double value, bool f32, bool debug) {
+    if (isnan(value)) {
+        kn_buf_puts(buf, "NaN");
+        return;
+    }
+    if (signbit(value)) {
+        kn_buf_puts(buf, "-");
+        value = -value;
+    }
+    if (isinf(value)) {
+        kn_buf_puts(buf, "inf");
+        return;
+    }
+    char digits[32];
+    int exponent = 0;
+    if (value == 0) {
+        strcpy(digits, "0");
+    } else {
+        kn_shortest_digits(value, f32, digits, &exponent);
+    }
+    int len = (int)strlen(digits);
+    if (debug && value != 0 && (value < 1e-4 || value >= 1e16)) {
+        kn_buf_put(buf, digits, 1);
+        if (len > 1) {
+            kn_buf_puts(buf, ".");
+            kn_buf_puts(buf, digits + 1);
+        }
+        char suffix[16];
+        snprintf(suffix, sizeof suffix, "e%d", exponent);
+        kn_buf_puts(buf, suffix);
+        return;
+    }
+    if (exponent < 0) {
+        kn_buf_puts(buf, "0.");
+        for (int i = 0; i < -exponent - 1; i++) {
+            kn_buf_puts(buf, "0");
+        }
+        kn_buf_puts(buf, digits);
+        return;
+    }
+    for (int i = 0; i <= exponent; i++) {
+        kn_buf_put(buf, i < len ? &digits[i] : "0", 1);
+    }
+    if (len > exponent + 1) {
+        kn_buf_puts(buf, ".");
+        kn_buf_puts(buf, digits + exponent + 1);
+    } else if (debug) {
+        kn_buf_puts(buf, ".0");
+    }
+}
+
+/* Quotes a string like Rust's `{:?}`. */
+static void kn_put_quoted(kn_buf *buf, const kn_string *string) {
+    kn_buf_puts(buf, "\"");
+    for (size_t i = 0; i < string->len; i++) {
+        unsigned char c = (unsigned char)string->data[i];
+        char escape[16];
+        switch (c) {
+        case '"': kn_buf_puts(buf, "\\\""); break;
+        case '\\': kn_buf_puts(buf, "\\\\"); break;
+        case '\n': kn_buf_puts(buf, "\\n"); break;
+        case '\r': kn_buf_puts(buf, "\\r"); break;
+        case '\t': kn_buf_puts(buf, "\\t"); break;
+        case '\0': kn_buf_puts(buf, "\\0"); break;
+        default:
+            if (c < 0x20 || c == 0x7f) {
+                snprintf(escape, sizeof escape, "\\u{%x}", c);
+                kn_buf_puts(buf, escape);
+            } else {
+                kn_buf_put(buf, (const char *)&c, 1);
+            }
+        }
+    }
+    kn_buf_puts(buf, "\"");
+}
+
+static void kn_display(kn_buf *buf, kn_value value, bool inspect) {
+    switch (value.tag) {
+    case KN_NULL: kn_buf_puts(buf, "null"); break;
+    case KN_INTEGER: kn_put_i128(buf, kn_int_value(value)); break;
+    case KN_FLOAT: kn_put_float(buf, kn_float_value(value), value.as.flt.width == KN_F32, true); break;
+    case KN_BOOLEAN: kn_buf_puts(buf, value.as.boolean ? "true" : "false"); break;
+    case KN_STRING:
+        if (inspect) {
+            kn_put_quoted(buf, value.as.string);
+        } else {
+            kn_buf_put(buf, value.as.string->data, value.as.string->len);
+        }
+        break;
+    case KN_ARRAY:
+        kn_buf_puts(buf, "[");
+        for (size_t i = 0; i < value.as.array->len; i++) {
+            kn_buf_puts(buf, i ? ", " : "");
+            kn_display(buf, value.as.array->items[i], true);
+        }
+        kn_buf_puts(buf, "]");
+        break;
+    case KN_HASH:
+        kn_buf_puts(buf, "{");
+        for (size_t i = 0; i < value.as.hash->len; i++) {
+            kn_buf_puts(buf, i ? ", " : "");
+            kn_display(buf, value.as.hash->keys[i], true);
+            kn_buf_puts(buf, ": ");
+            kn_display(buf, value.as.hash->values[i], true);
+        }
+        kn_buf_puts(buf, "}");
+        break;
+    case KN_FUNCTION: kn_buf_puts(buf, value.as.function->source); break;
+    case KN_BUILTIN:
+        kn_buf_puts(buf, "<builtin ");
+        kn_buf_puts(buf, value.as.builtin->name);
+        kn_buf_puts(buf, ">");
+        break;
+    default: break;
+    }
+}
+
+static const char *kn_to_string(kn_value value, bool inspect) {
+    kn_buf buf = { 0 };
+    kn_display(&buf, value, inspect);
+    return kn_buf_text(&buf);
+}
+
+/* Variables */
+
+/* Reads a variable from the innermost of `slots` that has been bound, falling back to a builtin. */
+static kn_value kn_lookup(kn_value **slots, int count, const char *name, kn_value fallback) {
+    for (int i = 0; i < count; i++) {
+        if (slots[i]->tag != KN_UNDEF) {
+            return *slots[i];
+        }
+    }
+    if (fallback.tag == KN_UNDEF) {
+        kn_fail("identifier not found: %s", name);
+    }
+    return fallback;
+}
+
+/* Operators */
+
+static const char *kn_op_name(kn_op op) {
+    static const char *names[] = { "+", "-", "*", "/", "==", "!=", "<", "<=", ">", ">=" };
+    return names[op];
+}
+
+static const char *kn_op_verb(kn_op op) {
+    static const char *verbs[] = { "add", "subtract", "multiply", "divide" };
+    return op <= KN_DIV ? verbs[op] : "compare";
+}
+
+static bool kn_compare(kn_op op, int ordering, bool unordered) {
+    if (unordered) {
+        return op == KN_NE;
+    }
+    switch (op) {
+    case KN_EQ: return ordering == 0;
+    case KN_NE: return ordering != 0;
+    case KN_LT: return ordering < 0;
+    case KN_LE: return ordering <= 0;
+    case KN_GT: return ordering > 0;
+    case KN_GE: return ordering >= 0;
+    default: return false;
+    }
+}
+
+/* Fits the exact result of an operation on integers of `width`, as `Integer::checked`. */
+static kn_value kn_int_checked(kn_int_width width, kn_i128 result, bool overflow, const char *verb) {
+    if (overflow || !kn_int_fits(width, result)) {
+        kn_fail("attempt to %s with overflow", verb);
+    }
+    return kn_int(width, result);
+}
+
+static kn_value kn_int_binary(kn_op op, kn_value left, kn_value right) {
+    kn_i128 l = kn_int_value(left), r = kn_int_value(right), result = 0;
+    kn_int_width width = left.as.integer.width;
+    bool overflow = false;
+    if (op == KN_DIV && r == 0) {
+        kn_fail("division by zero");
+    }
+    if (width != right.as.integer.width) {
+        kn_fail("integer width mismatch: cannot %s %s and %s", kn_op_verb(op), kn_int_width_name(width),
+                kn_int_width_name(right.as.integer.width));
+    }
+    switch (op) {
+    case KN_ADD: overflow = __builtin_add_overflow(l, r, &result); break;
+    case KN_SUB: overflow = __builtin_sub_overflow(l, r, &result); break;
+    case KN_MUL: overflow = __builtin_mul_overflow(l, r, &result); break;
+    case KN_DIV:
+        overflow = r == -1 && l == (kn_i128)((unsigned __int128)1 << 127);
+        result = overflow ? 0 : l / r;
+        break;
+    default: return kn_bool(kn_compare(op, (l > r) - (l < r), false));
+    }
+    return kn_int_checked(width, result, overflow, kn_op_verb(op));
+}
+
+static kn_value kn_float_binary(kn_op op, kn_value left, kn_value right) {
+    if (left.as.flt.width != right.as.flt.width) {
+        kn_fail("float width mismatch: cannot %s %s and %s", kn_op_verb(op), kn_describe_type(left), kn_describe_type(right));
+    }
+    double l = kn_float_value(left), r = kn_float_value(right);
+    switch (op) {
+    case KN_ADD: return kn_float_like(left, l + r);
+    case KN_SUB: return kn_float_like(left, l - r);
+    case KN_MUL: return kn_float_like(left, l * r);
+    case KN_DIV: return kn_float_like(left, l / r);
+    default: return kn_bool(kn_compare(op, (l > r) - (l < r), isnan(l) || isnan(r)));
+    }
+}
+
+static kn_value kn_binary(kn_op op, kn_value left, kn_value right) {
+    if (left.tag == KN_INTEGER && right.tag == KN_INTEGER) {
+        return kn_int_binary(op, left, right);
+    }
+    if (left.tag == KN_FLOAT && right.tag == KN_FLOAT) {
+        return kn_float_binary(op, left, right);
+    }
+    if (left.tag == KN_STRING && right.tag == KN_STRING) {
+        const kn_string *l = left.as.string, *r = right.as.string;
+        bool equal = l->len == r->len && memcmp(l->data, r->data, l->len) == 0;
+        if (op == KN_ADD) {
+            kn_value result = kn_str(l->data, l->len + r->len);
+            memcpy(result.as.string->data + l->len, r->data, r->len);
+            return result;
+        }
+        if (op == KN_EQ || op == KN_NE) {
+            return kn_bool(equal == (op == KN_EQ));
+        }
+        kn_fail("unknown operator: STRING %s STRING", kn_op_name(op));
+    }
+    if (left.tag == KN_BOOLEAN && right.tag == KN_BOOLEAN) {
+        if (op == KN_EQ || op == KN_NE) {
+            return kn_bool((left.as.boolean == right.as.boolean) == (op == KN_EQ));
+        }
+        kn_fail("unknown operator: BOOLEAN %s BOOLEAN", kn_op_name(op));
+    }
+    const char *problem = strcmp(kn_type_name(left), kn_type_name(right)) ? "type mismatch" : "unknown operator";
+    kn_fail("%s: %s %s %s", problem, kn_type_name(left), kn_op_name(op), kn_type_name(right));
+    return kn_null();
+}
+
+static kn_value kn_not(kn_value right) {
+    return kn_bool(!kn_truthy(right));
+}
+
+static kn_value kn_negate(kn_value right) {
+    if (right.tag == KN_INTEGER) {
+        kn_i128 value = kn_int_value(right);
+        bool overflow = value == (kn_i128)((unsigned __int128)1 << 127);
+        return kn_int_checked(right.as.integer.width, overflow ? 0 : -value, overflow, "negate");
+    }
+    if (right.tag == KN_FLOAT) {
+        return kn_float_like(right, -kn_float_value(right));
+    }
+    kn_fail("unknown operator: -%s", kn_type_name(right));
+    return kn_null();
+}
+
+/* Hashes */
+
+static void kn_check_key(kn_value key) {
+    if (key.tag != KN_BOOLEAN && key.tag != KN_INTEGER && key.tag != KN_STRING) {
+        kn_fail("unusable as hash key: %s", kn_type_name(key));
+    }
+}
+
+/* Orders keys as the interpreter does: booleans, then integers by width and value, then strings. */
+static int kn_key_compare(kn_value left, kn_value right) {
+    if (left.tag != right.tag) {
+        int rank[] = { [KN_BOOLEAN] = 0, [KN_INTEGER] = 1, [KN_STRING] = 2 };
+        return rank[left.tag] - rank[right.tag];
+    }
+    if (left.tag == KN_BOOLEAN) {
+        return (int)left.as.boolean - (int)right.as.boolean;
+    }
+    if (left.tag == KN_INTEGER) {
+        if (left.as.integer.width != right.as.integer.width) {
+            return (int)left.as.integer.width - (int)right.as.integer.width;
+        }
+        kn_i128 l = kn_int_value(left), r = kn_int_value(right);
+        return (l > r) - (l < r);
+    }
+    const kn_string *l = left.as.string, *r = right.as.string;
+    int order = memcmp(l->data, r->data, l->len < r->len ? l->len : r->len);
+    return order ? order : (l->len > r->len) - (l->len < r->len);
+}
+
+/* The position of `key` in `hash`, or where it would be inserted. */
+static size_t kn_hash_find(const kn_hash *hash, kn_value key, bool *found) {
+    size_t low = 0, high = hash->len;
+    while (low < high) {
+        size_t middle = (low + high) / 2;
+        int order = kn_key_compare(hash->keys[middle], key);
+        if (order == 0) {
+            *found = true;
+            return middle;
+        }
+        if (order < 0) {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    *found = false;
+    return low;
+}
+
+static void kn_hash_set(kn_value hash, kn_value key, kn_value value) {
+    kn_hash *pairs = hash.as.hash;
+    bool found;
+    size_t at = kn_hash_find(pairs, key, &found);
+    if (found) {
+        pairs->values[at] = value;
+        return;
+    }
+    pairs->keys = realloc(pairs->keys, (pairs->len + 1) * sizeof(kn_value));
+    pairs->values = realloc(pairs->values, (pairs->len + 1) * sizeof(kn_value));
+    if (!pairs->keys || !pairs->values) {
+        kn_fail("out of memory");
+    }
+    memmove(pairs->keys + at + 1, pairs->keys + at, (pairs->len - at) * sizeof(kn_value));
+    memmove(pairs->values + at + 1, pairs->values + at, (pairs->len - at) * sizeof(kn_value));
+    pairs->keys[at] = key;
+    pairs->values[at] = value;
+    pairs->len++;
+}
+
+/* Indexing and calls */
+
+static kn_value kn_index(kn_value left, kn_value index) {
+    if (left.tag == KN_ARRAY && index.tag == KN_INTEGER) {
+        kn_i128 at = kn_int_value(index);
+        if (at < 0 || (unsigned __int128)at >= left.as.array->len) {
+            kn_buf buf = { 0 };
+            kn_put_i128(&buf, at);
+            kn_fail("index out of bounds: the length is %zu but the index is %s", left.as.array->len, kn_buf_text(&buf));
+        }
+        return left.as.array->items[(size_t)at];
+    }
+    if (left.tag == KN_HASH) {
+        bool found;
+        kn_check_key(index);
+        size_t at = kn_hash_find(left.as.hash, index, &found);
+        return found ? left.as.hash->values[at] : kn_null();
+    }
+    kn_fail("index operator not supported: %s[%s]", kn_type_name(left), kn_type_name(index));
+    return kn_null();
+}
+
+static kn_value kn_call(kn_value function, int argc, kn_value *argv) {
+    if (function.tag == KN_BUILTIN) {
+        return function.as.builtin->function(argc, argv);
+    }
+    if (function.tag != KN_FUNCTION) {
+        kn_fail("not a function: %s", kn_type_name(function));
+    }
+    kn_closure *closure = function.as.function;
+    if (argc != closure->arity) {
+        kn_fail("wrong number of arguments to %s: expected %d, got %d", closure->name ? closure->name : "<anonymous fn>",
+                closure->arity, argc);
+    }
+    return closure->code(closure, argc, argv);
+}
+
+/* Builtins */
+
+static void kn_arity(const char *name, int argc, int expected) {
+    if (argc != expected) {
+        kn_fail("wrong number of arguments to %s: expected %d, got %d", name, expected, argc);
+    }
+}
+
+static void kn_unsupported(const char *name, kn_value arg) {
+    kn_fail("argument to `%s` not supported, got %s", name, kn_type_name(arg));
+}
+
+static kn_value kn_write_out(int argc, kn_value *argv, bool newline) {
+    kn_buf buf = { 0 };
+    for (int i = 0; i < argc; i++) {
+        kn_buf_puts(&buf, i ? " " : "");
+        kn_display(&buf, argv[i], false);
+    }
+    fwrite(kn_buf_text(&buf), 1, buf.len, stdout);
+    if (newline) {
+        fputc('\n', stdout);
+    } else {
+        fflush(stdout);
+    }
+    return kn_null();
+}
+
+static kn_value kn_print(int argc, kn_value *argv) {
+    return kn_write_out(argc, argv, false);
+}
+
+static kn_value kn_println(int argc, kn_value *argv) {
+    return kn_write_out(argc, argv, true);
+}
+
+static kn_value kn_len(int argc, kn_value *argv) {
+    kn_arity("len", argc, 1);
+    switch (argv[0].tag) {
+    case KN_STRING: {
+        int64_t chars = 0;
+        for (size_t i = 0; i < argv[0].as.string->len; i++) {
+            chars += ((unsigned char)argv[0].as.string->data[i] & 0xc0) != 0x80;
+        }
+        return kn_int(KN_I64, chars);
+    }
+    case KN_ARRAY: return kn_int(KN_I64, (int64_t)argv[0].as.array->len);
+    case KN_HASH: return kn_int(KN_I64, (int64_t)argv[0].as.hash->len);
+    default: kn_unsupported("len", argv[0]); return kn_null();
+    }
+}
+
+static kn_value kn_str_builtin(int argc, kn_value *argv) {
+    kn_arity("str", argc, 1);
+    const char *text = kn_to_string(argv[0], false);
+    return kn_str(text, strlen(text));
+}
+
+static const kn_array *kn_array_arg(const char *name, int argc, kn_value *argv) {
+    kn_arity(name, argc, 1);
+    if (argv[0].tag != KN_ARRAY) {
+        kn_unsupported(name, argv[0]);
+    }
+    return argv[0].as.array;
+}
+
+static kn_value kn_first(int argc, kn_value *argv) {
+    const kn_array *array = kn_array_arg("first", argc, argv);
+    return array->len ? array->items[0] : kn_null();
+}
+
+static kn_value kn_last(int argc, kn_value *argv) {
+    const kn_array *array = kn_array_arg("last", argc, argv);
+    return array->len ? array->items[array->len - 1] : kn_null();
+}
+
+static kn_value kn_rest(int argc, kn_value *argv) {
+    const kn_array *array = kn_array_arg("rest", argc, argv);
+    return array->len ? kn_array_of(array->len - 1, array->items + 1) : kn_null();
+}
+
+static kn_value kn_push(int argc, kn_value *argv) {
+    kn_arity("push", argc, 2);
+    if (argv[0].tag != KN_ARRAY) {
+        kn_unsupported("push", argv[0]);
+    }
+    const kn_array *array = argv[0].as.array;
+    kn_value result = kn_array_of(array->len + 1, NULL);
+    memcpy(result.as.array->items, array->items, array->len * sizeof(kn_value));
+    result.as.array->items[array->len] = argv[1];
+    return result;
+}
+
+static kn_value kn_keys(int argc, kn_value *argv) {
+    kn_arity("keys", argc, 1);
+    if (argv[0].tag != KN_HASH) {
+        kn_unsupported("keys", argv[0]);
+    }
+    return kn_array_of(argv[0].as.hash->len, argv[0].as.hash->keys);
+}
+
+static kn_value kn_values(int argc, kn_value *argv) {
+    kn_arity("values", argc, 1);
+    if (argv[0].tag != KN_HASH) {
+        kn_unsupported("values", argv[0]);
+    }
+    return kn_array_of(argv[0].as.hash->len, argv[0].as.hash->values);
+}
+
+static kn_value kn_abs(int argc, kn_value *argv) {
+    kn_arity("abs", argc, 1);
+    if (argv[0].tag == KN_INTEGER) {
+        kn_i128 value = kn_int_value(argv[0]);
+        bool overflow = value == (kn_i128)((unsigned __int128)1 << 127);
+        return kn_int_checked(argv[0].as.integer.width, overflow || value >= 0 ? value : -value, overflow, "take absolute value");
+    }
+    if (argv[0].tag == KN_FLOAT) {
+        return kn_float_like(argv[0], fabs(kn_float_value(argv[0])));
+    }
+    kn_unsupported("abs", argv[0]);
+    return kn_null();
+}
+
+/* Orders doubles like Rust's `f64::total_cmp`. */
+static int kn_total_compare(double left, double right) {
+    int64_t l, r;
+    memcpy(&l, &left, sizeof l);
+    memcpy(&r, &right, sizeof r);
+    l ^= (int64_t)((uint64_t)(l >> 63) >> 1);
+    r ^= (int64_t)((uint64_t)(r >> 63) >> 1);
+    return (l > r) - (l < r);
+}
+
+static kn_value kn_extremum(const char *name, int argc, kn_value *argv, int sign) {
+    kn_arity(name, argc, 2);
+    kn_value left = argv[0], right = argv[1];
+    int ordering;
+    if (left.tag == KN_INTEGER && right.tag == KN_INTEGER && left.as.integer.width == right.as.integer.width) {
+        kn_i128 l = kn_int_value(left), r = kn_int_value(right);
+        ordering = (l > r) - (l < r);
+    } else if (left.tag == KN_FLOAT && right.tag == KN_FLOAT && left.as.flt.width == right.as.flt.width) {
+        ordering = kn_total_compare(kn_float_value(left), kn_float_value(right));
+    } else {
+        kn_fail("arguments to `%s` must be numbers of the same type, got %s and %s", name, kn_describe_type(left),
+                kn_describe_type(right));
+        return kn_null();
+    }
+    return ordering * sign > 0 ? left : right;
+}
+
+static kn_value kn_min(int argc, kn_value *argv) {
+    return kn_extremum("min", argc, argv, -1);
+}
+
+static kn_value kn_max(int argc, kn_value *argv) {
+    return kn_extremum("max", argc, argv, 1);
+}
+
+static bool kn_parse_i128(const char *text, kn_i128 *value) {
+    bool negative = *text == '-';
+    text += *text == '-' || *text == '+';
+    if (!*text) {
+        return false;
+    }
+    unsigned __int128 magnitude = 0, limit = ((unsigned __int128)1 << 127) - !negative;
+    for (; *text; text++) {
+        if (*text < '0' || *text > '9') {
+            return false;
+        }
+        unsigned digit = (unsigned)(*text - '0');
+        if (magnitude > (limit - digit) / 10) {
+            return false;
+        }
+        magnitude = magnitude * 10 + digit;
+    }
+    *value = negative ? (kn_i128)(0 - magnitude) : (kn_i128)magnitude;
+    return true;
+}
+
+/* The argument of a conversion as text with surrounding whitespace removed. */
+static const char *kn_trimmed(const kn_string *string) {
+    size_t start = 0, end = string->len;
+    while (start < end && strchr(" \t\n\r\f\v", string->data[start])) {
+        start++;
+    }
+    while (end > start && strchr(" \t\n\r\f\v", string->data[end - 1])) {
+        end--;
+    }
+    char *text = kn_alloc(end - start + 1);
+    memcpy(text, string->data + start, end - start);
+    return text;
+}
+
+static kn_value kn_to_integer(kn_int_width width, int argc, kn_value *argv) {
+    const char *name = kn_int_width_name(width);
+    kn_arity(name, argc, 1);
+    kn_value arg = argv[0];
+    kn_i128 value = 0;
+    kn_buf buf = { 0 };
+    switch (arg.tag) {
+    case KN_INTEGER: value = kn_int_value(arg); break;
+    case KN_FLOAT: {
+        double number = kn_float_value(arg);
+        if (!isfinite(number) || trunc(number) < -0x1p127 || trunc(number) >= 0x1p127) {
+            kn_put_float(&buf, number, false, false);
+            kn_fail("%s out of range for %s", kn_buf_text(&buf), name);
+        }
+        value = (kn_i128)trunc(number);
+        break;
+    }
+    case KN_STRING:
+        if (!kn_parse_i128(kn_trimmed(arg.as.string), &value)) {
+            kn_fail("could not parse %s as %s", kn_to_string(arg, true), name);
+        }
+        break;
+    case KN_BOOLEAN: value = arg.as.boolean; break;
+    default: kn_unsupported(name, arg);
+    }
+    if (!kn_int_fits(width, value)) {
+        kn_put_i128(&buf, value);
+        kn_fail("%s out of range for %s", kn_buf_text(&buf), name);
+    }
+    return kn_int(width, value);
+}
+
+static kn_value kn_i8_builtin(int argc, kn_value *argv) { return kn_to_integer(KN_I8, argc, argv); }
+static kn_value kn_i16_builtin(int argc, kn_value *argv) { return kn_to_integer(KN_I16, argc, argv); }
+static kn_value kn_i32_builtin(int argc, kn_value *argv) { return kn_to_integer(KN_I32, argc, argv); }
+static kn_value kn_i64_builtin(int argc, kn_value *argv) { return kn_to_integer(KN_I64, argc, argv); }
+static kn_value kn_i128_builtin(int argc, kn_value *argv) { return kn_to_integer(KN_I128, argc, argv); }
+
+static kn_value kn_to_float(kn_float_width width, int argc, kn_value *argv) {
+    const char *name = width == KN_F32 ? "f32" : "f64";
+    kn_arity(name, argc, 1);
+    kn_value arg = argv[0];
+    double value = 0;
+    switch (arg.tag) {
+    case KN_INTEGER: value = (double)kn_int_value(arg); break;
+    case KN_FLOAT: value = kn_float_value(arg); break;
+    case KN_STRING: {
+        const char *text = kn_trimmed(arg.as.string);
+        char *end;
+        value = strtod(text, &end);
+        if (!*text || *end) {
+            kn_fail("could not parse %s as %s", kn_to_string(arg, true), name);
+        }
+        break;
+    }
+    default: kn_unsupported(name, arg);
+    }
+    return width == KN_F32 ? kn_f32((float)value) : kn_f64(value);
+}
+
+static kn_value kn_f32_builtin(int argc, kn_value *argv) { return kn_to_float(KN_F32, argc, argv); }
+static kn_value kn_f64_builtin(int argc, kn_value *argv) { return kn_to_float(KN_F64, argc, argv); }
+
+static kn_value kn_assert(int argc, kn_value *argv) {
+    if (argc != 2) {
+        kn_arity("assert", argc, 1);
+    }
+    if (!kn_truthy(argv[0])) {
+        if (argc == 2) {
+            kn_fail("assertion failed: %s", kn_to_string(argv[1], false));
+        }
+        kn_fail("assertion failed");
+    }
+    return kn_null();
+}
+
+static const kn_builtin kn_builtins[] = {
+    { "print", kn_print }, { "println", kn_println }, { "len", kn_len }, { "str", kn_str_builtin },
+    { "first", kn_first }, { "last", kn_last }, { "rest", kn_rest }, { "push", kn_push },
+    { "keys", kn_keys }, { "values", kn_values }, { "abs", kn_abs }, { "min", kn_min },
+    { "max", kn_max }, { "assert", kn_assert }, { "i8", kn_i8_builtin }, { "i16", kn_i16_builtin },
+    { "i32", kn_i32_builtin }, { "i64", kn_i64_builtin }, { "i128", kn_i128_builtin }, { "f32", kn_f32_builtin },
+    { "f64", kn_f64_builtin },
+};
+
+static kn_value kn_builtin_named(const char *name) {
+    for (size_t i = 0; i < sizeof kn_builtins / sizeof kn_builtins[0]; i++) {
+        if (strcmp(kn_builtins[i].name, name) == 0) {
+            return kn_builtin_value(&kn_builtins[i]);
+        }
+    }
+    kn_fail("identifier not found: %s", name);
+    return kn_null();
+}
+
+/* Prints the value of the program, as `keynes run` does, and exits. */
+static int kn_finish(kn_value result) {
+    if (result.tag != KN_NULL) {
+        puts(kn_to_string(result, false));
+    }
+    fflush(stdout);
+    return 0;
+}
@@ -0,0 +1,99 @@
+use std::{
+    path::PathBuf,
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::*;
+
+use crate::{backend::front_end, optimizer::OptLevel};
+
+use test_case::test_case;
+
+static NEXT_BINARY: AtomicUsize = AtomicUsize::new(0);
+
+fn c_source(source: &str) -> Result<String, Vec<String>> {
+    let diagnostics = |diagnostics: Vec<Diagnostic>| diagnostics.into_iter().map(|diagnostic| diagnostic.message).collect::<Vec<_>>();
+    let program = front_end(source, OptLevel::O1).map_err(diagnostics)?;
+    compile("main.ks", &program).map_err(diagnostics)
+}
+
+/// Compiles and runs `source`, returning its exit code, stdout and stderr.
+fn run(source: &str) -> (i32, String, String) {
+    let c_source = c_source(source).unwrap();
+    let binary = std::env::temp_dir().join(format!(
+        "keynes-c-{}-{}",
+        std::process::id(),
+        NEXT_BINARY.fetch_add(1, Ordering::SeqCst)
+    ));
+    build(&c_source, &binary).unwrap();
+    let output = Command::new(&binary).output().unwrap();
+    let _ = std::fs::remove_file(&binary);
+    (
+        output.status.code().unwrap_or(-1),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+#[test_case("1 + 2 * 3", "7\n"; "arithmetic")]
+#[test_case("println(\"hello\"); print(\"a\", 1, true)", "hello\na 1 true"; "printing")]
+#[test_case("let add = fn(a) { fn(b) { a + b } }; add(2)(40)", "42\n"; "closures")]
+#[test_case("let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) }; fib(20)", "6765\n"; "recursion")]
+#[test_case("let x = 1; let f = fn() { x }; let x = 2; f()", "2\n"; "closures see rebinding")]
+#[test_case("let f = fn(x) { let y = x * 2; if (y > 5) { let y = 0; } y }; [f(1), f(3)]", "[2, 0]\n"; "blocks share the function scope")]
+#[test_case("i8(100) + i8(27)", "127\n"; "i8")]
+#[test_case("i128(9223372036854775807) * i128(4)", "36893488147419103228\n"; "i128")]
+#[test_case("[1.0, 0.1 + 0.2, f32(0.1), 1000000000000000000000.0, 1.0 / 3.0]", "[1.0, 0.30000000000000004, 0.1, 1e21, 0.3333333333333333]\n"; "float display")]
+#[test_case("{\"b\": 1, 2: 2, true: 3, \"a\": 4}", "{true: 3, 2: 2, \"a\": 4, \"b\": 1}\n"; "hash key order")]
+#[test_case("let h = {\"k\": [1, 2]}; h[\"k\"][1] + len(\"four\")", "6\n"; "indexing")]
+#[test_case("let a = push([1], 2); [first(a), last(a), rest(a), len(a)]", "[1, 2, [2], 2]\n"; "array builtins")]
+#[test_case("[keys({\"x\": 1}), values({\"x\": 1}), abs(-3), min(4, 2), max(4, 2), str(1.5)]", "[[\"x\"], [1], 3, 2, 4, \"1.5\"]\n"; "other builtins")]
+#[test_case("if (1 > 2) { 1 }", ""; "null result prints nothing")]
+#[test_case("return 5; 6", "5\n"; "top level return")]
+#[test_case("let len = fn(x) { 0 }; len(\"abc\")", "0\n"; "shadowed builtin")]
+#[test_case("test \"ignored\" { assert(false) } 1", "1\n"; "tests are skipped")]
+fn test_compiled_output(source: &str, expected: &str) {
+    assert_eq!(run(source), (0, expected.to_string(), String::new()));
+}
+
+#[test_case("let xs = [true]; 1 + xs[0]", "type mismatch: INTEGER + BOOLEAN"; "type mismatch")]
+#[test_case("i8(100) + 100", "integer width mismatch: cannot add i8 and i64"; "width mismatch")]
+#[test_case("i8(100) + i8(28)", "attempt to add with overflow"; "overflow")]
+#[test_case("let x = 0; 1 / x", "division by zero"; "division by zero")]
+#[test_case("[1, 2][5]", "index out of bounds: the length is 2 but the index is 5"; "index out of bounds")]
+#[test_case("let fs = [fn(a) { a }]; fs[0](1, 2)", "wrong number of arguments to <anonymous fn>: expected 1, got 2"; "arity")]
+#[test_case("let xs = [1]; xs[0]()", "not a function: INTEGER"; "not a function")]
+#[test_case("len(1)", "argument to `len` not supported, got INTEGER"; "builtin argument")]
+fn test_runtime_errors(source: &str, message: &str) {
+    let (code, out, err) = run(source);
+    assert_eq!((code, out.as_str()), (1, ""));
+    assert!(err.starts_with(&format!("error: {}\n --> main.ks:1:", message)), "{}", err);
+}
+
+#[test]
+fn test_output_before_error_is_kept() {
+    let (code, out, err) = run("println(\"before\");\nlet total = 1 + [true][0];\nprintln(\"after\");");
+    assert_eq!((code, out.as_str()), (1, "before\n"));
+    assert_eq!(err, "error: type mismatch: INTEGER + BOOLEAN\n --> main.ks:2:1\n");
+}
+
+#[test_case("import \"other.ks\" as other;", "modules are not supported by the C backend"; "import")]
+#[test_case("let f = fn() { let x = if (true) { return 1; }; x };", "`return` inside an `if` used as a value is not supported by the C backend"; "return in value")]
+fn test_unsupported(source: &str, message: &str) {
+    assert_eq!(c_source(source), Err(vec![message.to_string()]));
+}
+
+#[test]
+fn test_programs_print_their_expected_output() {
+    let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("programs");
+    for entry in std::fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|extension| extension != "ks") || path.with_extension("err").exists() {
+            continue;
+        }
+        let expected = std::fs::read_to_string(path.with_extension("out")).unwrap_or_default().replace("\r\n", "\n");
+        let (code, out, err) = run(&std::fs::read_to_string(&path).unwrap());
+        assert_eq!((code, out, err), (0, expected, String::new()), "{}", path.display());
+    }
+}
@@ -0,0 +1,358 @@
+//! Ahead-of-time compilers for `keynes build`. Each backend lowers a program
+//! that parsed, checked and was optimized cleanly, as produced by [`front_end`].
+//!
+//! The WebAssembly and x86-64 backends share their type inference, which lives here:
+//! a function is compiled once for each combination of argument types it is called
+//! with, by `instance`, and each backend only generates the code of a `Frame`.
+
+pub mod c;
+pub mod wat;
+pub mod x86_64;
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    ast::{expressions::*, program::Program, statements::*},
+    checker::{check_program, collect_declared},
+    diagnostics::Diagnostic,
+    lexer::{Lexer, Span},
+    optimizer::{optimize, OptLevel},
+    parser::Parser,
+};
+
+/// Parses, checks and optimizes `source`, or returns why it cannot be compiled.
+pub fn front_end(source: &str, level: OptLevel) -> Result<Program, Vec<Diagnostic>> {
+    let mut lexer = Lexer::new(source.to_string());
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+    if !parser.errors.is_empty() {
+        return Err(parser.errors);
+    }
+    let analysis = check_program(&program);
+    if !analysis.diagnostics.is_empty() {
+        return Err(analysis.diagnostics);
+    }
+    Ok(optimize(program, level))
+}
+
+/// Compiles `source`, written to a temporary file with `extension`, to an executable at
+/// `output` with the system C compiler, `$CC` or `cc`. `libraries` come after the source.
+fn cc(source: &str, extension: &str, flags: &[&str], libraries: &[&str], output: &Path) -> Result<(), String> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let name = format!("keynes-{}-{}.{}", std::process::id(), NEXT.fetch_add(1, Ordering::SeqCst), extension);
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, source).map_err(|err| format!("could not write {}: {}", path.display(), err))?;
+
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let result = Command::new(&compiler)
+        .args(flags)
+        .arg("-o")
+        .arg(output)
+        .arg(&path)
+        .args(libraries)
+        .output();
+    let _ = std::fs::remove_file(&path);
+    match result {
+        Ok(result) if result.status.success() => Ok(()),
+        Ok(result) => Err(format!("{} failed:\n{}", compiler, String::from_utf8_lossy(&result.stderr))),
+        Err(err) => Err(format!("could not run {}: {}", compiler, err)),
+    }
+}
+
+/// The static type of a value. Integers narrower than 64 bits and floats are only
+/// supported by the WebAssembly backend.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    Null,
+    Boolean,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    /// Of a `return`, which does not produce a value where it is.
+    Never,
+    /// Of a recursive call while the return type of the function is still being inferred,
+    /// and of expressions that could not be compiled.
+    Unknown,
+}
+
+impl Type {
+    fn width(name: &str) -> Option<Type> {
+        match name {
+            "i8" => Some(Type::I8),
+            "i16" => Some(Type::I16),
+            "i32" => Some(Type::I32),
+            "i64" => Some(Type::I64),
+            "f32" => Some(Type::F32),
+            "f64" => Some(Type::F64),
+            _ => None,
+        }
+    }
+
+    /// Whether values of the type are held anywhere, which null and the types without
+    /// values are not.
+    fn has_value(self) -> bool {
+        self == Type::Boolean || self.is_integer() || self.is_float()
+    }
+
+    fn is_integer(self) -> bool {
+        matches!(self, Type::I8 | Type::I16 | Type::I32 | Type::I64)
+    }
+
+    fn is_float(self) -> bool {
+        matches!(self, Type::F32 | Type::F64)
+    }
+
+    /// The smallest and largest value of an integer type.
+    fn range(self) -> (i64, i64) {
+        match self {
+            Type::I8 => (i8::MIN.into(), i8::MAX.into()),
+            Type::I16 => (i16::MIN.into(), i16::MAX.into()),
+            Type::I32 => (i32::MIN.into(), i32::MAX.into()),
+            _ => (i64::MIN, i64::MAX),
+        }
+    }
+
+    /// Like `Object::type_name`.
+    fn name(self) -> &'static str {
+        match self {
+            Type::Null => "NULL",
+            Type::Boolean => "BOOLEAN",
+            Type::I8 | Type::I16 | Type::I32 | Type::I64 => "INTEGER",
+            Type::F32 | Type::F64 => "FLOAT",
+            Type::Never | Type::Unknown => "UNKNOWN",
+        }
+    }
+
+    /// Like `Object::describe_type`.
+    fn describe(self) -> &'static str {
+        match self {
+            Type::I8 => "i8",
+            Type::I16 => "i16",
+            Type::I32 => "i32",
+            Type::I64 => "i64",
+            Type::F32 => "f32",
+            Type::F64 => "f64",
+            other => other.name(),
+        }
+    }
+}
+
+/// The type of a place two values can end up in, such as the result of an `if` with both
+/// its branches, or `None` if they cannot share one.
+fn merge(a: Type, b: Type) -> Option<Type> {
+    match (a, b) {
+        _ if a == b => Some(a),
+        (Type::Never, other) | (other, Type::Never) => Some(other),
+        (Type::Unknown, other) | (other, Type::Unknown) => Some(other),
+        _ => None,
+    }
+}
+
+/// What becomes of the value of a statement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode<T = ()> {
+    /// It is kept, where `T` says if the backend needs telling.
+    Value(T),
+    Discard,
+    /// It is written out, as the value of the program.
+    Write,
+}
+
+/// The code a backend generates for one function.
+trait Code: Default {
+    /// Where the value of a variable is held.
+    type Variable: Copy;
+
+    /// Adds the parameter `name` of type `ty`, returning where it is held.
+    fn parameter(&mut self, name: &str, ty: Type) -> Self::Variable;
+}
+
+/// A function being compiled: one instance of a Keynes function, or `main`.
+struct Frame<C: Code> {
+    /// Name of the Keynes function, or `None` in `main`, whose variables are globals.
+    function: Option<String>,
+    /// The parameters and `let`s of the function, with their type and where they are held
+    /// once bound.
+    variables: HashMap<String, Option<(Type, C::Variable)>>,
+    /// Type of the values returned so far.
+    returns: Type,
+    /// Whether the result of a call was not yet known, so the function must be compiled again.
+    pending: bool,
+    code: C,
+}
+
+impl<C: Code> Frame<C> {
+    fn new(function: Option<String>) -> Frame<C> {
+        Frame {
+            function,
+            variables: HashMap::new(),
+            returns: Type::Never,
+            pending: false,
+            code: C::default(),
+        }
+    }
+
+    /// The frame of `function`, called `name`, for `arguments`, with its parameters bound.
+    fn instance(name: &str, function: &FunctionLiteral, arguments: &[Type]) -> Frame<C> {
+        let mut frame = Frame::<C>::new(Some(name.to_string()));
+        let mut declared = Vec::new();
+        collect_declared(&function.body.statements, &mut declared);
+        for name in declared {
+            frame.variables.insert(name, None);
+        }
+        for (parameter, ty) in function.parameters.iter().zip(arguments) {
+            let name = parameter.to_string();
+            let variable = frame.code.parameter(&name, *ty);
+            frame.variables.insert(name, Some((*ty, variable)));
+        }
+        frame
+    }
+}
+
+/// A Keynes function compiled for the given argument types.
+#[derive(Debug, Clone)]
+struct Instance {
+    name: String,
+    arguments: Vec<Type>,
+    symbol: String,
+    result: Type,
+}
+
+/// A backend's compiler, as [`instance`] and [`define_functions`] use it.
+trait Backend<'a> {
+    type Code: Code;
+
+    /// Name of the backend in diagnostics.
+    const NAME: &'static str;
+
+    /// Functions bound by `let` at the top level of the program.
+    fn functions(&mut self) -> &mut HashMap<String, &'a FunctionLiteral>;
+    fn instances(&mut self) -> &mut Vec<Instance>;
+    fn frame(&mut self) -> &mut Frame<Self::Code>;
+    fn diagnostics(&mut self) -> &mut Vec<Diagnostic>;
+
+    /// How `ty` is described in diagnostics.
+    fn describe(ty: Type) -> &'static str {
+        ty.describe()
+    }
+
+    /// The symbol of instance number `index` of `name`, for `arguments`.
+    fn symbol(&self, name: &str, arguments: &[Type], index: usize) -> String;
+
+    /// Compiles the body of `function` in the current frame, returning the type of its value.
+    fn body(&mut self, function: &FunctionLiteral) -> Type;
+
+    /// Keeps the code of `instance`, compiled into `frame`.
+    fn compiled(&mut self, instance: Instance, frame: Frame<Self::Code>);
+}
+
+/// Reports that `what` is not supported by the backend.
+fn unsupported<'a, B: Backend<'a>>(backend: &mut B, span: Span, what: &str) {
+    let message = format!("{} not supported by the {} backend", what, B::NAME);
+    backend.diagnostics().push(Diagnostic::new(message, span));
+}
+
+/// Records the functions bound by `let` at the top level of `statements`.
+fn define_functions<'a, B: Backend<'a>>(backend: &mut B, statements: &'a [Box<dyn Statement>]) {
+    let mut declared = Vec::new();
+    collect_declared(statements, &mut declared);
+    for statement in statements {
+        let Some(statement) = statement.as_any().downcast_ref::<LetStatement>() else {
+            continue;
+        };
+        let Some(function) = statement.value.as_any().downcast_ref::<FunctionLiteral>() else {
+            continue;
+        };
+        let name = statement.name.to_string();
+        if declared.iter().filter(|declared| **declared == name).count() > 1 {
+            unsupported(backend, statement.span, &format!("binding `{}` more than once when it is a function is", name));
+        }
+        backend.functions().entry(name).or_insert(function);
+    }
+}
+
+/// The symbol of the instance of `function` for `arguments`, compiling it on first use, and
+/// its result type.
+///
+/// A recursive call is compiled before the result type is known, so the body is compiled a
+/// second time once it is, as inferred from the values returned without recursing.
+fn instance<'a, B: Backend<'a>>(
+    backend: &mut B,
+    name: &str,
+    function: &'a FunctionLiteral,
+    arguments: Vec<Type>,
+) -> (String, Type) {
+    if let Some(instance) = backend.instances().iter().find(|i| i.name == name && i.arguments == arguments) {
+        return (instance.symbol.clone(), instance.result);
+    }
+    let index = backend.instances().len();
+    let symbol = backend.symbol(name, &arguments, index);
+    backend.instances().push(Instance {
+        name: name.to_string(),
+        arguments: arguments.clone(),
+        symbol: symbol.clone(),
+        result: Type::Unknown,
+    });
+
+    loop {
+        let outer = std::mem::replace(backend.frame(), Frame::instance(name, function, &arguments));
+        let value = backend.body(function);
+        let frame = std::mem::replace(backend.frame(), outer);
+        let result = match merge(value, frame.returns) {
+            Some(result) => result,
+            None => {
+                let what = format!("returning both {} and {} from `{}` is", B::describe(frame.returns), B::describe(value), name);
+                unsupported(backend, function.span, &what);
+                Type::Unknown
+            },
+        };
+
+        let retry = frame.pending && backend.instances()[index].result == Type::Unknown && result != Type::Unknown;
+        backend.instances()[index].result = result;
+        if retry {
+            continue;
+        }
+        if result == Type::Unknown && backend.diagnostics().is_empty() {
+            let message = format!("cannot infer the return type of `{}`", name);
+            backend.diagnostics().push(Diagnostic::new(message, function.span));
+        }
+        let instance = backend.instances()[index].clone();
+        backend.compiled(instance, frame);
+        return (symbol, result);
+    }
+}
+
+/// `diagnostics` without repeats, as a function compiled twice reports its problems twice.
+fn distinct(diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    let mut distinct: Vec<Diagnostic> = Vec::new();
+    for diagnostic in diagnostics {
+        if !distinct.contains(&diagnostic) {
+            distinct.push(diagnostic);
+        }
+    }
+    distinct
+}
+
+/// The span of `expression`, for the kinds of expressions the backends report errors in.
+fn span_of(expression: &dyn Expression) -> Option<Span> {
+    let any = expression.as_any();
+    if let Some(identifier) = any.downcast_ref::<IdentifierLiteral>() {
+        Some(identifier.span)
+    } else if let Some(prefix) = any.downcast_ref::<PrefixExpression>() {
+        Some(prefix.span)
+    } else if let Some(infix) = any.downcast_ref::<InfixExpression>() {
+        Some(infix.span)
+    } else if let Some(if_expression) = any.downcast_ref::<IfExpression>() {
+        Some(if_expression.span)
+    } else {
+        any.downcast_ref::<CallExpression>().map(|call| call.span)
+    }
+}
@@ -0,0 +1,1043 @@
+//! Compiles Keynes programs to the WebAssembly text format, for `keynes build --target wat`.
+//!
+//! Only the statically typed core of the language is supported: integers of up to 64 bits,
+//! floats and booleans, `if`, and functions bound by a top-level `let`. A function is
+//! compiled once for each combination of argument types it is called with. Strings may only
+//! be given to `print`, `println` and `assert`.
+//!
+//! The module imports its output from the host, under `keynes`: `write(ptr, len)` writes
+//! text from the exported `memory`, and `write_int(i64)`, `write_f32`, `write_f64` and
+//! `write_bool(i32)` write a value the way `keynes run` shows it. Runtime errors call
+//! `fail(ptr, len, location_ptr, location_len)` with the message and the `file:line:column`
+//! of the statement that failed, then trap. The exported `main` runs the program and
+//! writes its value, if any, on a line of its own.
+
+use std::collections::HashMap;
+
+use super::{define_functions, distinct, instance, merge, span_of, Backend, Code, Frame, Instance, Mode, Type};
+use crate::{
+    ast::{expressions::*, program::Program, statements::*},
+    builtins,
+    diagnostics::Diagnostic,
+    lexer::Span,
+};
+
+use log::*;
+
+/// Lowers `program`, read from `file`, to a WebAssembly module in the text format.
+pub fn compile(file: &str, program: &Program) -> Result<String, Vec<Diagnostic>> {
+    trace!("compile to WebAssembly: {}", file);
+    let mut compiler = Compiler {
+        file,
+        functions: HashMap::new(),
+        instances: Vec::new(),
+        compiled: Vec::new(),
+        globals: HashMap::new(),
+        global_declarations: Vec::new(),
+        data: Vec::new(),
+        messages: HashMap::new(),
+        frame: Frame::new(None),
+        span: Span::default(),
+        diagnostics: Vec::new(),
+    };
+    define_functions(&mut compiler, &program.statements);
+    let main = compiler.main(&program.statements);
+    if !compiler.diagnostics.is_empty() {
+        return Err(distinct(compiler.diagnostics));
+    }
+    Ok(compiler.module(main))
+}
+
+impl Type {
+    /// The WebAssembly type values of the type are held in. Integers narrower than 64 bits
+    /// and booleans are held in an `i32`.
+    fn wasm(self) -> Option<&'static str> {
+        match self {
+            Type::Boolean | Type::I8 | Type::I16 | Type::I32 => Some("i32"),
+            Type::I64 => Some("i64"),
+            Type::F32 => Some("f32"),
+            Type::F64 => Some("f64"),
+            Type::Null | Type::Never | Type::Unknown => None,
+        }
+    }
+}
+
+/// The code of a WebAssembly function.
+struct Body {
+    parameters: Vec<(String, &'static str)>,
+    locals: Vec<(String, &'static str)>,
+    lines: Vec<String>,
+    /// Indentation of the next instruction.
+    depth: usize,
+}
+
+impl Default for Body {
+    fn default() -> Body {
+        Body { parameters: Vec::new(), locals: Vec::new(), lines: Vec::new(), depth: 2 }
+    }
+}
+
+impl Code for Body {
+    /// Variables are held in the local or global named after them.
+    type Variable = ();
+
+    fn parameter(&mut self, name: &str, ty: Type) {
+        self.parameters.push((format!("v_{}", name), ty.wasm().unwrap()));
+    }
+}
+
+impl Body {
+    fn text(self, id: &str, export: Option<&str>, result: Type) -> String {
+        let mut text = format!("  (func ${}", id);
+        if let Some(export) = export {
+            text.push_str(&format!(" (export \"{}\")", export));
+        }
+        for (name, ty) in &self.parameters {
+            text.push_str(&format!(" (param ${} {})", name, ty));
+        }
+        if let Some(result) = result.wasm() {
+            text.push_str(&format!(" (result {})", result));
+        }
+        text.push('\n');
+        for (name, ty) in &self.locals {
+            text.push_str(&format!("    (local ${} {})\n", name, ty));
+        }
+        for line in &self.lines {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text.push_str("  )\n");
+        text
+    }
+}
+
+struct Compiler<'a> {
+    file: &'a str,
+    /// Functions bound by `let` at the top level of the program.
+    functions: HashMap<String, &'a FunctionLiteral>,
+    instances: Vec<Instance>,
+    compiled: Vec<String>,
+    globals: HashMap<String, Type>,
+    global_declarations: Vec<String>,
+    /// Contents of memory: every message and location, each stored once.
+    data: Vec<u8>,
+    messages: HashMap<String, usize>,
+    frame: Frame<Body>,
+    /// Span of the innermost statement or expression with one, for diagnostics.
+    span: Span,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Compiler<'a> {
+    fn emit(&mut self, instruction: impl AsRef<str>) {
+        let line = format!("{}{}", "  ".repeat(self.frame.code.depth), instruction.as_ref());
+        self.frame.code.lines.push(line);
+    }
+
+    fn error(&mut self, span: Span, message: String) {
+        self.diagnostics.push(Diagnostic::new(message, span));
+    }
+
+    fn unsupported(&mut self, span: Span, what: &str) -> Type {
+        super::unsupported(self, span, what);
+        Type::Unknown
+    }
+
+    /// Stores `text` in memory, returning its address and length.
+    fn message(&mut self, text: &str) -> (usize, usize) {
+        let offset = match self.messages.get(text) {
+            Some(offset) => *offset,
+            None => {
+                let offset = self.data.len();
+                self.data.extend_from_slice(text.as_bytes());
+                self.messages.insert(text.to_string(), offset);
+                offset
+            },
+        };
+        (offset, text.len())
+    }
+
+    fn push_message(&mut self, text: &str) {
+        let (offset, length) = self.message(text);
+        self.emit(format!("i32.const {}", offset));
+        self.emit(format!("i32.const {}", length));
+    }
+
+    fn fail(&mut self, message: &str) {
+        self.push_message(message);
+        self.emit("call $fail");
+    }
+
+    fn temp(&mut self, ty: &'static str) -> String {
+        let temp = format!("t{}", self.frame.code.locals.len());
+        self.frame.code.locals.push((temp.clone(), ty));
+        temp
+    }
+
+    fn main(&mut self, statements: &[Box<dyn Statement>]) -> String {
+        for (i, statement) in statements.iter().enumerate() {
+            let mode = if i + 1 == statements.len() { Mode::Write } else { Mode::Discard };
+            self.statement(statement.as_ref(), mode, true);
+        }
+        std::mem::replace(&mut self.frame, Frame::new(None)).code.text("main", Some("main"), Type::Null)
+    }
+
+    fn module(mut self, main: String) -> String {
+        let helpers = self.helpers();
+        let mut module = String::from("(module\n");
+        for (name, parameters) in [
+            ("write", "i32 i32"),
+            ("write_int", "i64"),
+            ("write_f32", "f32"),
+            ("write_f64", "f64"),
+            ("write_bool", "i32"),
+            ("fail", "i32 i32 i32 i32"),
+        ] {
+            // `fail` is wrapped by a function that adds the location and traps.
+            let id = if name == "fail" { "host_fail" } else { name };
+            module.push_str(&format!("  (import \"keynes\" \"{}\" (func ${} (param {})))\n", name, id, parameters));
+        }
+        module.push_str(&format!("  (memory (export \"memory\") {})\n", self.data.len() / 65536 + 1));
+        module.push_str(&format!("  (data (i32.const 0) \"{}\")\n", wat_string(&self.data)));
+        module.push_str("  (global $location (mut i32) (i32.const 0))\n");
+        module.push_str("  (global $location_length (mut i32) (i32.const 0))\n");
+        for global in &self.global_declarations {
+            module.push_str(global);
+        }
+        module.push_str(&helpers);
+        for function in &self.compiled {
+            module.push_str(function);
+        }
+        module.push_str(&main);
+        module.push_str(")\n");
+        module
+    }
+
+    /// Functions that report runtime errors and do checked integer arithmetic.
+    fn helpers(&mut self) -> String {
+        let (add, add_length) = self.message("attempt to add with overflow");
+        let (subtract, subtract_length) = self.message("attempt to subtract with overflow");
+        let (multiply, multiply_length) = self.message("attempt to multiply with overflow");
+        let (divide, divide_length) = self.message("attempt to divide with overflow");
+        let (zero, zero_length) = self.message("division by zero");
+        let (negate, negate_length) = self.message("attempt to negate with overflow");
+        format!(
+            r#"  (func $fail (param $message i32) (param $length i32)
+    local.get $message
+    local.get $length
+    global.get $location
+    global.get $location_length
+    call $host_fail
+    unreachable
+  )
+  (func $fit (param $value i64) (param $min i64) (param $max i64) (param $message i32) (param $length i32) (result i64)
+    local.get $value
+    local.get $min
+    i64.lt_s
+    local.get $value
+    local.get $max
+    i64.gt_s
+    i32.or
+    if
+      local.get $message
+      local.get $length
+      call $fail
+    end
+    local.get $value
+  )
+  (func $add_i64 (param $a i64) (param $b i64) (result i64)
+    (local $result i64)
+    local.get $a
+    local.get $b
+    i64.add
+    local.tee $result
+    local.get $a
+    i64.xor
+    local.get $result
+    local.get $b
+    i64.xor
+    i64.and
+    i64.const 0
+    i64.lt_s
+    if
+      i32.const {add}
+      i32.const {add_length}
+      call $fail
+    end
+    local.get $result
+  )
+  (func $sub_i64 (param $a i64) (param $b i64) (result i64)
+    (local $result i64)
+    local.get $a
+    local.get $b
+    i64.sub
+    local.tee $result
+    local.get $a
+    i64.xor
+    local.get $a
+    local.get $b
+    i64.xor
+    i64.and
+    i64.const 0
+    i64.lt_s
+    if
+      i32.const {subtract}
+      i32.const {subtract_length}
+      call $fail
+    end
+    local.get $result
+  )
+  (func $mul_i64 (param $a i64) (param $b i64) (result i64)
+    (local $result i64)
+    local.get $a
+    i64.const -1
+    i64.eq
+    if
+      local.get $b
+      i64.const -9223372036854775808
+      i64.eq
+      if
+        i32.const {multiply}
+        i32.const {multiply_length}
+        call $fail
+      end
+      i64.const 0
+      local.get $b
+      i64.sub
+      return
+    end
+    local.get $a
+    i64.eqz
+    if
+      i64.const 0
+      return
+    end
+    local.get $a
+    local.get $b
+    i64.mul
+    local.tee $result
+    local.get $a
+    i64.div_s
+    local.get $b
+    i64.ne
+    if
+      i32.const {multiply}
+      i32.const {multiply_length}
+      call $fail
+    end
+    local.get $result
+  )
+  (func $div_i64 (param $a i64) (param $b i64) (result i64)
+    local.get $b
+    i64.eqz
+    if
+      i32.const {zero}
+      i32.const {zero_length}
+      call $fail
+    end
+    local.get $a
+    i64.const -9223372036854775808
+    i64.eq
+    local.get $b
+    i64.const -1
+    i64.eq
+    i32.and
+    if
+      i32.const {divide}
+      i32.const {divide_length}
+      call $fail
+    end
+    local.get $a
+    local.get $b
+    i64.div_s
+  )
+  (func $neg_i64 (param $a i64) (result i64)
+    local.get $a
+    i64.const -9223372036854775808
+    i64.eq
+    if
+      i32.const {negate}
+      i32.const {negate_length}
+      call $fail
+    end
+    i64.const 0
+    local.get $a
+    i64.sub
+  )
+  (func $truncate (param $value f64) (param $message i32) (param $length i32) (result i64)
+    local.get $value
+    f64.trunc
+    local.tee $value
+    local.get $value
+    f64.ne
+    local.get $value
+    f64.const -0x1p63
+    f64.lt
+    i32.or
+    local.get $value
+    f64.const 0x1p63
+    f64.ge
+    i32.or
+    if
+      local.get $message
+      local.get $length
+      call $fail
+    end
+    local.get $value
+    i64.trunc_f64_s
+  )
+"#
+        )
+    }
+
+    /// Compiles `statement`, leaving its value as `mode` says. `returns` is whether a `return`
+    /// in it leaves the function, as it does unless it is part of an `if` whose value is used.
+    fn statement(&mut self, statement: &dyn Statement, mode: Mode, returns: bool) -> Type {
+        self.span = statement.span();
+        let (location, length) = self.message(&format!("{}:{}", self.file, statement.span().start));
+        self.emit(format!("i32.const {}", location));
+        self.emit("global.set $location");
+        self.emit(format!("i32.const {}", length));
+        self.emit("global.set $location_length");
+
+        let any = statement.as_any();
+        let ty = if let Some(statement) = any.downcast_ref::<LetStatement>() {
+            self.let_statement(statement);
+            Type::Null
+        } else if let Some(statement) = any.downcast_ref::<ReturnStatement>() {
+            if !returns {
+                return self.unsupported(statement.span, "`return` inside an `if` used as a value is");
+            }
+            self.return_statement(statement)
+        } else if let Some(statement) = any.downcast_ref::<ExpressionStatement>() {
+            match statement.expression.as_any().downcast_ref::<IfExpression>() {
+                Some(if_expression) => return self.if_expression(if_expression, mode, returns),
+                None => self.expression(statement.expression.as_ref()),
+            }
+        } else if let Some(block) = any.downcast_ref::<BlockStatement>() {
+            return self.block(&block.statements, mode, returns);
+        } else if any.is::<TestStatement>() {
+            // Tests only run under `keynes test`.
+            Type::Null
+        } else if any.is::<ImportStatement>() || any.is::<UseStatement>() {
+            self.unsupported(statement.span(), "modules are")
+        } else if any.is::<LetTupleStatement>() {
+            self.unsupported(statement.span(), "destructuring is")
+        } else {
+            self.unsupported(statement.span(), &format!("`{}` is", statement))
+        };
+        self.finish(ty, mode)
+    }
+
+    /// Does with a value of type `ty` on the stack what `mode` says, returning the type left.
+    fn finish(&mut self, ty: Type, mode: Mode) -> Type {
+        match mode {
+            Mode::Value(()) => return ty,
+            Mode::Discard if ty.wasm().is_some() => self.emit("drop"),
+            Mode::Discard => {},
+            Mode::Write => self.write_line(ty),
+        }
+        match ty {
+            Type::Never => Type::Never,
+            _ => Type::Null,
+        }
+    }
+
+    fn block(&mut self, statements: &[Box<dyn Statement>], mode: Mode, returns: bool) -> Type {
+        let Some((last, rest)) = statements.split_last() else {
+            return self.finish(Type::Null, mode);
+        };
+        for statement in rest {
+            self.statement(statement.as_ref(), Mode::Discard, returns);
+        }
+        self.statement(last.as_ref(), mode, returns)
+    }
+
+    fn let_statement(&mut self, statement: &LetStatement) {
+        let name = statement.name.to_string();
+        if let Some(function) = statement.value.as_any().downcast_ref::<FunctionLiteral>() {
+            if function.is_async {
+                self.unsupported(function.span, "async functions are");
+                return;
+            }
+            let defined = self.functions.get(&name).is_some_and(|defined| std::ptr::eq(*defined, function));
+            if !defined || self.frame.function.is_some() {
+                self.unsupported(function.span, "functions not bound by a top-level `let` are");
+            }
+            return;
+        }
+        let ty = self.expression(statement.value.as_ref());
+        if ty == Type::Null {
+            self.unsupported(statement.span, &format!("binding `{}` to NULL is", name));
+            return;
+        }
+
+        let local = self.frame.function.is_some();
+        let previous = match local {
+            true => self.frame.variables.get(&name).copied().flatten().map(|(ty, ())| ty),
+            false => self.globals.get(&name).copied(),
+        };
+        match previous {
+            Some(previous) if previous == ty || ty == Type::Unknown => {},
+            Some(Type::Unknown) | None => {
+                if let Some(wasm) = ty.wasm() {
+                    match local {
+                        true => self.frame.code.locals.push((format!("v_{}", name), wasm)),
+                        false => self.global_declarations.push(format!(
+                            "  (global $g_{} (mut {}) ({}.const 0))\n",
+                            name, wasm, wasm
+                        )),
+                    }
+                }
+                if local {
+                    self.frame.variables.insert(name.clone(), Some((ty, ())));
+                } else {
+                    self.globals.insert(name.clone(), ty);
+                }
+            },
+            Some(previous) => {
+                self.unsupported(
+                    statement.span,
+                    &format!("rebinding `{}` from {} to {} is", name, previous.describe(), ty.describe()),
+                );
+                return;
+            },
+        }
+        if ty.wasm().is_some() {
+            self.emit(match local {
+                true => format!("local.set $v_{}", name),
+                false => format!("global.set $g_{}", name),
+            });
+        }
+    }
+
+    fn return_statement(&mut self, statement: &ReturnStatement) -> Type {
+        let ty = self.expression(statement.expression.as_ref());
+        match self.frame.function.clone() {
+            None => self.write_line(ty),
+            Some(function) => match merge(self.frame.returns, ty) {
+                Some(returns) => self.frame.returns = returns,
+                None => {
+                    let what = format!("returning both {} and {} from `{}` is", self.frame.returns.describe(), ty.describe(), function);
+                    self.unsupported(statement.span, &what);
+                },
+            },
+        }
+        self.emit("return");
+        Type::Never
+    }
+
+    fn if_expression(&mut self, if_expression: &IfExpression, mode: Mode, returns: bool) -> Type {
+        let span = if_expression.span;
+        self.condition(if_expression.condition.as_ref());
+
+        // The branches are compiled first, as their types decide the type of the `if`.
+        let outer = std::mem::take(&mut self.frame.code.lines);
+        self.frame.code.depth += 1;
+        let consequence = self.block(&if_expression.consequence.statements, mode, returns);
+        let consequence_code = std::mem::take(&mut self.frame.code.lines);
+        let alternative = match &if_expression.alternative {
+            Some(alternative) => self.block(&alternative.statements, mode, returns),
+            None => self.finish(Type::Null, mode),
+        };
+        let alternative_code = std::mem::replace(&mut self.frame.code.lines, outer);
+        self.frame.code.depth -= 1;
+
+        let ty = match merge(consequence, alternative) {
+            Some(ty) => ty,
+            None => {
+                let what = format!("`if` branches of types {} and {} are", consequence.describe(), alternative.describe());
+                self.unsupported(span, &what)
+            },
+        };
+        match ty.wasm() {
+            Some(wasm) => self.emit(format!("if (result {})", wasm)),
+            None => self.emit("if"),
+        }
+        self.frame.code.lines.extend(consequence_code);
+        self.emit("else");
+        self.frame.code.lines.extend(alternative_code);
+        self.emit("end");
+        if ty == Type::Never && mode == Mode::Value(()) {
+            self.emit("unreachable");
+        }
+        ty
+    }
+
+    /// Compiles `condition` to an `i32` that is 0 when it is not truthy.
+    fn condition(&mut self, condition: &dyn Expression) {
+        match self.expression(condition) {
+            Type::Boolean | Type::Never | Type::Unknown => {},
+            Type::Null => self.emit("i32.const 0"),
+            _ => {
+                self.emit("drop");
+                self.emit("i32.const 1");
+            },
+        }
+    }
+
+    fn expression(&mut self, expression: &dyn Expression) -> Type {
+        let outer = self.span;
+        if let Some(span) = span_of(expression) {
+            self.span = span;
+        }
+        let ty = self.expression_value(expression);
+        self.span = outer;
+        ty
+    }
+
+    fn expression_value(&mut self, expression: &dyn Expression) -> Type {
+        let any = expression.as_any();
+        if let Some(integer) = any.downcast_ref::<IntegerLiteral>() {
+            self.emit(format!("i64.const {}", integer.value));
+            Type::I64
+        } else if let Some(float) = any.downcast_ref::<FloatLiteral>() {
+            self.emit(format!("f64.const {}", wat_float(float.value)));
+            Type::F64
+        } else if let Some(boolean) = any.downcast_ref::<BooleanLiteral>() {
+            self.emit(format!("i32.const {}", boolean.value as i32));
+            Type::Boolean
+        } else if any.is::<StringLiteral>() {
+            self.unsupported(self.span, "strings outside `print`, `println` and `assert` are")
+        } else if let Some(identifier) = any.downcast_ref::<IdentifierLiteral>() {
+            self.identifier(identifier)
+        } else if let Some(prefix) = any.downcast_ref::<PrefixExpression>() {
+            self.prefix(prefix)
+        } else if let Some(infix) = any.downcast_ref::<InfixExpression>() {
+            self.infix(infix)
+        } else if let Some(if_expression) = any.downcast_ref::<IfExpression>() {
+            self.if_expression(if_expression, Mode::Value(()), false)
+        } else if let Some(function) = any.downcast_ref::<FunctionLiteral>() {
+            self.unsupported(function.span, "functions not bound by a top-level `let` are")
+        } else if let Some(call) = any.downcast_ref::<CallExpression>() {
+            self.call(call)
+        } else if let Some(array) = any.downcast_ref::<ArrayLiteral>() {
+            self.unsupported(array.span, "arrays are")
+        } else if let Some(hash) = any.downcast_ref::<HashLiteral>() {
+            self.unsupported(hash.span, "hashes are")
+        } else if let Some(index) = any.downcast_ref::<IndexExpression>() {
+            self.unsupported(index.span, "indexing is")
+        } else if let Some(spawn) = any.downcast_ref::<SpawnExpression>() {
+            self.unsupported(spawn.span, "tasks are")
+        } else if let Some(select) = any.downcast_ref::<SelectExpression>() {
+            self.unsupported(select.span, "channels are")
+        } else if let Some(await_expression) = any.downcast_ref::<AwaitExpression>() {
+            self.unsupported(await_expression.span, "futures are")
+        } else if let Some(path) = any.downcast_ref::<PathExpression>() {
+            self.unsupported(path.span, "modules are")
+        } else {
+            self.unsupported(self.span, &format!("`{}` is", expression))
+        }
+    }
+
+    /// The type of the variable `name` and the instruction reading it, if it is one.
+    fn variable(&self, name: &str) -> Option<(Option<Type>, String)> {
+        match self.frame.variables.get(name) {
+            Some(ty) => Some((ty.map(|(ty, ())| ty), format!("local.get $v_{}", name))),
+            None => self.globals.get(name).map(|ty| (Some(*ty), format!("global.get $g_{}", name))),
+        }
+    }
+
+    fn identifier(&mut self, identifier: &IdentifierLiteral) -> Type {
+        let name = identifier.to_string();
+        match self.variable(&name) {
+            Some((Some(ty), get)) => {
+                if ty.wasm().is_some() {
+                    self.emit(get);
+                }
+                ty
+            },
+            Some((None, _)) => self.unsupported(identifier.span, &format!("reading `{}` before it is bound is", name)),
+            None if self.functions.contains_key(&name) || builtins::get(&name).is_some() => {
+                self.unsupported(identifier.span, "functions as values are")
+            },
+            None => {
+                self.error(identifier.span, format!("identifier not found: {}", name));
+                Type::Unknown
+            },
+        }
+    }
+
+    fn prefix(&mut self, prefix: &PrefixExpression) -> Type {
+        let right = self.expression(prefix.right.as_ref());
+        match (&prefix.operator, right) {
+            (_, Type::Unknown) => Type::Unknown,
+            (PrefixOperator::BANG, Type::Boolean) => {
+                self.emit("i32.eqz");
+                Type::Boolean
+            },
+            (PrefixOperator::BANG, Type::Null) => {
+                self.emit("i32.const 1");
+                Type::Boolean
+            },
+            (PrefixOperator::BANG, _) => {
+                self.emit("drop");
+                self.emit("i32.const 0");
+                Type::Boolean
+            },
+            (PrefixOperator::MINUS, Type::I64) => {
+                self.emit("call $neg_i64");
+                Type::I64
+            },
+            (PrefixOperator::MINUS, ty) if ty.is_integer() => {
+                self.emit("i64.extend_i32_s");
+                self.emit("call $neg_i64");
+                self.fit(ty, "attempt to negate with overflow");
+                ty
+            },
+            (PrefixOperator::MINUS, ty) if ty.is_float() => {
+                self.emit(format!("{}.neg", ty.wasm().unwrap()));
+                ty
+            },
+            (operator, right) => {
+                self.error(prefix.span, format!("unknown operator: {}{}", operator, right.name()));
+                Type::Unknown
+            },
+        }
+    }
+
+    /// Checks that the `i64` on the stack fits the narrower integer type `ty`, and wraps it to an `i32`.
+    fn fit(&mut self, ty: Type, message: &str) {
+        let (min, max) = ty.range();
+        self.emit(format!("i64.const {}", min));
+        self.emit(format!("i64.const {}", max));
+        self.push_message(message);
+        self.emit("call $fit");
+        self.emit("i32.wrap_i64");
+    }
+
+    fn infix(&mut self, infix: &InfixExpression) -> Type {
+        use InfixOperator::*;
+        let left = self.expression(infix.left.as_ref());
+        let right = self.expression(infix.right.as_ref());
+        let comparison = !matches!(infix.operator, PLUS | MINUS | MULTIPLY | DIVIDE);
+        let verb = match infix.operator {
+            PLUS => "add",
+            MINUS => "subtract",
+            MULTIPLY => "multiply",
+            DIVIDE => "divide",
+            _ => "compare",
+        };
+        let suffix = |signed: bool| match (&infix.operator, signed) {
+            (EQUAL, _) => "eq",
+            (NOT_EQUAL, _) => "ne",
+            (LESS_THAN, true) => "lt_s",
+            (LESS_THAN, false) => "lt",
+            (LESS_THAN_EQUAL, true) => "le_s",
+            (LESS_THAN_EQUAL, false) => "le",
+            (GREATER_THAN, true) => "gt_s",
+            (GREATER_THAN, false) => "gt",
+            (GREATER_THAN_EQUAL, true) => "ge_s",
+            (GREATER_THAN_EQUAL, false) => "ge",
+            (PLUS, _) => "add",
+            (MINUS, _) => "sub",
+            (MULTIPLY, _) => "mul",
+            (DIVIDE, _) => "div",
+        };
+
+        match (left, right) {
+            (Type::Unknown, _) | (_, Type::Unknown) if comparison => Type::Boolean,
+            (Type::Unknown, other) | (other, Type::Unknown) => other,
+            (left, right) if left.is_integer() && right.is_integer() => {
+                if left != right {
+                    self.error(
+                        infix.span,
+                        format!("integer width mismatch: cannot {} {} and {}", verb, left.describe(), right.describe()),
+                    );
+                    return Type::Unknown;
+                }
+                let wasm = left.wasm().unwrap();
+                if comparison {
+                    self.emit(format!("{}.{}", wasm, suffix(true)));
+                    return Type::Boolean;
+                }
+                if left == Type::I64 {
+                    self.emit(format!("call ${}_i64", suffix(true)));
+                    return left;
+                }
+                // Narrower integers are widened, so the operation cannot overflow before the range is checked.
+                let temp = self.temp("i32");
+                self.emit(format!("local.set ${}", temp));
+                self.emit("i64.extend_i32_s");
+                self.emit(format!("local.get ${}", temp));
+                self.emit("i64.extend_i32_s");
+                match infix.operator {
+                    DIVIDE => self.emit("call $div_i64"),
+                    _ => self.emit(format!("i64.{}", suffix(true))),
+                }
+                self.fit(left, &format!("attempt to {} with overflow", verb));
+                left
+            },
+            (left, right) if left.is_float() && right.is_float() => {
+                if left != right {
+                    self.error(
+                        infix.span,
+                        format!("float width mismatch: cannot {} {} and {}", verb, left.describe(), right.describe()),
+                    );
+                    return Type::Unknown;
+                }
+                self.emit(format!("{}.{}", left.wasm().unwrap(), suffix(false)));
+                if comparison { Type::Boolean } else { left }
+            },
+            (Type::Boolean, Type::Boolean) if matches!(infix.operator, EQUAL | NOT_EQUAL) => {
+                self.emit(format!("i32.{}", suffix(true)));
+                Type::Boolean
+            },
+            (left, right) => {
+                let problem = if left.name() != right.name() { "type mismatch" } else { "unknown operator" };
+                self.error(infix.span, format!("{}: {} {} {}", problem, left.name(), infix.operator, right.name()));
+                Type::Unknown
+            },
+        }
+    }
+
+    fn call(&mut self, call: &CallExpression) -> Type {
+        let Some(callee) = call.function.as_any().downcast_ref::<IdentifierLiteral>() else {
+            return self.unsupported(call.span, "calling anything but a function by name is");
+        };
+        let name = callee.to_string();
+        if self.variable(&name).is_some() {
+            return self.unsupported(callee.span, "functions as values are");
+        }
+        if let Some(function) = self.functions.get(&name).copied() {
+            return self.call_function(&name, function, call);
+        }
+        if builtins::get(&name).is_some() {
+            return self.builtin(&name, call);
+        }
+        self.error(callee.span, format!("identifier not found: {}", name));
+        Type::Unknown
+    }
+
+    fn call_function(&mut self, name: &str, function: &'a FunctionLiteral, call: &CallExpression) -> Type {
+        if function.parameters.len() != call.arguments.len() {
+            self.error(
+                call.span,
+                format!(
+                    "wrong number of arguments to {}: expected {}, got {}",
+                    name,
+                    function.parameters.len(),
+                    call.arguments.len()
+                ),
+            );
+            return Type::Unknown;
+        }
+        let arguments = call.arguments.iter().map(|argument| self.expression(argument.as_ref())).collect::<Vec<_>>();
+        if arguments.contains(&Type::Unknown) {
+            return Type::Unknown;
+        }
+        if arguments.contains(&Type::Null) {
+            return self.unsupported(call.span, &format!("passing NULL to `{}` is", name));
+        }
+        let (id, result) = instance(self, name, function, arguments);
+        if result == Type::Unknown {
+            self.frame.pending = true;
+        }
+        self.emit(format!("call ${}", id));
+        result
+    }
+
+    fn builtin(&mut self, name: &str, call: &CallExpression) -> Type {
+        match name {
+            "print" | "println" => {
+                for (i, argument) in call.arguments.iter().enumerate() {
+                    if i > 0 {
+                        self.write_text(" ");
+                    }
+                    match argument.as_any().downcast_ref::<StringLiteral>() {
+                        Some(string) => self.write_text(&string.value),
+                        None => match self.expression(argument.as_ref()) {
+                            Type::Null => self.write_text("null"),
+                            ty => self.write(ty),
+                        },
+                    }
+                }
+                if name == "println" {
+                    self.write_text("\n");
+                }
+                Type::Null
+            },
+            "assert" => {
+                let message = match call.arguments.len() {
+                    1 => "assertion failed".to_string(),
+                    2 => match call.arguments[1].as_any().downcast_ref::<StringLiteral>() {
+                        Some(message) => format!("assertion failed: {}", message.value),
+                        None => return self.unsupported(call.span, "assertion messages other than string literals are"),
+                    },
+                    count => {
+                        self.error(call.span, format!("wrong number of arguments to assert: expected 1, got {}", count));
+                        return Type::Unknown;
+                    },
+                };
+                self.condition(call.arguments[0].as_ref());
+                self.emit("i32.eqz");
+                self.emit("if");
+                self.frame.code.depth += 1;
+                self.fail(&message);
+                self.frame.code.depth -= 1;
+                self.emit("end");
+                Type::Null
+            },
+            _ => match Type::width(name) {
+                Some(target) => self.convert(name, target, call),
+                None => self.unsupported(call.span, &format!("the builtin `{}` is", name)),
+            },
+        }
+    }
+
+    /// Compiles a call of the width builtin `name`. Values that do not fit fail at runtime, though
+    /// unlike the evaluator the message leaves out the value itself.
+    fn convert(&mut self, name: &str, target: Type, call: &CallExpression) -> Type {
+        let [argument] = call.arguments.as_slice() else {
+            self.error(
+                call.span,
+                format!("wrong number of arguments to {}: expected 1, got {}", name, call.arguments.len()),
+            );
+            return Type::Unknown;
+        };
+        if let (Some(integer), true) = (argument.as_any().downcast_ref::<IntegerLiteral>(), target.is_integer()) {
+            let (min, max) = target.range();
+            if !(min..=max).contains(&integer.value) {
+                self.error(call.span, format!("{} out of range for {}", integer.value, name));
+                return Type::Unknown;
+            }
+            self.emit(format!("{}.const {}", target.wasm().unwrap(), integer.value));
+            return target;
+        }
+
+        let source = self.expression(argument.as_ref());
+        let widen = |compiler: &mut Self| {
+            if source.is_integer() && source != Type::I64 {
+                compiler.emit("i64.extend_i32_s");
+            }
+        };
+        match (source, target) {
+            (Type::Unknown, _) => {},
+            (source, target) if source == target => {},
+            (Type::Boolean, Type::I64) => self.emit("i64.extend_i32_u"),
+            (Type::Boolean, target) if target.is_integer() => {},
+            (source, target) if target.is_integer() && (source.is_integer() || source.is_float()) => {
+                let message = format!("{} out of range for {}", if source.is_float() { "float" } else { "integer" }, name);
+                if source == Type::F32 {
+                    self.emit("f64.promote_f32");
+                }
+                if source.is_float() {
+                    self.push_message(&message);
+                    self.emit("call $truncate");
+                }
+                widen(self);
+                if target != Type::I64 {
+                    self.fit(target, &message);
+                }
+            },
+            (source, target) if source.is_integer() => {
+                // Like the evaluator, `f32` goes through `f64`, rounding twice.
+                widen(self);
+                self.emit("f64.convert_i64_s");
+                if target == Type::F32 {
+                    self.emit("f32.demote_f64");
+                }
+            },
+            (Type::F32, Type::F64) => self.emit("f64.promote_f32"),
+            (Type::F64, Type::F32) => self.emit("f32.demote_f64"),
+            (source, _) => {
+                self.error(call.span, format!("argument to `{}` not supported, got {}", name, source.name()));
+                return Type::Unknown;
+            },
+        }
+        target
+    }
+
+    fn write_text(&mut self, text: &str) {
+        self.push_message(text);
+        self.emit("call $write");
+    }
+
+    /// Writes the value of type `ty` on the stack.
+    fn write(&mut self, ty: Type) {
+        match ty {
+            Type::I8 | Type::I16 | Type::I32 => {
+                self.emit("i64.extend_i32_s");
+                self.emit("call $write_int");
+            },
+            Type::I64 => self.emit("call $write_int"),
+            Type::F32 => self.emit("call $write_f32"),
+            Type::F64 => self.emit("call $write_f64"),
+            Type::Boolean => self.emit("call $write_bool"),
+            Type::Null | Type::Never | Type::Unknown => {},
+        }
+    }
+
+    /// Writes the value of type `ty` on the stack on a line of its own, unless it is null.
+    fn write_line(&mut self, ty: Type) {
+        if ty.wasm().is_some() {
+            self.write(ty);
+            self.write_text("\n");
+        }
+    }
+}
+
+impl<'a> Backend<'a> for Compiler<'a> {
+    type Code = Body;
+
+    const NAME: &'static str = "WebAssembly";
+
+    fn functions(&mut self) -> &mut HashMap<String, &'a FunctionLiteral> {
+        &mut self.functions
+    }
+
+    fn instances(&mut self) -> &mut Vec<Instance> {
+        &mut self.instances
+    }
+
+    fn frame(&mut self) -> &mut Frame<Body> {
+        &mut self.frame
+    }
+
+    fn diagnostics(&mut self) -> &mut Vec<Diagnostic> {
+        &mut self.diagnostics
+    }
+
+    fn symbol(&self, name: &str, arguments: &[Type], _: usize) -> String {
+        std::iter::once(name).chain(arguments.iter().map(|argument| argument.describe())).collect::<Vec<_>>().join("/")
+    }
+
+    fn body(&mut self, function: &FunctionLiteral) -> Type {
+        let value = self.block(&function.body.statements, Mode::Value(()), true);
+        if value == Type::Never {
+            self.emit("unreachable");
+        }
+        value
+    }
+
+    fn compiled(&mut self, instance: Instance, frame: Frame<Body>) {
+        self.compiled.push(frame.code.text(&instance.symbol, None, instance.result));
+    }
+}
+
+fn wat_float(value: f64) -> String {
+    if value.is_nan() {
+        "nan".to_string()
+    } else if value.is_infinite() {
+        format!("{}inf", if value < 0.0 { "-" } else { "" })
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+/// The contents of a WebAssembly string literal holding `bytes`.
+fn wat_string(bytes: &[u8]) -> String {
+    let mut literal = String::new();
+    for byte in bytes {
+        match byte {
+            b'"' | b'\\' => literal.push_str(&format!("\\{}", *byte as char)),
+            b' '..=b'~' => literal.push(*byte as char),
+            _ => literal.push_str(&format!("\\{:02x}", byte)),
+        }
+    }
+    literal
+}
+
+#[cfg(test)]
+#[path = "./wat_tests.rs"]
+mod tests;
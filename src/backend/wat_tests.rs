@@ -0,0 +1,164 @@
+use wasmi::{Caller, Engine, Linker, Module, Store};
+
+use super::*;
+
+use crate::{backend::front_end, generator::Generator, optimizer::OptLevel, Object};
+
+use test_case::test_case;
+
+fn wat_source(source: &str) -> Result<String, Vec<String>> {
+    let diagnostics = |diagnostics: Vec<Diagnostic>| diagnostics.into_iter().map(|diagnostic| diagnostic.message).collect::<Vec<_>>();
+    let program = front_end(source, OptLevel::O1).map_err(diagnostics)?;
+    compile("main.ks", &program).map_err(diagnostics)
+}
+
+/// What a module wrote, and the error it failed with, with its location.
+#[derive(Default)]
+struct Host {
+    out: String,
+    error: Option<String>,
+}
+
+fn text(caller: &Caller<'_, Host>, pointer: i32, length: i32) -> String {
+    let memory = caller.get_export("memory").and_then(|export| export.into_memory()).unwrap();
+    let bytes = &memory.data(caller)[pointer as usize..(pointer + length) as usize];
+    String::from_utf8_lossy(bytes).to_string()
+}
+
+/// Runs the module compiled from `source` in an interpreter, returning what it wrote
+/// and the error it failed with.
+fn run(source: &str) -> (String, Option<String>) {
+    let wasm = wat::parse_str(wat_source(source).unwrap()).unwrap();
+    let engine = Engine::default();
+    let module = Module::new(&engine, &wasm).unwrap();
+    let mut store = Store::new(&engine, Host::default());
+    let mut linker = Linker::<Host>::new(&engine);
+    linker
+        .func_wrap("keynes", "write", |mut caller: Caller<'_, Host>, pointer: i32, length: i32| {
+            let text = text(&caller, pointer, length);
+            caller.data_mut().out.push_str(&text);
+        })
+        .unwrap()
+        .func_wrap("keynes", "write_int", |mut caller: Caller<'_, Host>, value: i64| {
+            caller.data_mut().out.push_str(&value.to_string());
+        })
+        .unwrap()
+        .func_wrap("keynes", "write_f32", |mut caller: Caller<'_, Host>, value: f32| {
+            caller.data_mut().out.push_str(&format!("{:?}", value));
+        })
+        .unwrap()
+        .func_wrap("keynes", "write_f64", |mut caller: Caller<'_, Host>, value: f64| {
+            caller.data_mut().out.push_str(&format!("{:?}", value));
+        })
+        .unwrap()
+        .func_wrap("keynes", "write_bool", |mut caller: Caller<'_, Host>, value: i32| {
+            caller.data_mut().out.push_str(&(value != 0).to_string());
+        })
+        .unwrap()
+        .func_wrap(
+            "keynes",
+            "fail",
+            |mut caller: Caller<'_, Host>, message: i32, length: i32, location: i32, location_length: i32| {
+                let error = format!("{} at {}", text(&caller, message, length), text(&caller, location, location_length));
+                caller.data_mut().error = Some(error);
+            },
+        )
+        .unwrap();
+    let instance = linker.instantiate(&mut store, &module).unwrap().start(&mut store).unwrap();
+    let main = instance.get_typed_func::<(), ()>(&store, "main").unwrap();
+    let trapped = main.call(&mut store, ()).is_err();
+    let host = store.into_data();
+    assert_eq!(trapped, host.error.is_some(), "only failures trap");
+    (host.out, host.error)
+}
+
+#[test_case("1 + 2 * 3", "7\n"; "arithmetic")]
+#[test_case("let x = 5; let y = x * 2; y - 1", "9\n"; "globals")]
+#[test_case("println(\"hello\", 1, true); print(\"a\", 2.5)", "hello 1 true\na 2.5"; "printing")]
+#[test_case("println(println())", "\nnull\n"; "printing null")]
+#[test_case("let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) }; fib(20)", "6765\n"; "recursion")]
+#[test_case("let fact = fn(n) { if (n == 0) { 1 } else { n * fact(n - 1) } }; fact(20)", "2432902008176640000\n"; "recursion in branch")]
+#[test_case("let id = fn(x) { x }; println(id(1)); println(id(true)); id(1.5)", "1\ntrue\n1.5\n"; "instance per argument type")]
+#[test_case("let x = 1; let f = fn() { x + 1 }; let x = 2; f()", "3\n"; "functions read globals")]
+#[test_case("let f = fn(x) { let y = x * 2; if (y > 5) { let y = 0; } y }; println(f(1)); f(3)", "2\n0\n"; "blocks share the function scope")]
+#[test_case("i8(100) + i8(27)", "127\n"; "i8")]
+#[test_case("i16(-300) * i16(100)", "-30000\n"; "i16")]
+#[test_case("i32(7) / i32(2)", "3\n"; "i32")]
+#[test_case("i64(i8(-5)) - 1", "-6\n"; "widening")]
+#[test_case("println(0.1 + 0.2); println(f32(0.1)); println(f32(1) / f32(3)); 1.0 / 0.0", "0.30000000000000004\n0.1\n0.33333334\ninf\n"; "floats")]
+#[test_case("println(i32(2.9)); println(i8(-2.5)); println(f64(i16(3))); i8(true)", "2\n-2\n3.0\n1\n"; "conversions")]
+#[test_case("!true == !1", "true\n"; "bang")]
+#[test_case("if (0) { 1 } else { 2 }", "1\n"; "integers are truthy")]
+#[test_case("let x = 3; if (x > 2) { x } else { false }", "3\n"; "program value may differ in type by branch")]
+#[test_case("let x = 3; if (x > 5) { x }", ""; "null program value")]
+#[test_case("return 5; 6", "5\n"; "top level return")]
+#[test_case("let f = fn(x) { if (x) { return 1; } else { return 2; } }; f(false)", "2\n"; "both branches return")]
+#[test_case("test \"ignored\" { assert(false) } 1", "1\n"; "tests are skipped")]
+#[test_case("assert(1 < 2, \"math\"); 1", "1\n"; "passing assertion")]
+fn test_compiled_output(source: &str, expected: &str) {
+    assert_eq!(run(source), (expected.to_string(), None));
+}
+
+#[test_case("9223372036854775807 + 1", "attempt to add with overflow"; "add")]
+#[test_case("let x = 0 - 9223372036854775807; x - 2", "attempt to subtract with overflow"; "subtract")]
+#[test_case("4611686018427387904 * 2", "attempt to multiply with overflow"; "multiply")]
+#[test_case("let x = 0 - 9223372036854775807 - 1; x / -1", "attempt to divide with overflow"; "divide")]
+#[test_case("let x = 0 - 9223372036854775807 - 1; -x", "attempt to negate with overflow"; "negate")]
+#[test_case("let x = 0; 1 / x", "division by zero"; "division by zero")]
+#[test_case("i8(100) + i8(28)", "attempt to add with overflow"; "i8 add")]
+#[test_case("-i8(-128)", "attempt to negate with overflow"; "i8 negate")]
+#[test_case("i32(65536) * i32(65536)", "attempt to multiply with overflow"; "i32 multiply")]
+#[test_case("let x = 300; i8(x)", "integer out of range for i8"; "narrowing")]
+#[test_case("i64(1.0 / 0.0)", "float out of range for i64"; "truncating")]
+#[test_case("assert(false)", "assertion failed"; "assertion")]
+#[test_case("assert(1 > 2, \"math\")", "assertion failed: math"; "assertion with message")]
+fn test_runtime_errors(source: &str, message: &str) {
+    let (out, error) = run(source);
+    assert_eq!((out.as_str(), error.unwrap().split(" at ").next()), ("", Some(message)));
+}
+
+#[test]
+fn test_error_location_and_output_before_it() {
+    let source = "println(\"before\");\nlet f = fn(x) {\n  x + 1\n};\nf(9223372036854775807);\nprintln(\"after\");";
+    assert_eq!(run(source), ("before\n".to_string(), Some("attempt to add with overflow at main.ks:3:3".to_string())));
+}
+
+#[test_case("\"a\"", "strings outside `print`, `println` and `assert` are not supported by the WebAssembly backend"; "strings")]
+#[test_case("[1]", "arrays are not supported by the WebAssembly backend"; "arrays")]
+#[test_case("{1: 2}", "hashes are not supported by the WebAssembly backend"; "hashes")]
+#[test_case("let f = fn() { fn() { 1 } }; f()", "functions not bound by a top-level `let` are not supported by the WebAssembly backend"; "closures")]
+#[test_case("let f = fn(g) { g(1) }; f(len)", "functions as values are not supported by the WebAssembly backend"; "function values")]
+#[test_case("i128(1)", "the builtin `i128` is not supported by the WebAssembly backend"; "i128")]
+#[test_case("let x = 1; let x = true;", "rebinding `x` from i64 to BOOLEAN is not supported by the WebAssembly backend"; "rebinding to another type")]
+#[test_case("let x = println();", "binding `x` to NULL is not supported by the WebAssembly backend"; "null variable")]
+#[test_case("let f = fn(x) { if (x) { return 1; } true }; f(true)", "returning both i64 and BOOLEAN from `f` is not supported by the WebAssembly backend"; "return types")]
+#[test_case("let c = true; let x = if (c) { 1 } else { 2.0 }; x", "`if` branches of types i64 and f64 are not supported by the WebAssembly backend"; "if types")]
+#[test_case("let f = fn(c) { let x = if (c) { return 1; } else { 2 }; x }; f(true)", "`return` inside an `if` used as a value is not supported by the WebAssembly backend"; "return in value")]
+#[test_case("let f = fn() { f() }; f()", "cannot infer the return type of `f`"; "endless recursion")]
+#[test_case("i8(100) + 100", "integer width mismatch: cannot add i8 and i64"; "width mismatch")]
+#[test_case("i8(300)", "300 out of range for i8"; "literal out of range")]
+fn test_unsupported(source: &str, message: &str) {
+    assert_eq!(wat_source(source), Err(vec![message.to_string()]));
+}
+
+/// The programs the backend accepts write what `keynes run` would print.
+#[test]
+fn test_generated_programs_match_the_evaluator() {
+    let mut compiled = 0;
+    for seed in 0..2000 {
+        let source = Generator::new(seed, 3).program();
+        if wat_source(&source).is_err() {
+            continue;
+        }
+        compiled += 1;
+        let expected = match crate::Engine::new().eval(&source) {
+            Ok(Object::Null) => (String::new(), None),
+            Ok(value) => (format!("{}\n", value), None),
+            Err(err) => (String::new(), Some(err.to_string())),
+        };
+        let (out, error) = run(&source);
+        let error = error.map(|error| error.split(" at ").next().unwrap().to_string());
+        assert_eq!((out, error), expected, "seed {}\n{}", seed, source);
+    }
+    assert!(compiled > 10, "only {} programs compiled", compiled);
+}
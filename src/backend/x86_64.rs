@@ -0,0 +1,1079 @@
+//! Compiles Keynes programs to x86-64 assembly for the GNU assembler, for
+//! `keynes build --target x86-64`.
+//!
+//! Only integers, booleans, `if`, and functions bound by a top-level `let` are supported;
+//! like the WebAssembly backend, a function is compiled once for each combination of
+//! argument types it is called with, and strings may only be given to `print`, `println`
+//! and `assert`. Both integers and booleans fit a 64-bit register.
+//!
+//! Each function is first lowered to [`Instruction`]s on an unlimited number of virtual
+//! registers, which linear scan then assigns to the callee-saved registers, spilling the
+//! rest to the stack. As those registers survive calls, nothing needs saving around them.
+//! Calls follow the System V ABI, and the generated code links against the C library for
+//! its output.
+
+use std::{collections::HashMap, path::Path};
+
+use super::{define_functions, distinct, instance, merge, span_of, Backend, Code, Frame, Instance, Mode, Type};
+use crate::{
+    ast::{expressions::*, program::Program, statements::*},
+    builtins,
+    diagnostics::Diagnostic,
+    lexer::Span,
+};
+
+use log::*;
+
+/// Output routines and error reporting used by the generated code.
+const RUNTIME: &str = r#"    .text
+kn_write_int:
+    pushq %rbp
+    movq %rsp, %rbp
+    movq %rdi, %rsi
+    leaq .Lkn_int_format(%rip), %rdi
+    xorl %eax, %eax
+    call printf@PLT
+    popq %rbp
+    ret
+kn_write_bool:
+    pushq %rbp
+    movq %rsp, %rbp
+    leaq .Lkn_true(%rip), %rsi
+    leaq .Lkn_false(%rip), %rax
+    testq %rdi, %rdi
+    cmoveq %rax, %rsi
+    leaq .Lkn_text_format(%rip), %rdi
+    xorl %eax, %eax
+    call printf@PLT
+    popq %rbp
+    ret
+kn_write_text:
+    pushq %rbp
+    movq %rsp, %rbp
+    movq %rdi, %rsi
+    leaq .Lkn_text_format(%rip), %rdi
+    xorl %eax, %eax
+    call printf@PLT
+    popq %rbp
+    ret
+# Prints the message in %rdi and where it happened, and exits. Jumped to with any stack alignment.
+kn_fail:
+    andq $-16, %rsp
+    movq %rdi, %rdx
+    movq kn_location(%rip), %rcx
+    movl $2, %edi
+    leaq .Lkn_error_format(%rip), %rsi
+    xorl %eax, %eax
+    call dprintf@PLT
+    movl $1, %edi
+    call exit@PLT
+kn_add_overflow:
+    leaq .Lkn_add_message(%rip), %rdi
+    jmp kn_fail
+kn_subtract_overflow:
+    leaq .Lkn_subtract_message(%rip), %rdi
+    jmp kn_fail
+kn_multiply_overflow:
+    leaq .Lkn_multiply_message(%rip), %rdi
+    jmp kn_fail
+kn_divide_overflow:
+    leaq .Lkn_divide_message(%rip), %rdi
+    jmp kn_fail
+kn_negate_overflow:
+    leaq .Lkn_negate_message(%rip), %rdi
+    jmp kn_fail
+kn_division_by_zero:
+    leaq .Lkn_zero_message(%rip), %rdi
+    jmp kn_fail
+
+    .section .rodata
+.Lkn_int_format:
+    .asciz "%lld"
+.Lkn_text_format:
+    .asciz "%s"
+.Lkn_error_format:
+    .asciz "error: %s\n --> %s\n"
+.Lkn_true:
+    .asciz "true"
+.Lkn_false:
+    .asciz "false"
+.Lkn_add_message:
+    .asciz "attempt to add with overflow"
+.Lkn_subtract_message:
+    .asciz "attempt to subtract with overflow"
+.Lkn_multiply_message:
+    .asciz "attempt to multiply with overflow"
+.Lkn_divide_message:
+    .asciz "attempt to divide with overflow"
+.Lkn_negate_message:
+    .asciz "attempt to negate with overflow"
+.Lkn_zero_message:
+    .asciz "division by zero"
+
+    .bss
+    .p2align 3
+kn_location:
+    .zero 8
+"#;
+
+/// The registers values are allocated to. Being callee-saved, they keep their values across calls.
+const REGISTERS: [&str; 5] = ["%rbx", "%r12", "%r13", "%r14", "%r15"];
+
+/// The registers the first arguments of a call are passed in.
+const ARGUMENTS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+
+/// Lowers `program`, read from `file`, to x86-64 assembly with a `main` function.
+pub fn compile(file: &str, program: &Program) -> Result<String, Vec<Diagnostic>> {
+    trace!("compile to x86-64: {}", file);
+    let mut compiler = Compiler {
+        file,
+        functions: HashMap::new(),
+        instances: Vec::new(),
+        compiled: Vec::new(),
+        globals: HashMap::new(),
+        strings: Vec::new(),
+        frame: Frame::new(None),
+        span: Span::default(),
+        diagnostics: Vec::new(),
+    };
+    define_functions(&mut compiler, &program.statements);
+    compiler.main(&program.statements);
+    if !compiler.diagnostics.is_empty() {
+        return Err(distinct(compiler.diagnostics));
+    }
+
+    let mut assembly = format!("# Compiled from {}\n", file);
+    for function in &compiler.compiled {
+        assembly.push_str(&emit(function, &allocate(function)));
+    }
+    assembly.push_str(RUNTIME);
+    let mut globals = compiler.globals.keys().collect::<Vec<_>>();
+    globals.sort();
+    for global in globals {
+        assembly.push_str(&format!("kn_global_{}:\n    .zero 8\n", global));
+    }
+    assembly.push_str("\n    .section .rodata\n");
+    for (i, string) in compiler.strings.iter().enumerate() {
+        assembly.push_str(&format!(".Lkn_string_{}:\n    .asciz \"{}\"\n", i, gas_string(string)));
+    }
+    assembly.push_str("\n    .section .note.GNU-stack,\"\",@progbits\n");
+    Ok(assembly)
+}
+
+/// Assembles and links the output of [`compile`] to an executable at `output` with the
+/// system C compiler, `$CC` or `cc`.
+pub fn build(assembly: &str, output: &Path) -> Result<(), String> {
+    super::cc(assembly, "s", &[], &[], output)
+}
+
+/// A virtual register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Value(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+/// The lowered form of a function. Jumps only go forward, as the language has no loops.
+#[derive(Debug, Clone, PartialEq)]
+enum Instruction {
+    Constant(Value, i64),
+    Copy(Value, Value),
+    /// Stores `op(left, right)` in the first value, failing on overflow.
+    Binary(Operator, Value, Value, Value),
+    Negate(Value, Value),
+    /// Negates a boolean.
+    Not(Value, Value),
+    Label(usize),
+    Jump(usize),
+    JumpIfZero(Value, usize),
+    Call(Option<Value>, String, Vec<Value>),
+    Return(Option<Value>),
+    LoadGlobal(Value, String),
+    StoreGlobal(String, Value),
+    WriteInteger(Value),
+    WriteBoolean(Value),
+    /// Writes the string with this index in the string table.
+    WriteText(usize),
+    /// Fails with the message with this index unless the value is true.
+    Assert(Value, usize),
+    /// Records the location with this index, for the errors of the code that follows.
+    Locate(usize),
+}
+
+impl Instruction {
+    /// Every value the instruction defines or uses.
+    fn values(&self) -> Vec<Value> {
+        match self {
+            Instruction::Constant(value, _) | Instruction::LoadGlobal(value, _) | Instruction::StoreGlobal(_, value) => vec![*value],
+            Instruction::Copy(a, b) | Instruction::Negate(a, b) | Instruction::Not(a, b) => vec![*a, *b],
+            Instruction::Binary(_, a, b, c) => vec![*a, *b, *c],
+            Instruction::JumpIfZero(value, _) | Instruction::Assert(value, _) => vec![*value],
+            Instruction::WriteInteger(value) | Instruction::WriteBoolean(value) => vec![*value],
+            Instruction::Call(result, _, arguments) => result.iter().chain(arguments).copied().collect(),
+            Instruction::Return(value) => value.iter().copied().collect(),
+            Instruction::Label(_) | Instruction::Jump(_) | Instruction::WriteText(_) | Instruction::Locate(_) => vec![],
+        }
+    }
+}
+
+/// A lowered function, ready for register allocation.
+#[derive(Debug, Clone, PartialEq)]
+struct Function {
+    symbol: String,
+    /// What the symbol stands for, for a comment.
+    description: String,
+    parameters: Vec<Value>,
+    instructions: Vec<Instruction>,
+    values: usize,
+}
+
+/// Where a value lives.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Location {
+    Register(&'static str),
+    /// A stack slot, counted from the top of the frame.
+    Stack(usize),
+}
+
+struct Allocation {
+    locations: Vec<Location>,
+    /// The registers the function uses, which it must save.
+    registers: Vec<&'static str>,
+    slots: usize,
+}
+
+/// Assigns every value of `function` a register or a stack slot by linear scan.
+///
+/// The live interval of a value runs from its first to its last appearance, parameters
+/// appearing at the start. As jumps only go forward, this covers every path between its
+/// definitions and uses.
+fn allocate(function: &Function) -> Allocation {
+    let mut intervals: Vec<Option<(usize, usize)>> = vec![None; function.values];
+    for value in &function.parameters {
+        intervals[value.0] = Some((0, 0));
+    }
+    for (i, instruction) in function.instructions.iter().enumerate() {
+        for value in instruction.values() {
+            let interval = intervals[value.0].get_or_insert((i + 1, i + 1));
+            interval.1 = i + 1;
+        }
+    }
+    let mut order = (0..function.values).filter_map(|value| intervals[value].map(|(start, end)| (start, end, value))).collect::<Vec<_>>();
+    order.sort();
+
+    let mut locations = vec![Location::Stack(0); function.values];
+    let mut free = REGISTERS.iter().rev().copied().collect::<Vec<_>>();
+    let mut used = Vec::new();
+    let mut slots = 0;
+    // Values in registers, by the end of their interval.
+    let mut active: Vec<(usize, usize, &'static str)> = Vec::new();
+    for (start, end, value) in order {
+        active.retain(|(active_end, _, register)| {
+            if *active_end < start {
+                free.push(register);
+            }
+            *active_end >= start
+        });
+        if let Some(register) = free.pop() {
+            locations[value] = Location::Register(register);
+            active.push((end, value, register));
+            if !used.contains(&register) {
+                used.push(register);
+            }
+            continue;
+        }
+        // Spill whichever value lives longest.
+        let (index, &(last_end, last, register)) = active.iter().enumerate().max_by_key(|(_, (end, _, _))| *end).unwrap();
+        if last_end > end {
+            locations[last] = Location::Stack(slots);
+            locations[value] = Location::Register(register);
+            active[index] = (end, value, register);
+        } else {
+            locations[value] = Location::Stack(slots);
+        }
+        slots += 1;
+    }
+    used.sort_by_key(|register| REGISTERS.iter().position(|r| r == register));
+    Allocation {
+        locations,
+        registers: used,
+        slots,
+    }
+}
+
+/// Emits the assembly for `function`, with its values where `allocation` put them.
+fn emit(function: &Function, allocation: &Allocation) -> String {
+    let saved = allocation.registers.len();
+    let operand = |value: &Value| match allocation.locations[value.0] {
+        Location::Register(register) => register.to_string(),
+        Location::Stack(slot) => format!("-{}(%rbp)", 8 * (saved + slot + 1)),
+    };
+    let label = |label: &usize| format!(".L{}_{}", function.symbol, label);
+
+    let mut out = vec![
+        format!("# {}", function.description),
+        "    .text".to_string(),
+    ];
+    if function.symbol == "main" {
+        out.push("    .globl main".to_string());
+    }
+    out.push(format!("{}:", function.symbol));
+    out.push("    pushq %rbp".to_string());
+    out.push("    movq %rsp, %rbp".to_string());
+    for register in &allocation.registers {
+        out.push(format!("    pushq {}", register));
+    }
+    // Keeps the stack aligned to 16 bytes at calls.
+    let frame = 8 * (allocation.slots + (saved + allocation.slots) % 2);
+    if frame > 0 {
+        out.push(format!("    subq ${}, %rsp", frame));
+    }
+    for (i, parameter) in function.parameters.iter().enumerate() {
+        match ARGUMENTS.get(i) {
+            Some(register) => out.push(format!("    movq {}, {}", register, operand(parameter))),
+            None => {
+                out.push(format!("    movq {}(%rbp), %rax", 16 + 8 * (i - ARGUMENTS.len())));
+                out.push(format!("    movq %rax, {}", operand(parameter)));
+            },
+        }
+    }
+
+    for instruction in &function.instructions {
+        let lines = match instruction {
+            Instruction::Constant(value, constant) => match i32::try_from(*constant) {
+                Ok(_) => vec![format!("movq ${}, {}", constant, operand(value))],
+                Err(_) => vec![format!("movabsq ${}, %rax", constant), format!("movq %rax, {}", operand(value))],
+            },
+            Instruction::Copy(to, from) => vec![format!("movq {}, %rax", operand(from)), format!("movq %rax, {}", operand(to))],
+            Instruction::Binary(Operator::Divide, result, left, right) => vec![
+                format!("movq {}, %rcx", operand(right)),
+                "testq %rcx, %rcx".to_string(),
+                "je kn_division_by_zero".to_string(),
+                format!("movq {}, %rax", operand(left)),
+                "cmpq $-1, %rcx".to_string(),
+                "jne 1f".to_string(),
+                "movabsq $-9223372036854775808, %rdx".to_string(),
+                "cmpq %rdx, %rax".to_string(),
+                "je kn_divide_overflow".to_string(),
+                "1:".to_string(),
+                "cqto".to_string(),
+                "idivq %rcx".to_string(),
+                format!("movq %rax, {}", operand(result)),
+            ],
+            Instruction::Binary(operator, result, left, right) => {
+                let mut lines = vec![format!("movq {}, %rax", operand(left))];
+                match operator {
+                    Operator::Add => lines.extend([format!("addq {}, %rax", operand(right)), "jo kn_add_overflow".to_string()]),
+                    Operator::Subtract => lines.extend([format!("subq {}, %rax", operand(right)), "jo kn_subtract_overflow".to_string()]),
+                    Operator::Multiply => lines.extend([format!("imulq {}, %rax", operand(right)), "jo kn_multiply_overflow".to_string()]),
+                    comparison => {
+                        let condition = match comparison {
+                            Operator::Equal => "e",
+                            Operator::NotEqual => "ne",
+                            Operator::Less => "l",
+                            Operator::LessEqual => "le",
+                            Operator::Greater => "g",
+                            _ => "ge",
+                        };
+                        lines.extend([
+                            format!("cmpq {}, %rax", operand(right)),
+                            format!("set{} %al", condition),
+                            "movzbq %al, %rax".to_string(),
+                        ]);
+                    },
+                }
+                lines.push(format!("movq %rax, {}", operand(result)));
+                lines
+            },
+            Instruction::Negate(result, value) => vec![
+                format!("movq {}, %rax", operand(value)),
+                "negq %rax".to_string(),
+                "jo kn_negate_overflow".to_string(),
+                format!("movq %rax, {}", operand(result)),
+            ],
+            Instruction::Not(result, value) => vec![
+                format!("movq {}, %rax", operand(value)),
+                "xorq $1, %rax".to_string(),
+                format!("movq %rax, {}", operand(result)),
+            ],
+            Instruction::Label(n) => {
+                out.push(format!("{}:", label(n)));
+                continue;
+            },
+            Instruction::Jump(n) => vec![format!("jmp {}", label(n))],
+            Instruction::JumpIfZero(value, n) => vec![format!("cmpq $0, {}", operand(value)), format!("je {}", label(n))],
+            Instruction::Call(result, symbol, arguments) => {
+                let mut lines = Vec::new();
+                let stack = arguments.len().saturating_sub(ARGUMENTS.len());
+                if stack % 2 == 1 {
+                    lines.push("subq $8, %rsp".to_string());
+                }
+                for argument in arguments.iter().skip(ARGUMENTS.len()).rev() {
+                    lines.push(format!("pushq {}", operand(argument)));
+                }
+                for (argument, register) in arguments.iter().zip(ARGUMENTS) {
+                    lines.push(format!("movq {}, {}", operand(argument), register));
+                }
+                lines.push(format!("call {}", symbol));
+                if stack > 0 {
+                    lines.push(format!("addq ${}, %rsp", 8 * (stack + stack % 2)));
+                }
+                if let Some(result) = result {
+                    lines.push(format!("movq %rax, {}", operand(result)));
+                }
+                lines
+            },
+            Instruction::Return(value) => {
+                let mut lines = value.iter().map(|value| format!("movq {}, %rax", operand(value))).collect::<Vec<_>>();
+                lines.push(format!("jmp .L{}_return", function.symbol));
+                lines
+            },
+            Instruction::LoadGlobal(value, name) => {
+                vec![format!("movq kn_global_{}(%rip), %rax", name), format!("movq %rax, {}", operand(value))]
+            },
+            Instruction::StoreGlobal(name, value) => {
+                vec![format!("movq {}, %rax", operand(value)), format!("movq %rax, kn_global_{}(%rip)", name)]
+            },
+            Instruction::WriteInteger(value) => vec![format!("movq {}, %rdi", operand(value)), "call kn_write_int".to_string()],
+            Instruction::WriteBoolean(value) => vec![format!("movq {}, %rdi", operand(value)), "call kn_write_bool".to_string()],
+            Instruction::WriteText(string) => vec![format!("leaq .Lkn_string_{}(%rip), %rdi", string), "call kn_write_text".to_string()],
+            Instruction::Assert(value, message) => vec![
+                format!("cmpq $0, {}", operand(value)),
+                "jne 1f".to_string(),
+                format!("leaq .Lkn_string_{}(%rip), %rdi", message),
+                "jmp kn_fail".to_string(),
+                "1:".to_string(),
+            ],
+            Instruction::Locate(location) => vec![
+                format!("leaq .Lkn_string_{}(%rip), %rax", location),
+                "movq %rax, kn_location(%rip)".to_string(),
+            ],
+        };
+        out.extend(lines.into_iter().map(|line| if line.ends_with(':') { line } else { format!("    {}", line) }));
+    }
+
+    out.push(format!(".L{}_return:", function.symbol));
+    if function.symbol == "main" {
+        out.push("    xorl %eax, %eax".to_string());
+    }
+    if saved > 0 {
+        out.push(format!("    leaq -{}(%rbp), %rsp", 8 * saved));
+    }
+    for register in allocation.registers.iter().rev() {
+        out.push(format!("    popq {}", register));
+    }
+    out.push("    leave".to_string());
+    out.push("    ret".to_string());
+    out.push(String::new());
+    out.join("\n")
+}
+
+/// The instructions of a function being lowered.
+#[derive(Default)]
+struct Body {
+    parameters: Vec<Value>,
+    instructions: Vec<Instruction>,
+    values: usize,
+    labels: usize,
+}
+
+impl Code for Body {
+    type Variable = Value;
+
+    fn parameter(&mut self, _: &str, _: Type) -> Value {
+        let value = self.value();
+        self.parameters.push(value);
+        value
+    }
+}
+
+impl Body {
+    fn value(&mut self) -> Value {
+        self.values += 1;
+        Value(self.values - 1)
+    }
+
+    fn label(&mut self) -> usize {
+        self.labels += 1;
+        self.labels - 1
+    }
+}
+
+struct Compiler<'a> {
+    file: &'a str,
+    /// Functions bound by `let` at the top level of the program.
+    functions: HashMap<String, &'a FunctionLiteral>,
+    instances: Vec<Instance>,
+    compiled: Vec<Function>,
+    globals: HashMap<String, Type>,
+    /// Every message, location and printed string, each stored once.
+    strings: Vec<String>,
+    frame: Frame<Body>,
+    /// Span of the innermost statement or expression with one, for diagnostics.
+    span: Span,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Compiler<'a> {
+    fn push(&mut self, instruction: Instruction) {
+        self.frame.code.instructions.push(instruction);
+    }
+
+    fn error(&mut self, span: Span, message: String) {
+        self.diagnostics.push(Diagnostic::new(message, span));
+    }
+
+    fn unsupported(&mut self, span: Span, what: &str) -> (Type, Option<Value>) {
+        super::unsupported(self, span, what);
+        (Type::Unknown, None)
+    }
+
+    fn string(&mut self, text: &str) -> usize {
+        match self.strings.iter().position(|string| string == text) {
+            Some(index) => index,
+            None => {
+                self.strings.push(text.to_string());
+                self.strings.len() - 1
+            },
+        }
+    }
+
+    fn write_text(&mut self, text: &str) {
+        let string = self.string(text);
+        self.push(Instruction::WriteText(string));
+    }
+
+    fn write(&mut self, ty: Type, value: Option<Value>) {
+        match (ty, value) {
+            (Type::I64, Some(value)) => self.push(Instruction::WriteInteger(value)),
+            (Type::Boolean, Some(value)) => self.push(Instruction::WriteBoolean(value)),
+            _ => {},
+        }
+    }
+
+    /// Writes a value on a line of its own, unless it is null.
+    fn write_line(&mut self, ty: Type, value: Option<Value>) {
+        if ty.has_value() && value.is_some() {
+            self.write(ty, value);
+            self.write_text("\n");
+        }
+    }
+
+    fn main(&mut self, statements: &[Box<dyn Statement>]) {
+        for (i, statement) in statements.iter().enumerate() {
+            let mode = if i + 1 == statements.len() { Mode::Write } else { Mode::Discard };
+            self.statement(statement.as_ref(), mode, true);
+        }
+        let frame = std::mem::replace(&mut self.frame, Frame::new(None));
+        self.compiled.push(Function {
+            symbol: "main".to_string(),
+            description: "The program".to_string(),
+            parameters: Vec::new(),
+            instructions: frame.code.instructions,
+            values: frame.code.values,
+        });
+    }
+
+    /// Lowers `statement`, doing with its value what `mode` says. `returns` is whether a
+    /// `return` in it leaves the function, as it does unless it is part of an `if` whose
+    /// value is used.
+    fn statement(&mut self, statement: &dyn Statement, mode: Mode<Value>, returns: bool) -> Type {
+        self.span = statement.span();
+        let location = self.string(&format!("{}:{}", self.file, statement.span().start));
+        self.push(Instruction::Locate(location));
+
+        let any = statement.as_any();
+        let (ty, value) = if let Some(statement) = any.downcast_ref::<LetStatement>() {
+            self.let_statement(statement);
+            (Type::Null, None)
+        } else if let Some(statement) = any.downcast_ref::<ReturnStatement>() {
+            if !returns {
+                return self.unsupported(statement.span, "`return` inside an `if` used as a value is").0;
+            }
+            self.return_statement(statement)
+        } else if let Some(statement) = any.downcast_ref::<ExpressionStatement>() {
+            match statement.expression.as_any().downcast_ref::<IfExpression>() {
+                Some(if_expression) => return self.if_expression(if_expression, mode, returns),
+                None => self.expression(statement.expression.as_ref()),
+            }
+        } else if let Some(block) = any.downcast_ref::<BlockStatement>() {
+            return self.block(&block.statements, mode, returns);
+        } else if any.is::<TestStatement>() {
+            // Tests only run under `keynes test`.
+            (Type::Null, None)
+        } else if any.is::<ImportStatement>() || any.is::<UseStatement>() {
+            self.unsupported(statement.span(), "modules are")
+        } else if any.is::<LetTupleStatement>() {
+            self.unsupported(statement.span(), "destructuring is")
+        } else {
+            self.unsupported(statement.span(), &format!("`{}` is", statement))
+        };
+        self.finish(ty, value, mode)
+    }
+
+    /// Does with a value what `mode` says, returning its type.
+    fn finish(&mut self, ty: Type, value: Option<Value>, mode: Mode<Value>) -> Type {
+        match (mode, value) {
+            (Mode::Value(result), Some(value)) => self.push(Instruction::Copy(result, value)),
+            (Mode::Write, _) => self.write_line(ty, value),
+            _ => {},
+        }
+        ty
+    }
+
+    fn block(&mut self, statements: &[Box<dyn Statement>], mode: Mode<Value>, returns: bool) -> Type {
+        let Some((last, rest)) = statements.split_last() else {
+            return Type::Null;
+        };
+        for statement in rest {
+            self.statement(statement.as_ref(), Mode::Discard, returns);
+        }
+        self.statement(last.as_ref(), mode, returns)
+    }
+
+    fn let_statement(&mut self, statement: &LetStatement) {
+        let name = statement.name.to_string();
+        if let Some(function) = statement.value.as_any().downcast_ref::<FunctionLiteral>() {
+            if function.is_async {
+                self.unsupported(function.span, "async functions are");
+                return;
+            }
+            let defined = self.functions.get(&name).is_some_and(|defined| std::ptr::eq(*defined, function));
+            if !defined || self.frame.function.is_some() {
+                self.unsupported(function.span, "functions not bound by a top-level `let` are");
+            }
+            return;
+        }
+        let (ty, value) = self.expression(statement.value.as_ref());
+        if ty == Type::Null {
+            self.unsupported(statement.span, &format!("binding `{}` to NULL is", name));
+            return;
+        }
+
+        let previous = match self.frame.function {
+            Some(_) => self.frame.variables.get(&name).copied().flatten().map(|(ty, _)| ty),
+            None => self.globals.get(&name).copied(),
+        };
+        if let Some(previous) = previous.filter(|previous| *previous != ty && ![previous, &ty].contains(&&Type::Unknown)) {
+            let what = format!("rebinding `{}` from {} to {} is", name, previous.name(), ty.name());
+            self.unsupported(statement.span, &what);
+            return;
+        }
+        let Some(value) = value else {
+            // Still bound, so its uses do not report it missing.
+            match self.frame.function {
+                Some(_) if self.frame.variables.get(&name).copied().flatten().is_none() => {
+                    let variable = self.frame.code.value();
+                    self.frame.variables.insert(name, Some((Type::Unknown, variable)));
+                },
+                Some(_) => {},
+                None => {
+                    self.globals.entry(name).or_insert(Type::Unknown);
+                },
+            }
+            return;
+        };
+        match self.frame.function {
+            Some(_) => {
+                // Each variable keeps one virtual register, as branches may bind it.
+                let variable = match self.frame.variables.get(&name).copied().flatten() {
+                    Some((_, variable)) => variable,
+                    None => self.frame.code.value(),
+                };
+                self.push(Instruction::Copy(variable, value));
+                self.frame.variables.insert(name, Some((ty, variable)));
+            },
+            None => {
+                self.globals.insert(name.clone(), ty);
+                self.push(Instruction::StoreGlobal(name, value));
+            },
+        }
+    }
+
+    fn return_statement(&mut self, statement: &ReturnStatement) -> (Type, Option<Value>) {
+        let (ty, value) = self.expression(statement.expression.as_ref());
+        match self.frame.function.clone() {
+            None => {
+                self.write_line(ty, value);
+                self.push(Instruction::Return(None));
+            },
+            Some(function) => {
+                match merge(self.frame.returns, ty) {
+                    Some(returns) => self.frame.returns = returns,
+                    None => {
+                        let what = format!("returning both {} and {} from `{}` is", self.frame.returns.name(), ty.name(), function);
+                        self.unsupported(statement.span, &what);
+                    },
+                }
+                self.push(Instruction::Return(value));
+            },
+        }
+        (Type::Never, None)
+    }
+
+    fn if_expression(&mut self, if_expression: &IfExpression, mode: Mode<Value>, returns: bool) -> Type {
+        let condition = self.condition(if_expression.condition.as_ref());
+        let alternative_label = self.frame.code.label();
+        let end = self.frame.code.label();
+        if let Some(condition) = condition {
+            self.push(Instruction::JumpIfZero(condition, alternative_label));
+        }
+        let consequence = self.block(&if_expression.consequence.statements, mode, returns);
+        self.push(Instruction::Jump(end));
+        self.push(Instruction::Label(alternative_label));
+        let alternative = match &if_expression.alternative {
+            Some(alternative) => self.block(&alternative.statements, mode, returns),
+            None => Type::Null,
+        };
+        self.push(Instruction::Label(end));
+
+        match (mode, merge(consequence, alternative)) {
+            (Mode::Value(_), Some(ty)) => ty,
+            (Mode::Value(_), None) => {
+                let what = format!("`if` branches of types {} and {} are", consequence.name(), alternative.name());
+                self.unsupported(if_expression.span, &what).0
+            },
+            (_, ty) => match ty {
+                Some(Type::Never) => Type::Never,
+                _ => Type::Null,
+            },
+        }
+    }
+
+    /// Lowers `condition` to a value that is 0 when it is not truthy.
+    fn condition(&mut self, condition: &dyn Expression) -> Option<Value> {
+        match self.expression(condition) {
+            (Type::Boolean, value) => value,
+            (Type::Never | Type::Unknown, _) => None,
+            (ty, _) => {
+                let value = self.frame.code.value();
+                self.push(Instruction::Constant(value, (ty != Type::Null) as i64));
+                Some(value)
+            },
+        }
+    }
+
+    fn expression(&mut self, expression: &dyn Expression) -> (Type, Option<Value>) {
+        let outer = self.span;
+        if let Some(span) = span_of(expression) {
+            self.span = span;
+        }
+        let result = self.expression_value(expression);
+        self.span = outer;
+        result
+    }
+
+    fn constant(&mut self, constant: i64) -> Value {
+        let value = self.frame.code.value();
+        self.push(Instruction::Constant(value, constant));
+        value
+    }
+
+    fn expression_value(&mut self, expression: &dyn Expression) -> (Type, Option<Value>) {
+        let any = expression.as_any();
+        if let Some(integer) = any.downcast_ref::<IntegerLiteral>() {
+            (Type::I64, Some(self.constant(integer.value)))
+        } else if let Some(boolean) = any.downcast_ref::<BooleanLiteral>() {
+            (Type::Boolean, Some(self.constant(boolean.value as i64)))
+        } else if any.is::<FloatLiteral>() {
+            self.unsupported(self.span, "floats are")
+        } else if any.is::<StringLiteral>() {
+            self.unsupported(self.span, "strings outside `print`, `println` and `assert` are")
+        } else if let Some(identifier) = any.downcast_ref::<IdentifierLiteral>() {
+            self.identifier(identifier)
+        } else if let Some(prefix) = any.downcast_ref::<PrefixExpression>() {
+            self.prefix(prefix)
+        } else if let Some(infix) = any.downcast_ref::<InfixExpression>() {
+            self.infix(infix)
+        } else if let Some(if_expression) = any.downcast_ref::<IfExpression>() {
+            let result = self.frame.code.value();
+            let ty = self.if_expression(if_expression, Mode::Value(result), false);
+            (ty, ty.has_value().then_some(result))
+        } else if let Some(function) = any.downcast_ref::<FunctionLiteral>() {
+            self.unsupported(function.span, "functions not bound by a top-level `let` are")
+        } else if let Some(call) = any.downcast_ref::<CallExpression>() {
+            self.call(call)
+        } else if let Some(array) = any.downcast_ref::<ArrayLiteral>() {
+            self.unsupported(array.span, "arrays are")
+        } else if let Some(hash) = any.downcast_ref::<HashLiteral>() {
+            self.unsupported(hash.span, "hashes are")
+        } else if let Some(index) = any.downcast_ref::<IndexExpression>() {
+            self.unsupported(index.span, "indexing is")
+        } else if let Some(spawn) = any.downcast_ref::<SpawnExpression>() {
+            self.unsupported(spawn.span, "tasks are")
+        } else if let Some(select) = any.downcast_ref::<SelectExpression>() {
+            self.unsupported(select.span, "channels are")
+        } else if let Some(await_expression) = any.downcast_ref::<AwaitExpression>() {
+            self.unsupported(await_expression.span, "futures are")
+        } else if let Some(path) = any.downcast_ref::<PathExpression>() {
+            self.unsupported(path.span, "modules are")
+        } else {
+            self.unsupported(self.span, &format!("`{}` is", expression))
+        }
+    }
+
+    /// Whether `name` is a variable where it is used.
+    fn is_variable(&self, name: &str) -> bool {
+        match self.frame.function {
+            Some(_) => self.frame.variables.contains_key(name) || self.globals.contains_key(name),
+            None => self.globals.contains_key(name),
+        }
+    }
+
+    fn identifier(&mut self, identifier: &IdentifierLiteral) -> (Type, Option<Value>) {
+        let name = identifier.to_string();
+        match self.frame.variables.get(&name).copied() {
+            Some(Some((ty, variable))) => {
+                // Copied, as the variable may be bound again before the value is used.
+                let value = self.frame.code.value();
+                self.push(Instruction::Copy(value, variable));
+                return (ty, Some(value));
+            },
+            Some(None) => return self.unsupported(identifier.span, &format!("reading `{}` before it is bound is", name)),
+            None => {},
+        }
+        match self.globals.get(&name).copied() {
+            Some(Type::Unknown) => (Type::Unknown, None),
+            Some(ty) => {
+                let value = self.frame.code.value();
+                self.push(Instruction::LoadGlobal(value, name));
+                (ty, Some(value))
+            },
+            None if self.functions.contains_key(&name) || builtins::get(&name).is_some() => {
+                self.unsupported(identifier.span, "functions as values are")
+            },
+            None => {
+                self.error(identifier.span, format!("identifier not found: {}", name));
+                (Type::Unknown, None)
+            },
+        }
+    }
+
+    fn prefix(&mut self, prefix: &PrefixExpression) -> (Type, Option<Value>) {
+        let (ty, value) = self.expression(prefix.right.as_ref());
+        let result = self.frame.code.value();
+        match (&prefix.operator, ty, value) {
+            (_, Type::Unknown, _) => return (Type::Unknown, None),
+            (PrefixOperator::BANG, Type::Boolean, Some(value)) => self.push(Instruction::Not(result, value)),
+            (PrefixOperator::BANG, ty, _) => self.push(Instruction::Constant(result, (ty == Type::Null) as i64)),
+            (PrefixOperator::MINUS, Type::I64, Some(value)) => {
+                self.push(Instruction::Negate(result, value));
+                return (Type::I64, Some(result));
+            },
+            (operator, ty, _) => {
+                self.error(prefix.span, format!("unknown operator: {}{}", operator, ty.name()));
+                return (Type::Unknown, None);
+            },
+        }
+        (Type::Boolean, Some(result))
+    }
+
+    fn infix(&mut self, infix: &InfixExpression) -> (Type, Option<Value>) {
+        let (left, left_value) = self.expression(infix.left.as_ref());
+        let (right, right_value) = self.expression(infix.right.as_ref());
+        let operator = match infix.operator {
+            InfixOperator::PLUS => Operator::Add,
+            InfixOperator::MINUS => Operator::Subtract,
+            InfixOperator::MULTIPLY => Operator::Multiply,
+            InfixOperator::DIVIDE => Operator::Divide,
+            InfixOperator::EQUAL => Operator::Equal,
+            InfixOperator::NOT_EQUAL => Operator::NotEqual,
+            InfixOperator::LESS_THAN => Operator::Less,
+            InfixOperator::LESS_THAN_EQUAL => Operator::LessEqual,
+            InfixOperator::GREATER_THAN => Operator::Greater,
+            InfixOperator::GREATER_THAN_EQUAL => Operator::GreaterEqual,
+        };
+        let arithmetic = matches!(operator, Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide);
+        let ty = match (left, right) {
+            (Type::Unknown, _) | (_, Type::Unknown) => return (if arithmetic { Type::Unknown } else { Type::Boolean }, None),
+            (Type::I64, Type::I64) if arithmetic => Type::I64,
+            (Type::I64, Type::I64) => Type::Boolean,
+            (Type::Boolean, Type::Boolean) if matches!(operator, Operator::Equal | Operator::NotEqual) => Type::Boolean,
+            (left, right) => {
+                let problem = if left.name() != right.name() { "type mismatch" } else { "unknown operator" };
+                self.error(infix.span, format!("{}: {} {} {}", problem, left.name(), infix.operator, right.name()));
+                return (Type::Unknown, None);
+            },
+        };
+        let (Some(left_value), Some(right_value)) = (left_value, right_value) else {
+            return (ty, None);
+        };
+        let result = self.frame.code.value();
+        self.push(Instruction::Binary(operator, result, left_value, right_value));
+        (ty, Some(result))
+    }
+
+    fn call(&mut self, call: &CallExpression) -> (Type, Option<Value>) {
+        let Some(callee) = call.function.as_any().downcast_ref::<IdentifierLiteral>() else {
+            return self.unsupported(call.span, "calling anything but a function by name is");
+        };
+        let name = callee.to_string();
+        if self.is_variable(&name) {
+            return self.unsupported(callee.span, "functions as values are");
+        }
+        if let Some(function) = self.functions.get(&name).copied() {
+            return self.call_function(&name, function, call);
+        }
+        if builtins::get(&name).is_some() {
+            return self.builtin(&name, call);
+        }
+        self.error(callee.span, format!("identifier not found: {}", name));
+        (Type::Unknown, None)
+    }
+
+    fn call_function(&mut self, name: &str, function: &'a FunctionLiteral, call: &CallExpression) -> (Type, Option<Value>) {
+        if function.parameters.len() != call.arguments.len() {
+            self.error(
+                call.span,
+                format!(
+                    "wrong number of arguments to {}: expected {}, got {}",
+                    name,
+                    function.parameters.len(),
+                    call.arguments.len()
+                ),
+            );
+            return (Type::Unknown, None);
+        }
+        let mut types = Vec::new();
+        let mut values = Vec::new();
+        for argument in &call.arguments {
+            let (ty, value) = self.expression(argument.as_ref());
+            types.push(ty);
+            values.extend(value);
+        }
+        if types.contains(&Type::Unknown) {
+            return (Type::Unknown, None);
+        }
+        if types.contains(&Type::Null) {
+            return self.unsupported(call.span, &format!("passing NULL to `{}` is", name));
+        }
+        let (symbol, result) = instance(self, name, function, types);
+        if result == Type::Unknown {
+            self.frame.pending = true;
+        }
+        let value = result.has_value().then(|| self.frame.code.value());
+        self.push(Instruction::Call(value, symbol, values));
+        (result, value)
+    }
+
+    fn builtin(&mut self, name: &str, call: &CallExpression) -> (Type, Option<Value>) {
+        match name {
+            "print" | "println" => {
+                for (i, argument) in call.arguments.iter().enumerate() {
+                    if i > 0 {
+                        self.write_text(" ");
+                    }
+                    match argument.as_any().downcast_ref::<StringLiteral>() {
+                        Some(string) => self.write_text(&string.value),
+                        None => match self.expression(argument.as_ref()) {
+                            (Type::Null, _) => self.write_text("null"),
+                            (ty, value) => self.write(ty, value),
+                        },
+                    }
+                }
+                if name == "println" {
+                    self.write_text("\n");
+                }
+                (Type::Null, None)
+            },
+            "assert" => {
+                let message = match call.arguments.len() {
+                    1 => "assertion failed".to_string(),
+                    2 => match call.arguments[1].as_any().downcast_ref::<StringLiteral>() {
+                        Some(message) => format!("assertion failed: {}", message.value),
+                        None => return self.unsupported(call.span, "assertion messages other than string literals are"),
+                    },
+                    count => {
+                        self.error(call.span, format!("wrong number of arguments to assert: expected 1, got {}", count));
+                        return (Type::Unknown, None);
+                    },
+                };
+                if let Some(condition) = self.condition(call.arguments[0].as_ref()) {
+                    let message = self.string(&message);
+                    self.push(Instruction::Assert(condition, message));
+                }
+                (Type::Null, None)
+            },
+            _ => self.unsupported(call.span, &format!("the builtin `{}` is", name)),
+        }
+    }
+}
+
+impl<'a> Backend<'a> for Compiler<'a> {
+    type Code = Body;
+
+    const NAME: &'static str = "x86-64";
+
+    fn functions(&mut self) -> &mut HashMap<String, &'a FunctionLiteral> {
+        &mut self.functions
+    }
+
+    fn instances(&mut self) -> &mut Vec<Instance> {
+        &mut self.instances
+    }
+
+    fn frame(&mut self) -> &mut Frame<Body> {
+        &mut self.frame
+    }
+
+    fn diagnostics(&mut self) -> &mut Vec<Diagnostic> {
+        &mut self.diagnostics
+    }
+
+    /// Integers are all 64 bits wide, so they are described like the evaluator names them.
+    fn describe(ty: Type) -> &'static str {
+        ty.name()
+    }
+
+    fn symbol(&self, _: &str, _: &[Type], index: usize) -> String {
+        format!("kn_function_{}", index)
+    }
+
+    fn body(&mut self, function: &FunctionLiteral) -> Type {
+        let result = self.frame.code.value();
+        let value = self.block(&function.body.statements, Mode::Value(result), true);
+        if value.has_value() {
+            self.push(Instruction::Return(Some(result)));
+        }
+        value
+    }
+
+    fn compiled(&mut self, instance: Instance, frame: Frame<Body>) {
+        let types = instance.arguments.iter().map(|argument| argument.name()).collect::<Vec<_>>();
+        self.compiled.push(Function {
+            symbol: instance.symbol,
+            description: format!("{}({})", instance.name, types.join(", ")),
+            parameters: frame.code.parameters,
+            instructions: frame.code.instructions,
+            values: frame.code.values,
+        });
+    }
+}
+
+/// The contents of a GNU assembler string literal holding `text`.
+fn gas_string(text: &str) -> String {
+    let mut literal = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'"' | b'\\' => literal.push_str(&format!("\\{}", byte as char)),
+            b' '..=b'~' => literal.push(byte as char),
+            _ => literal.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    literal
+}
+
+#[cfg(all(test, target_os = "linux", target_arch = "x86_64"))]
+#[path = "./x86_64_tests.rs"]
+mod tests;
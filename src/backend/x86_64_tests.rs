@@ -0,0 +1,150 @@
+use std::{
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::*;
+
+use crate::{backend::front_end, generator::Generator, optimizer::OptLevel, Object};
+
+use test_case::test_case;
+
+static NEXT_BINARY: AtomicUsize = AtomicUsize::new(0);
+
+fn assembly(source: &str) -> Result<String, Vec<String>> {
+    let diagnostics = |diagnostics: Vec<Diagnostic>| diagnostics.into_iter().map(|diagnostic| diagnostic.message).collect::<Vec<_>>();
+    let program = front_end(source, OptLevel::O1).map_err(diagnostics)?;
+    compile("main.ks", &program).map_err(diagnostics)
+}
+
+/// Assembles, links and runs `source`, returning its exit code, stdout and stderr.
+fn run(source: &str) -> (i32, String, String) {
+    let assembly = assembly(source).unwrap();
+    let binary = std::env::temp_dir().join(format!(
+        "keynes-x86-64-{}-{}",
+        std::process::id(),
+        NEXT_BINARY.fetch_add(1, Ordering::SeqCst)
+    ));
+    build(&assembly, &binary).unwrap();
+    let output = Command::new(&binary).output().unwrap();
+    let _ = std::fs::remove_file(&binary);
+    (
+        output.status.code().unwrap_or(-1),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+#[test_case("1 + 2 * 3", "7\n"; "arithmetic")]
+#[test_case("let x = 5; let y = x * 2; y - 1", "9\n"; "globals")]
+#[test_case("println(\"hello\", 1, true); print(\"a\\\"b\", false)", "hello 1 true\na\"b false"; "printing")]
+#[test_case("println(println())", "\nnull\n"; "printing null")]
+#[test_case("let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) }; fib(25)", "75025\n"; "recursion")]
+#[test_case("let fact = fn(n) { if (n == 0) { 1 } else { n * fact(n - 1) } }; fact(20)", "2432902008176640000\n"; "recursion in branch")]
+#[test_case("let id = fn(x) { x }; println(id(1)); id(true)", "1\ntrue\n"; "instance per argument type")]
+#[test_case("let x = 1; let f = fn() { x + 1 }; let x = 2; f()", "3\n"; "functions read globals")]
+#[test_case("let f = fn(x) { let y = x * 2; if (y > 5) { let y = 0; } y }; println(f(1)); f(3)", "2\n0\n"; "blocks share the function scope")]
+#[test_case("let f = fn(a, b, c, d, e, f, g, h) { a - b + c - d + e - f + g * h }; f(1, 2, 3, 4, 5, 6, 7, 8)", "53\n"; "stack arguments")]
+#[test_case("let f = fn(a, b, c, d, e, f, g) { a * 1000000 + g }; f(1, 2, 3, 4, 5, 6, 7)", "1000007\n"; "odd stack arguments")]
+#[test_case("let f = fn(a, b, c) { let d = a + b; let e = b + c; let g = a * c; let h = d - e; let i = g + h; let j = d * e; a + b + c + d + e + g + h + i + j }; f(1, 2, 3)", "31\n"; "spilling")]
+#[test_case("let x = 5; x / 2 + -x / 2", "0\n"; "division truncates")]
+#[test_case("!true == !1", "true\n"; "bang")]
+#[test_case("if (0) { 1 } else { 2 }", "1\n"; "integers are truthy")]
+#[test_case("let x = 3; if (x > 2) { x } else { false }", "3\n"; "program value may differ in type by branch")]
+#[test_case("let x = 3; if (x > 5) { x }", ""; "null program value")]
+#[test_case("return 5; 6", "5\n"; "top level return")]
+#[test_case("let f = fn(x) { if (x) { return 1; } else { return 2; } }; f(false)", "2\n"; "both branches return")]
+#[test_case("9223372036854775807 - 4294967296", "9223372032559808511\n"; "large constants")]
+#[test_case("test \"ignored\" { assert(false) } 1", "1\n"; "tests are skipped")]
+#[test_case("assert(1 < 2, \"math\"); 1", "1\n"; "passing assertion")]
+fn test_compiled_output(source: &str, expected: &str) {
+    assert_eq!(run(source), (0, expected.to_string(), String::new()));
+}
+
+#[test_case("9223372036854775807 + 1", "attempt to add with overflow"; "add")]
+#[test_case("let x = 0 - 9223372036854775807; x - 2", "attempt to subtract with overflow"; "subtract")]
+#[test_case("4611686018427387904 * 2", "attempt to multiply with overflow"; "multiply")]
+#[test_case("let x = 0 - 9223372036854775807 - 1; x / -1", "attempt to divide with overflow"; "divide")]
+#[test_case("let x = 0 - 9223372036854775807 - 1; -x", "attempt to negate with overflow"; "negate")]
+#[test_case("let x = 0; 1 / x", "division by zero"; "division by zero")]
+#[test_case("assert(false)", "assertion failed"; "assertion")]
+#[test_case("assert(1 > 2, \"math\")", "assertion failed: math"; "assertion with message")]
+fn test_runtime_errors(source: &str, message: &str) {
+    let (code, out, err) = run(source);
+    assert_eq!((code, out.as_str()), (1, ""));
+    assert!(err.starts_with(&format!("error: {}\n --> main.ks:1:", message)), "{}", err);
+}
+
+#[test]
+fn test_error_location_and_output_before_it() {
+    let source = "println(\"before\");\nlet f = fn(x) {\n  x + 1\n};\nf(9223372036854775807);\nprintln(\"after\");";
+    let (code, out, err) = run(source);
+    assert_eq!((code, out.as_str()), (1, "before\n"));
+    assert_eq!(err, "error: attempt to add with overflow\n --> main.ks:3:3\n");
+}
+
+#[test_case("\"a\"", "strings outside `print`, `println` and `assert` are not supported by the x86-64 backend"; "strings")]
+#[test_case("1.5", "floats are not supported by the x86-64 backend"; "floats")]
+#[test_case("[1]", "arrays are not supported by the x86-64 backend"; "arrays")]
+#[test_case("{1: 2}", "hashes are not supported by the x86-64 backend"; "hashes")]
+#[test_case("let f = fn() { fn() { 1 } }; f()", "functions not bound by a top-level `let` are not supported by the x86-64 backend"; "closures")]
+#[test_case("let f = fn(g) { g(1) }; f(len)", "functions as values are not supported by the x86-64 backend"; "function values")]
+#[test_case("i8(1)", "the builtin `i8` is not supported by the x86-64 backend"; "widths")]
+#[test_case("let x = 1; let x = true;", "rebinding `x` from INTEGER to BOOLEAN is not supported by the x86-64 backend"; "rebinding to another type")]
+#[test_case("let x = println();", "binding `x` to NULL is not supported by the x86-64 backend"; "null variable")]
+#[test_case("let f = fn(x) { if (x) { return 1; } true }; f(true)", "returning both INTEGER and BOOLEAN from `f` is not supported by the x86-64 backend"; "return types")]
+#[test_case("let c = true; let x = if (c) { 1 } else { false }; x", "`if` branches of types INTEGER and BOOLEAN are not supported by the x86-64 backend"; "if types")]
+#[test_case("let f = fn(c) { let x = if (c) { return 1; } else { 2 }; x }; f(true)", "`return` inside an `if` used as a value is not supported by the x86-64 backend"; "return in value")]
+#[test_case("let f = fn() { f() }; f()", "cannot infer the return type of `f`"; "endless recursion")]
+fn test_unsupported(source: &str, message: &str) {
+    assert_eq!(assembly(source), Err(vec![message.to_string()]));
+}
+
+#[test]
+fn test_allocation_spills_and_never_shares_registers() {
+    // Seven values live at once, more than there are registers.
+    let mut instructions = (0..7).map(|i| Instruction::Constant(Value(i), i as i64)).collect::<Vec<_>>();
+    instructions.extend((0..7).map(|i| Instruction::WriteInteger(Value(i))));
+    let function = Function {
+        symbol: "kn_function_0".to_string(),
+        description: "test".to_string(),
+        parameters: vec![],
+        instructions,
+        values: 7,
+    };
+    let allocation = allocate(&function);
+    assert_eq!(allocation.slots, 2);
+    assert_eq!(allocation.registers, REGISTERS.to_vec());
+    let registers = allocation
+        .locations
+        .iter()
+        .filter_map(|location| match location {
+            Location::Register(register) => Some(register),
+            Location::Stack(_) => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(registers.len(), 5);
+    assert!(registers.iter().enumerate().all(|(i, register)| !registers[..i].contains(register)));
+}
+
+/// The programs the backend accepts print what `keynes run` would.
+#[test]
+fn test_generated_programs_match_the_evaluator() {
+    let mut compiled = 0;
+    for seed in 0..2000 {
+        let source = Generator::new(seed, 3).program();
+        if assembly(&source).is_err() {
+            continue;
+        }
+        compiled += 1;
+        let expected = match crate::Engine::new().eval(&source) {
+            Ok(Object::Null) => (0, String::new(), None),
+            Ok(value) => (0, format!("{}\n", value), None),
+            Err(err) => (1, String::new(), Some(err.to_string())),
+        };
+        let (code, out, err) = run(&source);
+        let error = err.strip_prefix("error: ").map(|err| err.lines().next().unwrap().to_string());
+        assert_eq!((code, out, error), expected, "seed {}\n{}", seed, source);
+    }
+    assert!(compiled > 10, "only {} programs compiled", compiled);
+}
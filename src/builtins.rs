@@ -0,0 +1,426 @@
+use std::{
+    collections::BTreeSet,
+    io::{BufRead, Write},
+    time::Duration,
+};
+
+use crate::{
+    channel,
+    evaluator::RuntimeError,
+    object::{Builtin, Float, Integer, Object},
+    scheduler,
+};
+
+/// Looks up the builtin called `name`. Bindings in scope shadow builtins.
+pub fn get(name: &str) -> Option<Object> {
+    let builtin = match name {
+        "print" => Builtin::new(name, |args| write_out(&args, false)),
+        "println" => Builtin::new(name, |args| write_out(&args, true)),
+        "input" => Builtin::new(name, input),
+        "read_file" => Builtin::new(name, read_file),
+        "write_file" => Builtin::new(name, write_file),
+        "abs" => Builtin::new(name, abs),
+        "min" => Builtin::new(name, |args| extremum("min", args, |ordering| ordering.is_lt())),
+        "max" => Builtin::new(name, |args| extremum("max", args, |ordering| ordering.is_gt())),
+        "pow" => Builtin::new(name, pow),
+        "sqrt" => Builtin::new(name, sqrt),
+        "len" => Builtin::new(name, len),
+        "split" => Builtin::new(name, split),
+        "trim" => Builtin::new(name, |args| map_string("trim", args, |s| s.trim().to_string())),
+        "to_upper" => Builtin::new(name, |args| map_string("to_upper", args, |s| s.to_uppercase())),
+        "to_lower" => Builtin::new(name, |args| map_string("to_lower", args, |s| s.to_lowercase())),
+        "first" => Builtin::new(name, |args| Ok(array("first", args)?.first().cloned().unwrap_or(Object::Null))),
+        "last" => Builtin::new(name, |args| Ok(array("last", args)?.last().cloned().unwrap_or(Object::Null))),
+        "rest" => Builtin::new(name, rest),
+        "push" => Builtin::new(name, push),
+        "keys" => Builtin::new(name, keys),
+        "values" => Builtin::new(name, values),
+        "str" => Builtin::new(name, |args| Ok(Object::String(one("str", args)?.to_string()))),
+        "assert" => Builtin::new(name, assert),
+        "assert_eq" => Builtin::new(name, assert_eq),
+        "join" => Builtin::new(name, join),
+        "yield" => Builtin::new(name, yield_now),
+        "channel" => Builtin::new(name, channel),
+        "send" => Builtin::new(name, send),
+        "recv" => Builtin::new(name, recv),
+        "close" => Builtin::new(name, close),
+        "timeout" => Builtin::new(name, timeout),
+        "i8" | "i16" | "i32" | "i64" | "i128" => {
+            let width = name.to_string();
+            Builtin::new(name, move |args| to_integer(&width, args))
+        },
+        "f32" | "f64" => {
+            let width = name.to_string();
+            Builtin::new(name, move |args| to_float(&width, args))
+        },
+        _ => return None,
+    };
+    Some(Object::Builtin(builtin))
+}
+
+fn arity(name: &str, args: &[Object], expected: usize) -> Result<(), RuntimeError> {
+    if args.len() != expected {
+        return Err(RuntimeError::new(format!(
+            "wrong number of arguments to {}: expected {}, got {}",
+            name,
+            expected,
+            args.len()
+        )));
+    }
+    Ok(())
+}
+
+fn unsupported(name: &str, arg: &Object) -> RuntimeError {
+    RuntimeError::new(format!("argument to `{}` not supported, got {}", name, arg.type_name()))
+}
+
+fn one(name: &str, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    arity(name, &args, 1)?;
+    Ok(args.into_iter().next().unwrap())
+}
+
+fn two(name: &str, args: Vec<Object>) -> Result<(Object, Object), RuntimeError> {
+    arity(name, &args, 2)?;
+    let mut args = args.into_iter();
+    Ok((args.next().unwrap(), args.next().unwrap()))
+}
+
+fn string(name: &str, arg: Object) -> Result<String, RuntimeError> {
+    match arg {
+        Object::String(value) => Ok(value),
+        other => Err(unsupported(name, &other)),
+    }
+}
+
+fn array(name: &str, args: Vec<Object>) -> Result<Vec<Object>, RuntimeError> {
+    match one(name, args)? {
+        Object::Array(elements) => Ok(elements),
+        other => Err(unsupported(name, &other)),
+    }
+}
+
+fn write_out(args: &[Object], newline: bool) -> Result<Object, RuntimeError> {
+    let line = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().join(" ");
+    let mut stdout = std::io::stdout().lock();
+    let result = if newline {
+        writeln!(stdout, "{}", line)
+    } else {
+        write!(stdout, "{}", line).and_then(|_| stdout.flush())
+    };
+    result.map_err(|err| RuntimeError::new(format!("could not write to stdout: {}", err)))?;
+    Ok(Object::Null)
+}
+
+/// Reads a line from stdin without its line terminator, or `null` at end of input.
+fn input(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    match args.len() {
+        0 => {},
+        1 => write_out(&args, false).map(|_| ())?,
+        _ => arity("input", &args, 1)?,
+    }
+    let mut line = String::new();
+    let read = std::io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .map_err(|err| RuntimeError::new(format!("could not read from stdin: {}", err)))?;
+    if read == 0 {
+        return Ok(Object::Null);
+    }
+    Ok(Object::String(line.trim_end_matches(['\n', '\r']).to_string()))
+}
+
+fn read_file(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    let path = string("read_file", one("read_file", args)?)?;
+    std::fs::read_to_string(&path)
+        .map(Object::String)
+        .map_err(|err| RuntimeError::new(format!("could not read {}: {}", path, err)))
+}
+
+fn write_file(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    let (path, contents) = two("write_file", args)?;
+    let path = string("write_file", path)?;
+    std::fs::write(&path, contents.to_string()).map_err(|err| RuntimeError::new(format!("could not write {}: {}", path, err)))?;
+    Ok(Object::Null)
+}
+
+fn abs(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    match one("abs", args)? {
+        Object::Integer(value) => value.checked(value, "take absolute value", |value, _| value.checked_abs()).map(Object::Integer),
+        Object::Float(value) => Ok(Object::Float(value.with_value(value.to_f64().abs()))),
+        other => Err(unsupported("abs", &other)),
+    }
+}
+
+fn extremum(name: &str, args: Vec<Object>, pick: fn(std::cmp::Ordering) -> bool) -> Result<Object, RuntimeError> {
+    let (left, right) = two(name, args)?;
+    let ordering = match (&left, &right) {
+        (Object::Integer(l), Object::Integer(r)) if l.width() == r.width() => l.to_i128().cmp(&r.to_i128()),
+        (Object::Float(l), Object::Float(r)) if l.width() == r.width() => l.to_f64().total_cmp(&r.to_f64()),
+        _ => {
+            return Err(RuntimeError::new(format!(
+                "arguments to `{}` must be numbers of the same type, got {} and {}",
+                name,
+                left.describe_type(),
+                right.describe_type()
+            )))
+        },
+    };
+    Ok(if pick(ordering) { left } else { right })
+}
+
+fn pow(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    match two("pow", args)? {
+        (Object::Integer(base), Object::Integer(exponent)) => {
+            let exponent = u32::try_from(exponent.to_i128())
+                .map_err(|_| RuntimeError::new(format!("exponent to `pow` must be non-negative, got {}", exponent)))?;
+            base.checked(base, "raise to a power", |base, _| base.checked_pow(exponent))
+                .map(Object::Integer)
+        },
+        (Object::Float(base), Object::Float(exponent)) => Ok(Object::Float(base.with_value(base.to_f64().powf(exponent.to_f64())))),
+        (Object::Float(base), Object::Integer(exponent)) => {
+            Ok(Object::Float(base.with_value(base.to_f64().powf(exponent.to_i128() as f64))))
+        },
+        (base, _) => Err(unsupported("pow", &base)),
+    }
+}
+
+fn sqrt(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    match one("sqrt", args)? {
+        Object::Integer(value) => Ok(Object::Float(Float::F64((value.to_i128() as f64).sqrt()))),
+        Object::Float(value) => Ok(Object::Float(value.with_value(value.to_f64().sqrt()))),
+        other => Err(unsupported("sqrt", &other)),
+    }
+}
+
+fn len(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    let length = match one("len", args)? {
+        Object::String(value) => value.chars().count(),
+        Object::Array(elements) => elements.len(),
+        Object::Hash(pairs) => pairs.len(),
+        other => return Err(unsupported("len", &other)),
+    };
+    Ok(Object::from(length as i64))
+}
+
+/// Splits on the given separator, or on whitespace when called with one argument.
+fn split(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    let parts: Vec<String> = match args.len() {
+        1 => string("split", one("split", args)?)?
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+        _ => {
+            let (value, separator) = two("split", args)?;
+            let (value, separator) = (string("split", value)?, string("split", separator)?);
+            value.split(separator.as_str()).map(str::to_string).collect()
+        },
+    };
+    Ok(Object::Array(parts.into_iter().map(Object::String).collect()))
+}
+
+fn map_string(name: &str, args: Vec<Object>, f: fn(&str) -> String) -> Result<Object, RuntimeError> {
+    Ok(Object::String(f(&string(name, one(name, args)?)?)))
+}
+
+fn rest(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    let elements = array("rest", args)?;
+    if elements.is_empty() {
+        return Ok(Object::Null);
+    }
+    Ok(Object::Array(elements[1..].to_vec()))
+}
+
+fn push(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    match two("push", args)? {
+        (Object::Array(mut elements), value) => {
+            elements.push(value);
+            Ok(Object::Array(elements))
+        },
+        (other, _) => Err(unsupported("push", &other)),
+    }
+}
+
+fn keys(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    match one("keys", args)? {
+        Object::Hash(pairs) => Ok(Object::Array(pairs.into_keys().map(Object::from).collect())),
+        other => Err(unsupported("keys", &other)),
+    }
+}
+
+fn values(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    match one("values", args)? {
+        Object::Hash(pairs) => Ok(Object::Array(pairs.into_values().collect())),
+        other => Err(unsupported("values", &other)),
+    }
+}
+
+/// Fails unless the first argument is truthy, with the second argument as the message if given.
+fn assert(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    let (condition, message) = match args.len() {
+        2 => {
+            let (condition, message) = two("assert", args)?;
+            (condition, format!("assertion failed: {}", message))
+        },
+        _ => (one("assert", args)?, "assertion failed".to_string()),
+    };
+    match condition.is_truthy() {
+        true => Ok(Object::Null),
+        false => Err(RuntimeError::new(message)),
+    }
+}
+
+/// Fails unless both arguments are equal, showing both values and where inside them they differ.
+fn assert_eq(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    let (left, right) = two("assert_eq", args)?;
+    if left == right {
+        return Ok(Object::Null);
+    }
+    let mut message = format!("assertion failed: left == right\n  left: {}\n right: {}", left.inspect(), right.inspect());
+    if matches!((&left, &right), (Object::Array(_), Object::Array(_)) | (Object::Hash(_), Object::Hash(_))) {
+        let mut differences = Vec::new();
+        differ("", &left, &right, &mut differences);
+        for difference in differences {
+            message.push_str(&format!("\n  diff: {}", difference));
+        }
+    }
+    Err(RuntimeError::new(message))
+}
+
+/// Collects a line for each place inside `left` and `right`, named by its index path, where they differ.
+fn differ(path: &str, left: &Object, right: &Object, differences: &mut Vec<String>) {
+    match (left, right) {
+        (Object::Array(left), Object::Array(right)) => {
+            for i in 0..left.len().max(right.len()) {
+                let path = format!("{}[{}]", path, i);
+                match (left.get(i), right.get(i)) {
+                    (Some(left), Some(right)) => differ(&path, left, right, differences),
+                    (Some(left), None) => differences.push(format!("{}: {} is missing on the right", path, left.inspect())),
+                    (None, Some(right)) => differences.push(format!("{}: {} is missing on the left", path, right.inspect())),
+                    (None, None) => {},
+                }
+            }
+        },
+        (Object::Hash(left), Object::Hash(right)) => {
+            let keys = left.keys().chain(right.keys()).collect::<BTreeSet<_>>();
+            for key in keys {
+                let path = format!("{}[{}]", path, Object::from(key.clone()).inspect());
+                match (left.get(key), right.get(key)) {
+                    (Some(left), Some(right)) => differ(&path, left, right, differences),
+                    (Some(left), None) => differences.push(format!("{}: {} is missing on the right", path, left.inspect())),
+                    (None, Some(right)) => differences.push(format!("{}: {} is missing on the left", path, right.inspect())),
+                    (None, None) => {},
+                }
+            }
+        },
+        (left, right) if left != right => {
+            differences.push(format!("{}: {} != {}", path, left.inspect(), right.inspect()));
+        },
+        _ => {},
+    }
+}
+
+fn join(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    match one("join", args)? {
+        Object::Task(task) => task.join(),
+        other => Err(unsupported("join", &other)),
+    }
+}
+
+fn yield_now(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    arity("yield", &args, 0)?;
+    scheduler::yield_now();
+    Ok(Object::Null)
+}
+
+/// A non-negative integer argument, e.g. a capacity or a number of milliseconds.
+fn count(name: &str, arg: Object) -> Result<u64, RuntimeError> {
+    match arg {
+        Object::Integer(value) => u64::try_from(value.to_i128())
+            .map_err(|_| RuntimeError::new(format!("argument to `{}` must be non-negative, got {}", name, value))),
+        other => Err(unsupported(name, &other)),
+    }
+}
+
+/// Makes a channel, bounded by the capacity given if any, as a `[sender, receiver]` pair.
+fn channel(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    let capacity = match args.len() {
+        0 => None,
+        _ => Some(count("channel", one("channel", args)?)? as usize),
+    };
+    let (sender, receiver) = channel::channel(capacity)?;
+    Ok(Object::Array(vec![Object::Sender(sender), Object::Receiver(receiver)]))
+}
+
+fn send(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    match two("send", args)? {
+        (Object::Sender(sender), value) => sender.send(value).map(|_| Object::Null),
+        (other, _) => Err(unsupported("send", &other)),
+    }
+}
+
+fn recv(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    match one("recv", args)? {
+        Object::Receiver(receiver) => receiver.recv(),
+        other => Err(unsupported("recv", &other)),
+    }
+}
+
+fn close(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    match one("close", args)? {
+        Object::Sender(sender) => sender.close().map(|_| Object::Null),
+        other => Err(unsupported("close", &other)),
+    }
+}
+
+/// A receiver whose channel closes once the milliseconds given have passed.
+fn timeout(args: Vec<Object>) -> Result<Object, RuntimeError> {
+    let milliseconds = count("timeout", one("timeout", args)?)?;
+    let (sender, receiver) = channel::channel(None)?;
+    scheduler::after(Duration::from_millis(milliseconds), move || {
+        sender.close().expect("only the timer closes the channel");
+    });
+    Ok(Object::Receiver(receiver))
+}
+
+/// Converts an integer, float or string to the integer type `width`, failing if it does not fit.
+fn to_integer(width: &str, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    let value = match one(width, args)? {
+        Object::Integer(value) => value.to_i128(),
+        Object::Float(value) => {
+            let value = value.to_f64();
+            if !value.is_finite() || value.trunc() < i128::MIN as f64 || value.trunc() >= i128::MAX as f64 {
+                return Err(RuntimeError::new(format!("{} out of range for {}", value, width)));
+            }
+            value.trunc() as i128
+        },
+        Object::String(value) => value
+            .trim()
+            .parse::<i128>()
+            .map_err(|_| RuntimeError::new(format!("could not parse {:?} as {}", value, width)))?,
+        Object::Boolean(value) => value as i128,
+        other => return Err(unsupported(width, &other)),
+    };
+    Integer::from_i128(width, value)
+        .map(Object::Integer)
+        .ok_or_else(|| RuntimeError::new(format!("{} out of range for {}", value, width)))
+}
+
+fn to_float(width: &str, args: Vec<Object>) -> Result<Object, RuntimeError> {
+    let value = match one(width, args)? {
+        Object::Integer(value) => value.to_i128() as f64,
+        Object::Float(value) => value.to_f64(),
+        Object::String(value) => value
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| RuntimeError::new(format!("could not parse {:?} as {}", value, width)))?,
+        other => return Err(unsupported(width, &other)),
+    };
+    Ok(Object::Float(match width {
+        "f32" => Float::F32(value as f32),
+        _ => Float::F64(value),
+    }))
+}
+
+#[cfg(test)]
+#[path = "./builtins_tests.rs"]
+mod tests;
@@ -0,0 +1,100 @@
+use crate::{environment::Environment, evaluator::Evaluator, lexer::Lexer, parser::Parser};
+
+use super::*;
+
+use test_case::test_case;
+
+fn eval(input: &str) -> Result<Object, RuntimeError> {
+    let mut lexer = Lexer::new(input.into());
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+    assert_eq!(parser.errors, vec![], "parser errors for {:?}", input);
+    Evaluator::new("test.ks").eval_program(&program, &Environment::new())
+}
+
+#[test_case("abs(-5)", "5"; "abs integer")]
+#[test_case("abs(-2.5)", "2.5"; "abs float")]
+#[test_case("min(3, 7)", "3"; "min")]
+#[test_case("max(3, 7)", "7"; "max")]
+#[test_case("max(1.5, 0.5)", "1.5"; "max float")]
+#[test_case("pow(2, 10)", "1024"; "pow")]
+#[test_case("pow(2.0, 3)", "8.0"; "pow float base")]
+#[test_case("sqrt(16)", "4.0"; "sqrt integer")]
+#[test_case("len(\"héllo\")", "5"; "len string counts chars")]
+#[test_case("len([1, 2, 3])", "3"; "len array")]
+#[test_case("len({1: 2})", "1"; "len hash")]
+#[test_case("split(\"a,b,,c\", \",\")", "[\"a\", \"b\", \"\", \"c\"]"; "split separator")]
+#[test_case("split(\"  a b\\tc \")", "[\"a\", \"b\", \"c\"]"; "split whitespace")]
+#[test_case("trim(\"  hi \\n\")", "hi"; "trim")]
+#[test_case("to_upper(\"abc\")", "ABC"; "to upper")]
+#[test_case("to_lower(\"ABC\")", "abc"; "to lower")]
+#[test_case("first([1, 2])", "1"; "first")]
+#[test_case("last([1, 2])", "2"; "last")]
+#[test_case("rest([1, 2, 3])", "[2, 3]"; "rest")]
+#[test_case("first([])", "null"; "first of empty")]
+#[test_case("push([1], 2)", "[1, 2]"; "push")]
+#[test_case("keys({\"b\": 1, \"a\": 2})", "[\"a\", \"b\"]"; "keys are sorted")]
+#[test_case("values({\"b\": 1, \"a\": 2})", "[2, 1]"; "values")]
+#[test_case("str(12) + str(true)", "12true"; "str")]
+#[test_case("i8(100) + i8(27)", "127"; "i8 arithmetic")]
+#[test_case("i128(9223372036854775807) * i128(4)", "36893488147419103228"; "i128 widens")]
+#[test_case("i32(\" 42 \")", "42"; "parse integer")]
+#[test_case("i64(-2.9)", "-2"; "float truncates")]
+#[test_case("f32(1) / f32(4)", "0.25"; "f32 arithmetic")]
+#[test_case("f64(\"1.5\")", "1.5"; "parse float")]
+#[test_case("assert_eq([1, 2], [1, 2])", "null"; "assert eq passes")]
+fn test_builtin(input: &str, expected: &str) {
+    assert_eq!(eval(input).map(|value| value.to_string()), Ok(expected.to_string()));
+}
+
+#[test_case("i8(128)", "128 out of range for i8"; "i8 out of range")]
+#[test_case("i8(100) + i8(100)", "attempt to add with overflow"; "i8 overflow")]
+#[test_case("i8(1) + 1", "integer width mismatch: cannot add i8 and i64"; "width mismatch")]
+#[test_case("i16(\"x\")", "could not parse \"x\" as i16"; "unparsable")]
+#[test_case("pow(2, 63)", "attempt to raise to a power with overflow"; "pow overflow")]
+#[test_case("pow(2, -1)", "exponent to `pow` must be non-negative, got -1"; "negative exponent")]
+#[test_case("min(1, 1.0)", "arguments to `min` must be numbers of the same type, got i64 and f64"; "min mixed")]
+#[test_case("len(1)", "argument to `len` not supported, got INTEGER"; "len unsupported")]
+#[test_case("len(1, 2)", "wrong number of arguments to len: expected 1, got 2"; "len arity")]
+#[test_case("abs(i8(-128))", "attempt to take absolute value with overflow"; "abs overflow")]
+#[test_case("assert(1 > 2)", "assertion failed"; "assert")]
+#[test_case("assert(false, \"sums\")", "assertion failed: sums"; "assert with message")]
+#[test_case("assert_eq(1, \"1\")", "assertion failed: left == right\n  left: 1\n right: \"1\""; "assert eq")]
+#[test_case(
+    "assert_eq([1, [2, 3]], [1, [2, 4], 5])",
+    "assertion failed: left == right\n  left: [1, [2, 3]]\n right: [1, [2, 4], 5]\n  diff: [1][1]: 3 != 4\n  diff: [2]: 5 is missing on the left";
+    "assert eq array diff"
+)]
+#[test_case(
+    "assert_eq({\"a\": 1, \"b\": 2}, {\"a\": 2})",
+    "assertion failed: left == right\n  left: {\"a\": 1, \"b\": 2}\n right: {\"a\": 2}\n  diff: [\"a\"]: 1 != 2\n  diff: [\"b\"]: 2 is missing on the right";
+    "assert eq hash diff"
+)]
+fn test_builtin_error(input: &str, expected: &str) {
+    assert_eq!(eval(input).unwrap_err().message, expected);
+}
+
+#[test]
+fn test_bindings_shadow_builtins() {
+    assert_eq!(eval("let len = fn(x) { 0 }; len(\"abc\")"), Ok(Object::from(0)));
+}
+
+#[test]
+fn test_builtin_errors_are_located_at_call_site() {
+    let err = eval("let f = fn(s) {\n  len(s)\n};\nf(1);").unwrap_err();
+    let frames = err.trace.iter().map(|frame| frame.function.as_str()).collect::<Vec<_>>();
+    assert_eq!(frames, vec!["f", "<main>"]);
+    assert_eq!(err.trace[0].span.unwrap().start, crate::lexer::Position { line: 2, column: 3 });
+}
+
+#[test]
+fn test_read_and_write_file() {
+    let path = std::env::temp_dir().join(format!("keynes-builtins-{}.txt", std::process::id()));
+    let path = path.to_string_lossy();
+    let input = format!("write_file({:?}, \"a\" + str(1)); read_file({:?})", path, path);
+    assert_eq!(eval(&input), Ok(Object::String("a1".to_string())));
+    std::fs::remove_file(&*path).unwrap();
+
+    let err = eval(&format!("read_file({:?})", path)).unwrap_err();
+    assert!(err.message.starts_with("could not read "), "{}", err.message);
+}
@@ -0,0 +1,170 @@
+//! Channels, which pass values between tasks in the order they were sent. A bounded channel
+//! holds up to its capacity, and sending blocks while it is full; receiving blocks while a
+//! channel is empty and still open. Once closed and empty, receiving gives `null`.
+//!
+//! Receiving a value, or the `null` of a closed channel, orders what the receiver does next
+//! after what the sender did before sending it, or closing the channel.
+
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    evaluator::RuntimeError,
+    object::Object,
+    race::VectorClock,
+    scheduler::{self, Waker},
+};
+
+/// Makes a channel holding up to `capacity` values, or any number of them without one.
+pub fn channel(capacity: Option<usize>) -> Result<(Sender, Receiver), RuntimeError> {
+    if capacity == Some(0) {
+        return Err(RuntimeError::new("capacity of a channel must be positive".to_string()));
+    }
+    let channel = Arc::new(Channel {
+        state: Mutex::new(State {
+            queue: VecDeque::new(),
+            capacity,
+            closed: None,
+            senders: vec![],
+            receivers: vec![],
+        }),
+    });
+    Ok((Sender(channel.clone()), Receiver(channel)))
+}
+
+#[derive(Debug, Clone)]
+pub struct Sender(Arc<Channel>);
+
+impl Sender {
+    /// Sends `value`, waiting for room in a full channel.
+    pub fn send(&self, value: Object) -> Result<(), RuntimeError> {
+        let mut value = Some((value, scheduler::release()));
+        scheduler::block_until("send", |waker| self.0.poll_send(&mut value, waker))?
+    }
+
+    /// Closes the channel: sending fails from now on, and receiving gives `null` once the
+    /// values already sent are taken.
+    pub fn close(&self) -> Result<(), RuntimeError> {
+        let clock = scheduler::release();
+        let woken = {
+            let mut state = self.0.state.lock().unwrap();
+            if state.closed.is_some() {
+                return Err(RuntimeError::new("close of a closed channel".to_string()));
+            }
+            state.closed = Some(clock);
+            let mut woken = std::mem::take(&mut state.senders);
+            woken.append(&mut state.receivers);
+            woken
+        };
+        woken.iter().for_each(Waker::wake);
+        Ok(())
+    }
+}
+
+impl PartialEq for Sender {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Display for Sender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<sender>")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Receiver(Arc<Channel>);
+
+impl Receiver {
+    /// Takes the oldest value sent, waiting for one while the channel is empty and open.
+    pub fn recv(&self) -> Result<Object, RuntimeError> {
+        scheduler::block_until("recv", |waker| self.poll(waker))
+    }
+
+    /// Takes the oldest value sent, or `null` from a closed and empty channel. Otherwise
+    /// gives none and has `waker` woken once it might give one.
+    pub fn poll(&self, waker: &Waker) -> Option<Object> {
+        let (value, clock, woken) = {
+            let mut state = self.0.state.lock().unwrap();
+            match state.queue.pop_front() {
+                Some((value, clock)) => (value, clock, std::mem::take(&mut state.senders)),
+                None => match &state.closed {
+                    Some(clock) => (Object::Null, clock.clone(), vec![]),
+                    None => {
+                        register(&mut state.receivers, waker);
+                        return None;
+                    },
+                },
+            }
+        };
+        scheduler::acquire(&clock);
+        woken.iter().for_each(Waker::wake);
+        Some(value)
+    }
+}
+
+impl PartialEq for Receiver {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Display for Receiver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<receiver>")
+    }
+}
+
+#[derive(Debug)]
+struct Channel {
+    state: Mutex<State>,
+}
+
+impl Channel {
+    /// Sends the value in `value` if there is room for it, and otherwise has `waker` woken
+    /// once there might be.
+    fn poll_send(&self, value: &mut Option<(Object, VectorClock)>, waker: &Waker) -> Option<Result<(), RuntimeError>> {
+        let woken = {
+            let mut state = self.state.lock().unwrap();
+            if state.closed.is_some() {
+                return Some(Err(RuntimeError::new("send on a closed channel".to_string())));
+            }
+            if state.capacity.is_some_and(|capacity| state.queue.len() >= capacity) {
+                register(&mut state.senders, waker);
+                return None;
+            }
+            state.queue.push_back(value.take().unwrap());
+            std::mem::take(&mut state.receivers)
+        };
+        woken.iter().for_each(Waker::wake);
+        Some(Ok(()))
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    /// The values sent, each with the clock of its sender when sending it.
+    queue: VecDeque<(Object, VectorClock)>,
+    capacity: Option<usize>,
+    /// The clock of the task closing the channel, once closed.
+    closed: Option<VectorClock>,
+    /// Who waits for room in the channel. All of them are woken on every change, and check
+    /// again.
+    senders: Vec<Waker>,
+    /// Who waits for a value.
+    receivers: Vec<Waker>,
+}
+
+fn register(wakers: &mut Vec<Waker>, waker: &Waker) {
+    if !wakers.contains(waker) {
+        wakers.push(waker.clone());
+    }
+}
+
+#[cfg(test)]
+#[path = "./channel_tests.rs"]
+mod tests;
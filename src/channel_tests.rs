@@ -0,0 +1,126 @@
+use super::*;
+
+use crate::{scheduler::Mode, Engine, EngineError};
+
+use test_case::test_case;
+
+const PIPELINE: &str = "
+let produce = fn(tx, n) { if (n > 0) { send(tx, n); produce(tx, n - 1) } else { close(tx) } };
+let consume = fn(rx, total) { let n = recv(rx); if (n) { consume(rx, total + n) } else { total } };
+let (tx, rx) = channel(4);
+spawn produce(tx, 100);
+consume(rx, 0)
+";
+
+fn engine(mode: Mode) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_scheduling(mode);
+    engine
+}
+
+fn runtime_error(result: Result<Object, EngineError>) -> String {
+    match result {
+        Err(EngineError::Runtime(err)) => err.message,
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+#[test_case(Mode::Parallel(1); "one thread")]
+#[test_case(Mode::Parallel(4); "four threads")]
+#[test_case(Mode::Deterministic(11); "deterministic")]
+fn test_pipeline(mode: Mode) {
+    assert_eq!(engine(mode).eval(PIPELINE), Ok(Object::from(5050)));
+}
+
+#[test]
+fn test_values_arrive_in_order() {
+    let mut engine = engine(Mode::Deterministic(2));
+    let source = "let (tx, rx) = channel(); send(tx, 1); send(tx, \"two\"); close(tx); [recv(rx), recv(rx), recv(rx), recv(rx)]";
+    assert_eq!(engine.eval(source).unwrap().to_string(), "[1, \"two\", null, null]");
+}
+
+#[test]
+fn test_full_channel_blocks_the_sender() {
+    let mut engine = engine(Mode::Deterministic(2));
+    assert_eq!(
+        runtime_error(engine.eval("let (tx, rx) = channel(1); send(tx, 1); send(tx, 2)")),
+        "deadlock: all tasks are blocked\n    <task 0> blocked in send at <eval#1>:1:41"
+    );
+}
+
+#[test_case("let (tx, rx) = channel(); close(tx); send(tx, 1)", "send on a closed channel"; "send after close")]
+#[test_case("let (tx, rx) = channel(); close(tx); close(tx)", "close of a closed channel"; "close twice")]
+#[test_case("channel(0)", "capacity of a channel must be positive"; "zero capacity")]
+#[test_case("channel(-1)", "argument to `channel` must be non-negative, got -1"; "negative capacity")]
+#[test_case("let (tx, rx) = channel(); recv(tx)", "argument to `recv` not supported, got SENDER"; "recv from a sender")]
+#[test_case("select { 1 => 2 }", "cannot select on INTEGER, expected RECEIVER"; "select on a non receiver")]
+#[test_case("let (a, b, c) = channel();", "cannot bind 3 names to an array of length 2"; "binding too many names")]
+#[test_case("let (a, b) = 1;", "cannot bind names to the elements of INTEGER"; "binding a non array")]
+fn test_errors(source: &str, expected: &str) {
+    assert_eq!(runtime_error(engine(Mode::Deterministic(1)).eval(source)), expected);
+}
+
+#[test_case(Mode::Parallel(2); "parallel")]
+#[test_case(Mode::Deterministic(4); "deterministic")]
+fn test_select_takes_the_ready_arm(mode: Mode) {
+    let mut engine = engine(mode);
+    let source = "
+let (tx1, rx1) = channel();
+let (tx2, rx2) = channel();
+send(tx2, 5);
+select {
+    rx1 as a => a,
+    rx2 as b => { b * 2 },
+}
+";
+    assert_eq!(engine.eval(source), Ok(Object::from(10)));
+}
+
+#[test_case(Mode::Parallel(2); "parallel")]
+#[test_case(Mode::Deterministic(4); "deterministic")]
+fn test_select_times_out(mode: Mode) {
+    let mut engine = engine(mode);
+    let source = "let (tx, rx) = channel(); select { rx => \"received\", timeout(20) => \"timed out\" }";
+    assert_eq!(engine.eval(source), Ok(Object::String("timed out".to_string())));
+}
+
+#[test]
+fn test_select_waits_for_a_sender() {
+    let mut engine = engine(Mode::Deterministic(9));
+    let source = "
+let (tx, rx) = channel();
+let later = fn() { yield(); send(tx, 3) };
+spawn later();
+select { rx as n => n + 1, timeout(1000) => 0 }
+";
+    assert_eq!(engine.eval(source), Ok(Object::from(4)));
+}
+
+#[test]
+fn test_deterministic_runs_repeat() {
+    let source = "
+let (tx, rx) = channel(2);
+let send_all = fn(name, n) { if (n > 0) { send(tx, name + str(n)); send_all(name, n - 1) } };
+spawn send_all(\"a\", 5);
+spawn send_all(\"b\", 5);
+let take = fn(n) { if (n == 0) { [] } else { let value = recv(rx); push(take(n - 1), value) } };
+take(10)
+";
+    for seed in 0..10 {
+        let first = engine(Mode::Deterministic(seed)).eval(source).unwrap();
+        assert_eq!(engine(Mode::Deterministic(seed)).eval(source).unwrap(), first, "seed {}", seed);
+    }
+}
+
+#[test]
+fn test_receiving_outside_tasks() {
+    let (sender, receiver) = channel(Some(1)).unwrap();
+    scheduler::spawn(move || {
+        sender.send(Object::from(1))?;
+        sender.send(Object::from(2))?;
+        sender.close()?;
+        Ok(Object::Null)
+    });
+    let received = (0..3).map(|_| receiver.recv().unwrap()).collect::<Vec<_>>();
+    assert_eq!(received, vec![Object::from(1), Object::from(2), Object::Null]);
+}
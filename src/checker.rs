@@ -0,0 +1,480 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{
+    ast::{expressions::*, program::Program, statements::*},
+    builtins,
+    diagnostics::Diagnostic,
+    lexer::{Lexer, Position, Span},
+    parser::Parser,
+};
+
+use log::*;
+
+/// What can be known about the value of an expression without running it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Unknown,
+    Null,
+    Integer,
+    Float,
+    Boolean,
+    String,
+    Array,
+    Hash,
+    /// A function literal with the given parameter names.
+    Function(Vec<String>),
+    Builtin,
+    Module,
+}
+
+impl Type {
+    /// The name the evaluator uses for values of this type in its errors, e.g. `INTEGER`.
+    fn name(&self) -> &'static str {
+        match self {
+            Type::Unknown => "UNKNOWN",
+            Type::Null => "NULL",
+            Type::Integer => "INTEGER",
+            Type::Float => "FLOAT",
+            Type::Boolean => "BOOLEAN",
+            Type::String => "STRING",
+            Type::Array => "ARRAY",
+            Type::Hash => "HASH",
+            Type::Function(_) => "FUNCTION",
+            Type::Builtin => "BUILTIN",
+            Type::Module => "MODULE",
+        }
+    }
+}
+
+/// Formats like `Object::describe_type`: number literals are `i64` and `f64`,
+/// functions show their parameters.
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Unknown => write!(f, "unknown"),
+            Type::Integer => write!(f, "i64"),
+            Type::Float => write!(f, "f64"),
+            Type::Function(parameters) => write!(f, "fn({})", parameters.join(", ")),
+            other => write!(f, "{}", other.name()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DefinitionKind {
+    /// A `let` binding of anything but a function literal.
+    Variable,
+    /// A `let` binding of a function literal.
+    Function,
+    Parameter,
+    /// The alias of an `import`.
+    Module,
+    /// A name brought in by `use`.
+    Use,
+}
+
+/// A name introduced by a `let`, a function parameter, an `import` or a `use`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub name: String,
+    pub kind: DefinitionKind,
+    pub ty: Type,
+    /// Where the name itself is written.
+    pub span: Span,
+    /// The whole statement introducing the name; the name itself for parameters.
+    pub full_span: Span,
+}
+
+/// A use of a name, resolved to an index into [`Analysis::definitions`]
+/// unless it refers to a builtin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub name: String,
+    pub span: Span,
+    pub definition: Option<usize>,
+}
+
+/// The result of checking a program: its parse and semantic errors, and
+/// every name defined and used in it.
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub diagnostics: Vec<Diagnostic>,
+    pub definitions: Vec<Definition>,
+    pub references: Vec<Reference>,
+}
+
+impl Analysis {
+    /// The definition of the name at `position`, which is either a use of
+    /// the name or the place it is defined.
+    pub fn definition_at(&self, position: Position) -> Option<&Definition> {
+        let reference = self.references.iter().find(|reference| contains(reference.span, position));
+        match reference {
+            Some(reference) => reference.definition.map(|index| &self.definitions[index]),
+            None => self.definitions.iter().find(|definition| contains(definition.span, position)),
+        }
+    }
+
+    /// The use of a name at `position`.
+    pub fn reference_at(&self, position: Position) -> Option<&Reference> {
+        self.references.iter().find(|reference| contains(reference.span, position))
+    }
+}
+
+/// Whether `position` is inside `span` or just after its end, where the
+/// cursor is right after typing a name.
+fn contains(span: Span, position: Position) -> bool {
+    span.start <= position && position <= span.end
+}
+
+/// Parses and checks `source`. Semantic errors are only reported when it
+/// parses, since a partial program has spurious ones.
+pub fn check(source: &str) -> Analysis {
+    let mut lexer = Lexer::new(source.to_string());
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+    let mut analysis = check_program(&program);
+    if !parser.errors.is_empty() {
+        analysis.diagnostics = parser.errors;
+    }
+    analysis
+}
+
+pub fn check_program(program: &Program) -> Analysis {
+    trace!("check_program");
+    let mut checker = Checker::default();
+    checker.enter(&program.statements, false);
+    for statement in &program.statements {
+        checker.statement(statement.as_ref());
+    }
+    checker.leave();
+    checker.analysis
+}
+
+#[derive(Debug, Default)]
+struct Scope {
+    bindings: HashMap<String, usize>,
+    /// Names defined anywhere in the statements of this scope. Function
+    /// bodies run after the scope is complete, so they may use them early.
+    declared: Vec<String>,
+    /// Whether this is the scope of a function body.
+    function: bool,
+    /// References from nested functions to names this scope defines later,
+    /// resolved when the scope ends.
+    deferred: Vec<(String, usize)>,
+}
+
+#[derive(Debug, Default)]
+struct Checker {
+    analysis: Analysis,
+    scopes: Vec<Scope>,
+}
+
+impl Checker {
+    fn error(&mut self, span: Span, message: String) {
+        self.analysis.diagnostics.push(Diagnostic::new(message, span));
+    }
+
+    /// Starts the scope of a program or function body. Blocks of `if`
+    /// expressions share the scope they are in, as in the evaluator.
+    fn enter(&mut self, statements: &[Box<dyn Statement>], function: bool) {
+        let mut declared = Vec::new();
+        collect_declared(statements, &mut declared);
+        self.scopes.push(Scope {
+            declared,
+            function,
+            ..Default::default()
+        });
+    }
+
+    fn leave(&mut self) {
+        let scope = self.scopes.pop().expect("scopes are balanced");
+        for (name, reference) in scope.deferred {
+            self.analysis.references[reference].definition = scope.bindings.get(&name).copied();
+        }
+    }
+
+    fn define(&mut self, identifier: &IdentifierLiteral, kind: DefinitionKind, ty: Type, full_span: Span) -> usize {
+        let index = self.analysis.definitions.len();
+        self.analysis.definitions.push(Definition {
+            name: identifier.to_string(),
+            kind,
+            ty,
+            span: identifier.span,
+            full_span,
+        });
+        let scope = self.scopes.last_mut().expect("a scope is open");
+        scope.bindings.insert(identifier.to_string(), index);
+        index
+    }
+
+    /// Resolves a use of `identifier`, reporting it if it is not defined.
+    fn resolve(&mut self, identifier: &IdentifierLiteral) -> Type {
+        let name = identifier.to_string();
+        let reference = self.analysis.references.len();
+        self.analysis.references.push(Reference {
+            name: name.clone(),
+            span: identifier.span,
+            definition: None,
+        });
+
+        let mut in_function = false;
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(&index) = scope.bindings.get(&name) {
+                self.analysis.references[reference].definition = Some(index);
+                return self.analysis.definitions[index].ty.clone();
+            }
+            if in_function && scope.declared.contains(&name) {
+                scope.deferred.push((name, reference));
+                return Type::Unknown;
+            }
+            in_function |= scope.function;
+        }
+
+        if builtins::get(&name).is_some() {
+            return Type::Builtin;
+        }
+        self.error(identifier.span, format!("identifier not found: {}", name));
+        Type::Unknown
+    }
+
+    fn statement(&mut self, statement: &dyn Statement) -> Type {
+        let any = statement.as_any();
+        if let Some(statement) = any.downcast_ref::<LetStatement>() {
+            let function = statement.value.as_any().downcast_ref::<FunctionLiteral>();
+            if let Some(function) = function {
+                // Defined before the body is checked so the function can call itself.
+                let ty = Type::Function(function.parameters.iter().map(|p| p.to_string()).collect());
+                self.define(&statement.name, DefinitionKind::Function, ty, statement.span);
+                self.expression(statement.value.as_ref());
+            } else {
+                let ty = self.expression(statement.value.as_ref());
+                self.define(&statement.name, DefinitionKind::Variable, ty, statement.span);
+            }
+            Type::Null
+        } else if let Some(statement) = any.downcast_ref::<LetTupleStatement>() {
+            self.expression(statement.value.as_ref());
+            for name in &statement.names {
+                self.define(name, DefinitionKind::Variable, Type::Unknown, statement.span);
+            }
+            Type::Null
+        } else if let Some(statement) = any.downcast_ref::<ReturnStatement>() {
+            self.expression(statement.expression.as_ref());
+            Type::Unknown
+        } else if let Some(statement) = any.downcast_ref::<ExpressionStatement>() {
+            self.expression(statement.expression.as_ref())
+        } else if let Some(block) = any.downcast_ref::<BlockStatement>() {
+            self.block(block)
+        } else if let Some(import) = any.downcast_ref::<ImportStatement>() {
+            self.define(&import.alias, DefinitionKind::Module, Type::Module, import.span);
+            Type::Null
+        } else if let Some(use_statement) = any.downcast_ref::<UseStatement>() {
+            self.module(&use_statement.module);
+            for name in &use_statement.names {
+                self.define(name, DefinitionKind::Use, Type::Unknown, use_statement.span);
+            }
+            Type::Null
+        } else if let Some(test) = any.downcast_ref::<TestStatement>() {
+            // Tests run once the whole file has, like a function called at its end.
+            self.enter(&test.body.statements, true);
+            self.block(&test.body);
+            self.leave();
+            Type::Null
+        } else {
+            Type::Unknown
+        }
+    }
+
+    /// Checks the statements of `block`, returning the type of its value.
+    fn block(&mut self, block: &BlockStatement) -> Type {
+        let mut ty = Type::Null;
+        for statement in &block.statements {
+            ty = self.statement(statement.as_ref());
+        }
+        ty
+    }
+
+    fn module(&mut self, identifier: &IdentifierLiteral) {
+        let ty = self.resolve(identifier);
+        if ty != Type::Module && ty != Type::Unknown {
+            self.error(identifier.span, format!("not a module: {} is {}", identifier, ty.name()));
+        }
+    }
+
+    fn expression(&mut self, expression: &dyn Expression) -> Type {
+        let any = expression.as_any();
+        if any.is::<IntegerLiteral>() {
+            Type::Integer
+        } else if any.is::<FloatLiteral>() {
+            Type::Float
+        } else if any.is::<StringLiteral>() {
+            Type::String
+        } else if any.is::<BooleanLiteral>() {
+            Type::Boolean
+        } else if let Some(identifier) = any.downcast_ref::<IdentifierLiteral>() {
+            self.resolve(identifier)
+        } else if let Some(prefix) = any.downcast_ref::<PrefixExpression>() {
+            let right = self.expression(prefix.right.as_ref());
+            match (&prefix.operator, right) {
+                (PrefixOperator::BANG, _) => Type::Boolean,
+                (PrefixOperator::MINUS, right @ (Type::Integer | Type::Float | Type::Unknown)) => right,
+                (operator, right) => {
+                    self.error(prefix.span, format!("unknown operator: {}{}", operator, right.name()));
+                    Type::Unknown
+                },
+            }
+        } else if let Some(infix) = any.downcast_ref::<InfixExpression>() {
+            let left = self.expression(infix.left.as_ref());
+            let right = self.expression(infix.right.as_ref());
+            self.infix(infix, left, right)
+        } else if let Some(if_expression) = any.downcast_ref::<IfExpression>() {
+            self.expression(if_expression.condition.as_ref());
+            let consequence = self.block(&if_expression.consequence);
+            let alternative = match &if_expression.alternative {
+                Some(alternative) => self.block(alternative),
+                None => Type::Null,
+            };
+            if consequence == alternative {
+                consequence
+            } else {
+                Type::Unknown
+            }
+        } else if let Some(function) = any.downcast_ref::<FunctionLiteral>() {
+            self.enter(&function.body.statements, true);
+            for parameter in &function.parameters {
+                self.define(parameter, DefinitionKind::Parameter, Type::Unknown, parameter.span);
+            }
+            self.block(&function.body);
+            self.leave();
+            Type::Function(function.parameters.iter().map(|p| p.to_string()).collect())
+        } else if let Some(call) = any.downcast_ref::<CallExpression>() {
+            let function = self.expression(call.function.as_ref());
+            for argument in &call.arguments {
+                self.expression(argument.as_ref());
+            }
+            match function {
+                Type::Function(parameters) if parameters.len() != call.arguments.len() => self.error(
+                    call.span,
+                    format!(
+                        "wrong number of arguments to {}: expected {}, got {}",
+                        call.function,
+                        parameters.len(),
+                        call.arguments.len()
+                    ),
+                ),
+                Type::Function(_) | Type::Builtin | Type::Unknown => {},
+                other => self.error(call.span, format!("not a function: {}", other.name())),
+            }
+            Type::Unknown
+        } else if let Some(array) = any.downcast_ref::<ArrayLiteral>() {
+            for element in &array.elements {
+                self.expression(element.as_ref());
+            }
+            Type::Array
+        } else if let Some(hash) = any.downcast_ref::<HashLiteral>() {
+            for (key, value) in &hash.pairs {
+                self.expression(key.as_ref());
+                self.expression(value.as_ref());
+            }
+            Type::Hash
+        } else if let Some(path) = any.downcast_ref::<PathExpression>() {
+            self.module(&path.module);
+            Type::Unknown
+        } else if let Some(index) = any.downcast_ref::<IndexExpression>() {
+            self.expression(index.left.as_ref());
+            self.expression(index.index.as_ref());
+            Type::Unknown
+        } else if let Some(spawn) = any.downcast_ref::<SpawnExpression>() {
+            self.expression(&spawn.call);
+            Type::Unknown
+        } else if let Some(await_expression) = any.downcast_ref::<AwaitExpression>() {
+            self.expression(await_expression.value.as_ref());
+            Type::Unknown
+        } else if let Some(select) = any.downcast_ref::<SelectExpression>() {
+            for arm in &select.arms {
+                self.expression(arm.receiver.as_ref());
+            }
+            for arm in &select.arms {
+                if let Some(binding) = &arm.binding {
+                    self.define(binding, DefinitionKind::Variable, Type::Unknown, select.span);
+                }
+                self.block(&arm.body);
+            }
+            Type::Unknown
+        } else {
+            Type::Unknown
+        }
+    }
+
+    /// Mirrors the operand rules of the evaluator for operands of known type.
+    fn infix(&mut self, infix: &InfixExpression, left: Type, right: Type) -> Type {
+        use InfixOperator::*;
+        let comparison = matches!(
+            infix.operator,
+            EQUAL | NOT_EQUAL | LESS_THAN | LESS_THAN_EQUAL | GREATER_THAN | GREATER_THAN_EQUAL
+        );
+        match (&left, &right) {
+            (Type::Unknown, _) | (_, Type::Unknown) if comparison => Type::Boolean,
+            (Type::Unknown, _) | (_, Type::Unknown) => Type::Unknown,
+            (Type::Integer, Type::Integer) | (Type::Float, Type::Float) if comparison => Type::Boolean,
+            (Type::Integer, Type::Integer) | (Type::Float, Type::Float) => left,
+            (Type::String, Type::String) if infix.operator == PLUS => Type::String,
+            (Type::String, Type::String) | (Type::Boolean, Type::Boolean)
+                if matches!(infix.operator, EQUAL | NOT_EQUAL) =>
+            {
+                Type::Boolean
+            },
+            (left, right) => {
+                let problem = if left.name() != right.name() { "type mismatch" } else { "unknown operator" };
+                self.error(
+                    infix.span,
+                    format!("{}: {} {} {}", problem, left.name(), infix.operator, right.name()),
+                );
+                Type::Unknown
+            },
+        }
+    }
+}
+
+/// Collects the names `let`, `import` and `use` statements define in
+/// `statements`, including inside blocks, `if` branches and `select` arms, which share
+/// their scope.
+pub(crate) fn collect_declared(statements: &[Box<dyn Statement>], declared: &mut Vec<String>) {
+    for statement in statements {
+        let any = statement.as_any();
+        if let Some(statement) = any.downcast_ref::<LetStatement>() {
+            declared_in_expression(statement.value.as_ref(), declared);
+            declared.push(statement.name.to_string());
+        } else if let Some(statement) = any.downcast_ref::<LetTupleStatement>() {
+            declared_in_expression(statement.value.as_ref(), declared);
+            declared.extend(statement.names.iter().map(|name| name.to_string()));
+        } else if let Some(import) = any.downcast_ref::<ImportStatement>() {
+            declared.push(import.alias.to_string());
+        } else if let Some(use_statement) = any.downcast_ref::<UseStatement>() {
+            declared.extend(use_statement.names.iter().map(|name| name.to_string()));
+        } else if let Some(block) = any.downcast_ref::<BlockStatement>() {
+            collect_declared(&block.statements, declared);
+        } else if let Some(statement) = any.downcast_ref::<ExpressionStatement>() {
+            declared_in_expression(statement.expression.as_ref(), declared);
+        }
+    }
+}
+
+/// Collects the names defined in the branches of `expression`, if it is an `if` or a `select`.
+fn declared_in_expression(expression: &dyn Expression, declared: &mut Vec<String>) {
+    let any = expression.as_any();
+    if let Some(if_expression) = any.downcast_ref::<IfExpression>() {
+        collect_declared(&if_expression.consequence.statements, declared);
+        if let Some(alternative) = &if_expression.alternative {
+            collect_declared(&alternative.statements, declared);
+        }
+    } else if let Some(select) = any.downcast_ref::<SelectExpression>() {
+        for arm in &select.arms {
+            declared.extend(arm.binding.iter().map(|binding| binding.to_string()));
+            collect_declared(&arm.body.statements, declared);
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./checker_tests.rs"]
+mod tests;
@@ -0,0 +1,98 @@
+use super::*;
+
+use test_case::test_case;
+
+fn messages(source: &str) -> Vec<String> {
+    check(source).diagnostics.iter().map(|diagnostic| diagnostic.message.clone()).collect()
+}
+
+fn position(line: usize, column: usize) -> Position {
+    Position { line, column }
+}
+
+#[test_case("let x = 1; x + 2"; "let binding")]
+#[test_case("let f = fn(n) { if (n < 1) { 0 } else { f(n - 1) } }; f(3)"; "recursion")]
+#[test_case("let even = fn(n) { odd(n) }; let odd = fn(n) { even(n) };"; "mutual recursion")]
+#[test_case("if (true) { let x = 1; } x"; "if blocks share the scope")]
+#[test_case("print(len(\"abc\"))"; "builtins")]
+#[test_case("import \"lib.ks\" as lib; use lib::{a}; lib::b + a"; "modules")]
+#[test_case("let x = fn(a) { a }; x(1) + 1"; "unknown result")]
+fn test_no_diagnostics(source: &str) {
+    assert_eq!(messages(source), Vec::<String>::new());
+}
+
+#[test_case("y", "identifier not found: y"; "undefined")]
+#[test_case("x; let x = 1;", "identifier not found: x"; "used before definition")]
+#[test_case("let f = fn() { g() }; f(); let h = fn() { 1 };", "identifier not found: g"; "never defined")]
+#[test_case("let f = fn(a) { a }; f(1, 2)", "wrong number of arguments to f: expected 1, got 2"; "arity")]
+#[test_case("1(2)", "not a function: INTEGER"; "call of literal")]
+#[test_case("1 + true", "type mismatch: INTEGER + BOOLEAN"; "mismatch")]
+#[test_case("\"a\" - \"b\"", "unknown operator: STRING - STRING"; "string minus")]
+#[test_case("-true", "unknown operator: -BOOLEAN"; "negated boolean")]
+#[test_case("let x = 1; x::y", "not a module: x is INTEGER"; "path of non module")]
+fn test_diagnostics(source: &str, expected: &str) {
+    assert_eq!(messages(source), vec![expected.to_string()]);
+}
+
+#[test]
+fn test_semantic_diagnostics_are_dropped_on_parse_errors() {
+    assert_eq!(messages("let = 1; y"), vec!["expected next token to be an identifier, got ASSIGN instead".to_string()]);
+}
+
+#[test]
+fn test_diagnostic_span() {
+    let analysis = check("let x = 1;\nx + y");
+    assert_eq!(analysis.diagnostics[0].span, Span::new(position(2, 5), position(2, 6)));
+}
+
+#[test]
+fn test_definition_at() {
+    let source = "let add = fn(a, b) { a + b };\nadd(1, 2)";
+    let analysis = check(source);
+
+    let parameter = analysis.definition_at(position(1, 22)).unwrap();
+    assert_eq!(parameter.name, "a");
+    assert_eq!(parameter.kind, DefinitionKind::Parameter);
+    assert_eq!(parameter.span, Span::new(position(1, 14), position(1, 15)));
+
+    let function = analysis.definition_at(position(2, 2)).unwrap();
+    assert_eq!(function.name, "add");
+    assert_eq!(function.kind, DefinitionKind::Function);
+    assert_eq!(function.ty, Type::Function(vec!["a".to_string(), "b".to_string()]));
+    assert_eq!(function.span, Span::new(position(1, 5), position(1, 8)));
+    assert_eq!(function.full_span.end, position(1, 30));
+
+    assert_eq!(analysis.definition_at(position(1, 6)), Some(function));
+    assert_eq!(analysis.definition_at(position(2, 5)), None);
+}
+
+#[test]
+fn test_shadowing_resolves_to_latest_definition() {
+    let analysis = check("let x = 1;\nlet x = \"a\";\nx");
+    let definition = analysis.definition_at(position(3, 1)).unwrap();
+    assert_eq!(definition.span.start.line, 2);
+    assert_eq!(definition.ty, Type::String);
+}
+
+#[test_case("1", "i64")]
+#[test_case("1.5 * 2.0", "f64")]
+#[test_case("\"a\" + \"b\"", "STRING")]
+#[test_case("1 < 2", "BOOLEAN")]
+#[test_case("[1]", "ARRAY")]
+#[test_case("{1: 2}", "HASH")]
+#[test_case("if (true) { 1 } else { 2 }", "i64")]
+#[test_case("if (true) { 1 }", "unknown")]
+#[test_case("fn(a, b) { a }", "fn(a, b)")]
+#[test_case("len", "BUILTIN")]
+fn test_inferred_types(value: &str, expected: &str) {
+    let analysis = check(&format!("let x = {};", value));
+    assert_eq!(analysis.definitions[0].ty.to_string(), expected);
+}
+
+#[test]
+fn test_builtin_references_have_no_definition() {
+    let analysis = check("len([])");
+    let reference = analysis.reference_at(position(1, 1)).unwrap();
+    assert_eq!(reference.name, "len");
+    assert_eq!(reference.definition, None);
+}
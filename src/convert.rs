@@ -0,0 +1,290 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    sync::Arc,
+};
+
+use crate::{
+    evaluator::RuntimeError,
+    object::{Float, HashKey, Integer, NativeFunction, Object},
+};
+
+/// Conversion of a Rust value into a Keynes value.
+pub trait IntoKeynes {
+    fn into_keynes(self) -> Object;
+}
+
+/// Conversion of a Keynes value into a Rust value, failing if the value has the wrong type.
+pub trait FromKeynes: Sized {
+    fn from_keynes(object: Object) -> Result<Self, RuntimeError>;
+}
+
+/// Rust types whose Keynes values can be used as hash keys.
+pub trait KeynesKey: IntoKeynes + FromKeynes {}
+
+/// The error for a value of the wrong type, e.g. "expected i32, got STRING".
+pub fn mismatch(expected: &str, object: &Object) -> RuntimeError {
+    RuntimeError::new(format!("expected {}, got {}", expected, object.type_name()))
+}
+
+impl IntoKeynes for Object {
+    fn into_keynes(self) -> Object {
+        self
+    }
+}
+
+impl FromKeynes for Object {
+    fn from_keynes(object: Object) -> Result<Self, RuntimeError> {
+        Ok(object)
+    }
+}
+
+macro_rules! impl_integer {
+    ($($rust:ident => $variant:ident),*) => {$(
+        impl IntoKeynes for $rust {
+            fn into_keynes(self) -> Object {
+                Object::Integer(Integer::$variant(self))
+            }
+        }
+
+        /// Accepts an integer of any width whose value fits.
+        impl FromKeynes for $rust {
+            fn from_keynes(object: Object) -> Result<Self, RuntimeError> {
+                match object {
+                    Object::Integer(value) => $rust::try_from(value.to_i128()).map_err(|_| {
+                        RuntimeError::new(format!("{} out of range for {}", value, stringify!($rust)))
+                    }),
+                    other => Err(mismatch(stringify!($rust), &other)),
+                }
+            }
+        }
+
+        impl KeynesKey for $rust {}
+    )*};
+}
+
+impl_integer!(i8 => I8, i16 => I16, i32 => I32, i64 => I64, i128 => I128);
+
+macro_rules! impl_float {
+    ($($rust:ident => $variant:ident),*) => {$(
+        impl IntoKeynes for $rust {
+            fn into_keynes(self) -> Object {
+                Object::Float(Float::$variant(self))
+            }
+        }
+
+        impl FromKeynes for $rust {
+            fn from_keynes(object: Object) -> Result<Self, RuntimeError> {
+                match object {
+                    Object::Float(value) => Ok(value.to_f64() as $rust),
+                    other => Err(mismatch(stringify!($rust), &other)),
+                }
+            }
+        }
+    )*};
+}
+
+impl_float!(f32 => F32, f64 => F64);
+
+impl IntoKeynes for bool {
+    fn into_keynes(self) -> Object {
+        Object::Boolean(self)
+    }
+}
+
+impl FromKeynes for bool {
+    fn from_keynes(object: Object) -> Result<Self, RuntimeError> {
+        match object {
+            Object::Boolean(value) => Ok(value),
+            other => Err(mismatch("bool", &other)),
+        }
+    }
+}
+
+impl KeynesKey for bool {}
+
+impl IntoKeynes for String {
+    fn into_keynes(self) -> Object {
+        Object::String(self)
+    }
+}
+
+impl IntoKeynes for &str {
+    fn into_keynes(self) -> Object {
+        Object::String(self.to_string())
+    }
+}
+
+impl FromKeynes for String {
+    fn from_keynes(object: Object) -> Result<Self, RuntimeError> {
+        match object {
+            Object::String(value) => Ok(value),
+            other => Err(mismatch("String", &other)),
+        }
+    }
+}
+
+impl KeynesKey for String {}
+
+/// `()` is `null`, so host functions without a result can be registered as is.
+impl IntoKeynes for () {
+    fn into_keynes(self) -> Object {
+        Object::Null
+    }
+}
+
+impl FromKeynes for () {
+    fn from_keynes(object: Object) -> Result<Self, RuntimeError> {
+        match object {
+            Object::Null => Ok(()),
+            other => Err(mismatch("()", &other)),
+        }
+    }
+}
+
+impl<T: IntoKeynes> IntoKeynes for Option<T> {
+    fn into_keynes(self) -> Object {
+        match self {
+            Some(value) => value.into_keynes(),
+            None => Object::Null,
+        }
+    }
+}
+
+impl<T: FromKeynes> FromKeynes for Option<T> {
+    fn from_keynes(object: Object) -> Result<Self, RuntimeError> {
+        match object {
+            Object::Null => Ok(None),
+            other => T::from_keynes(other).map(Some),
+        }
+    }
+}
+
+impl<T: IntoKeynes> IntoKeynes for Vec<T> {
+    fn into_keynes(self) -> Object {
+        Object::Array(self.into_iter().map(IntoKeynes::into_keynes).collect())
+    }
+}
+
+impl<T: FromKeynes> FromKeynes for Vec<T> {
+    fn from_keynes(object: Object) -> Result<Self, RuntimeError> {
+        match object {
+            Object::Array(elements) => elements.into_iter().map(T::from_keynes).collect(),
+            other => Err(mismatch("Vec", &other)),
+        }
+    }
+}
+
+fn into_hash<K: KeynesKey, V: IntoKeynes>(pairs: impl IntoIterator<Item = (K, V)>) -> Object {
+    let pairs = pairs
+        .into_iter()
+        .map(|(key, value)| {
+            let key = HashKey::try_from(key.into_keynes()).expect("KeynesKey types convert to hash keys");
+            (key, value.into_keynes())
+        })
+        .collect();
+    Object::Hash(pairs)
+}
+
+fn from_hash<K: KeynesKey, V: FromKeynes, C: FromIterator<(K, V)>>(expected: &str, object: Object) -> Result<C, RuntimeError> {
+    match object {
+        Object::Hash(pairs) => pairs
+            .into_iter()
+            .map(|(key, value)| Ok((K::from_keynes(key.into())?, V::from_keynes(value)?)))
+            .collect(),
+        other => Err(mismatch(expected, &other)),
+    }
+}
+
+impl<K: KeynesKey + Eq + Hash, V: IntoKeynes> IntoKeynes for HashMap<K, V> {
+    fn into_keynes(self) -> Object {
+        into_hash(self)
+    }
+}
+
+impl<K: KeynesKey + Eq + Hash, V: FromKeynes> FromKeynes for HashMap<K, V> {
+    fn from_keynes(object: Object) -> Result<Self, RuntimeError> {
+        from_hash("HashMap", object)
+    }
+}
+
+impl<K: KeynesKey + Ord, V: IntoKeynes> IntoKeynes for BTreeMap<K, V> {
+    fn into_keynes(self) -> Object {
+        into_hash(self)
+    }
+}
+
+impl<K: KeynesKey + Ord, V: FromKeynes> FromKeynes for BTreeMap<K, V> {
+    fn from_keynes(object: Object) -> Result<Self, RuntimeError> {
+        from_hash("BTreeMap", object)
+    }
+}
+
+/// Return types of host functions: any [`IntoKeynes`] value, or a `Result` of one.
+pub trait IntoKeynesResult {
+    fn into_keynes_result(self) -> Result<Object, RuntimeError>;
+}
+
+impl<T: IntoKeynes> IntoKeynesResult for T {
+    fn into_keynes_result(self) -> Result<Object, RuntimeError> {
+        Ok(self.into_keynes())
+    }
+}
+
+impl<T: IntoKeynes> IntoKeynesResult for Result<T, RuntimeError> {
+    fn into_keynes_result(self) -> Result<Object, RuntimeError> {
+        self.map(IntoKeynes::into_keynes)
+    }
+}
+
+/// Rust closures that can be registered as Keynes functions with [`crate::Engine::register_typed_fn`].
+///
+/// `Args` is a tuple of the parameter types; it only exists to keep the
+/// implementations for different arities apart.
+pub trait IntoNativeFunction<Args> {
+    fn into_native_function(self, name: &str) -> NativeFunction;
+}
+
+macro_rules! impl_into_native_function {
+    ($($arg:ident $ty:ident),*) => {
+        impl<F, R, $($ty),*> IntoNativeFunction<($($ty,)*)> for F
+        where
+            F: Fn($($ty),*) -> R + Send + Sync + 'static,
+            R: IntoKeynesResult,
+            $($ty: FromKeynes),*
+        {
+            #[allow(unused_mut, unused_variables)]
+            fn into_native_function(self, name: &str) -> NativeFunction {
+                let name = name.to_string();
+                Arc::new(move |args: Vec<Object>| {
+                    let expected = <[&str]>::len(&[$(stringify!($ty)),*]);
+                    if args.len() != expected {
+                        return Err(RuntimeError::new(format!(
+                            "wrong number of arguments to {}: expected {}, got {}",
+                            name,
+                            expected,
+                            args.len()
+                        )));
+                    }
+                    let mut args = args.into_iter();
+                    $(let $arg = $ty::from_keynes(args.next().unwrap()).map_err(|err| {
+                        RuntimeError::new(format!("invalid argument to {}: {}", name, err.message))
+                    })?;)*
+                    self($($arg),*).into_keynes_result()
+                })
+            }
+        }
+    };
+}
+
+impl_into_native_function!();
+impl_into_native_function!(a T1);
+impl_into_native_function!(a T1, b T2);
+impl_into_native_function!(a T1, b T2, c T3);
+impl_into_native_function!(a T1, b T2, c T3, d T4);
+impl_into_native_function!(a T1, b T2, c T3, d T4, e T5);
+impl_into_native_function!(a T1, b T2, c T3, d T4, e T5, f T6);
+
+#[cfg(test)]
+#[path = "./convert_tests.rs"]
+mod tests;
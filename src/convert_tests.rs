@@ -0,0 +1,101 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{Engine, EngineError, FromKeynes, IntoKeynes};
+
+use super::*;
+
+use test_case::test_case;
+
+#[derive(Debug, Clone, PartialEq, IntoKeynes, FromKeynes)]
+struct Point {
+    x: i64,
+    y: i64,
+    label: Option<String>,
+}
+
+fn roundtrip<T: IntoKeynes + FromKeynes>(value: T) -> Result<T, RuntimeError> {
+    T::from_keynes(value.into_keynes())
+}
+
+#[test]
+fn test_roundtrip_scalars() {
+    assert_eq!(roundtrip(-5i8), Ok(-5));
+    assert_eq!(roundtrip(i16::MAX), Ok(i16::MAX));
+    assert_eq!(roundtrip(i128::MIN), Ok(i128::MIN));
+    assert_eq!(roundtrip(0.5f32), Ok(0.5));
+    assert_eq!(roundtrip(2.25f64), Ok(2.25));
+    assert_eq!(roundtrip(true), Ok(true));
+    assert_eq!(roundtrip("hi".to_string()), Ok("hi".to_string()));
+    assert_eq!(roundtrip(()), Ok(()));
+}
+
+#[test]
+fn test_roundtrip_collections() {
+    assert_eq!(roundtrip(vec![1i32, 2, 3]), Ok(vec![1, 2, 3]));
+    assert_eq!(roundtrip(Some(vec![Some(1i64), None])), Ok(Some(vec![Some(1), None])));
+    assert_eq!(roundtrip(None::<i64>), Ok(None));
+    let map = HashMap::from([("a".to_string(), 1i64), ("b".to_string(), 2)]);
+    assert_eq!(roundtrip(map.clone()), Ok(map));
+    let map = BTreeMap::from([(1i8, vec![true]), (2, vec![])]);
+    assert_eq!(roundtrip(map.clone()), Ok(map));
+}
+
+#[test]
+fn test_integer_widths_are_preserved() {
+    assert_eq!(7i8.into_keynes().to_string(), "7");
+    assert_eq!(7i8.into_keynes(), Object::Integer(Integer::I8(7)));
+    assert_eq!(7i128.into_keynes(), Object::Integer(Integer::I128(7)));
+    assert_eq!(1.5f32.into_keynes(), Object::Float(Float::F32(1.5)));
+}
+
+#[test_case(i8::from_keynes(Object::from(300)), "300 out of range for i8"; "integer out of range")]
+#[test_case(i64::from_keynes(Object::Boolean(true)), "expected i64, got BOOLEAN"; "integer mismatch")]
+#[test_case(String::from_keynes(Object::from(1)).map(|_| 0), "expected String, got INTEGER"; "string mismatch")]
+#[test_case(Vec::<i64>::from_keynes(Object::Array(vec![Object::Null])).map(|_| 0), "expected i64, got NULL"; "vec element mismatch")]
+fn test_conversion_errors(result: Result<impl std::fmt::Debug, RuntimeError>, expected: &str) {
+    assert_eq!(result.unwrap_err().message, expected);
+}
+
+#[test]
+fn test_derive_struct() {
+    let point = Point { x: 1, y: -2, label: None };
+    assert_eq!(point.clone().into_keynes().to_string(), "{\"label\": null, \"x\": 1, \"y\": -2}");
+    assert_eq!(roundtrip(point.clone()), Ok(point));
+
+    let err = Point::from_keynes(Object::from(1)).unwrap_err();
+    assert_eq!(err.message, "expected Point, got INTEGER");
+    let err = Point::from_keynes(Object::Hash(BTreeMap::new())).unwrap_err();
+    assert_eq!(err.message, "field `x` of Point: expected i64, got NULL");
+}
+
+#[test]
+fn test_register_typed_functions() {
+    let mut engine = Engine::new();
+    engine.register_typed_fn("answer", || 42i64);
+    engine.register_typed_fn("scale", |point: Point, factor: i64| Point {
+        x: point.x * factor,
+        y: point.y * factor,
+        label: point.label.map(|label| label.to_uppercase()),
+    });
+    engine.register_typed_fn("checked_div", |a: i32, b: i32| {
+        a.checked_div(b).ok_or_else(|| RuntimeError::new("cannot divide by zero".to_string()))
+    });
+    engine.register_typed_fn("total", |values: Vec<f64>| values.iter().sum::<f64>());
+
+    assert_eq!(engine.eval("answer()"), Ok(Object::from(42)));
+    engine.eval("let p = scale({\"x\": 1, \"y\": 2, \"label\": \"p\"}, 3);").unwrap();
+    assert_eq!(
+        engine.get_global_as::<Point>("p"),
+        Ok(Some(Point { x: 3, y: 6, label: Some("P".to_string()) }))
+    );
+    assert_eq!(engine.eval("checked_div(i32(7), i32(2))"), Ok(Object::Integer(Integer::I32(3))));
+    assert_eq!(engine.eval("total([0.5, 1.5])"), Ok(Object::Float(Float::F64(2.0))));
+
+    let message = |result: Result<Object, EngineError>| match result {
+        Err(EngineError::Runtime(err)) => err.message,
+        other => panic!("expected runtime error, got {:?}", other),
+    };
+    assert_eq!(message(engine.eval("checked_div(i32(1), i32(0))")), "cannot divide by zero");
+    assert_eq!(message(engine.eval("answer(1)")), "wrong number of arguments to answer: expected 0, got 1");
+    assert_eq!(message(engine.eval("total(1)")), "invalid argument to total: expected Vec, got INTEGER");
+}
@@ -0,0 +1,63 @@
+use std::fmt::Display;
+
+use crate::lexer::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn new(message: String, span: Span) -> Diagnostic {
+        Diagnostic { message, span }
+    }
+
+    /// Renders the diagnostic with a header, location and the offending source line.
+    pub fn render(&self, file: &str, source: &str) -> String {
+        render(&self.message, file, Some(self.span), source)
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.span, self.message)
+    }
+}
+
+pub fn render(message: &str, file: &str, span: Option<Span>, source: &str) -> String {
+    let mut out = format!("error: {}\n", message);
+    let span = match span {
+        Some(span) => span,
+        None => {
+            out.push_str(&format!(" --> {}\n", file));
+            return out;
+        }
+    };
+    out.push_str(&format!(" --> {}:{}\n", file, span.start));
+
+    let line = match source.lines().nth(span.start.line.saturating_sub(1)) {
+        Some(line) => line,
+        None => return out,
+    };
+    let gutter = span.start.line.to_string();
+    let padding = " ".repeat(gutter.len());
+    let line_length = line.chars().count();
+    let end_column = if span.end.line == span.start.line {
+        span.end.column
+    } else {
+        line_length + 1
+    };
+    let start_column = span.start.column.max(1);
+    let underline = end_column.saturating_sub(start_column).max(1);
+
+    out.push_str(&format!("{} |\n", padding));
+    out.push_str(&format!("{} | {}\n", gutter, line));
+    out.push_str(&format!(
+        "{} | {}{}\n",
+        padding,
+        " ".repeat(start_column - 1),
+        "^".repeat(underline)
+    ));
+    out
+}
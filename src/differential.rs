@@ -0,0 +1,270 @@
+use std::fmt::Display;
+
+use crate::{
+    ast::{expressions::*, statements::*},
+    ast2,
+    lexer::Lexer,
+    parser::Parser,
+    parser2,
+};
+
+/// A syntax tree in the form both parsers' trees are reduced to before they are compared.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Normal {
+    Let { name: String, mutable: bool, value: Box<Normal> },
+    Return(Box<Normal>),
+    Expression(Box<Normal>),
+    Block(Vec<Normal>),
+    Identifier(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Prefix(String, Box<Normal>),
+    Infix(String, Box<Normal>, Box<Normal>),
+    If { conditions: Vec<(Normal, Normal)>, alternative: Option<Box<Normal>> },
+    Function { is_async: bool, parameters: Vec<String>, body: Box<Normal> },
+    Await(Box<Normal>),
+    /// A construct only one of the parsers knows, kept as that parser prints it.
+    Other(String),
+}
+
+impl Display for Normal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Normal::Let { name, mutable: true, value } => write!(f, "(let mut {} {})", name, value),
+            Normal::Let { name, mutable: false, value } => write!(f, "(let {} {})", name, value),
+            Normal::Return(value) => write!(f, "(return {})", value),
+            Normal::Expression(expression) => write!(f, "{}", expression),
+            Normal::Block(statements) => {
+                let statements = statements.iter().map(|statement| format!(" {}", statement)).collect::<String>();
+                write!(f, "(block{})", statements)
+            },
+            // `parser2` reads anything alphanumeric as an identifier, so mark the ones that could not be.
+            Normal::Identifier(name) if !name.starts_with(|ch: char| ch.is_alphabetic() || ch == '_') => {
+                write!(f, "`{}`", name)
+            },
+            Normal::Identifier(name) => write!(f, "{}", name),
+            Normal::Integer(value) => write!(f, "{}", value),
+            Normal::Float(value) => write!(f, "{:?}", value),
+            Normal::Boolean(value) => write!(f, "{}", value),
+            Normal::Prefix(operator, right) => write!(f, "({} {})", operator, right),
+            Normal::Infix(operator, left, right) => write!(f, "({} {} {})", operator, left, right),
+            Normal::If { conditions, alternative } => {
+                write!(f, "(if")?;
+                for (condition, consequence) in conditions {
+                    write!(f, " {} {}", condition, consequence)?;
+                }
+                if let Some(alternative) = alternative {
+                    write!(f, " else {}", alternative)?;
+                }
+                write!(f, ")")
+            },
+            Normal::Function { is_async, parameters, body } => {
+                let keyword = if *is_async { "async fn" } else { "fn" };
+                write!(f, "({} ({}) {})", keyword, parameters.join(" "), body)
+            },
+            Normal::Await(value) => write!(f, "(await {})", value),
+            Normal::Other(source) => write!(f, "<{}>", source),
+        }
+    }
+}
+
+/// A way the two parsers disagree about one source.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Divergence {
+    /// `parser` rejected what `parser2` accepted.
+    RejectedByParser(Vec<String>),
+    /// `parser2` rejected what `parser` accepted.
+    RejectedByParser2(Vec<String>),
+    StatementCount { parser: usize, parser2: usize },
+    Statement { index: usize, parser: Normal, parser2: Normal },
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Divergence::RejectedByParser(errors) => write!(f, "only parser2 accepts it; parser: {}", errors.join("; ")),
+            Divergence::RejectedByParser2(errors) => write!(f, "only parser accepts it; parser2: {}", errors.join("; ")),
+            Divergence::StatementCount { parser, parser2 } => {
+                write!(f, "statement count: parser {}, parser2 {}", parser, parser2)
+            },
+            Divergence::Statement { index, parser, parser2 } => {
+                write!(f, "statement {}: parser {}, parser2 {}", index + 1, parser, parser2)
+            },
+        }
+    }
+}
+
+/// Parses `source` with both parsers and lists every way their trees differ. Sources both
+/// parsers reject do not diverge.
+pub fn compare(source: &str) -> Vec<Divergence> {
+    let mut lexer = Lexer::new(source.to_string());
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+    let first = match parser.errors.is_empty() {
+        true => Ok(program.statements.iter().map(|statement| statement_of(statement.as_ref())).collect::<Vec<_>>()),
+        false => Err(parser.errors.iter().map(|error| error.to_string()).collect::<Vec<_>>()),
+    };
+    let second = match parser2::program::parse_program(source) {
+        Ok(program) => Ok(program.statements.iter().map(|statement| statement2_of(statement.as_ref())).collect::<Vec<_>>()),
+        Err(errors) => Err(errors.iter().map(|error| error.to_string()).collect()),
+    };
+
+    match (first, second) {
+        (Err(_), Err(_)) => Vec::new(),
+        (Err(errors), Ok(_)) => vec![Divergence::RejectedByParser(errors)],
+        (Ok(_), Err(errors)) => vec![Divergence::RejectedByParser2(errors)],
+        (Ok(first), Ok(second)) => {
+            let mut divergences = Vec::new();
+            if first.len() != second.len() {
+                divergences.push(Divergence::StatementCount {
+                    parser: first.len(),
+                    parser2: second.len(),
+                });
+            }
+            for (index, (parser, parser2)) in first.into_iter().zip(second).enumerate() {
+                if parser != parser2 {
+                    divergences.push(Divergence::Statement { index, parser, parser2 });
+                }
+            }
+            divergences
+        },
+    }
+}
+
+fn statement_of(statement: &dyn Statement) -> Normal {
+    let any = statement.as_any();
+    if let Some(statement) = any.downcast_ref::<LetStatement>().filter(|statement| !statement.public) {
+        Normal::Let {
+            name: statement.name.to_string(),
+            mutable: statement.mutable,
+            value: Box::new(expression_of(statement.value.as_ref())),
+        }
+    } else if let Some(statement) = any.downcast_ref::<ReturnStatement>() {
+        Normal::Return(Box::new(expression_of(statement.expression.as_ref())))
+    } else if let Some(statement) = any.downcast_ref::<ExpressionStatement>() {
+        Normal::Expression(Box::new(expression_of(statement.expression.as_ref())))
+    } else if let Some(block) = any.downcast_ref::<BlockStatement>() {
+        block_of(block)
+    } else {
+        Normal::Other(statement.to_string())
+    }
+}
+
+fn block_of(block: &BlockStatement) -> Normal {
+    Normal::Block(block.statements.iter().map(|statement| statement_of(statement.as_ref())).collect())
+}
+
+fn expression_of(expression: &dyn Expression) -> Normal {
+    let any = expression.as_any();
+    if let Some(identifier) = any.downcast_ref::<IdentifierLiteral>() {
+        Normal::Identifier(identifier.to_string())
+    } else if let Some(integer) = any.downcast_ref::<IntegerLiteral>() {
+        Normal::Integer(integer.value)
+    } else if let Some(float) = any.downcast_ref::<FloatLiteral>() {
+        Normal::Float(float.value)
+    } else if let Some(boolean) = any.downcast_ref::<BooleanLiteral>() {
+        Normal::Boolean(boolean.value)
+    } else if let Some(prefix) = any.downcast_ref::<PrefixExpression>() {
+        Normal::Prefix(prefix.operator.to_string(), Box::new(expression_of(prefix.right.as_ref())))
+    } else if let Some(infix) = any.downcast_ref::<InfixExpression>() {
+        Normal::Infix(
+            infix.operator.to_string(),
+            Box::new(expression_of(infix.left.as_ref())),
+            Box::new(expression_of(infix.right.as_ref())),
+        )
+    } else if let Some(if_expression) = any.downcast_ref::<IfExpression>() {
+        Normal::If {
+            conditions: vec![(expression_of(if_expression.condition.as_ref()), block_of(&if_expression.consequence))],
+            alternative: if_expression.alternative.as_ref().map(|alternative| Box::new(block_of(alternative))),
+        }
+    } else if let Some(function) = any.downcast_ref::<FunctionLiteral>() {
+        Normal::Function {
+            is_async: function.is_async,
+            parameters: function.parameters.iter().map(|parameter| parameter.to_string()).collect(),
+            body: Box::new(block_of(&function.body)),
+        }
+    } else if let Some(await_expression) = any.downcast_ref::<AwaitExpression>() {
+        Normal::Await(Box::new(expression_of(await_expression.value.as_ref())))
+    } else {
+        Normal::Other(expression.to_string())
+    }
+}
+
+fn statement2_of(statement: &dyn ast2::traits::Statement) -> Normal {
+    use ast2::statements::*;
+
+    let any = statement.as_any();
+    if let Some(statement) = any.downcast_ref::<LetStatement>() {
+        Normal::Let {
+            name: statement.name.name.clone(),
+            mutable: statement.mutable,
+            value: Box::new(expression2_of(statement.value.as_ref())),
+        }
+    } else if let Some(statement) = any.downcast_ref::<ReturnStatement>() {
+        Normal::Return(Box::new(expression2_of(statement.value.as_ref())))
+    } else if let Some(statement) = any.downcast_ref::<ExpressionStatement>() {
+        Normal::Expression(Box::new(expression2_of(statement.expression.as_ref())))
+    } else if let Some(block) = any.downcast_ref::<BlockStatement>() {
+        block2_of(block)
+    } else {
+        Normal::Other(format!("{:?}", statement))
+    }
+}
+
+fn block2_of(block: &ast2::statements::BlockStatement) -> Normal {
+    Normal::Block(block.statements.iter().map(|statement| statement2_of(statement.as_ref())).collect())
+}
+
+/// Integer and float suffixes are dropped, since `parser` has no sized numbers to compare them with.
+fn expression2_of(expression: &dyn ast2::traits::Expression) -> Normal {
+    use ast2::expressions::*;
+
+    let any = expression.as_any();
+    if let Some(identifier) = any.downcast_ref::<ast2::literals::IdentifierLiteral>() {
+        Normal::Identifier(identifier.name.clone())
+    } else if let Some(integer) = any.downcast_ref::<ast2::literals::IntegerLiteral>() {
+        match integer.value.replace('_', "").parse() {
+            Ok(value) => Normal::Integer(value),
+            Err(_) => Normal::Other(format!("{:?}", integer)),
+        }
+    } else if let Some(float) = any.downcast_ref::<ast2::literals::FloatLiteral>() {
+        match float.value.replace('_', "").parse() {
+            Ok(value) => Normal::Float(value),
+            Err(_) => Normal::Other(format!("{:?}", float)),
+        }
+    } else if let Some(boolean) = any.downcast_ref::<ast2::literals::BooleanLiteral>() {
+        Normal::Boolean(boolean.value)
+    } else if let Some(prefix) = any.downcast_ref::<PrefixExpression>() {
+        Normal::Prefix(prefix.operator.clone(), Box::new(expression2_of(prefix.right.as_ref())))
+    } else if let Some(infix) = any.downcast_ref::<InfixExpression>() {
+        Normal::Infix(
+            infix.operator.clone(),
+            Box::new(expression2_of(infix.left.as_ref())),
+            Box::new(expression2_of(infix.right.as_ref())),
+        )
+    } else if let Some(if_expression) = any.downcast_ref::<IfExpression>() {
+        Normal::If {
+            conditions: if_expression
+                .conditions
+                .iter()
+                .map(|(condition, consequence)| (expression2_of(condition.as_ref()), block2_of(consequence)))
+                .collect(),
+            alternative: if_expression.alternative.as_ref().map(|alternative| Box::new(block2_of(alternative))),
+        }
+    } else if let Some(function) = any.downcast_ref::<FunctionLiteral>() {
+        Normal::Function {
+            is_async: function.is_async,
+            parameters: function.parameters.iter().map(|parameter| parameter.name.clone()).collect(),
+            body: Box::new(block2_of(&function.body)),
+        }
+    } else if let Some(await_expression) = any.downcast_ref::<AwaitExpression>() {
+        Normal::Await(Box::new(expression2_of(await_expression.value.as_ref())))
+    } else {
+        Normal::Other(format!("{:?}", expression))
+    }
+}
+
+#[cfg(test)]
+#[path = "./differential_tests.rs"]
+mod tests;
@@ -0,0 +1,81 @@
+use std::{fs, path::PathBuf};
+
+use super::*;
+
+use test_case::test_case;
+
+fn corpus() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("corpus")
+}
+
+/// Every divergence over the corpus, one per line and prefixed by the file it was found in.
+fn report() -> String {
+    let mut files = fs::read_dir(corpus())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ks"))
+        .collect::<Vec<_>>();
+    files.sort();
+
+    let mut report = String::new();
+    for path in files {
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        let source = fs::read_to_string(&path).unwrap();
+        let divergences = compare(&source);
+        // A file both parsers reject tests nothing, so it is listed too.
+        if divergences.is_empty() && parser2::program::parse_program(&source).is_err() {
+            report.push_str(&format!("{}: both parsers reject it\n", name));
+        }
+        for divergence in divergences {
+            report.push_str(&format!("{}: {}\n", name, divergence));
+        }
+    }
+    report
+}
+
+/// The corpus must diverge exactly as recorded in `divergences.txt`, so converging the parsers
+/// is a deliberate change to that file. Run with `KEYNES_BLESS=1` to rewrite it.
+#[test]
+fn test_corpus_divergences() {
+    let expected_path = corpus().join("divergences.txt");
+    let actual = report();
+    if std::env::var_os("KEYNES_BLESS").is_some() {
+        fs::write(&expected_path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&expected_path).unwrap_or_default().replace("\r\n", "\n");
+    assert!(
+        actual == expected,
+        "parser divergences changed; rerun with KEYNES_BLESS=1 if this is intended\n--- expected\n{}--- actual\n{}",
+        expected,
+        actual
+    );
+}
+
+#[test_case("let x = y;"; "let")]
+#[test_case("let mut x = -y + z * w;"; "operators")]
+#[test_case("if(x) { return true; } else { x; };"; "if")]
+#[test_case("let f = async fn(a, b) { await a + b; };"; "async function")]
+#[test_case("let f = async fn(x) { await(x); };"; "await without a space")]
+#[test_case("let x = ;"; "rejected by both")]
+fn test_agrees(source: &str) {
+    assert_eq!(compare(source), Vec::new());
+}
+
+#[test]
+fn test_divergent_statement() {
+    let divergences = compare("let x = y;\nlet y = 2;");
+    assert_eq!(divergences.len(), 1);
+    assert_eq!(
+        divergences[0].to_string(),
+        "statement 2: parser (let y 2), parser2 (let y `2`)"
+    );
+}
+
+#[test_case("x", "only parser accepts it; parser2: 1:2: expected \";\" in expression statement, found end of input"; "missing semicolon")]
+#[test_case("a..b;", "only parser2 accepts it; parser: 1:2: unhandled prefix parse for RANGE"; "range")]
+#[test_case("a..=b;", "only parser2 accepts it; parser: 1:2: unhandled prefix parse for RANGE"; "inclusive range")]
+fn test_rejected_by_one(source: &str, expected: &str) {
+    let divergences = compare(source).iter().map(|divergence| divergence.to_string()).collect::<Vec<_>>();
+    assert_eq!(divergences, vec![expected.to_string()]);
+}
@@ -0,0 +1,284 @@
+use std::{fmt::Display, path::Path};
+
+use crate::{
+    ast::{program::Program, statements::TestStatement},
+    diagnostics::Diagnostic,
+    environment::{Env, Environment},
+    evaluator::{Evaluator, RuntimeError},
+    ir::{self, passes::PassManager},
+    lexer::Lexer,
+    modules::{Loader, ModuleLoader},
+    convert::{FromKeynes, IntoKeynes, IntoNativeFunction},
+    object::{Builtin, Object},
+    optimizer::{optimize, OptLevel},
+    parser::Parser,
+    scheduler::{Future, Mode, Scheduler},
+};
+
+use log::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineError {
+    /// The source could not be read.
+    Io { file: String, message: String },
+    /// The source did not parse.
+    Parse { file: String, diagnostics: Vec<Diagnostic> },
+    /// Evaluation failed.
+    Runtime(RuntimeError),
+}
+
+impl Display for EngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineError::Io { file, message } => write!(f, "could not read {}: {}", file, message),
+            EngineError::Parse { file, diagnostics } => {
+                let diagnostics = diagnostics.iter().map(|d| format!("{}:{}", file, d)).collect::<Vec<_>>();
+                write!(f, "{}", diagnostics.join("\n"))
+            },
+            EngineError::Runtime(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<RuntimeError> for EngineError {
+    fn from(err: RuntimeError) -> Self {
+        EngineError::Runtime(err)
+    }
+}
+
+/// The result of one test run by [`Engine::eval_tests`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestOutcome {
+    pub name: String,
+    pub result: Result<(), EngineError>,
+}
+
+/// An embeddable Keynes interpreter.
+///
+/// Globals, registered functions and loaded modules persist across calls to
+/// [`Engine::eval`], so an engine can be fed a script piece by piece. Each call runs
+/// as a task on the engine's [`Scheduler`], next to the tasks scripts `spawn`.
+///
+/// The scheduler runs tasks on a pool of worker threads, so registered Rust functions
+/// may be called from any of them, several at once. They must therefore be `Send + Sync`:
+/// state they share with the host goes in an `Arc` with atomics or a `Mutex`, not in an
+/// `Rc` or a `Cell`.
+///
+/// ```
+/// use keynes::{Engine, Object};
+///
+/// let mut engine = Engine::new();
+/// engine.register_typed_fn("double", |value: i64| value * 2);
+/// engine.set_global("base", 20i64);
+/// engine.eval("let answer = double(base) + 2;").unwrap();
+/// assert_eq!(engine.get_global("answer"), Some(Object::from(42)));
+/// assert_eq!(engine.get_global_as::<i64>("answer"), Ok(Some(42)));
+/// ```
+#[derive(Debug)]
+pub struct Engine {
+    env: Env,
+    loader: Loader,
+    level: OptLevel,
+    scheduler: Scheduler,
+    /// The passes to run over the IR of sources when they are run from it rather than the AST.
+    ir: Option<PassManager>,
+    /// Calls of [`Engine::eval`] so far, which name the sources they are given.
+    evals: usize,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine {
+            env: Environment::new(),
+            loader: ModuleLoader::new(),
+            level: OptLevel::default(),
+            scheduler: Scheduler::new(Mode::default()),
+            ir: None,
+            evals: 0,
+        }
+    }
+
+    /// Sets how sources evaluated from now on, and the modules they import, are optimized.
+    pub fn set_opt_level(&mut self, level: OptLevel) {
+        self.level = level;
+        self.loader.lock().unwrap().set_opt_level(level);
+    }
+
+    /// Sets how tasks are run from now on. Tasks spawned before are abandoned.
+    pub fn set_scheduling(&mut self, mode: Mode) {
+        self.scheduler = Scheduler::with_max_call_depth(mode, self.scheduler.detects_races(), self.scheduler.max_call_depth());
+    }
+
+    /// Sets whether data races on `let mut` bindings are reported as errors from now on.
+    /// Tasks spawned before are abandoned.
+    pub fn set_race_detection(&mut self, enabled: bool) {
+        self.scheduler = Scheduler::with_max_call_depth(self.scheduler.mode(), enabled, self.scheduler.max_call_depth());
+    }
+
+    /// Sets how many calls of Keynes functions may be nested before deep recursion fails
+    /// with an error, [`MAX_CALL_DEPTH`](crate::evaluator::MAX_CALL_DEPTH) unless set. Tasks spawned before are abandoned.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.scheduler = Scheduler::with_max_call_depth(self.scheduler.mode(), self.scheduler.detects_races(), depth);
+    }
+
+    /// Sets whether sources evaluated from now on are lowered to the IR, optimized by
+    /// `passes` and run from it, or evaluated from their AST with `None`, the default.
+    /// Modules they import are still evaluated from their AST.
+    pub fn set_ir_passes(&mut self, passes: Option<PassManager>) {
+        self.ir = passes;
+    }
+
+    /// Evaluates `source` in the global scope, returning the value of its last statement.
+    /// Each source is reported as a file of its own, `<eval#1>` for the first and so on, so
+    /// that errors in functions it defines are shown in it when a later call runs them.
+    pub fn eval(&mut self, source: &str) -> Result<Object, EngineError> {
+        self.evals += 1;
+        let file = format!("<eval#{}>", self.evals);
+        self.eval_named(&file, source)
+    }
+
+    /// Evaluates the file at `path`. Its imports are resolved relative to it.
+    pub fn eval_file(&mut self, path: impl AsRef<Path>) -> Result<Object, EngineError> {
+        let file = path.as_ref().to_string_lossy().to_string();
+        let source = std::fs::read_to_string(&file).map_err(|err| EngineError::Io {
+            file: file.clone(),
+            message: err.to_string(),
+        })?;
+        self.eval_named(&file, &source)
+    }
+
+    /// Evaluates `source` in the global scope, reporting locations in it as being in `file`.
+    pub fn eval_named(&mut self, file: &str, source: &str) -> Result<Object, EngineError> {
+        trace!("engine eval: {}", file);
+        let program = self.parse(file, source)?;
+        let evaluator = Evaluator::with_loader(file, self.loader.clone());
+        let env = self.env.clone();
+        if let Some(passes) = &self.ir {
+            let mut module = ir::lower_globals(&program);
+            passes.run(&mut module);
+            return Ok(self.scheduler.block_on(move || ir::interpret(module, &evaluator, &env))?);
+        }
+        Ok(self.scheduler.block_on(move || evaluator.eval_program(&program, &env))?)
+    }
+
+    /// Evaluates the file at `path`, then runs each of its top level tests whose
+    /// name `filter` accepts, every one in a scope of its own.
+    pub fn eval_tests(&mut self, path: impl AsRef<Path>, filter: impl Fn(&str) -> bool) -> Result<Vec<TestOutcome>, EngineError> {
+        let file = path.as_ref().to_string_lossy().to_string();
+        let source = std::fs::read_to_string(&file).map_err(|err| EngineError::Io {
+            file: file.clone(),
+            message: err.to_string(),
+        })?;
+        self.parse(&file, &source).and_then(|program| {
+            let evaluator = Evaluator::with_loader(&file, self.loader.clone());
+            let tests = program
+                .statements
+                .iter()
+                .filter_map(|statement| statement.as_any().downcast_ref::<TestStatement>())
+                .filter(|test| filter(&test.name))
+                .cloned()
+                .collect::<Vec<_>>();
+            let (main, env) = (evaluator.clone(), self.env.clone());
+            self.scheduler.block_on(move || main.eval_program(&program, &env))?;
+            Ok(tests
+                .into_iter()
+                .map(|test| {
+                    let (evaluator, env) = (evaluator.clone(), self.env.clone());
+                    let name = test.name.clone();
+                    let result = self.scheduler.block_on(move || evaluator.eval_test(&test, &env).map(|_| Object::Null));
+                    TestOutcome { name, result: result.map(|_| ()).map_err(EngineError::from) }
+                })
+                .collect())
+        })
+    }
+
+    fn parse(&mut self, file: &str, source: &str) -> Result<Program, EngineError> {
+        self.loader.lock().unwrap().add_source(file.into(), source.to_string());
+        let mut lexer = Lexer::new(source.to_string());
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+        if !parser.errors.is_empty() {
+            return Err(EngineError::Parse {
+                file: file.to_string(),
+                diagnostics: parser.errors,
+            });
+        }
+        Ok(optimize(program, self.level))
+    }
+
+    /// Makes a Rust function callable from scripts as a global called `name`. It receives
+    /// the arguments of a call unconverted, so it can take any number of them. It may run
+    /// on any worker thread of the scheduler, hence the `Send + Sync` bound.
+    pub fn register_fn(&mut self, name: &str, function: impl Fn(Vec<Object>) -> Result<Object, RuntimeError> + Send + Sync + 'static) {
+        self.set_global(name, Object::Builtin(Builtin::new(name, function)));
+    }
+
+    /// Like [`Engine::register_fn`], but the arguments of the function are converted with
+    /// [`FromKeynes`] and its result with [`IntoKeynes`]; it may also return a
+    /// `Result<_, RuntimeError>`.
+    pub fn register_typed_fn<Args>(&mut self, name: &str, function: impl IntoNativeFunction<Args>) {
+        let function = function.into_native_function(name);
+        self.set_global(
+            name,
+            Object::Builtin(Builtin {
+                name: name.into(),
+                function,
+            }),
+        );
+    }
+
+    /// Makes an async Rust function callable from scripts as a global called `name`. A call
+    /// gives a future right away, which scripts `await`; meanwhile the Rust future runs on the
+    /// engine's scheduler, which runs other tasks while it is pending, e.g. waiting for I/O.
+    /// The future is woken by whatever it waits for, so it must not block or rely on the
+    /// reactor of another runtime.
+    pub fn register_async_fn<F>(&mut self, name: &str, function: impl Fn(Vec<Object>) -> F + Send + Sync + 'static)
+    where
+        F: std::future::Future<Output = Result<Object, RuntimeError>> + Send + 'static,
+    {
+        self.register_fn(name, move |arguments| Ok(Object::Future(Future::from_rust(function(arguments)))));
+    }
+
+    pub fn set_global(&mut self, name: &str, value: impl IntoKeynes) {
+        self.env.lock().unwrap().set(name.to_string(), value.into_keynes());
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Object> {
+        self.env.lock().unwrap().get(name)
+    }
+
+    /// All globals, sorted by name. Builtins of the prelude are not included.
+    pub fn globals(&self) -> Vec<(String, Object)> {
+        self.env.lock().unwrap().bindings()
+    }
+
+    /// Reads a global and converts it to a Rust value.
+    pub fn get_global_as<T: FromKeynes>(&self, name: &str) -> Result<Option<T>, RuntimeError> {
+        self.get_global(name).map(T::from_keynes).transpose()
+    }
+
+    /// Renders `err` with the offending source line, as the CLI prints it.
+    pub fn render_error(&self, err: &EngineError) -> String {
+        let loader = self.loader.lock().unwrap();
+        match err {
+            EngineError::Io { .. } => format!("error: {}\n", err),
+            EngineError::Parse { file, diagnostics } => {
+                let source = loader.source(file).unwrap_or("");
+                diagnostics.iter().map(|d| d.render(file, source)).collect()
+            },
+            EngineError::Runtime(err) => err.render(err.file().and_then(|file| loader.source(file)).unwrap_or("")),
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./engine_tests.rs"]
+mod tests;
@@ -0,0 +1,190 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{Poll, Waker},
+    time::Duration,
+};
+
+use crate::object::Integer;
+
+use super::*;
+
+use test_case::test_case;
+
+#[test]
+fn test_globals_persist_between_evals() {
+    let mut engine = Engine::new();
+    engine.eval("let x = 40;").unwrap();
+    assert_eq!(engine.eval("x + 2"), Ok(Object::from(42)));
+    assert_eq!(engine.get_global("x"), Some(Object::from(40)));
+    assert_eq!(engine.get_global("y"), None);
+}
+
+#[test]
+fn test_set_ir_passes() {
+    let mut engine = Engine::new();
+    engine.set_ir_passes(Some(PassManager::for_level(OptLevel::O1)));
+    engine.eval("let x = 40; let add = fn(a) { a + x };").unwrap();
+    assert_eq!(engine.eval("add(2)"), Ok(Object::from(42)));
+    assert_eq!(engine.get_global("x"), Some(Object::from(40)));
+    engine.set_ir_passes(None);
+    assert_eq!(engine.eval("add(x)"), Ok(Object::from(80)));
+}
+
+#[test]
+fn test_set_global_is_visible_to_scripts() {
+    let mut engine = Engine::new();
+    engine.set_global("greeting", Object::String("hi".to_string()));
+    assert_eq!(engine.eval("greeting + \"!\""), Ok(Object::String("hi!".to_string())));
+}
+
+#[test]
+fn test_register_fn() {
+    let mut engine = Engine::new();
+    // Registered functions run on the scheduler's worker threads, so the count is shared
+    // through an `Arc` and an atomic.
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    engine.register_fn("add_one", move |args| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        match args.as_slice() {
+            [Object::Integer(Integer::I64(value))] => Ok(Object::from(value + 1)),
+            _ => Err(RuntimeError::new("add_one expects an i64".to_string())),
+        }
+    });
+    assert_eq!(engine.eval("add_one(add_one(1))"), Ok(Object::from(3)));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    let err = engine.eval("let f = fn() { add_one(true) };\nf()").unwrap_err();
+    match err {
+        EngineError::Runtime(err) => {
+            assert_eq!(err.message, "add_one expects an i64");
+            let frames = err.trace.iter().map(|frame| frame.function.as_str()).collect::<Vec<_>>();
+            assert_eq!(frames, vec!["f", "<main>"]);
+        },
+        other => panic!("expected runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_registered_fn_shadows_builtin() {
+    let mut engine = Engine::new();
+    engine.register_fn("len", |_| Ok(Object::from(-1)));
+    assert_eq!(engine.eval("len([1, 2])"), Ok(Object::from(-1)));
+}
+
+#[test]
+fn test_render_errors() {
+    let mut engine = Engine::new();
+    let err = engine.eval("let x = ;").unwrap_err();
+    assert!(matches!(err, EngineError::Parse { .. }), "{:?}", err);
+    assert!(engine.render_error(&err).contains("\n1 | let x = ;\n"), "{}", engine.render_error(&err));
+
+    let err = engine.eval("1 / 0").unwrap_err();
+    assert_eq!(err.to_string(), "division by zero");
+    assert_eq!(engine.render_error(&err), "error: division by zero
+ --> <eval#2>:1:1
+  |
+1 | 1 / 0
+  | ^^^^^
+stack backtrace:
+   0: <main> at <eval#2>:1:1
+");
+
+    let err = engine.eval_file("/nonexistent/file.ks").unwrap_err();
+    assert!(matches!(err, EngineError::Io { .. }), "{:?}", err);
+}
+
+#[test]
+fn test_errors_are_shown_in_the_eval_defining_the_function() {
+    let mut engine = Engine::new();
+    engine.eval("let f = fn() {\n  1 + true\n};").unwrap();
+    let err = engine.eval("f()").unwrap_err();
+    assert_eq!(engine.render_error(&err), "error: type mismatch: INTEGER + BOOLEAN
+ --> <eval#1>:2:3
+  |
+2 |   1 + true
+  |   ^^^^^^^^
+stack backtrace:
+   0: f at <eval#1>:2:3
+   1: <main> at <eval#2>:1:1
+");
+}
+
+#[test]
+fn test_eval_file_resolves_imports_relative_to_file() {
+    let dir = std::env::temp_dir().join(format!("keynes-engine-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("main.ks"), "import \"lib.ks\" as lib;\nlib::value * 2").unwrap();
+    std::fs::write(dir.join("lib.ks"), "pub let value = 21;").unwrap();
+
+    let mut engine = Engine::new();
+    assert_eq!(engine.eval_file(dir.join("main.ks")), Ok(Object::from(42)));
+    assert_eq!(engine.eval_file(dir.join("main.ks")), Ok(Object::from(42)));
+}
+
+/// Resolves to `value` once a thread has slept for `delay`, as a call doing I/O would.
+fn delayed(value: Object, delay: Duration) -> impl std::future::Future<Output = Result<Object, RuntimeError>> {
+    let slot = Arc::new(Mutex::new((None, None::<Waker>)));
+    let filled = slot.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(delay);
+        let mut slot = filled.lock().unwrap();
+        slot.0 = Some(value);
+        if let Some(waker) = slot.1.take() {
+            waker.wake();
+        }
+    });
+    std::future::poll_fn(move |context| {
+        let mut slot = slot.lock().unwrap();
+        match slot.0.take() {
+            Some(value) => Poll::Ready(Ok(value)),
+            None => {
+                slot.1 = Some(context.waker().clone());
+                Poll::Pending
+            },
+        }
+    })
+}
+
+#[test_case(Mode::Parallel(2); "parallel")]
+#[test_case(Mode::Deterministic(4); "deterministic")]
+fn test_register_async_fn(mode: Mode) {
+    let mut engine = Engine::new();
+    engine.set_scheduling(mode);
+    engine.register_async_fn("fetch", |args| delayed(args[0].clone(), Duration::from_millis(20)));
+    assert_eq!(engine.eval("await fetch(7)"), Ok(Object::from(7)));
+
+    // Other tasks run while the fetch is pending.
+    let source = "
+let pending = fetch(1);
+let sum = fn(n) { if (n == 0) { 0 } else { n + sum(n - 1) } };
+let t = spawn sum(100);
+[join(t), await pending]
+";
+    assert_eq!(engine.eval(source).unwrap().to_string(), "[5050, 1]");
+}
+
+#[test]
+fn test_async_fn_errors_are_raised_by_await() {
+    let mut engine = Engine::new();
+    engine.register_async_fn("fail", |_| async { Err(RuntimeError::new("offline".to_string())) });
+    let future = engine.eval("let f = fail(); f").unwrap();
+    assert!(matches!(future, Object::Future(_)));
+    assert_eq!(engine.eval("await f").unwrap_err().to_string(), "offline");
+}
+
+#[test]
+fn test_set_max_call_depth() {
+    let mut engine = Engine::new();
+    engine.eval("let spin = fn(n) { if (n == 0) { 0 } else { spin(n - 1) } };").unwrap();
+    assert_eq!(engine.eval("spin(5000)"), Ok(Object::from(0)));
+
+    engine.set_max_call_depth(50);
+    engine.set_scheduling(Mode::Deterministic(1));
+    assert_eq!(engine.eval("spin(49)"), Ok(Object::from(0)));
+    assert_eq!(engine.eval("spin(50)").unwrap_err().to_string(), "maximum call depth exceeded");
+    assert_eq!(engine.eval("join(spawn spin(50))").unwrap_err().to_string(), "maximum call depth exceeded");
+}
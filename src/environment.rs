@@ -0,0 +1,81 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    evaluator::RuntimeError,
+    object::Object,
+    race::{self, Accesses},
+    scheduler::Site,
+};
+
+pub type Env = Arc<Mutex<Environment>>;
+
+#[derive(Debug, Default)]
+pub struct Environment {
+    store: HashMap<String, Object>,
+    outer: Option<Env>,
+    /// Accesses to the bindings whose data races are reported.
+    accesses: HashMap<String, Accesses>,
+}
+
+impl Environment {
+    pub fn new() -> Env {
+        Arc::new(Mutex::new(Environment::default()))
+    }
+
+    pub fn new_enclosed(outer: Env) -> Env {
+        Arc::new(Mutex::new(Environment {
+            store: HashMap::new(),
+            outer: Some(outer),
+            accesses: HashMap::new(),
+        }))
+    }
+
+    pub fn get(&self, name: &str) -> Option<Object> {
+        match self.store.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.outer.as_ref().and_then(|outer| outer.lock().unwrap().get(name)),
+        }
+    }
+
+    pub fn set(&mut self, name: String, value: Object) {
+        self.store.insert(name, value);
+    }
+
+    /// Looks `name` up like [`get`](Environment::get), failing if the current task reading it
+    /// at `site` races with a write.
+    pub fn read(&mut self, name: &str, site: impl FnOnce() -> Site) -> Result<Option<Object>, RuntimeError> {
+        match self.store.get(name) {
+            Some(value) => {
+                if let Some(accesses) = self.accesses.get_mut(name) {
+                    race::read(name, accesses, site())?;
+                }
+                Ok(Some(value.clone()))
+            },
+            None => match &self.outer {
+                Some(outer) => outer.lock().unwrap().read(name, site),
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// Binds `name` like [`set`](Environment::set), failing if the current task writing it at
+    /// `site` races with another access. Races on the binding are reported from now on if
+    /// `track`, and go on being reported if they already were.
+    pub fn write(&mut self, name: String, value: Object, track: bool, site: impl FnOnce() -> Site) -> Result<(), RuntimeError> {
+        if track || self.accesses.contains_key(&name) {
+            race::write(&name, self.accesses.entry(name.clone()).or_default(), site())?;
+        }
+        self.store.insert(name, value);
+        Ok(())
+    }
+
+    /// The bindings of this scope, not including enclosing ones, sorted by name.
+    pub fn bindings(&self) -> Vec<(String, Object)> {
+        let mut bindings = self.store.iter().map(|(name, value)| (name.clone(), value.clone())).collect::<Vec<_>>();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+}
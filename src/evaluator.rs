@@ -0,0 +1,657 @@
+use std::{collections::BTreeMap, fmt::Display, sync::Arc};
+
+use crate::{
+    ast::{expressions::*, program::Program, statements::*},
+    builtins, diagnostics,
+    environment::{Env, Environment},
+    ir::interpret,
+    lexer::{quote_string, Span, Token},
+    modules::{Entered, ImportChain, Loader, Module, ModuleLoader},
+    object::{Float, Function, HashKey, Integer, Object},
+    scheduler::{self, Future, Site},
+};
+
+use log::*;
+
+/// Most calls of Keynes functions that can be nested in a task unless the scheduler says
+/// otherwise, so that deep recursion fails with an error rather than overflowing the stack
+/// of the task.
+pub const MAX_CALL_DEPTH: usize = 10_000;
+
+/// One entry of a runtime stack trace: the function that was executing and
+/// where in it execution was when the error unwound through it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub function: String,
+    pub file: Arc<str>,
+    pub span: Option<Span>,
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "{} at {}:{}", self.function, self.file, span.start),
+            None => write!(f, "{} at {}", self.function, self.file),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    /// Location in the innermost function that has not been unwound yet.
+    pub span: Option<Span>,
+    /// Call frames, innermost first.
+    pub trace: Vec<Frame>,
+}
+
+impl RuntimeError {
+    pub fn new(message: String) -> RuntimeError {
+        RuntimeError {
+            message,
+            span: None,
+            trace: Vec::new(),
+        }
+    }
+
+    /// Attaches `span` unless a more precise location is already known.
+    pub(crate) fn at(mut self, span: Span) -> RuntimeError {
+        if self.span.is_none() {
+            self.span = Some(span);
+        }
+        self
+    }
+
+    /// Records that the error unwound out of `function`.
+    pub(crate) fn unwind(mut self, function: String, file: Arc<str>) -> RuntimeError {
+        self.trace.push(Frame {
+            function,
+            file,
+            span: self.span.take(),
+        });
+        self
+    }
+
+    /// File the error was raised in.
+    pub fn file(&self) -> Option<&str> {
+        self.trace.first().map(|frame| &*frame.file)
+    }
+
+    /// Renders the error like a parse diagnostic, followed by the stack trace.
+    /// `source` is the contents of the file the error was raised in. An error raised
+    /// outside of any code, such as a deadlock, is only its message.
+    pub fn render(&self, source: &str) -> String {
+        let (file, span) = match self.trace.first() {
+            Some(frame) => (frame.file.to_string(), frame.span),
+            None if self.span.is_none() => return format!("error: {}\n", self.message),
+            None => ("<unknown>".to_string(), self.span),
+        };
+        let mut out = diagnostics::render(&self.message, &file, span, source);
+        if self.trace.is_empty() {
+            return out;
+        }
+        out.push_str("stack backtrace:\n");
+        for (i, frame) in self.trace.iter().enumerate() {
+            out.push_str(&format!("{:>4}: {}\n", i, frame));
+        }
+        out
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Evaluator {
+    file: Arc<str>,
+    loader: Loader,
+    /// The files being evaluated that led to this evaluation, for reporting import cycles.
+    /// Tasks inherit it from the task spawning them.
+    importing: ImportChain,
+    /// Calls of Keynes functions the current task is nested in.
+    depth: usize,
+}
+
+impl Evaluator {
+    pub fn new(file: &str) -> Evaluator {
+        Evaluator::with_loader(file, ModuleLoader::new())
+    }
+
+    /// Creates an evaluator for `file` that shares already loaded modules with `loader`.
+    pub fn with_loader(file: &str, loader: Loader) -> Evaluator {
+        Evaluator {
+            file: file.into(),
+            loader,
+            importing: ModuleLoader::main_chain(file),
+            depth: 0,
+        }
+    }
+
+    pub fn loader(&self) -> &Loader {
+        &self.loader
+    }
+
+    /// The file being evaluated.
+    pub(crate) fn file(&self) -> &Arc<str> {
+        &self.file
+    }
+
+    pub fn eval_program(&self, program: &Program, env: &Env) -> Result<Object, RuntimeError> {
+        trace!("eval_program");
+        self.eval_top_level(program, env, "<main>")
+    }
+
+    /// Runs the body of `test` in a scope of its own inside `env`.
+    pub fn eval_test(&self, test: &TestStatement, env: &Env) -> Result<(), RuntimeError> {
+        let scope = Environment::new_enclosed(env.clone());
+        self.eval_block_statement(&test.body, &scope)
+            .map(|_| ())
+            .map_err(|err| err.unwind(format!("test {}", quote_string(&test.name)), self.file.clone()))
+    }
+
+    fn eval_top_level(&self, program: &Program, env: &Env, frame: &str) -> Result<Object, RuntimeError> {
+        let mut result = Object::Null;
+        for statement in &program.statements {
+            result = self
+                .eval_statement(statement.as_ref(), env)
+                .map_err(|err| err.unwind(frame.to_string(), self.file.clone()))?;
+            if let Object::ReturnValue(value) = result {
+                return Ok(*value);
+            }
+        }
+        Ok(result)
+    }
+
+    fn eval_block_statement(&self, block: &BlockStatement, env: &Env) -> Result<Object, RuntimeError> {
+        let mut result = Object::Null;
+        for statement in &block.statements {
+            result = self.eval_statement(statement.as_ref(), env)?;
+            if let Object::ReturnValue(_) = result {
+                return Ok(result);
+            }
+        }
+        Ok(result)
+    }
+
+    fn eval_statement(&self, statement: &dyn Statement, env: &Env) -> Result<Object, RuntimeError> {
+        trace!("eval_statement: {}", statement);
+        let any = statement.as_any();
+        if let Some(statement) = any.downcast_ref::<ExpressionStatement>() {
+            self.eval_expression(statement.expression.as_ref(), env)
+                .map_err(|err| err.at(statement.span))
+        } else if let Some(statement) = any.downcast_ref::<LetStatement>() {
+            let value = match self.eval_expression(statement.value.as_ref(), env) {
+                Ok(Object::Function(mut function)) if function.name.is_none() => {
+                    function.name = Some(statement.name.to_string());
+                    Object::Function(function)
+                },
+                Ok(value) => value,
+                Err(err) => return Err(err.at(statement.span)),
+            };
+            let track = statement.mutable && scheduler::detecting_races();
+            env.lock()
+                .unwrap()
+                .write(statement.name.to_string(), value, track, || self.site(statement.name.span))
+                .map_err(|err| err.at(statement.span))?;
+            Ok(Object::Null)
+        } else if let Some(statement) = any.downcast_ref::<LetTupleStatement>() {
+            self.eval_let_tuple_statement(statement, env)
+                .map_err(|err| err.at(statement.span))
+        } else if let Some(statement) = any.downcast_ref::<ReturnStatement>() {
+            let value = self
+                .eval_expression(statement.expression.as_ref(), env)
+                .map_err(|err| err.at(statement.span))?;
+            Ok(Object::ReturnValue(Box::new(value)))
+        } else if let Some(block) = any.downcast_ref::<BlockStatement>() {
+            self.eval_block_statement(block, env)
+        } else if let Some(import) = any.downcast_ref::<ImportStatement>() {
+            let module = self.import(&import.path).map_err(|err| err.at(import.span))?;
+            env.lock().unwrap().set(import.alias.to_string(), Object::Module(module));
+            Ok(Object::Null)
+        } else if let Some(use_statement) = any.downcast_ref::<UseStatement>() {
+            self.eval_use_statement(use_statement, env)
+                .map_err(|err| err.at(use_statement.span))
+        } else if any.is::<TestStatement>() {
+            // Tests only run when asked for, through `eval_test`.
+            Ok(Object::Null)
+        } else {
+            Err(RuntimeError::new(format!("unknown statement: {}", statement)))
+        }
+    }
+
+    fn eval_expression(&self, expression: &dyn Expression, env: &Env) -> Result<Object, RuntimeError> {
+        trace!("eval_expression: {}", expression);
+        let any = expression.as_any();
+        if let Some(integer) = any.downcast_ref::<IntegerLiteral>() {
+            Ok(Object::Integer(Integer::I64(integer.value)))
+        } else if let Some(float) = any.downcast_ref::<FloatLiteral>() {
+            Ok(Object::Float(Float::F64(float.value)))
+        } else if let Some(string) = any.downcast_ref::<StringLiteral>() {
+            Ok(Object::String(string.value.clone()))
+        } else if let Some(boolean) = any.downcast_ref::<BooleanLiteral>() {
+            Ok(Object::Boolean(boolean.value))
+        } else if let Some(identifier) = any.downcast_ref::<IdentifierLiteral>() {
+            self.eval_identifier(identifier, env)
+        } else if let Some(prefix) = any.downcast_ref::<PrefixExpression>() {
+            let right = self.eval_expression(prefix.right.as_ref(), env)?;
+            self.eval_prefix_expression(&prefix.operator, right)
+                .map_err(|err| err.at(prefix.span))
+        } else if let Some(infix) = any.downcast_ref::<InfixExpression>() {
+            let left = self.eval_expression(infix.left.as_ref(), env)?;
+            let right = self.eval_expression(infix.right.as_ref(), env)?;
+            self.eval_infix_expression(&infix.operator, left, right)
+                .map_err(|err| err.at(infix.span))
+        } else if let Some(if_expression) = any.downcast_ref::<IfExpression>() {
+            self.eval_if_expression(if_expression, env)
+                .map_err(|err| err.at(if_expression.span))
+        } else if let Some(function) = any.downcast_ref::<FunctionLiteral>() {
+            Ok(Object::Function(Function {
+                name: None,
+                is_async: function.is_async,
+                parameters: function.parameters.clone(),
+                body: Arc::new(function.body.clone()),
+                env: env.clone(),
+                file: self.file.clone(),
+                span: function.span,
+            }))
+        } else if let Some(call) = any.downcast_ref::<CallExpression>() {
+            let function = self
+                .eval_expression(call.function.as_ref(), env)
+                .map_err(|err| err.at(call.span))?;
+            let mut arguments = Vec::new();
+            for argument in &call.arguments {
+                arguments.push(self.eval_expression(argument.as_ref(), env).map_err(|err| err.at(call.span))?);
+            }
+            let result = match function {
+                // Builtins are where tasks block, so a deadlock report gives the call.
+                Object::Builtin(_) => scheduler::at(self.site(call.span), || self.apply_function(function, arguments)),
+                function => self.apply_function(function, arguments),
+            };
+            result.map_err(|err| err.at(call.span))
+        } else if let Some(spawn) = any.downcast_ref::<SpawnExpression>() {
+            let function = self
+                .eval_expression(spawn.call.function.as_ref(), env)
+                .map_err(|err| err.at(spawn.span))?;
+            let mut arguments = Vec::new();
+            for argument in &spawn.call.arguments {
+                arguments.push(self.eval_expression(argument.as_ref(), env).map_err(|err| err.at(spawn.span))?);
+            }
+            let evaluator = self.on_new_stack();
+            Ok(Object::Task(scheduler::spawn(move || evaluator.apply_function(function, arguments))))
+        } else if let Some(await_expression) = any.downcast_ref::<AwaitExpression>() {
+            let value = self
+                .eval_expression(await_expression.value.as_ref(), env)
+                .map_err(|err| err.at(await_expression.span))?;
+            match value {
+                Object::Future(future) => {
+                    scheduler::at(self.site(await_expression.span), || future.wait()).map_err(|err| err.at(await_expression.span))
+                },
+                other => Err(RuntimeError::new(format!("cannot await {}, expected FUTURE", other.type_name())).at(await_expression.span)),
+            }
+        } else if let Some(select) = any.downcast_ref::<SelectExpression>() {
+            self.eval_select_expression(select, env)
+                .map_err(|err| err.at(select.span))
+        } else if let Some(array) = any.downcast_ref::<ArrayLiteral>() {
+            let mut elements = Vec::new();
+            for element in &array.elements {
+                elements.push(self.eval_expression(element.as_ref(), env).map_err(|err| err.at(array.span))?);
+            }
+            Ok(Object::Array(elements))
+        } else if let Some(hash) = any.downcast_ref::<HashLiteral>() {
+            self.eval_hash_literal(hash, env)
+                .map_err(|err| err.at(hash.span))
+        } else if let Some(path) = any.downcast_ref::<PathExpression>() {
+            self.eval_module_identifier(&path.module, env)
+                .and_then(|module| module.get(&path.member.to_string()))
+                .map_err(|err| err.at(path.span))
+        } else if let Some(index) = any.downcast_ref::<IndexExpression>() {
+            let left = self.eval_expression(index.left.as_ref(), env)?;
+            let position = self.eval_expression(index.index.as_ref(), env)?;
+            self.eval_index_expression(left, position)
+                .map_err(|err| err.at(index.span))
+        } else {
+            Err(RuntimeError::new(format!("unknown expression: {}", expression)))
+        }
+    }
+
+    fn eval_identifier(&self, identifier: &IdentifierLiteral, env: &Env) -> Result<Object, RuntimeError> {
+        match &identifier.token {
+            Token::IDENTIFIER(name) => self.lookup(name, identifier.span, env),
+            _ => Err(RuntimeError::new(format!("invalid identifier: {}", identifier))),
+        }
+    }
+
+    /// Reads the variable `name` at `span`, or the builtin of that name if there is none.
+    pub(crate) fn lookup(&self, name: &str, span: Span, env: &Env) -> Result<Object, RuntimeError> {
+        if let Some(value) = env.lock().unwrap().read(name, || self.site(span))? {
+            return Ok(value);
+        }
+        match builtins::get(name) {
+            Some(builtin) => Ok(builtin),
+            None => Err(RuntimeError::new(format!("identifier not found: {}", name))),
+        }
+    }
+
+    fn eval_module_identifier(&self, identifier: &IdentifierLiteral, env: &Env) -> Result<Module, RuntimeError> {
+        match self.eval_identifier(identifier, env)? {
+            Object::Module(module) => Ok(module),
+            other => Err(RuntimeError::new(format!("not a module: {} is {}", identifier, other.type_name()))),
+        }
+    }
+
+    fn eval_use_statement(&self, use_statement: &UseStatement, env: &Env) -> Result<Object, RuntimeError> {
+        let module = self.eval_module_identifier(&use_statement.module, env)?;
+        for name in &use_statement.names {
+            let name = name.to_string();
+            let value = module.get(&name)?;
+            env.lock().unwrap().set(name, value);
+        }
+        Ok(Object::Null)
+    }
+
+    /// Loads the module at `path`, relative to the current file, evaluating it on first use.
+    /// While another task evaluates it, this one waits for it.
+    pub(crate) fn import(&self, path: &str) -> Result<Module, RuntimeError> {
+        let path = ModuleLoader::resolve(&self.file, path);
+        let key = ModuleLoader::canonicalize(&path)?;
+        let name: Arc<str> = path.to_string_lossy().into();
+        let entered = scheduler::block_until("import", |waker| {
+            self.loader.lock().unwrap().enter(&key, &name, &self.importing, waker)
+        })??;
+        let program = match entered {
+            Entered::Loaded(module) => return Ok(module),
+            Entered::Started(program) => program,
+        };
+
+        let mut importing = self.importing.as_ref().clone();
+        importing.push((key.clone(), name.clone()));
+        let env = Environment::new();
+        let evaluator = Evaluator {
+            file: name.clone(),
+            loader: self.loader.clone(),
+            importing: Arc::new(importing),
+            depth: self.depth,
+        };
+        let result = evaluator
+            .eval_top_level(&program, &env, "<module>")
+            .map(|_| Module::from_program(name, &program, &env));
+        self.loader.lock().unwrap().leave(&key, result.as_ref().ok().cloned());
+        result
+    }
+
+    pub(crate) fn eval_prefix_expression(&self, operator: &PrefixOperator, right: Object) -> Result<Object, RuntimeError> {
+        match (operator, right) {
+            (PrefixOperator::BANG, right) => Ok(Object::Boolean(!right.is_truthy())),
+            (PrefixOperator::MINUS, Object::Integer(value)) => value
+                .checked(value, "negate", |value, _| value.checked_neg())
+                .map(Object::Integer),
+            (PrefixOperator::MINUS, Object::Float(value)) => Ok(Object::Float(value.with_value(-value.to_f64()))),
+            (operator, right) => Err(RuntimeError::new(format!("unknown operator: {}{}", operator, right.type_name()))),
+        }
+    }
+
+    pub(crate) fn eval_infix_expression(&self, operator: &InfixOperator, left: Object, right: Object) -> Result<Object, RuntimeError> {
+        match (left, right) {
+            (Object::Integer(left), Object::Integer(right)) => self.eval_integer_infix_expression(operator, left, right),
+            (Object::Float(left), Object::Float(right)) => self.eval_float_infix_expression(operator, left, right),
+            (Object::String(left), Object::String(right)) => match operator {
+                InfixOperator::PLUS => Ok(Object::String(left + &right)),
+                InfixOperator::EQUAL => Ok(Object::Boolean(left == right)),
+                InfixOperator::NOT_EQUAL => Ok(Object::Boolean(left != right)),
+                _ => Err(RuntimeError::new(format!("unknown operator: STRING {} STRING", operator))),
+            },
+            (Object::Boolean(left), Object::Boolean(right)) => match operator {
+                InfixOperator::EQUAL => Ok(Object::Boolean(left == right)),
+                InfixOperator::NOT_EQUAL => Ok(Object::Boolean(left != right)),
+                _ => Err(RuntimeError::new(format!("unknown operator: BOOLEAN {} BOOLEAN", operator))),
+            },
+            (left, right) if left.type_name() != right.type_name() => Err(RuntimeError::new(format!(
+                "type mismatch: {} {} {}",
+                left.type_name(),
+                operator,
+                right.type_name()
+            ))),
+            (left, right) => Err(RuntimeError::new(format!(
+                "unknown operator: {} {} {}",
+                left.type_name(),
+                operator,
+                right.type_name()
+            ))),
+        }
+    }
+
+    fn eval_integer_infix_expression(&self, operator: &InfixOperator, left: Integer, right: Integer) -> Result<Object, RuntimeError> {
+        match operator {
+            InfixOperator::PLUS => left.checked(right, "add", i128::checked_add).map(Object::Integer),
+            InfixOperator::MINUS => left.checked(right, "subtract", i128::checked_sub).map(Object::Integer),
+            InfixOperator::MULTIPLY => left.checked(right, "multiply", i128::checked_mul).map(Object::Integer),
+            InfixOperator::DIVIDE => {
+                if right.to_i128() == 0 {
+                    return Err(RuntimeError::new("division by zero".to_string()));
+                }
+                left.checked(right, "divide", i128::checked_div).map(Object::Integer)
+            },
+            _ => {
+                if left.width() != right.width() {
+                    return Err(RuntimeError::new(format!(
+                        "integer width mismatch: cannot {} {} and {}",
+                        verb(operator),
+                        left.width(),
+                        right.width()
+                    )));
+                }
+                Ok(Object::Boolean(compare(operator, &left.to_i128(), &right.to_i128())))
+            },
+        }
+    }
+
+    fn eval_float_infix_expression(&self, operator: &InfixOperator, left: Float, right: Float) -> Result<Object, RuntimeError> {
+        if left.width() != right.width() {
+            return Err(RuntimeError::new(format!(
+                "float width mismatch: cannot {} {} and {}",
+                verb(operator),
+                left.width(),
+                right.width()
+            )));
+        }
+        let (l, r) = (left.to_f64(), right.to_f64());
+        match operator {
+            InfixOperator::PLUS => Ok(Object::Float(left.with_value(l + r))),
+            InfixOperator::MINUS => Ok(Object::Float(left.with_value(l - r))),
+            InfixOperator::MULTIPLY => Ok(Object::Float(left.with_value(l * r))),
+            InfixOperator::DIVIDE => Ok(Object::Float(left.with_value(l / r))),
+            _ => Ok(Object::Boolean(compare(operator, &l, &r))),
+        }
+    }
+
+    fn eval_hash_literal(&self, hash: &HashLiteral, env: &Env) -> Result<Object, RuntimeError> {
+        let mut pairs = BTreeMap::new();
+        for (key, value) in &hash.pairs {
+            let key = HashKey::try_from(self.eval_expression(key.as_ref(), env)?)?;
+            let value = self.eval_expression(value.as_ref(), env)?;
+            pairs.insert(key, value);
+        }
+        Ok(Object::Hash(pairs))
+    }
+
+    fn eval_if_expression(&self, if_expression: &IfExpression, env: &Env) -> Result<Object, RuntimeError> {
+        let condition = self.eval_expression(if_expression.condition.as_ref(), env)?;
+        if condition.is_truthy() {
+            self.eval_block_statement(&if_expression.consequence, env)
+        } else if let Some(alternative) = &if_expression.alternative {
+            self.eval_block_statement(alternative, env)
+        } else {
+            Ok(Object::Null)
+        }
+    }
+
+    fn eval_let_tuple_statement(&self, statement: &LetTupleStatement, env: &Env) -> Result<Object, RuntimeError> {
+        let elements = match self.eval_expression(statement.value.as_ref(), env)? {
+            Object::Array(elements) if elements.len() == statement.names.len() => elements,
+            Object::Array(elements) => {
+                return Err(RuntimeError::new(format!(
+                    "cannot bind {} names to an array of length {}",
+                    statement.names.len(),
+                    elements.len()
+                )));
+            },
+            other => return Err(RuntimeError::new(format!("cannot bind names to the elements of {}", other.type_name()))),
+        };
+        let mut env = env.lock().unwrap();
+        for (name, value) in statement.names.iter().zip(elements) {
+            env.write(name.to_string(), value, false, || self.site(name.span))?;
+        }
+        Ok(Object::Null)
+    }
+
+    /// Evaluates the receivers of the arms in order, then waits for the first of them to
+    /// have a value. Like the blocks of an `if`, arms bind in the enclosing scope.
+    fn eval_select_expression(&self, select: &SelectExpression, env: &Env) -> Result<Object, RuntimeError> {
+        let mut receivers = Vec::new();
+        for arm in &select.arms {
+            receivers.push(self.eval_expression(arm.receiver.as_ref(), env)?);
+        }
+        let (arm, value) = self.select(receivers, select.span)?;
+        let arm = &select.arms[arm];
+        if let Some(binding) = &arm.binding {
+            env.lock().unwrap().write(binding.to_string(), value, false, || self.site(binding.span))?;
+        }
+        self.eval_block_statement(&arm.body, env)
+    }
+
+    /// Waits at `span` for the first of `receivers` to have a value, giving its index and the value.
+    pub(crate) fn select(&self, receivers: Vec<Object>, span: Span) -> Result<(usize, Object), RuntimeError> {
+        let receivers = receivers
+            .into_iter()
+            .map(|receiver| match receiver {
+                Object::Receiver(receiver) => Ok(receiver),
+                other => Err(RuntimeError::new(format!("cannot select on {}, expected RECEIVER", other.type_name()))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        scheduler::at(self.site(span), || {
+            scheduler::block_until("select", |waker| {
+                receivers.iter().enumerate().find_map(|(i, receiver)| receiver.poll(waker).map(|value| (i, value)))
+            })
+        })
+    }
+
+    /// A copy of the evaluator for a task of its own, which starts with no calls on its stack.
+    pub(crate) fn on_new_stack(&self) -> Evaluator {
+        Evaluator { depth: 0, ..self.clone() }
+    }
+
+    pub(crate) fn site(&self, span: Span) -> Site {
+        Site {
+            file: self.file.clone(),
+            span,
+        }
+    }
+
+    pub(crate) fn eval_index_expression(&self, left: Object, index: Object) -> Result<Object, RuntimeError> {
+        match (left, index) {
+            (Object::Array(elements), Object::Integer(index)) => {
+                let index = index.to_i128();
+                if index < 0 || index as usize >= elements.len() {
+                    return Err(RuntimeError::new(format!(
+                        "index out of bounds: the length is {} but the index is {}",
+                        elements.len(),
+                        index
+                    )));
+                }
+                Ok(elements[index as usize].clone())
+            },
+            (Object::Hash(pairs), key) => {
+                let key = HashKey::try_from(key)?;
+                Ok(pairs.get(&key).cloned().unwrap_or(Object::Null))
+            },
+            (left, index) => Err(RuntimeError::new(format!(
+                "index operator not supported: {}[{}]",
+                left.type_name(),
+                index.type_name()
+            ))),
+        }
+    }
+
+    pub(crate) fn apply_function(&self, function: Object, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+        let function = match function {
+            Object::Function(function) => function,
+            Object::Builtin(builtin) => return (builtin.function)(arguments),
+            Object::Closure(closure) => return interpret::apply(self, closure, arguments),
+            other => return Err(RuntimeError::new(format!("not a function: {}", other.type_name()))),
+        };
+        if arguments.len() != function.parameters.len() {
+            return Err(RuntimeError::new(format!(
+                "wrong number of arguments to {}: expected {}, got {}",
+                function.display_name(),
+                function.parameters.len(),
+                arguments.len()
+            )));
+        }
+        if function.is_async {
+            let evaluator = self.on_new_stack();
+            return Ok(Object::Future(Future::spawn(move || evaluator.call(&function, arguments))));
+        }
+        scheduler::preempt();
+        self.call(&function, arguments)
+    }
+
+    /// Runs the body of `function` with `arguments` bound to its parameters.
+    fn call(&self, function: &Function, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+        let evaluator = self.callee(function.file.clone())?;
+        let env = Environment::new_enclosed(function.env.clone());
+        for (parameter, argument) in function.parameters.iter().zip(arguments) {
+            env.lock().unwrap().set(parameter.to_string(), argument);
+        }
+
+        let result = evaluator
+            .eval_block_statement(&function.body, &env)
+            .map_err(|err| err.unwind(function.display_name(), function.file.clone()))?;
+        match result {
+            Object::ReturnValue(value) => Ok(*value),
+            value => Ok(value),
+        }
+    }
+
+    /// The evaluator for the body of a function from `file` called from here, or an error if
+    /// the call would nest deeper than the scheduler allows.
+    pub(crate) fn callee(&self, file: Arc<str>) -> Result<Evaluator, RuntimeError> {
+        if self.depth >= scheduler::max_call_depth() {
+            return Err(RuntimeError::new("maximum call depth exceeded".to_string()));
+        }
+        Ok(Evaluator {
+            file,
+            loader: self.loader.clone(),
+            importing: self.importing.clone(),
+            depth: self.depth + 1,
+        })
+    }
+}
+
+fn verb(operator: &InfixOperator) -> &'static str {
+    match operator {
+        InfixOperator::PLUS => "add",
+        InfixOperator::MINUS => "subtract",
+        InfixOperator::MULTIPLY => "multiply",
+        InfixOperator::DIVIDE => "divide",
+        _ => "compare",
+    }
+}
+
+fn compare<T: PartialOrd>(operator: &InfixOperator, left: &T, right: &T) -> bool {
+    match operator {
+        InfixOperator::EQUAL => left == right,
+        InfixOperator::NOT_EQUAL => left != right,
+        InfixOperator::LESS_THAN => left < right,
+        InfixOperator::LESS_THAN_EQUAL => left <= right,
+        InfixOperator::GREATER_THAN => left > right,
+        InfixOperator::GREATER_THAN_EQUAL => left >= right,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+#[path = "./evaluator_tests.rs"]
+mod tests;
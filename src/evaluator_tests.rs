@@ -0,0 +1,188 @@
+use super::*;
+
+use crate::{lexer::{Lexer, Position}, object::Float, parser::Parser, scheduler::{Mode, Scheduler}};
+
+use test_case::test_case;
+
+fn eval(input: &str) -> Result<Object, RuntimeError> {
+    let mut lexer = Lexer::new(input.into());
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+    assert_eq!(parser.errors, vec![], "parser errors for {:?}", input);
+    Evaluator::new("test.ks").eval_program(&program, &Environment::new())
+}
+
+fn location(line: usize, column: usize) -> Position {
+    Position { line, column }
+}
+
+#[test_case("5", 5; "integer")]
+#[test_case("-10", -10; "negative integer")]
+#[test_case("5 + 5 + 5 + 5 - 10", 10; "sum")]
+#[test_case("2 * 2 * 2 * 2 * 2", 32; "product")]
+#[test_case("50 / 2 * 2 + 10", 60; "divide then multiply")]
+#[test_case("3 * (3 * 3) + 10", 37; "grouped")]
+#[test_case("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50; "mixed")]
+fn test_eval_integer_expression(input: &str, expected: i64) {
+    assert_eq!(eval(input), Ok(Object::from(expected)));
+}
+
+#[test_case("true", true; "true")]
+#[test_case("1 < 2", true; "less than")]
+#[test_case("1 > 2", false; "greater than")]
+#[test_case("1 == 1", true; "integers equal")]
+#[test_case("true != false", true; "booleans not equal")]
+#[test_case("(1 < 2) == true", true; "comparison equals true")]
+#[test_case("!5", false; "bang integer")]
+#[test_case("!!true", true; "double bang")]
+fn test_eval_boolean_expression(input: &str, expected: bool) {
+    assert_eq!(eval(input), Ok(Object::Boolean(expected)));
+}
+
+#[test_case("if (true) { 10 }", Object::from(10); "if true")]
+#[test_case("if (false) { 10 }", Object::Null; "if false")]
+#[test_case("if (1 > 2) { 10 } else { 20 }", Object::from(20); "if else")]
+#[test_case("if (10 > 1) { if (10 > 1) { return 10; } return 1; }", Object::from(10); "nested return")]
+#[test_case("9; return 2 * 5; 9;", Object::from(10); "return stops program")]
+fn test_eval_control_flow(input: &str, expected: Object) {
+    assert_eq!(eval(input), Ok(expected));
+}
+
+#[test_case("let a = 5; a;", 5; "let binding")]
+#[test_case("let a = 5; let b = a; let c = a + b + 5; c;", 15; "let chain")]
+#[test_case("let identity = fn(x) { x; }; identity(5);", 5; "identity")]
+#[test_case("let add = fn(x, y) { return x + y; }; add(5, add(5, 5));", 15; "nested calls")]
+#[test_case("fn(x) { x; }(5)", 5; "immediate call")]
+#[test_case("let adder = fn(x) { fn(y) { x + y } }; let addTwo = adder(2); addTwo(3);", 5; "closures")]
+#[test_case("[1, 2 * 2, 3 + 3][1]", 4; "array index")]
+#[test_case("let a = [1, 2, 3]; a[0] + a[1] + a[2];", 6; "array index sum")]
+fn test_eval_bindings_and_functions(input: &str, expected: i64) {
+    assert_eq!(eval(input), Ok(Object::from(expected)));
+}
+
+#[test]
+fn test_function_is_named_after_binding() {
+    match eval("let add = fn(x, y) { x + y }; add") {
+        Ok(Object::Function(function)) => assert_eq!(function.display_name(), "add"),
+        other => panic!("expected function, got {:?}", other),
+    }
+    match eval("fn(x) { x }") {
+        Ok(Object::Function(function)) => assert_eq!(function.display_name(), "<anonymous fn>"),
+        other => panic!("expected function, got {:?}", other),
+    }
+}
+
+#[test_case("5 + true;", "type mismatch: INTEGER + BOOLEAN"; "type mismatch")]
+#[test_case("-true", "unknown operator: -BOOLEAN"; "unknown prefix operator")]
+#[test_case("true + false;", "unknown operator: BOOLEAN + BOOLEAN"; "unknown infix operator")]
+#[test_case("foobar", "identifier not found: foobar"; "unknown identifier")]
+#[test_case("10 / 0", "division by zero"; "division by zero")]
+#[test_case("9223372036854775807 + 1", "attempt to add with overflow"; "overflow")]
+#[test_case("let x = 5; x(1)", "not a function: INTEGER"; "call non function")]
+#[test_case("[1, 2][2]", "index out of bounds: the length is 2 but the index is 2"; "index out of bounds")]
+#[test_case("[1, 2][-1]", "index out of bounds: the length is 2 but the index is -1"; "negative index")]
+#[test_case("fn(x) { x }(1, 2)", "wrong number of arguments to <anonymous fn>: expected 1, got 2"; "wrong arity")]
+fn test_eval_error_message(input: &str, expected: &str) {
+    assert_eq!(eval(input).unwrap_err().message, expected);
+}
+
+#[test]
+fn test_error_at_top_level_has_single_frame() {
+    let err = eval("let a = 1;\nlet b = a / 0;").unwrap_err();
+    assert_eq!(err.message, "division by zero");
+    assert_eq!(err.trace.len(), 1);
+    assert_eq!(err.trace[0].function, "<main>");
+    assert_eq!(&*err.trace[0].file, "test.ks");
+    assert_eq!(err.trace[0].span.unwrap().start, location(2, 9));
+}
+
+#[test]
+fn test_error_stack_trace_through_calls() {
+    let input = "let divide = fn(a, b) {
+    a / b
+};
+let apply = fn(f) {
+    fn(x) { f(x, 0) }(10)
+};
+apply(divide);";
+    let err = eval(input).unwrap_err();
+    assert_eq!(err.message, "division by zero");
+    let frames = err
+        .trace
+        .iter()
+        .map(|frame| (frame.function.as_str(), frame.span.unwrap().start))
+        .collect::<Vec<_>>();
+    assert_eq!(frames, vec![
+        ("divide", location(2, 5)),
+        ("<anonymous fn>", location(5, 13)),
+        ("apply", location(5, 5)),
+        ("<main>", location(7, 1)),
+    ]);
+}
+
+#[test]
+fn test_deep_recursion_is_an_error() {
+    let input = "let spin = fn(n) { if (n == 0) { 0 } else { spin(n - 1) } };\nspin(20000)";
+    // On a task, whose stack the limit is meant for, rather than the smaller one of the test.
+    let err = Scheduler::new(Mode::Deterministic(0)).block_on(move || eval(input)).unwrap_err();
+    assert_eq!(err.message, "maximum call depth exceeded");
+    assert_eq!(err.trace.len(), MAX_CALL_DEPTH + 1);
+    assert_eq!(err.trace[0].function, "spin");
+    assert_eq!(err.trace[0].span.unwrap().start, location(1, 45));
+    assert_eq!(err.trace[MAX_CALL_DEPTH].function, "<main>");
+
+    let input = "let spin = fn(n) { if (n == 0) { 0 } else { spin(n - 1) } };\nspin(5000)";
+    assert_eq!(Scheduler::new(Mode::Deterministic(0)).block_on(move || eval(input)), Ok(Object::from(0)));
+}
+
+#[test]
+fn test_error_location_of_unknown_identifier_uses_enclosing_statement() {
+    let err = eval("let a = 1;\n  let b = c;").unwrap_err();
+    assert_eq!(err.message, "identifier not found: c");
+    assert_eq!(err.trace[0].span.unwrap().start, location(2, 3));
+}
+
+#[test]
+fn test_render_runtime_error() {
+    let input = "let f = fn() {\n  [1][3]\n};\nf();";
+    let err = eval(input).unwrap_err();
+    assert_eq!(err.render(input), "error: index out of bounds: the length is 1 but the index is 3
+ --> test.ks:2:3
+  |
+2 |   [1][3]
+  |   ^^^^^^
+stack backtrace:
+   0: f at test.ks:2:3
+   1: <main> at test.ks:4:1
+");
+}
+
+#[test_case("\"foo\" + \"bar\"", Object::String("foobar".to_string()); "string concatenation")]
+#[test_case("\"a\" == \"a\"", Object::Boolean(true); "string equality")]
+#[test_case("1.5 + 2.25", Object::Float(Float::F64(3.75)); "float addition")]
+#[test_case("-0.5 * 4.0", Object::Float(Float::F64(-2.0)); "float negation")]
+#[test_case("1.5 < 2.5", Object::Boolean(true); "float comparison")]
+#[test_case("{\"a\": 1, true: 2, 3: 4}[\"a\"]", Object::from(1); "hash string key")]
+#[test_case("{\"a\": 1, true: 2, 3: 4}[true]", Object::from(2); "hash boolean key")]
+#[test_case("let k = 3; {k: 4}[k]", Object::from(4); "hash expression key")]
+#[test_case("{1: 2}[5]", Object::Null; "hash missing key")]
+fn test_eval_strings_floats_and_hashes(input: &str, expected: Object) {
+    assert_eq!(eval(input), Ok(expected));
+}
+
+#[test_case("\"a\" - \"b\"", "unknown operator: STRING - STRING"; "string minus")]
+#[test_case("{fn(x) { x }: 1}", "unusable as hash key: FUNCTION"; "unusable hash key")]
+#[test_case("1.0 + 1", "type mismatch: FLOAT + INTEGER"; "float integer mismatch")]
+#[test_case("f32(1) < 1.0", "float width mismatch: cannot compare f32 and f64"; "float width mismatch")]
+#[test_case("i16(1) == 1", "integer width mismatch: cannot compare i16 and i64"; "integer width mismatch")]
+fn test_eval_value_error_message(input: &str, expected: &str) {
+    assert_eq!(eval(input).unwrap_err().message, expected);
+}
+
+#[test]
+fn test_hash_display_is_sorted_and_quoted() {
+    assert_eq!(
+        eval("{\"b\": \"x\", \"a\": [1, \"y\"]}").unwrap().to_string(),
+        "{\"a\": [1, \"y\"], \"b\": \"x\"}"
+    );
+}
@@ -0,0 +1,294 @@
+use crate::{
+    ast::{expressions::*, program::Program, statements::*},
+    diagnostics::Diagnostic,
+    lexer::{quote_string, Comment, Lexer, Position, Token},
+    parser::Parser,
+};
+
+use log::*;
+
+const INDENT: &str = "    ";
+
+/// Blocks holding a single expression at most this long stay on one line, as in `fn(x) { x * 2 }`.
+const INLINE_BLOCK_WIDTH: usize = 40;
+
+/// Formats `source` as canonical Keynes source, or returns its parse errors.
+pub fn format_source(source: &str) -> Result<String, Vec<Diagnostic>> {
+    let mut lexer = Lexer::new(source.to_string());
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+    if !parser.errors.is_empty() {
+        return Err(parser.errors);
+    }
+    let comments = lexer.comments().to_vec();
+    Ok(Formatter::new(&comments).program(&program))
+}
+
+/// Binding strength of an expression, used to decide where parentheses are needed.
+fn precedence(expression: &dyn Expression) -> Precedence {
+    let any = expression.as_any();
+    if let Some(infix) = any.downcast_ref::<InfixExpression>() {
+        Precedence::from(infix.token.clone())
+    } else if any.is::<PrefixExpression>() || any.is::<AwaitExpression>() {
+        Precedence::PREFIX
+    } else if any.is::<CallExpression>() {
+        Precedence::CALL
+    } else {
+        Precedence::INDEX
+    }
+}
+
+struct Formatter<'a> {
+    comments: &'a [Comment],
+    /// Index of the first comment that has not been written yet.
+    next_comment: usize,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(comments: &'a [Comment]) -> Formatter<'a> {
+        Formatter {
+            comments,
+            next_comment: 0,
+        }
+    }
+
+    fn program(&mut self, program: &Program) -> String {
+        trace!("format program");
+        let mut out = String::new();
+        self.statements(&mut out, &program.statements, 0, None, None);
+        out
+    }
+
+    fn peek_comment(&self) -> Option<&'a Comment> {
+        self.comments.get(self.next_comment)
+    }
+
+    fn has_comment_before(&self, position: Position) -> bool {
+        self.peek_comment().is_some_and(|comment| comment.span.start < position)
+    }
+
+    /// Writes the comments starting before `position` on lines of their own,
+    /// keeping a blank line wherever the source had one.
+    fn comments_before(&mut self, out: &mut String, position: Option<Position>, depth: usize, last_line: &mut Option<usize>) {
+        while let Some(comment) = self.peek_comment() {
+            if position.is_some_and(|position| comment.span.start >= position) {
+                break;
+            }
+            if last_line.is_some_and(|last| comment.span.start.line > last + 1) {
+                out.push('\n');
+            }
+            out.push_str(&format!("{}//{}\n", INDENT.repeat(depth), comment.text));
+            *last_line = Some(comment.span.end.line);
+            self.next_comment += 1;
+        }
+    }
+
+    /// Writes `statements` one per line at `depth`, followed by the comments
+    /// before `end`. `first_line` is the line of the opening brace of a block.
+    fn statements(
+        &mut self,
+        out: &mut String,
+        statements: &[Box<dyn Statement>],
+        depth: usize,
+        first_line: Option<usize>,
+        end: Option<Position>,
+    ) {
+        let in_block = first_line.is_some();
+        let mut last_line = first_line;
+        for (i, statement) in statements.iter().enumerate() {
+            let span = statement.span();
+            self.comments_before(out, Some(span.start), depth, &mut last_line);
+            if last_line.is_some_and(|last| span.start.line > last + 1) {
+                out.push('\n');
+            }
+
+            let next = statements.get(i + 1);
+            let text = self.statement(statement.as_ref(), depth, in_block && next.is_none());
+            // Comments inside an expression, outside of any block, move above the statement.
+            self.comments_before(out, Some(span.end), depth, &mut None);
+            out.push_str(INDENT.repeat(depth).as_str());
+            out.push_str(&text);
+
+            if let Some(comment) = self.peek_comment() {
+                let before_next = next.is_none_or(|next| comment.span.start < next.span().start);
+                if comment.span.start.line == span.end.line && before_next {
+                    out.push_str(&format!(" //{}", comment.text));
+                    self.next_comment += 1;
+                }
+            }
+            out.push('\n');
+            last_line = Some(span.end.line);
+        }
+        self.comments_before(out, end, depth, &mut last_line);
+    }
+
+    fn statement(&mut self, statement: &dyn Statement, depth: usize, last_in_block: bool) -> String {
+        let any = statement.as_any();
+        if let Some(statement) = any.downcast_ref::<LetStatement>() {
+            format!(
+                "{}let {}{} = {};",
+                if statement.public { "pub " } else { "" },
+                if statement.mutable { "mut " } else { "" },
+                statement.name,
+                self.expression(statement.value.as_ref(), depth)
+            )
+        } else if let Some(statement) = any.downcast_ref::<LetTupleStatement>() {
+            let names = statement.names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+            format!("let ({}) = {};", names.join(", "), self.expression(statement.value.as_ref(), depth))
+        } else if let Some(statement) = any.downcast_ref::<ReturnStatement>() {
+            format!("return {};", self.expression(statement.expression.as_ref(), depth))
+        } else if let Some(statement) = any.downcast_ref::<ExpressionStatement>() {
+            let expression = self.expression(statement.expression.as_ref(), depth);
+            // The last expression of a block is its value and reads best without a semicolon.
+            if last_in_block {
+                expression
+            } else {
+                format!("{};", expression)
+            }
+        } else if let Some(block) = any.downcast_ref::<BlockStatement>() {
+            self.block(block, depth)
+        } else if let Some(import) = any.downcast_ref::<ImportStatement>() {
+            format!("import {} as {};", quote_string(&import.path), import.alias)
+        } else if let Some(use_statement) = any.downcast_ref::<UseStatement>() {
+            let names = use_statement.names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+            match names.as_slice() {
+                [name] => format!("use {}::{};", use_statement.module, name),
+                names => format!("use {}::{{{}}};", use_statement.module, names.join(", ")),
+            }
+        } else if let Some(test) = any.downcast_ref::<TestStatement>() {
+            format!("test {} {}", quote_string(&test.name), self.block(&test.body, depth))
+        } else {
+            statement.to_string()
+        }
+    }
+
+    fn block(&mut self, block: &BlockStatement, depth: usize) -> String {
+        if !self.has_comment_before(block.span.end) {
+            if block.statements.is_empty() {
+                return "{}".to_string();
+            }
+            if let [statement] = block.statements.as_slice() {
+                if let Some(statement) = statement.as_any().downcast_ref::<ExpressionStatement>() {
+                    let expression = self.expression(statement.expression.as_ref(), depth + 1);
+                    if !expression.contains('\n') && expression.len() <= INLINE_BLOCK_WIDTH {
+                        return format!("{{ {} }}", expression);
+                    }
+                }
+            }
+        }
+
+        let mut out = String::from("{\n");
+        self.statements(&mut out, &block.statements, depth + 1, Some(block.span.start.line), Some(block.span.end));
+        out.push_str(&INDENT.repeat(depth));
+        out.push('}');
+        out
+    }
+
+    /// Writes each arm on lines of its own, keeping bodies written as a single expression
+    /// that way.
+    fn select(&mut self, select: &SelectExpression, depth: usize) -> String {
+        let mut out = String::from("select {\n");
+        for arm in &select.arms {
+            out.push_str(&INDENT.repeat(depth + 1));
+            out.push_str(&self.expression(arm.receiver.as_ref(), depth + 1));
+            if let Some(binding) = &arm.binding {
+                out.push_str(&format!(" as {}", binding));
+            }
+            out.push_str(" => ");
+            let single = match arm.body.statements.as_slice() {
+                [statement] if arm.body.token == Token::FAT_ARROW => statement.as_any().downcast_ref::<ExpressionStatement>(),
+                _ => None,
+            };
+            let body = match single {
+                Some(statement) => self.expression(statement.expression.as_ref(), depth + 1),
+                None => self.block(&arm.body, depth + 1),
+            };
+            out.push_str(&body);
+            out.push_str(",\n");
+        }
+        out.push_str(&INDENT.repeat(depth));
+        out.push('}');
+        out
+    }
+
+    fn expressions(&mut self, expressions: &[Box<dyn Expression>], depth: usize) -> String {
+        expressions
+            .iter()
+            .map(|expression| self.expression(expression.as_ref(), depth))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Formats `expression`, parenthesised if it binds less tightly than `minimum`.
+    fn operand(&mut self, expression: &dyn Expression, minimum: Precedence, depth: usize) -> String {
+        let formatted = self.expression(expression, depth);
+        if precedence(expression) < minimum {
+            format!("({})", formatted)
+        } else {
+            formatted
+        }
+    }
+
+    fn expression(&mut self, expression: &dyn Expression, depth: usize) -> String {
+        let any = expression.as_any();
+        if let Some(string) = any.downcast_ref::<StringLiteral>() {
+            quote_string(&string.value)
+        } else if let Some(prefix) = any.downcast_ref::<PrefixExpression>() {
+            format!("{}{}", prefix.operator, self.operand(prefix.right.as_ref(), Precedence::PREFIX, depth))
+        } else if let Some(infix) = any.downcast_ref::<InfixExpression>() {
+            let own = Precedence::from(infix.token.clone());
+            let left = self.operand(infix.left.as_ref(), own.clone(), depth);
+            // Operators are left associative, so an equally binding right operand keeps its parentheses.
+            let right = self.expression(infix.right.as_ref(), depth);
+            let right = if precedence(infix.right.as_ref()) <= own {
+                format!("({})", right)
+            } else {
+                right
+            };
+            format!("{} {} {}", left, infix.operator, right)
+        } else if let Some(if_expression) = any.downcast_ref::<IfExpression>() {
+            let mut out = format!(
+                "if ({}) {}",
+                self.expression(if_expression.condition.as_ref(), depth),
+                self.block(&if_expression.consequence, depth)
+            );
+            if let Some(alternative) = &if_expression.alternative {
+                out.push_str(" else ");
+                out.push_str(&self.block(alternative, depth));
+            }
+            out
+        } else if let Some(function) = any.downcast_ref::<FunctionLiteral>() {
+            let parameters = function.parameters.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+            let keyword = if function.is_async { "async fn" } else { "fn" };
+            format!("{}({}) {}", keyword, parameters.join(", "), self.block(&function.body, depth))
+        } else if let Some(call) = any.downcast_ref::<CallExpression>() {
+            let function = self.operand(call.function.as_ref(), Precedence::CALL, depth);
+            format!("{}({})", function, self.expressions(&call.arguments, depth))
+        } else if let Some(array) = any.downcast_ref::<ArrayLiteral>() {
+            format!("[{}]", self.expressions(&array.elements, depth))
+        } else if let Some(hash) = any.downcast_ref::<HashLiteral>() {
+            let pairs = hash
+                .pairs
+                .iter()
+                .map(|(key, value)| format!("{}: {}", self.expression(key.as_ref(), depth), self.expression(value.as_ref(), depth)))
+                .collect::<Vec<_>>();
+            format!("{{{}}}", pairs.join(", "))
+        } else if let Some(index) = any.downcast_ref::<IndexExpression>() {
+            let left = self.operand(index.left.as_ref(), Precedence::INDEX, depth);
+            format!("{}[{}]", left, self.expression(index.index.as_ref(), depth))
+        } else if let Some(spawn) = any.downcast_ref::<SpawnExpression>() {
+            format!("spawn {}", self.expression(&spawn.call, depth))
+        } else if let Some(await_expression) = any.downcast_ref::<AwaitExpression>() {
+            format!("await {}", self.operand(await_expression.value.as_ref(), Precedence::PREFIX, depth))
+        } else if let Some(select) = any.downcast_ref::<SelectExpression>() {
+            self.select(select, depth)
+        } else {
+            // Identifiers, paths and the remaining literals print as written.
+            expression.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./formatter_tests.rs"]
+mod tests;
@@ -0,0 +1,124 @@
+use super::*;
+
+use test_case::test_case;
+
+fn format(source: &str) -> String {
+    format_source(source).unwrap_or_else(|errors| panic!("parse errors for {:?}: {:?}", source, errors))
+}
+
+#[test_case("a + b + c", "a + b + c;\n"; "left associative sum")]
+#[test_case("a + (b + c)", "a + (b + c);\n"; "right grouping is kept")]
+#[test_case("(a * b) + c", "a * b + c;\n"; "redundant parentheses are dropped")]
+#[test_case("(a + b) * c", "(a + b) * c;\n"; "needed parentheses are kept")]
+#[test_case("a - (b - c)", "a - (b - c);\n"; "non associative")]
+#[test_case("-(a + b)", "-(a + b);\n"; "prefix of infix")]
+#[test_case("-(-a)", "--a;\n"; "nested prefix")]
+#[test_case("!(a == b)", "!(a == b);\n"; "bang of comparison")]
+#[test_case("(a < b) == (c > d)", "a < b == c > d;\n"; "comparison inside equality")]
+#[test_case("(-f)(x)", "(-f)(x);\n"; "prefix callee")]
+#[test_case("-f(x)", "-f(x);\n"; "prefix of call")]
+#[test_case("(a + b)[0]", "(a + b)[0];\n"; "indexed sum")]
+#[test_case("let   x=[1,2 ,  3][ 0 ] ;", "let x = [1, 2, 3][0];\n"; "spacing")]
+#[test_case("pub let mut x = {\"a\":1, true : 2};", "pub let mut x = {\"a\": 1, true: 2};\n"; "let modifiers and hash")]
+#[test_case("\"a\\\"b\\n\"", "\"a\\\"b\\n\";\n"; "string escapes")]
+#[test_case("import \"lib.ks\" as lib; use lib::{a}; use lib::{a,b};", "import \"lib.ks\" as lib;\nuse lib::a;\nuse lib::{a, b};\n"; "modules")]
+#[test_case("math::add(1, 2)", "math::add(1, 2);\n"; "path call")]
+#[test_case("let t=spawn  f(1,2)", "let t = spawn f(1, 2);\n"; "spawn")]
+#[test_case("let (tx,rx)=channel( 1 )", "let (tx, rx) = channel(1);\n"; "let tuple")]
+#[test_case("select{rx as m=>m+1,timeout(5)=>{0}}", "select {\n    rx as m => m + 1,\n    timeout(5) => { 0 },\n};\n"; "select")]
+#[test_case("let f=async fn(x){await  g(x)+1}", "let f = async fn(x) { await g(x) + 1 };\n"; "async function")]
+#[test_case("await(a+b)", "await (a + b);\n"; "await a sum")]
+#[test_case("", ""; "empty program")]
+fn test_format_expressions(source: &str, expected: &str) {
+    assert_eq!(format(source), expected);
+}
+
+#[test]
+fn test_format_blocks() {
+    let source = "let add = fn(a, b) { a + b; };
+let max = fn(a, b) { if (a > b) { return a; } else { b } };
+let apply = fn(f) {
+        let x = 1; f(x) };
+let nothing = fn() {};";
+    assert_eq!(format(source), "let add = fn(a, b) { a + b };
+let max = fn(a, b) {
+    if (a > b) {
+        return a;
+    } else { b }
+};
+let apply = fn(f) {
+    let x = 1;
+    f(x)
+};
+let nothing = fn() {};
+");
+}
+
+#[test]
+fn test_long_single_expressions_break_the_block() {
+    let source = "fn(x) { some_function_name(x, another_argument, a_third_one) }";
+    assert_eq!(format(source), "fn(x) {
+    some_function_name(x, another_argument, a_third_one)
+};
+");
+}
+
+#[test]
+fn test_comments_and_blank_lines_are_preserved() {
+    let source = "// leading comment
+let a = 1; // trailing comment
+
+
+// second group
+let f = fn(x) {
+  // inside
+  x * 2 // doubled
+
+  // before closing brace
+};
+let b = 2; let c = 3; // after c
+// at the end
+";
+    assert_eq!(format(source), "// leading comment
+let a = 1; // trailing comment
+
+// second group
+let f = fn(x) {
+    // inside
+    x * 2 // doubled
+
+    // before closing brace
+};
+let b = 2;
+let c = 3; // after c
+// at the end
+");
+}
+
+#[test]
+fn test_comment_inside_expression_moves_above_statement() {
+    let source = "let xs = [1, // one
+  2];";
+    assert_eq!(format(source), "// one\nlet xs = [1, 2];\n");
+}
+
+#[test_case("let a = 1; let f = fn(x) { x }; f(a)"; "simple")]
+#[test_case("// c\nlet f = fn(a, b) {\n  if (a < b) { a } else {\n    // pick b\n    b\n  }\n};\n\nf(1, 2)"; "nested with comments")]
+#[test_case("(1 + 2) * -(3 - 4) / f(5)[6]"; "operators")]
+fn test_format_is_idempotent_and_preserves_meaning(source: &str) {
+    let once = format(source);
+    assert_eq!(format(&once), once);
+
+    let parse = |source: &str| {
+        let mut lexer = Lexer::new(source.to_string());
+        let mut parser = Parser::new(&mut lexer);
+        parser.parse_program().to_string()
+    };
+    assert_eq!(parse(&once), parse(source));
+}
+
+#[test]
+fn test_parse_errors_are_returned() {
+    let errors = format_source("let = 1;").unwrap_err();
+    assert!(!errors.is_empty());
+}
@@ -0,0 +1,159 @@
+//! Random, syntactically valid Keynes programs for stress testing the front ends, and the
+//! harness the `front_ends` fuzz target feeds arbitrary bytes through.
+
+use crate::{
+    lexer::{Lexer, Token},
+    parser::Parser,
+    parser2,
+    random::Random,
+};
+
+const NAMES: [&str; 8] = ["a", "b", "x", "y", "count", "total", "f", "_tmp"];
+const MODULES: [&str; 3] = ["m", "math", "lib"];
+const INFIX: [&str; 10] = ["+", "-", "*", "/", "==", "!=", "<", "<=", ">", ">="];
+const STRING_PARTS: [&str; 8] = ["a", "hello", " ", "é", "\\n", "\\t", "\\\"", "\\\\"];
+
+/// Runs the lexer and both parsers on `data`, which must return whatever the bytes.
+pub fn feed_front_ends(data: &[u8]) {
+    let source = String::from_utf8_lossy(data).to_string();
+
+    let mut lexer = Lexer::new(source.clone());
+    while lexer.next_token() != Token::EOF {}
+
+    let mut lexer = Lexer::new(source.clone());
+    Parser::new(&mut lexer).parse_program();
+
+    let _ = parser2::program::parse_program(&source);
+}
+
+/// Generates programs from the grammar accepted by `parser`. The same seed always
+/// generates the same programs, and no expression nests deeper than the depth budget.
+pub struct Generator {
+    random: Random,
+    depth: usize,
+}
+
+impl Generator {
+    pub fn new(seed: u64, depth: usize) -> Generator {
+        Generator {
+            random: Random::new(seed),
+            depth,
+        }
+    }
+
+    /// A program of one to eight top level statements.
+    pub fn program(&mut self) -> String {
+        let count = 1 + self.below(8);
+        (0..count).map(|_| self.top_level_statement() + "\n").collect()
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        self.random.below(bound)
+    }
+
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    fn pick<'a>(&mut self, choices: &[&'a str]) -> &'a str {
+        choices[self.below(choices.len())]
+    }
+
+    fn top_level_statement(&mut self) -> String {
+        match self.below(10) {
+            0 => format!("import \"{}.ks\" as {};", self.pick(&MODULES), self.pick(&MODULES)),
+            1 => {
+                let names = (0..1 + self.below(3)).map(|_| self.pick(&NAMES)).collect::<Vec<_>>();
+                format!("use {}::{{{}}};", self.pick(&MODULES), names.join(", "))
+            },
+            2 => format!("pub let {} = {};", self.pick(&NAMES), self.expression(self.depth)),
+            _ => self.statement(self.depth),
+        }
+    }
+
+    fn statement(&mut self, depth: usize) -> String {
+        match self.below(4) {
+            0 => {
+                let mutable = if self.chance(30) { "mut " } else { "" };
+                format!("let {}{} = {};", mutable, self.pick(&NAMES), self.expression(depth))
+            },
+            1 => format!("return {};", self.expression(depth)),
+            _ => format!("{};", self.expression(depth)),
+        }
+    }
+
+    fn block(&mut self, depth: usize) -> String {
+        let statements = (0..self.below(3)).map(|_| self.statement(depth)).collect::<Vec<_>>();
+        match statements.is_empty() {
+            true => "{ }".to_string(),
+            false => format!("{{ {} }}", statements.join(" ")),
+        }
+    }
+
+    fn expression(&mut self, depth: usize) -> String {
+        if depth == 0 {
+            return self.atom();
+        }
+        let depth = depth - 1;
+        match self.below(12) {
+            0 => format!("{}{}", self.pick(&["-", "!"]), self.expression(depth)),
+            1 | 2 => format!("{} {} {}", self.expression(depth), self.pick(&INFIX), self.expression(depth)),
+            3 => format!("({})", self.expression(depth)),
+            4 => {
+                let mut expression = format!("if ({}) {}", self.expression(depth), self.block(depth));
+                if self.chance(50) {
+                    expression.push_str(&format!(" else {}", self.block(depth)));
+                }
+                expression
+            },
+            5 => {
+                let parameters = (0..self.below(3)).map(|_| self.pick(&NAMES)).collect::<Vec<_>>();
+                format!("fn({}) {}", parameters.join(", "), self.block(depth))
+            },
+            6 => {
+                let arguments = self.list(depth);
+                format!("{}({})", self.callee(depth), arguments)
+            },
+            7 => format!("[{}]", self.list(depth)),
+            8 => {
+                let pairs = (0..self.below(3))
+                    .map(|_| format!("{}: {}", self.expression(depth), self.expression(depth)))
+                    .collect::<Vec<_>>();
+                format!("{{{}}}", pairs.join(", "))
+            },
+            9 => format!("{}[{}]", self.callee(depth), self.expression(depth)),
+            _ => self.atom(),
+        }
+    }
+
+    /// An expression that binds tightly enough to be called or indexed without parentheses.
+    fn callee(&mut self, depth: usize) -> String {
+        match self.below(3) {
+            0 => format!("({})", self.expression(depth)),
+            _ => self.pick(&NAMES).to_string(),
+        }
+    }
+
+    fn list(&mut self, depth: usize) -> String {
+        (0..self.below(4)).map(|_| self.expression(depth)).collect::<Vec<_>>().join(", ")
+    }
+
+    fn atom(&mut self) -> String {
+        match self.below(8) {
+            0 => self.below(1000).to_string(),
+            1 => i64::MAX.to_string(),
+            2 => format!("{}.{}", self.below(100), self.below(100)),
+            3 => {
+                let parts = (0..self.below(4)).map(|_| self.pick(&STRING_PARTS)).collect::<String>();
+                format!("\"{}\"", parts)
+            },
+            4 => self.pick(&["true", "false"]).to_string(),
+            5 => format!("{}::{}", self.pick(&MODULES), self.pick(&NAMES)),
+            _ => self.pick(&NAMES).to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./generator_tests.rs"]
+mod tests;
@@ -0,0 +1,79 @@
+use super::*;
+
+use crate::{formatter::format_source, lexer::Lexer, parser::{Parser, MAX_NESTING}};
+
+use test_case::test_case;
+
+const SEEDS: u64 = 200;
+
+fn parse(source: &str) -> Result<String, Vec<String>> {
+    let mut lexer = Lexer::new(source.to_string());
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+    match parser.errors.is_empty() {
+        true => Ok(program.to_string()),
+        false => Err(parser.errors.iter().map(|error| error.to_string()).collect()),
+    }
+}
+
+#[test]
+fn test_same_seed_same_program() {
+    assert_eq!(Generator::new(7, 4).program(), Generator::new(7, 4).program());
+    assert_ne!(Generator::new(7, 4).program(), Generator::new(8, 4).program());
+}
+
+#[test_case(0; "atoms only")]
+#[test_case(3; "shallow")]
+#[test_case(5; "deep")]
+fn test_generated_programs_parse(depth: usize) {
+    for seed in 0..SEEDS {
+        let source = Generator::new(seed, depth).program();
+        if let Err(errors) = parse(&source) {
+            panic!("seed {} generated a program that does not parse: {:?}\n{}", seed, errors, source);
+        }
+    }
+}
+
+#[test]
+fn test_print_round_trip() {
+    for seed in 0..SEEDS {
+        let source = Generator::new(seed, 4).program();
+        let printed = format_source(&source).unwrap();
+        assert_eq!(parse(&printed), parse(&source), "seed {} changed when printed as\n{}", seed, printed);
+    }
+}
+
+#[test_case(b"run x;"; "run")]
+#[test_case(b"spawn f();"; "spawn")]
+#[test_case(b"\"unterminated \\"; "unterminated string")]
+#[test_case(b"9223372036854775808 ..= 1.2.3"; "bad numbers")]
+#[test_case(b"let \xff\xfe = fn(,) { [}; ) :: ::"; "garbage")]
+#[test_case(b"((((((((((((((((((((((((((((((("; "open parentheses")]
+#[test_case(&[b'('; 20_000]; "parentheses nested past the limit")]
+#[test_case("if (a) { ".repeat(MAX_NESTING * 2).as_bytes(); "blocks nested past the limit")]
+fn test_front_ends_do_not_panic(data: &[u8]) {
+    feed_front_ends(data);
+}
+
+#[test]
+fn test_front_ends_do_not_panic_on_mangled_programs() {
+    for seed in 0..SEEDS {
+        let mut generator = Generator::new(seed, 4);
+        let mut data = generator.program().into_bytes();
+        // Truncate, drop and duplicate bytes the way a mutating fuzzer would.
+        for _ in 0..1 + generator.below(4) {
+            let at = generator.below(data.len());
+            match generator.below(3) {
+                0 => data.truncate(at),
+                1 => {
+                    data.remove(at);
+                },
+                _ => data.insert(at, data[at]),
+            }
+            if data.is_empty() {
+                break;
+            }
+        }
+        feed_front_ends(&data);
+    }
+}
@@ -0,0 +1,205 @@
+//! Runs the IR, so that programs can be run as the [`passes`](super::passes) optimized them.
+//!
+//! Values are the evaluator's objects, and each operation does what the [`Evaluator`] does
+//! for the expression it was lowered from, mostly by asking it to. Imported modules are
+//! still evaluated from their AST, and the functions they export are called through it.
+//! As the IR does not say which bindings are `let mut`, data races on them are not reported.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{Debug, Display},
+    sync::Arc,
+};
+
+use super::*;
+use crate::{
+    environment::{Env, Environment},
+    evaluator::{Evaluator, RuntimeError},
+    object::{HashKey, Integer},
+    scheduler::{self, Future},
+};
+
+use log::*;
+
+/// A function of the IR, closing over the environment of the call that created it.
+#[derive(Clone)]
+pub struct Closure {
+    pub module: Arc<Module>,
+    pub function: FunctionId,
+    /// Whether calls give a future of the result, computed in a task of its own.
+    pub is_async: bool,
+    pub env: Env,
+    /// The file the module was lowered from.
+    pub file: Arc<str>,
+}
+
+impl Closure {
+    fn definition(&self) -> &Function {
+        &self.module.functions[self.function.0]
+    }
+}
+
+impl Debug for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Closure")
+            .field("function", &self.function)
+            .field("name", &self.definition().name)
+            .field("is_async", &self.is_async)
+            .field("file", &self.file)
+            .finish()
+    }
+}
+
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.module, &other.module) && self.function == other.function && Arc::ptr_eq(&self.env, &other.env)
+    }
+}
+
+impl Display for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.definition().name)
+    }
+}
+
+/// Runs `module`, lowered from the program `evaluator` is for, with its top level binding
+/// in `env`. Gives the value of the program like [`Evaluator::eval_program`].
+pub fn interpret(module: Module, evaluator: &Evaluator, env: &Env) -> Result<Object, RuntimeError> {
+    trace!("interpret module");
+    let module = Arc::new(module);
+    run(evaluator, &module, FunctionId(0), Vec::new(), env).map_err(|err| err.unwind("<main>".to_string(), evaluator.file().clone()))
+}
+
+/// Calls `closure` with `arguments` like [`Evaluator::apply_function`] calls functions.
+pub(crate) fn apply(evaluator: &Evaluator, closure: Closure, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    let function = closure.definition();
+    if arguments.len() != function.parameters.len() {
+        return Err(RuntimeError::new(format!(
+            "wrong number of arguments to {}: expected {}, got {}",
+            function.name,
+            function.parameters.len(),
+            arguments.len()
+        )));
+    }
+    if closure.is_async {
+        let evaluator = evaluator.on_new_stack();
+        return Ok(Object::Future(Future::spawn(move || call(&evaluator, &closure, arguments))));
+    }
+    scheduler::preempt();
+    call(evaluator, &closure, arguments)
+}
+
+/// Runs the body of `closure` with `arguments` bound to its parameters.
+fn call(evaluator: &Evaluator, closure: &Closure, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    let callee = evaluator.callee(closure.file.clone())?;
+    let env = Environment::new_enclosed(closure.env.clone());
+    run(&callee, &closure.module, closure.function, arguments, &env)
+        .map_err(|err| err.unwind(closure.definition().name.clone(), closure.file.clone()))
+}
+
+/// Runs function `id` of `module` in `env`, from its entry block until it returns.
+fn run(evaluator: &Evaluator, module: &Arc<Module>, id: FunctionId, arguments: Vec<Object>, env: &Env) -> Result<Object, RuntimeError> {
+    let function = &module.functions[id.0];
+    let mut values = vec![Object::Null; function.values];
+    for (parameter, argument) in function.parameters.iter().zip(arguments) {
+        values[parameter.0] = argument;
+    }
+    let mut previous = None;
+    let mut current = BlockId(0);
+    loop {
+        let block = &function.blocks[current.0];
+        // Phis all read the values from before the block, so none sees the result of another.
+        let incoming = block
+            .phis
+            .iter()
+            .map(|phi| {
+                let (_, value) = phi.incoming.iter().find(|(from, _)| Some(*from) == previous).expect("a phi has a value for every predecessor");
+                values[value.0].clone()
+            })
+            .collect::<Vec<_>>();
+        for (phi, value) in block.phis.iter().zip(incoming) {
+            values[phi.result.0] = value;
+        }
+        for instruction in &block.instructions {
+            let value = execute(evaluator, module, instruction, &values, env).map_err(|err| err.at(instruction.span))?;
+            if let Some(result) = instruction.result {
+                values[result.0] = value;
+            }
+        }
+        previous = Some(current);
+        current = match &block.terminator {
+            Terminator::Jump(target) => *target,
+            Terminator::Branch(condition, then, _) if values[condition.0].is_truthy() => *then,
+            Terminator::Branch(_, _, otherwise) => *otherwise,
+            Terminator::Return(value) => return Ok(values[value.0].clone()),
+        };
+    }
+}
+
+/// Performs `instruction`, whose operands are in `values`, in a call whose environment is `env`.
+fn execute(evaluator: &Evaluator, module: &Arc<Module>, instruction: &Instruction, values: &[Object], env: &Env) -> Result<Object, RuntimeError> {
+    let read = |value: &Value| values[value.0].clone();
+    let span = instruction.span;
+    match &instruction.op {
+        Op::Constant(constant) => Ok(match constant {
+            Constant::Null => Object::Null,
+            Constant::Integer(value) => Object::Integer(Integer::I64(*value)),
+            Constant::Float(value) => Object::Float(Float::F64(*value)),
+            Constant::Boolean(value) => Object::Boolean(*value),
+            Constant::String(value) => Object::String(value.clone()),
+        }),
+        Op::Prefix(operator, right) => evaluator.eval_prefix_expression(operator, read(right)),
+        Op::Infix(operator, left, right) => evaluator.eval_infix_expression(operator, read(left), read(right)),
+        Op::Array(elements) => Ok(Object::Array(elements.iter().map(read).collect())),
+        Op::Hash(pairs) => {
+            let mut hash = BTreeMap::new();
+            for (key, value) in pairs {
+                hash.insert(HashKey::try_from(read(key))?, read(value));
+            }
+            Ok(Object::Hash(hash))
+        },
+        Op::Index(left, index) => evaluator.eval_index_expression(read(left), read(index)),
+        Op::Call(function, arguments) => {
+            let arguments = arguments.iter().map(read).collect();
+            match read(function) {
+                // Builtins are where tasks block, so a deadlock report gives the call.
+                function @ Object::Builtin(_) => scheduler::at(evaluator.site(span), || evaluator.apply_function(function, arguments)),
+                function => evaluator.apply_function(function, arguments),
+            }
+        },
+        Op::Spawn(function, arguments) => {
+            let (function, arguments) = (read(function), arguments.iter().map(read).collect());
+            let evaluator = evaluator.on_new_stack();
+            Ok(Object::Task(scheduler::spawn(move || evaluator.apply_function(function, arguments))))
+        },
+        Op::Select(receivers) => {
+            let (index, value) = evaluator.select(receivers.iter().map(read).collect(), span)?;
+            Ok(Object::Array(vec![Object::from(index as i64), value]))
+        },
+        Op::Closure(function) | Op::AsyncClosure(function) => Ok(Object::Closure(Closure {
+            module: module.clone(),
+            function: *function,
+            is_async: matches!(instruction.op, Op::AsyncClosure(_)),
+            env: env.clone(),
+            file: evaluator.file().clone(),
+        })),
+        Op::Await(future) => match read(future) {
+            Object::Future(future) => scheduler::at(evaluator.site(span), || future.wait()),
+            other => Err(RuntimeError::new(format!("cannot await {}, expected FUTURE", other.type_name()))),
+        },
+        Op::Load(name) => evaluator.lookup(name, span, env),
+        Op::Store(name, value) => {
+            env.lock().unwrap().write(name.clone(), read(value), false, || evaluator.site(span))?;
+            Ok(Object::Null)
+        },
+        Op::Import(path) => evaluator.import(path).map(Object::Module),
+        Op::Member(module, name) => match read(module) {
+            Object::Module(module) => module.get(name),
+            other => Err(RuntimeError::new(format!("not a module: {}", other.type_name()))),
+        },
+    }
+}
+
+#[cfg(test)]
+#[path = "./interpret_tests.rs"]
+mod tests;
@@ -0,0 +1,88 @@
+use std::path::PathBuf;
+
+use crate::{
+    engine::{Engine, EngineError},
+    generator::Generator,
+    ir::passes::{Pass, PassManager},
+    optimizer::OptLevel,
+    scheduler::Mode,
+};
+
+use test_case::test_case;
+
+#[test_case("1 + 2 * 3", Ok("7"); "arithmetic")]
+#[test_case("let x = 3; if (x > 2) { x * 10 } else { 0 }", Ok("30"); "branches")]
+#[test_case("let x = 1; if (x > 2) { x * 10 }", Ok("null"); "branch without alternative")]
+#[test_case("let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15)", Ok("610"); "recursion")]
+#[test_case("let add = fn(a) { fn(b) { a + b } }; add(1)(2)", Ok("3"); "closures")]
+#[test_case("let f = async fn(a) { a + 1 }; await f(41)", Ok("42"); "futures")]
+#[test_case("let c = channel(); spawn fn() { send(c[0], 5) }(); recv(c[1])", Ok("5"); "tasks")]
+#[test_case("{\"k\": [1, 2]}[\"k\"][1]", Ok("2"); "collections")]
+#[test_case("let f = fn(a) { a }; f", Ok("<fn f>"); "functions")]
+#[test_case("let f = fn(a) { a }; f(1, 2)", Err("wrong number of arguments to f: expected 1, got 2"); "arity")]
+#[test_case("[1, 2][5]", Err("index out of bounds: the length is 2 but the index is 5"); "errors")]
+#[test_case("await 1", Err("cannot await INTEGER, expected FUTURE"); "await of a value")]
+#[test_case("y", Err("identifier not found: y"); "unknown identifier")]
+fn test_interpret(source: &str, expected: Result<&str, &str>) {
+    for passes in managers() {
+        let result = outcome(source, Some(passes.clone()));
+        assert_eq!(result.as_deref(), expected.map_err(str::to_string).as_deref(), "{:?}", passes.passes());
+    }
+}
+
+#[test]
+fn test_errors_locate_the_instruction() {
+    let mut engine = Engine::new();
+    engine.set_ir_passes(Some(PassManager::new(Vec::new())));
+    let err = engine.eval("let f = fn(a) {\n  a[3]\n};\nf([1])").unwrap_err();
+    let EngineError::Runtime(err) = err else { panic!("expected a runtime error, got {:?}", err) };
+    let frames = err.trace.iter().map(|frame| (frame.function.as_str(), frame.span.map(|span| span.start.line))).collect::<Vec<_>>();
+    assert_eq!(frames, [("f", Some(2)), ("<main>", Some(4))]);
+}
+
+#[test]
+fn test_programs_keep_results() {
+    let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("programs");
+    for entry in std::fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|extension| extension == "ks") {
+            let source = std::fs::read_to_string(&path).unwrap();
+            assert_same_results(&source, &path.display().to_string());
+        }
+    }
+}
+
+#[test]
+fn test_generated_programs_keep_results() {
+    for seed in 0..150 {
+        let source = Generator::new(seed, 4).program();
+        assert_same_results(&source, &format!("seed {}\n{}", seed, source));
+    }
+}
+
+/// Checks that `source` gives the same result run from its IR before the passes as after
+/// every pass alone, then all of them.
+fn assert_same_results(source: &str, name: &str) {
+    let expected = outcome(source, Some(PassManager::new(Vec::new())));
+    for passes in managers() {
+        assert_eq!(outcome(source, Some(passes.clone())), expected, "{} after {:?}", name, passes.passes());
+    }
+}
+
+fn managers() -> Vec<PassManager> {
+    Pass::ALL.map(|pass| PassManager::new(vec![pass])).into_iter().chain([PassManager::for_level(OptLevel::O1)]).collect()
+}
+
+/// The value of `source`, or the message of the error it fails with, run from its IR with
+/// `passes` on a deterministic scheduler.
+fn outcome(source: &str, passes: Option<PassManager>) -> Result<String, String> {
+    let mut engine = Engine::new();
+    engine.set_opt_level(OptLevel::O0);
+    engine.set_scheduling(Mode::Deterministic(1));
+    engine.set_ir_passes(passes);
+    match engine.eval(source) {
+        Ok(value) => Ok(value.to_string()),
+        Err(EngineError::Runtime(err)) => Err(err.message),
+        Err(err) => Err(err.to_string()),
+    }
+}
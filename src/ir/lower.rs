@@ -0,0 +1,657 @@
+//! Lowers the AST to the IR.
+//!
+//! SSA form is built while lowering, by looking up the definitions of a variable through
+//! the predecessors of the block reading it and adding phi nodes where they differ. As
+//! the language has no loops, every predecessor of a block is known by the time it is
+//! entered. A variable read where some path does not bind it falls back to the enclosing
+//! scope at runtime, so the function is lowered again with it in the environment.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ast::{expressions::*, program::Program, statements::*},
+    checker::collect_declared,
+    lexer::Span,
+};
+
+use super::*;
+
+use log::*;
+
+/// Lowers `program`, which must have parsed cleanly. The top level is the function `<main>`.
+pub fn lower(program: &Program) -> Module {
+    trace!("lower program");
+    let exported = program
+        .statements
+        .iter()
+        .filter_map(|statement| statement.as_any().downcast_ref::<LetStatement>())
+        .filter(|statement| statement.public)
+        .map(|statement| statement.name.to_string())
+        .collect::<HashSet<_>>();
+    lower_with(program, exported)
+}
+
+/// Lowers `program` like [`lower`], but binds every variable of its top level in the
+/// environment, so that programs run after it in the same environment can read them.
+pub fn lower_globals(program: &Program) -> Module {
+    trace!("lower program with globals");
+    let mut globals = Vec::new();
+    collect_declared(&program.statements, &mut globals);
+    lower_with(program, globals.into_iter().collect())
+}
+
+fn lower_with(program: &Program, environment: HashSet<String>) -> Module {
+    let mut lowerer = Lowerer { functions: Vec::new() };
+    lowerer.function("<main>", &[], &program.statements, environment, Span::default());
+    Module {
+        functions: lowerer.functions.into_iter().map(|function| function.expect("every function is lowered")).collect(),
+    }
+}
+
+struct Lowerer {
+    /// Functions by id, `None` while they are being lowered.
+    functions: Vec<Option<Function>>,
+}
+
+impl Lowerer {
+    /// Lowers a function, keeping `environment` and the variables nested functions capture
+    /// in the environment.
+    fn function(
+        &mut self,
+        name: &str,
+        parameters: &[IdentifierLiteral],
+        body: &[Box<dyn Statement>],
+        mut environment: HashSet<String>,
+        span: Span,
+    ) -> FunctionId {
+        let id = FunctionId(self.functions.len());
+        self.functions.push(None);
+
+        let mut locals = parameters.iter().map(|parameter| parameter.to_string()).collect::<Vec<_>>();
+        collect_declared(body, &mut locals);
+        let mut captured = HashSet::new();
+        captured_in_statements(body, false, &mut captured);
+        environment.extend(locals.iter().filter(|local| captured.contains(*local)).cloned());
+
+        loop {
+            let mut builder = Builder {
+                lowerer: self,
+                blocks: Vec::new(),
+                current: None,
+                values: 0,
+                locals: locals.iter().filter(|local| !environment.contains(*local)).cloned().collect(),
+                environment: &environment,
+                unbound: HashSet::new(),
+                span,
+            };
+            let function = builder.function(name, parameters, body, span);
+            let unbound = std::mem::take(&mut builder.unbound);
+            if unbound.is_empty() {
+                self.functions[id.0] = Some(function);
+                return id;
+            }
+            trace!("lower {} again with {:?} in the environment", name, unbound);
+            environment.extend(unbound);
+            self.functions.truncate(id.0 + 1);
+        }
+    }
+}
+
+/// What control does after a statement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flow {
+    /// It continues, with the value of the statement.
+    Value(Value),
+    /// It continues, and the statement has no value, which is null.
+    Null,
+    /// It left the function.
+    Returned,
+}
+
+/// A block being built.
+#[derive(Default)]
+struct Partial {
+    phis: Vec<Phi>,
+    instructions: Vec<Instruction>,
+    terminator: Option<Terminator>,
+    predecessors: Vec<BlockId>,
+    /// The values of the SSA variables at the end of the block, as far as it is built.
+    definitions: HashMap<String, Value>,
+}
+
+/// Lowers the body of one function.
+struct Builder<'a> {
+    lowerer: &'a mut Lowerer,
+    blocks: Vec<Partial>,
+    /// The block being appended to, or `None` after a `return`.
+    current: Option<BlockId>,
+    values: usize,
+    /// Variables of the function that are SSA values.
+    locals: HashSet<String>,
+    environment: &'a HashSet<String>,
+    /// Locals read where some path does not bind them.
+    unbound: HashSet<String>,
+    /// Span of the innermost statement or expression with one.
+    span: Span,
+}
+
+impl<'a> Builder<'a> {
+    fn function(&mut self, name: &str, parameters: &[IdentifierLiteral], body: &[Box<dyn Statement>], span: Span) -> Function {
+        let entry = self.block(Vec::new());
+        self.current = Some(entry);
+        let mut values = Vec::new();
+        for parameter in parameters {
+            let value = self.value();
+            values.push(value);
+            self.bind(&parameter.to_string(), value);
+        }
+        match self.statements(body) {
+            Flow::Value(value) => self.terminate(Terminator::Return(value)),
+            Flow::Null => {
+                let null = self.emit(Op::Constant(Constant::Null));
+                self.terminate(Terminator::Return(null));
+            },
+            Flow::Returned => {},
+        }
+
+        let blocks = std::mem::take(&mut self.blocks)
+            .into_iter()
+            .map(|block| Block {
+                phis: block.phis,
+                instructions: block.instructions,
+                terminator: block.terminator.expect("every block is terminated"),
+            })
+            .collect();
+        let mut function = Function {
+            name: name.to_string(),
+            parameters: values,
+            blocks,
+            values: self.values,
+            span,
+        };
+        function.renumber();
+        function
+    }
+
+    fn value(&mut self) -> Value {
+        self.values += 1;
+        Value(self.values - 1)
+    }
+
+    fn block(&mut self, predecessors: Vec<BlockId>) -> BlockId {
+        self.blocks.push(Partial {
+            predecessors,
+            ..Default::default()
+        });
+        BlockId(self.blocks.len() - 1)
+    }
+
+    fn current(&self) -> BlockId {
+        self.current.expect("control reaches the code being lowered")
+    }
+
+    /// Appends an instruction with a result to `block`.
+    fn emit_in(&mut self, block: BlockId, op: Op) -> Value {
+        let result = self.value();
+        self.blocks[block.0].instructions.push(Instruction {
+            result: Some(result),
+            op,
+            span: self.span,
+        });
+        result
+    }
+
+    fn emit(&mut self, op: Op) -> Value {
+        self.emit_in(self.current(), op)
+    }
+
+    fn terminate(&mut self, terminator: Terminator) {
+        let block = self.current();
+        self.blocks[block.0].terminator = Some(terminator);
+        self.current = None;
+    }
+
+    fn bind(&mut self, name: &str, value: Value) {
+        if self.environment.contains(name) {
+            let block = self.current();
+            self.blocks[block.0].instructions.push(Instruction {
+                result: None,
+                op: Op::Store(name.to_string(), value),
+                span: self.span,
+            });
+        } else {
+            let block = self.current();
+            self.blocks[block.0].definitions.insert(name.to_string(), value);
+        }
+    }
+
+    fn read(&mut self, name: &str) -> Value {
+        if self.locals.contains(name) {
+            if let Some(value) = self.read_variable(name, self.current()) {
+                return value;
+            }
+            self.unbound.insert(name.to_string());
+        }
+        self.emit(Op::Load(name.to_string()))
+    }
+
+    /// The value of the SSA variable `name` at the end of `block`, as far as it is built,
+    /// or `None` if some path to it does not bind the variable.
+    fn read_variable(&mut self, name: &str, block: BlockId) -> Option<Value> {
+        if let Some(value) = self.blocks[block.0].definitions.get(name) {
+            return Some(*value);
+        }
+        let predecessors = self.blocks[block.0].predecessors.clone();
+        let value = match predecessors.as_slice() {
+            [] => return None,
+            [predecessor] => self.read_variable(name, *predecessor)?,
+            _ => {
+                let mut incoming = Vec::new();
+                for predecessor in predecessors {
+                    incoming.push((predecessor, self.read_variable(name, predecessor)?));
+                }
+                if incoming.iter().all(|(_, value)| *value == incoming[0].1) {
+                    incoming[0].1
+                } else {
+                    let result = self.value();
+                    self.blocks[block.0].phis.push(Phi { result, incoming });
+                    result
+                }
+            },
+        };
+        self.blocks[block.0].definitions.insert(name.to_string(), value);
+        Some(value)
+    }
+
+    fn statements(&mut self, statements: &[Box<dyn Statement>]) -> Flow {
+        let mut flow = Flow::Null;
+        for statement in statements {
+            flow = self.statement(statement.as_ref());
+            if flow == Flow::Returned {
+                // The rest of the block cannot run.
+                break;
+            }
+        }
+        flow
+    }
+
+    fn statement(&mut self, statement: &dyn Statement) -> Flow {
+        self.span = statement.span();
+        let any = statement.as_any();
+        if let Some(statement) = any.downcast_ref::<LetStatement>() {
+            let name = statement.name.to_string();
+            let value = match statement.value.as_any().downcast_ref::<FunctionLiteral>() {
+                Some(function) => Some(self.closure(&name, function)),
+                None => self.expression(statement.value.as_ref()),
+            };
+            let Some(value) = value else {
+                return Flow::Returned;
+            };
+            self.bind(&name, value);
+            Flow::Null
+        } else if let Some(statement) = any.downcast_ref::<LetTupleStatement>() {
+            let Some(value) = self.expression(statement.value.as_ref()) else {
+                return Flow::Returned;
+            };
+            // Indexing fails on an array that is too short, as binding does, but a longer
+            // one is not caught here.
+            for (i, name) in statement.names.iter().enumerate() {
+                let position = self.emit(Op::Constant(Constant::Integer(i as i64)));
+                let element = self.emit(Op::Index(value, position));
+                self.bind(&name.to_string(), element);
+            }
+            Flow::Null
+        } else if let Some(statement) = any.downcast_ref::<ReturnStatement>() {
+            if let Some(value) = self.expression(statement.expression.as_ref()) {
+                self.terminate(Terminator::Return(value));
+            }
+            Flow::Returned
+        } else if let Some(statement) = any.downcast_ref::<ExpressionStatement>() {
+            if let Some(if_expression) = statement.expression.as_any().downcast_ref::<IfExpression>() {
+                return self.if_expression(if_expression);
+            }
+            if let Some(select) = statement.expression.as_any().downcast_ref::<SelectExpression>() {
+                return self.select_expression(select);
+            }
+            match self.expression(statement.expression.as_ref()) {
+                Some(value) => Flow::Value(value),
+                None => Flow::Returned,
+            }
+        } else if let Some(block) = any.downcast_ref::<BlockStatement>() {
+            self.statements(&block.statements)
+        } else if let Some(import) = any.downcast_ref::<ImportStatement>() {
+            let module = self.emit(Op::Import(import.path.clone()));
+            self.bind(&import.alias.to_string(), module);
+            Flow::Null
+        } else if let Some(use_statement) = any.downcast_ref::<UseStatement>() {
+            let module = self.read(&use_statement.module.to_string());
+            for name in &use_statement.names {
+                let name = name.to_string();
+                let value = self.emit(Op::Member(module, name.clone()));
+                self.bind(&name, value);
+            }
+            Flow::Null
+        } else {
+            // Tests only run under `keynes test`, and a program that parsed has no error statements.
+            Flow::Null
+        }
+    }
+
+    /// Lowers `if_expression`, leaving control in the block where its branches meet.
+    fn if_expression(&mut self, if_expression: &IfExpression) -> Flow {
+        let outer = self.span;
+        self.span = if_expression.span;
+        let Some(condition) = self.expression(if_expression.condition.as_ref()) else {
+            self.span = outer;
+            return Flow::Returned;
+        };
+        let from = self.current();
+        let then = self.block(vec![from]);
+        let otherwise = self.block(vec![from]);
+        self.terminate(Terminator::Branch(condition, then, otherwise));
+
+        self.current = Some(then);
+        let then_flow = self.statements(&if_expression.consequence.statements);
+        let then_end = self.current;
+        self.current = Some(otherwise);
+        let otherwise_flow = match &if_expression.alternative {
+            Some(alternative) => self.statements(&alternative.statements),
+            None => Flow::Null,
+        };
+        let otherwise_end = self.current;
+        self.span = if_expression.span;
+        let flow = self.join(vec![(then_end, then_flow), (otherwise_end, otherwise_flow)]);
+        self.span = outer;
+        flow
+    }
+
+    /// Lowers `select`: each arm tests the index the select gives in turn, and the last
+    /// one is left when all others fail.
+    fn select_expression(&mut self, select: &SelectExpression) -> Flow {
+        let outer = self.span;
+        self.span = select.span;
+        let mut receivers = Vec::new();
+        for arm in &select.arms {
+            let Some(receiver) = self.expression(arm.receiver.as_ref()) else {
+                self.span = outer;
+                return Flow::Returned;
+            };
+            receivers.push(receiver);
+        }
+        let selected = self.emit(Op::Select(receivers));
+        let zero = self.emit(Op::Constant(Constant::Integer(0)));
+        let index = self.emit(Op::Index(selected, zero));
+        let one = self.emit(Op::Constant(Constant::Integer(1)));
+        let value = self.emit(Op::Index(selected, one));
+
+        let mut ends = Vec::new();
+        for (i, arm) in select.arms.iter().enumerate() {
+            if i + 1 < select.arms.len() {
+                let position = self.emit(Op::Constant(Constant::Integer(i as i64)));
+                let test = self.emit(Op::Infix(InfixOperator::EQUAL, index, position));
+                let from = self.current();
+                let body = self.block(vec![from]);
+                let next = self.block(vec![from]);
+                self.terminate(Terminator::Branch(test, body, next));
+                self.current = Some(body);
+                ends.push(self.arm(arm, value));
+                self.current = Some(next);
+            } else {
+                ends.push(self.arm(arm, value));
+            }
+            self.span = select.span;
+        }
+        let flow = self.join(ends);
+        self.span = outer;
+        flow
+    }
+
+    /// Lowers the body of a select arm that received `value`, giving where it ends.
+    fn arm(&mut self, arm: &SelectArm, value: Value) -> (Option<BlockId>, Flow) {
+        if let Some(binding) = &arm.binding {
+            self.bind(&binding.to_string(), value);
+        }
+        let flow = self.statements(&arm.body.statements);
+        (self.current, flow)
+    }
+
+    /// Joins the branches ending in `ends`, or `None` for those that left the function,
+    /// into a new block, leaving control there. The value is a phi of those of the branches.
+    fn join(&mut self, ends: Vec<(Option<BlockId>, Flow)>) -> Flow {
+        let arms = ends.into_iter().filter_map(|(end, flow)| end.map(|end| (end, flow))).collect::<Vec<_>>();
+        if arms.is_empty() {
+            return Flow::Returned;
+        }
+        let join = self.block(arms.iter().map(|(end, _)| *end).collect());
+        let flow = if arms.iter().all(|(_, flow)| *flow == Flow::Null) {
+            Flow::Null
+        } else {
+            let mut incoming = Vec::new();
+            for (end, flow) in &arms {
+                let value = match flow {
+                    Flow::Value(value) => *value,
+                    _ => self.emit_in(*end, Op::Constant(Constant::Null)),
+                };
+                incoming.push((*end, value));
+            }
+            if incoming.iter().all(|(_, value)| *value == incoming[0].1) {
+                Flow::Value(incoming[0].1)
+            } else {
+                let result = self.value();
+                self.blocks[join.0].phis.push(Phi { result, incoming });
+                Flow::Value(result)
+            }
+        };
+        for (end, _) in &arms {
+            self.blocks[end.0].terminator = Some(Terminator::Jump(join));
+        }
+        self.current = Some(join);
+        flow
+    }
+
+    /// The value of `expression`, or `None` if control leaves the function while computing it.
+    fn expression(&mut self, expression: &dyn Expression) -> Option<Value> {
+        let outer = self.span;
+        if let Some(span) = span_of(expression) {
+            self.span = span;
+        }
+        let value = self.expression_value(expression);
+        self.span = outer;
+        value
+    }
+
+    fn expression_value(&mut self, expression: &dyn Expression) -> Option<Value> {
+        let any = expression.as_any();
+        let op = if let Some(integer) = any.downcast_ref::<IntegerLiteral>() {
+            Op::Constant(Constant::Integer(integer.value))
+        } else if let Some(float) = any.downcast_ref::<FloatLiteral>() {
+            Op::Constant(Constant::Float(float.value))
+        } else if let Some(string) = any.downcast_ref::<StringLiteral>() {
+            Op::Constant(Constant::String(string.value.clone()))
+        } else if let Some(boolean) = any.downcast_ref::<BooleanLiteral>() {
+            Op::Constant(Constant::Boolean(boolean.value))
+        } else if let Some(identifier) = any.downcast_ref::<IdentifierLiteral>() {
+            return Some(self.read(&identifier.to_string()));
+        } else if let Some(prefix) = any.downcast_ref::<PrefixExpression>() {
+            let right = self.expression(prefix.right.as_ref())?;
+            Op::Prefix(prefix.operator.clone(), right)
+        } else if let Some(infix) = any.downcast_ref::<InfixExpression>() {
+            let left = self.expression(infix.left.as_ref())?;
+            let right = self.expression(infix.right.as_ref())?;
+            Op::Infix(infix.operator.clone(), left, right)
+        } else if let Some(if_expression) = any.downcast_ref::<IfExpression>() {
+            let flow = self.if_expression(if_expression);
+            return self.flow_value(flow);
+        } else if let Some(select) = any.downcast_ref::<SelectExpression>() {
+            let flow = self.select_expression(select);
+            return self.flow_value(flow);
+        } else if let Some(function) = any.downcast_ref::<FunctionLiteral>() {
+            return Some(self.closure("<anonymous fn>", function));
+        } else if let Some(call) = any.downcast_ref::<CallExpression>() {
+            let function = self.expression(call.function.as_ref())?;
+            let mut arguments = Vec::new();
+            for argument in &call.arguments {
+                arguments.push(self.expression(argument.as_ref())?);
+            }
+            Op::Call(function, arguments)
+        } else if let Some(spawn) = any.downcast_ref::<SpawnExpression>() {
+            let function = self.expression(spawn.call.function.as_ref())?;
+            let mut arguments = Vec::new();
+            for argument in &spawn.call.arguments {
+                arguments.push(self.expression(argument.as_ref())?);
+            }
+            Op::Spawn(function, arguments)
+        } else if let Some(await_expression) = any.downcast_ref::<AwaitExpression>() {
+            let future = self.expression(await_expression.value.as_ref())?;
+            Op::Await(future)
+        } else if let Some(array) = any.downcast_ref::<ArrayLiteral>() {
+            let mut elements = Vec::new();
+            for element in &array.elements {
+                elements.push(self.expression(element.as_ref())?);
+            }
+            Op::Array(elements)
+        } else if let Some(hash) = any.downcast_ref::<HashLiteral>() {
+            let mut pairs = Vec::new();
+            for (key, value) in &hash.pairs {
+                let key = self.expression(key.as_ref())?;
+                pairs.push((key, self.expression(value.as_ref())?));
+            }
+            Op::Hash(pairs)
+        } else if let Some(index) = any.downcast_ref::<IndexExpression>() {
+            let left = self.expression(index.left.as_ref())?;
+            let position = self.expression(index.index.as_ref())?;
+            Op::Index(left, position)
+        } else if let Some(path) = any.downcast_ref::<PathExpression>() {
+            let module = self.read(&path.module.to_string());
+            Op::Member(module, path.member.to_string())
+        } else {
+            Op::Constant(Constant::Null)
+        };
+        Some(self.emit(op))
+    }
+
+    /// The value of an expression that ended with `flow`.
+    fn flow_value(&mut self, flow: Flow) -> Option<Value> {
+        match flow {
+            Flow::Value(value) => Some(value),
+            Flow::Null => Some(self.emit(Op::Constant(Constant::Null))),
+            Flow::Returned => None,
+        }
+    }
+
+    fn closure(&mut self, name: &str, function: &FunctionLiteral) -> Value {
+        let id = self.lowerer.function(name, &function.parameters, &function.body.statements, HashSet::new(), function.span);
+        self.emit(if function.is_async { Op::AsyncClosure(id) } else { Op::Closure(id) })
+    }
+}
+
+fn span_of(expression: &dyn Expression) -> Option<Span> {
+    let any = expression.as_any();
+    if let Some(identifier) = any.downcast_ref::<IdentifierLiteral>() {
+        Some(identifier.span)
+    } else if let Some(prefix) = any.downcast_ref::<PrefixExpression>() {
+        Some(prefix.span)
+    } else if let Some(infix) = any.downcast_ref::<InfixExpression>() {
+        Some(infix.span)
+    } else if let Some(if_expression) = any.downcast_ref::<IfExpression>() {
+        Some(if_expression.span)
+    } else if let Some(function) = any.downcast_ref::<FunctionLiteral>() {
+        Some(function.span)
+    } else if let Some(call) = any.downcast_ref::<CallExpression>() {
+        Some(call.span)
+    } else if let Some(array) = any.downcast_ref::<ArrayLiteral>() {
+        Some(array.span)
+    } else if let Some(hash) = any.downcast_ref::<HashLiteral>() {
+        Some(hash.span)
+    } else if let Some(index) = any.downcast_ref::<IndexExpression>() {
+        Some(index.span)
+    } else if let Some(spawn) = any.downcast_ref::<SpawnExpression>() {
+        Some(spawn.span)
+    } else if let Some(select) = any.downcast_ref::<SelectExpression>() {
+        Some(select.span)
+    } else if let Some(await_expression) = any.downcast_ref::<AwaitExpression>() {
+        Some(await_expression.span)
+    } else {
+        any.downcast_ref::<PathExpression>().map(|path| path.span)
+    }
+}
+
+/// Adds the names used inside the function literals in `statements` to `captured`, or
+/// every name used in them if they are `inside` one. Names a nested function binds
+/// itself are included too, which only keeps more variables in the environment.
+fn captured_in_statements(statements: &[Box<dyn Statement>], inside: bool, captured: &mut HashSet<String>) {
+    for statement in statements {
+        let any = statement.as_any();
+        if let Some(statement) = any.downcast_ref::<LetStatement>() {
+            captured_in_expression(statement.value.as_ref(), inside, captured);
+        } else if let Some(statement) = any.downcast_ref::<LetTupleStatement>() {
+            captured_in_expression(statement.value.as_ref(), inside, captured);
+        } else if let Some(statement) = any.downcast_ref::<ReturnStatement>() {
+            captured_in_expression(statement.expression.as_ref(), inside, captured);
+        } else if let Some(statement) = any.downcast_ref::<ExpressionStatement>() {
+            captured_in_expression(statement.expression.as_ref(), inside, captured);
+        } else if let Some(block) = any.downcast_ref::<BlockStatement>() {
+            captured_in_statements(&block.statements, inside, captured);
+        } else if let Some(use_statement) = any.downcast_ref::<UseStatement>() {
+            if inside {
+                captured.insert(use_statement.module.to_string());
+            }
+        }
+    }
+}
+
+fn captured_in_expression(expression: &dyn Expression, inside: bool, captured: &mut HashSet<String>) {
+    let any = expression.as_any();
+    if let Some(identifier) = any.downcast_ref::<IdentifierLiteral>() {
+        if inside {
+            captured.insert(identifier.to_string());
+        }
+    } else if let Some(prefix) = any.downcast_ref::<PrefixExpression>() {
+        captured_in_expression(prefix.right.as_ref(), inside, captured);
+    } else if let Some(infix) = any.downcast_ref::<InfixExpression>() {
+        captured_in_expression(infix.left.as_ref(), inside, captured);
+        captured_in_expression(infix.right.as_ref(), inside, captured);
+    } else if let Some(if_expression) = any.downcast_ref::<IfExpression>() {
+        captured_in_expression(if_expression.condition.as_ref(), inside, captured);
+        captured_in_statements(&if_expression.consequence.statements, inside, captured);
+        if let Some(alternative) = &if_expression.alternative {
+            captured_in_statements(&alternative.statements, inside, captured);
+        }
+    } else if let Some(function) = any.downcast_ref::<FunctionLiteral>() {
+        captured_in_statements(&function.body.statements, true, captured);
+    } else if let Some(call) = any.downcast_ref::<CallExpression>() {
+        captured_in_expression(call.function.as_ref(), inside, captured);
+        for argument in &call.arguments {
+            captured_in_expression(argument.as_ref(), inside, captured);
+        }
+    } else if let Some(array) = any.downcast_ref::<ArrayLiteral>() {
+        for element in &array.elements {
+            captured_in_expression(element.as_ref(), inside, captured);
+        }
+    } else if let Some(hash) = any.downcast_ref::<HashLiteral>() {
+        for (key, value) in &hash.pairs {
+            captured_in_expression(key.as_ref(), inside, captured);
+            captured_in_expression(value.as_ref(), inside, captured);
+        }
+    } else if let Some(index) = any.downcast_ref::<IndexExpression>() {
+        captured_in_expression(index.left.as_ref(), inside, captured);
+        captured_in_expression(index.index.as_ref(), inside, captured);
+    } else if let Some(spawn) = any.downcast_ref::<SpawnExpression>() {
+        captured_in_expression(&spawn.call, inside, captured);
+    } else if let Some(await_expression) = any.downcast_ref::<AwaitExpression>() {
+        captured_in_expression(await_expression.value.as_ref(), inside, captured);
+    } else if let Some(select) = any.downcast_ref::<SelectExpression>() {
+        for arm in &select.arms {
+            captured_in_expression(arm.receiver.as_ref(), inside, captured);
+            captured_in_statements(&arm.body.statements, inside, captured);
+        }
+    } else if let Some(path) = any.downcast_ref::<PathExpression>() {
+        if inside {
+            captured.insert(path.module.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./lower_tests.rs"]
+mod tests;
@@ -0,0 +1,329 @@
+use std::path::PathBuf;
+
+use super::*;
+
+use crate::{generator::Generator, lexer::Lexer, parser::Parser};
+
+use test_case::test_case;
+
+fn parse(source: &str) -> Option<Program> {
+    let mut lexer = Lexer::new(source.to_string());
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+    parser.errors.is_empty().then_some(program)
+}
+
+/// The verified IR of `source`.
+fn ir(source: &str) -> String {
+    let module = lower(&parse(source).expect("source parses"));
+    assert_eq!(verify(&module), Ok(()), "{}", module);
+    module.to_string()
+}
+
+#[test_case("[1, \"s\", 2.5]; {true: 0}[0]; -x; !x", "fn @0 <main>() {
+b0:
+    %0 = const 1
+    %1 = const \"s\"
+    %2 = const 2.5
+    %3 = array [%0, %1, %2]
+    %4 = const true
+    %5 = const 0
+    %6 = hash {%4: %5}
+    %7 = const 0
+    %8 = index %6, %7
+    %9 = load x
+    %10 = neg %9
+    %11 = load x
+    %12 = not %11
+    return %12
+}
+"; "expressions")]
+#[test_case("let x = 1; let y = x * f(x); y", "fn @0 <main>() {
+b0:
+    %0 = const 1
+    %1 = load f
+    %2 = call %1(%0)
+    %3 = mul %0, %2
+    return %3
+}
+"; "variables are values")]
+#[test_case("let t = spawn f(1, 2); join(t)", "fn @0 <main>() {
+b0:
+    %0 = load f
+    %1 = const 1
+    %2 = const 2
+    %3 = spawn %0(%1, %2)
+    %4 = load join
+    %5 = call %4(%3)
+    return %5
+}
+"; "spawn")]
+#[test_case("let f = async fn(x) { x }; await f(1)", "fn @0 <main>() {
+b0:
+    %0 = async closure @1
+    %1 = const 1
+    %2 = call %0(%1)
+    %3 = await %2
+    return %3
+}
+
+fn @1 f(%0) {
+b0:
+    return %0
+}
+"; "async function")]
+#[test_case("let (tx, rx) = channel(); let x = select { rx as v => v, timeout(5) => 0 }; x", "fn @0 <main>() {
+b0:
+    %0 = load channel
+    %1 = call %0()
+    %2 = const 0
+    %3 = index %1, %2
+    %4 = const 1
+    %5 = index %1, %4
+    %6 = load timeout
+    %7 = const 5
+    %8 = call %6(%7)
+    %9 = select [%5, %8]
+    %10 = const 0
+    %11 = index %9, %10
+    %12 = const 1
+    %13 = index %9, %12
+    %14 = const 0
+    %15 = eq %11, %14
+    branch %15, b1, b2
+b1:
+    jump b3
+b2:
+    %16 = const 0
+    jump b3
+b3:
+    %17 = phi [b1: %13], [b2: %16]
+    return %17
+}
+"; "select arms meet in a phi")]
+#[test_case("let x = if (c) { 1 } else { 2 }; x + 1", "fn @0 <main>() {
+b0:
+    %0 = load c
+    branch %0, b1, b2
+b1:
+    %1 = const 1
+    jump b3
+b2:
+    %2 = const 2
+    jump b3
+b3:
+    %3 = phi [b1: %1], [b2: %2]
+    %4 = const 1
+    %5 = add %3, %4
+    return %5
+}
+"; "branches meet in a phi")]
+#[test_case("let x = if (c) { 1 }; x", "fn @0 <main>() {
+b0:
+    %0 = load c
+    branch %0, b1, b2
+b1:
+    %1 = const 1
+    jump b3
+b2:
+    %2 = const null
+    jump b3
+b3:
+    %3 = phi [b1: %1], [b2: %2]
+    return %3
+}
+"; "missing else is null")]
+#[test_case("let x = 1; if (c) { let x = 2; } x", "fn @0 <main>() {
+b0:
+    %0 = const 1
+    %1 = load c
+    branch %1, b1, b2
+b1:
+    %2 = const 2
+    jump b3
+b2:
+    jump b3
+b3:
+    %3 = phi [b1: %2], [b2: %0]
+    return %3
+}
+"; "rebinding in a branch")]
+#[test_case("return 1; 2", "fn @0 <main>() {
+b0:
+    %0 = const 1
+    return %0
+}
+"; "code after return is dropped")]
+#[test_case("import \"m.ks\" as m; use m::{a}; m::b + a", "fn @0 <main>() {
+b0:
+    %0 = import \"m.ks\"
+    %1 = member %0, a
+    %2 = member %0, b
+    %3 = add %2, %1
+    return %3
+}
+"; "modules")]
+#[test_case("pub let a = 1; let b = 2; a + b", "fn @0 <main>() {
+b0:
+    %0 = const 1
+    store a, %0
+    %1 = const 2
+    %2 = load a
+    %3 = add %2, %1
+    return %3
+}
+"; "exported variables are stored")]
+#[test_case("test \"t\" { 1 }", "fn @0 <main>() {
+b0:
+    %0 = const null
+    return %0
+}
+"; "tests are skipped")]
+fn test_lower_top_level(source: &str, expected: &str) {
+    assert_eq!(ir(source), expected);
+}
+
+#[test]
+fn test_lower_functions() {
+    let source = "let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) };
+let sign = fn(n) { if (n < 0) { return -1; } else { return 1; } };
+fn(x) { x }(1)";
+    assert_eq!(ir(source), "fn @0 <main>() {
+b0:
+    %0 = closure @1
+    store fib, %0
+    %1 = closure @2
+    %2 = closure @3
+    %3 = const 1
+    %4 = call %2(%3)
+    return %4
+}
+
+fn @1 fib(%0) {
+b0:
+    %1 = const 2
+    %2 = lt %0, %1
+    branch %2, b1, b2
+b1:
+    return %0
+b2:
+    jump b3
+b3:
+    %3 = load fib
+    %4 = const 1
+    %5 = sub %0, %4
+    %6 = call %3(%5)
+    %7 = load fib
+    %8 = const 2
+    %9 = sub %0, %8
+    %10 = call %7(%9)
+    %11 = add %6, %10
+    return %11
+}
+
+fn @2 sign(%0) {
+b0:
+    %1 = const 0
+    %2 = lt %0, %1
+    branch %2, b1, b2
+b1:
+    %3 = const 1
+    %4 = neg %3
+    return %4
+b2:
+    %5 = const 1
+    return %5
+}
+
+fn @3 <anonymous fn>(%0) {
+b0:
+    return %0
+}
+");
+}
+
+#[test]
+fn test_captured_variables_live_in_the_environment() {
+    let source = "let y = 1; let add = fn(x) { fn(z) { x + y + z } }; let y = 2; add(1)(2)";
+    assert_eq!(ir(source), "fn @0 <main>() {
+b0:
+    %0 = const 1
+    store y, %0
+    %1 = closure @1
+    %2 = const 2
+    store y, %2
+    %3 = const 1
+    %4 = call %1(%3)
+    %5 = const 2
+    %6 = call %4(%5)
+    return %6
+}
+
+fn @1 add(%0) {
+b0:
+    store x, %0
+    %1 = closure @2
+    return %1
+}
+
+fn @2 <anonymous fn>(%0) {
+b0:
+    %1 = load x
+    %2 = load y
+    %3 = add %1, %2
+    %4 = add %3, %0
+    return %4
+}
+");
+}
+
+#[test]
+fn test_variables_bound_on_some_paths_live_in_the_environment() {
+    let source = "let f = fn(c) { let y = z; if (c) { let z = 1; } z }";
+    assert_eq!(ir(source), "fn @0 <main>() {
+b0:
+    %0 = closure @1
+    %1 = const null
+    return %1
+}
+
+fn @1 f(%0) {
+b0:
+    %1 = load z
+    branch %0, b1, b2
+b1:
+    %2 = const 1
+    store z, %2
+    jump b3
+b2:
+    jump b3
+b3:
+    %3 = load z
+    return %3
+}
+");
+}
+
+#[test]
+fn test_programs_lower_to_valid_ir() {
+    let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("programs");
+    for entry in std::fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|extension| extension == "ks") {
+            if let Some(program) = parse(&std::fs::read_to_string(&path).unwrap()) {
+                assert_eq!(verify(&lower(&program)), Ok(()), "{}", path.display());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_generated_programs_lower_to_valid_ir() {
+    for seed in 0..500 {
+        let source = Generator::new(seed, 4).program();
+        if let Some(program) = parse(&source) {
+            let module = lower(&program);
+            assert_eq!(verify(&module), Ok(()), "seed {}\n{}\n{}", seed, source, module);
+        }
+    }
+}
@@ -0,0 +1,450 @@
+//! An intermediate representation of programs as control-flow graphs in SSA form, for
+//! backends to compile from instead of the AST. [`lower`] builds it from a program that
+//! parsed and checked cleanly, [`verify`] checks that it is well formed, and the
+//! [`passes`] optimize it. [`interpret`] runs it, for `keynes run --ir`, and
+//! `keynes build --emit=ir` writes it out.
+//!
+//! Values are dynamically typed, like the evaluator's objects. A variable that nested
+//! functions capture, or that other modules can import, lives in the environment, where
+//! `load` and `store` read and bind it; every other variable becomes SSA values, with phi
+//! nodes where branches binding it differently meet. `load` also reads globals, builtins
+//! and the variables of enclosing functions.
+//!
+//! Each function prints as its blocks, entry first:
+//!
+//! ```text
+//! fn @1 fib(%0) {
+//! b0:
+//!     %1 = const 2
+//!     %2 = lt %0, %1
+//!     branch %2, b1, b2
+//! ...
+//! }
+//! ```
+
+pub mod interpret;
+pub mod lower;
+pub mod passes;
+pub mod verify;
+
+pub use interpret::interpret;
+pub use lower::{lower, lower_globals};
+pub use verify::verify;
+
+use std::fmt::Display;
+
+use crate::{
+    ast::expressions::{InfixOperator, PrefixOperator},
+    lexer::{quote_string, Span},
+    object::{Float, Object},
+};
+
+/// A lowered program. The first function is the top level of the program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub functions: Vec<Function>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FunctionId(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub usize);
+
+/// An SSA value, numbered from zero within its function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Value(pub usize);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    /// Name of the binding the function literal was assigned to, as in stack traces.
+    pub name: String,
+    pub parameters: Vec<Value>,
+    /// The entry block comes first.
+    pub blocks: Vec<Block>,
+    /// How many values the function defines.
+    pub values: usize,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub phis: Vec<Phi>,
+    pub instructions: Vec<Instruction>,
+    pub terminator: Terminator,
+}
+
+/// Picks the value coming from whichever predecessor control arrived from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Phi {
+    pub result: Value,
+    pub incoming: Vec<(BlockId, Value)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    /// `None` for instructions run only for their effect.
+    pub result: Option<Value>,
+    pub op: Op,
+    /// Where errors raised by the instruction are reported.
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Null,
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    String(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    Constant(Constant),
+    Prefix(PrefixOperator, Value),
+    Infix(InfixOperator, Value, Value),
+    Array(Vec<Value>),
+    Hash(Vec<(Value, Value)>),
+    Index(Value, Value),
+    Call(Value, Vec<Value>),
+    /// Calls the function in a task of its own, giving the task.
+    Spawn(Value, Vec<Value>),
+    /// Waits for one of the receivers to have a value, giving `[index, value]` for the
+    /// first receiver that has one.
+    Select(Vec<Value>),
+    /// Creates the function with this id, closing over the current environment.
+    Closure(FunctionId),
+    /// Like `closure`, for an `async fn`: calling it gives a future of the result.
+    AsyncClosure(FunctionId),
+    /// Waits for a future to resolve, giving its value.
+    Await(Value),
+    /// Reads a variable from the environment, then the builtins.
+    Load(String),
+    /// Binds a variable in the environment of the current call.
+    Store(String, Value),
+    /// Loads the module at a path, relative to the current file.
+    Import(String),
+    /// Reads a member of a module.
+    Member(Value, String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    Jump(BlockId),
+    /// Goes to the first block if the value is truthy, and to the second otherwise.
+    Branch(Value, BlockId, BlockId),
+    Return(Value),
+}
+
+impl Op {
+    /// The values the operation reads.
+    pub fn operands(&self) -> Vec<Value> {
+        match self {
+            Op::Constant(_) | Op::Closure(_) | Op::AsyncClosure(_) | Op::Load(_) | Op::Import(_) => vec![],
+            Op::Prefix(_, value) | Op::Await(value) | Op::Store(_, value) | Op::Member(value, _) => vec![*value],
+            Op::Infix(_, left, right) | Op::Index(left, right) => vec![*left, *right],
+            Op::Array(elements) | Op::Select(elements) => elements.clone(),
+            Op::Hash(pairs) => pairs.iter().flat_map(|(key, value)| [*key, *value]).collect(),
+            Op::Call(function, arguments) | Op::Spawn(function, arguments) => {
+                std::iter::once(*function).chain(arguments.iter().copied()).collect()
+            },
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Op::Constant(_) | Op::Closure(_) | Op::AsyncClosure(_) | Op::Load(_) | Op::Import(_) => vec![],
+            Op::Prefix(_, value) | Op::Await(value) | Op::Store(_, value) | Op::Member(value, _) => vec![value],
+            Op::Infix(_, left, right) | Op::Index(left, right) => vec![left, right],
+            Op::Array(elements) | Op::Select(elements) => elements.iter_mut().collect(),
+            Op::Hash(pairs) => pairs.iter_mut().flat_map(|(key, value)| [key, value]).collect(),
+            Op::Call(function, arguments) | Op::Spawn(function, arguments) => {
+                std::iter::once(function).chain(arguments.iter_mut()).collect()
+            },
+        }
+    }
+
+    /// Whether the operation can neither fail nor change anything, so that it can be
+    /// removed when its result is unused, or run earlier than written.
+    pub fn is_pure(&self) -> bool {
+        matches!(
+            self,
+            Op::Constant(_) | Op::Array(_) | Op::Prefix(PrefixOperator::BANG, _) | Op::Closure(_) | Op::AsyncClosure(_)
+        )
+    }
+}
+
+impl Terminator {
+    /// The blocks control can go to next.
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch(_, then, otherwise) => vec![*then, *otherwise],
+            Terminator::Return(_) => vec![],
+        }
+    }
+
+    pub fn successors_mut(&mut self) -> Vec<&mut BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![target],
+            Terminator::Branch(_, then, otherwise) => vec![then, otherwise],
+            Terminator::Return(_) => vec![],
+        }
+    }
+
+    pub fn operands(&self) -> Vec<Value> {
+        match self {
+            Terminator::Jump(_) => vec![],
+            Terminator::Branch(value, _, _) | Terminator::Return(value) => vec![*value],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Terminator::Jump(_) => vec![],
+            Terminator::Branch(value, _, _) | Terminator::Return(value) => vec![value],
+        }
+    }
+}
+
+impl Function {
+    /// The predecessors of every block, in the order of the blocks that jump to them.
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut predecessors = vec![Vec::new(); self.blocks.len()];
+        for (i, block) in self.blocks.iter().enumerate() {
+            for successor in block.terminator.successors() {
+                if let Some(predecessors) = predecessors.get_mut(successor.0) {
+                    predecessors.push(BlockId(i));
+                }
+            }
+        }
+        predecessors
+    }
+
+    /// For each block, which blocks are on every path from the entry to it. Unreachable
+    /// blocks are not even dominated by themselves.
+    pub fn dominators(&self) -> Vec<Vec<bool>> {
+        let count = self.blocks.len();
+        let predecessors = self.predecessors();
+        let mut reachable = vec![false; count];
+        let mut stack = vec![0];
+        while let Some(block) = stack.pop() {
+            if block < count && !std::mem::replace(&mut reachable[block], true) {
+                stack.extend(self.blocks[block].terminator.successors().iter().map(|successor| successor.0));
+            }
+        }
+
+        let mut dominators = (0..count)
+            .map(|block| match (block, reachable[block]) {
+                (0, _) => (0..count).map(|other| other == 0).collect(),
+                (_, true) => reachable.clone(),
+                (_, false) => vec![false; count],
+            })
+            .collect::<Vec<Vec<bool>>>();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in (1..count).filter(|block| reachable[*block]) {
+                let mut next = reachable.clone();
+                for predecessor in predecessors[block].iter().filter(|predecessor| reachable[predecessor.0]) {
+                    next.iter_mut().zip(&dominators[predecessor.0]).for_each(|(next, dominates)| *next &= *dominates);
+                }
+                next[block] = true;
+                if next != dominators[block] {
+                    dominators[block] = next;
+                    changed = true;
+                }
+            }
+        }
+        dominators
+    }
+
+    /// Every value read by a phi node, instruction or terminator.
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        let mut operands = Vec::new();
+        for block in &mut self.blocks {
+            for phi in &mut block.phis {
+                operands.extend(phi.incoming.iter_mut().map(|(_, value)| value));
+            }
+            for instruction in &mut block.instructions {
+                operands.extend(instruction.op.operands_mut());
+            }
+            operands.extend(block.terminator.operands_mut());
+        }
+        operands
+    }
+
+    /// Makes everything that reads `from` read `to` instead.
+    pub fn replace_uses(&mut self, from: Value, to: Value) {
+        for operand in self.operands_mut() {
+            if *operand == from {
+                *operand = to;
+            }
+        }
+    }
+
+    /// How many times each value is read.
+    pub fn uses(&self) -> Vec<usize> {
+        let mut uses = vec![0; self.values];
+        for block in &self.blocks {
+            let phis = block.phis.iter().flat_map(|phi| phi.incoming.iter().map(|(_, value)| *value));
+            let instructions = block.instructions.iter().flat_map(|instruction| instruction.op.operands());
+            for operand in phis.chain(instructions).chain(block.terminator.operands()) {
+                if let Some(count) = uses.get_mut(operand.0) {
+                    *count += 1;
+                }
+            }
+        }
+        uses
+    }
+
+    /// Numbers the values again in the order they are defined, dropping unused numbers.
+    pub fn renumber(&mut self) {
+        let mut numbers = vec![None; self.values];
+        let mut next = 0;
+        let mut number = |value: &mut Value| {
+            let renumbered = *numbers[value.0].get_or_insert_with(|| {
+                next += 1;
+                Value(next - 1)
+            });
+            *value = renumbered;
+        };
+        for parameter in &mut self.parameters {
+            number(parameter);
+        }
+        for block in &mut self.blocks {
+            for phi in &mut block.phis {
+                number(&mut phi.result);
+            }
+            for instruction in &mut block.instructions {
+                if let Some(result) = &mut instruction.result {
+                    number(result);
+                }
+            }
+        }
+        for operand in self.operands_mut() {
+            *operand = numbers.get(operand.0).copied().flatten().unwrap_or(*operand);
+        }
+        self.values = next;
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl Display for BlockId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "b{}", self.0)
+    }
+}
+
+impl Display for FunctionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "@{}", self.0)
+    }
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Constant::Null => write!(f, "null"),
+            Constant::Integer(value) => write!(f, "{}", value),
+            Constant::Float(value) => write!(f, "{}", Object::Float(Float::F64(*value))),
+            Constant::Boolean(value) => write!(f, "{}", value),
+            Constant::String(value) => write!(f, "{}", quote_string(value)),
+        }
+    }
+}
+
+/// Joins values with commas.
+fn list(values: &[Value]) -> String {
+    values.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+impl Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Op::Constant(constant) => write!(f, "const {}", constant),
+            Op::Prefix(PrefixOperator::BANG, value) => write!(f, "not {}", value),
+            Op::Prefix(PrefixOperator::MINUS, value) => write!(f, "neg {}", value),
+            Op::Infix(operator, left, right) => {
+                let mnemonic = match operator {
+                    InfixOperator::PLUS => "add",
+                    InfixOperator::MINUS => "sub",
+                    InfixOperator::MULTIPLY => "mul",
+                    InfixOperator::DIVIDE => "div",
+                    InfixOperator::EQUAL => "eq",
+                    InfixOperator::NOT_EQUAL => "ne",
+                    InfixOperator::LESS_THAN => "lt",
+                    InfixOperator::LESS_THAN_EQUAL => "le",
+                    InfixOperator::GREATER_THAN => "gt",
+                    InfixOperator::GREATER_THAN_EQUAL => "ge",
+                };
+                write!(f, "{} {}, {}", mnemonic, left, right)
+            },
+            Op::Array(elements) => write!(f, "array [{}]", list(elements)),
+            Op::Hash(pairs) => {
+                let pairs = pairs.iter().map(|(key, value)| format!("{}: {}", key, value)).collect::<Vec<_>>();
+                write!(f, "hash {{{}}}", pairs.join(", "))
+            },
+            Op::Index(left, index) => write!(f, "index {}, {}", left, index),
+            Op::Call(function, arguments) => write!(f, "call {}({})", function, list(arguments)),
+            Op::Spawn(function, arguments) => write!(f, "spawn {}({})", function, list(arguments)),
+            Op::Select(receivers) => write!(f, "select [{}]", list(receivers)),
+            Op::Closure(function) => write!(f, "closure {}", function),
+            Op::AsyncClosure(function) => write!(f, "async closure {}", function),
+            Op::Await(future) => write!(f, "await {}", future),
+            Op::Load(name) => write!(f, "load {}", name),
+            Op::Store(name, value) => write!(f, "store {}, {}", name, value),
+            Op::Import(path) => write!(f, "import {}", quote_string(path)),
+            Op::Member(module, name) => write!(f, "member {}, {}", module, name),
+        }
+    }
+}
+
+impl Display for Terminator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Terminator::Jump(target) => write!(f, "jump {}", target),
+            Terminator::Branch(condition, then, otherwise) => write!(f, "branch {}, {}, {}", condition, then, otherwise),
+            Terminator::Return(value) => write!(f, "return {}", value),
+        }
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}({}) {{", self.name, list(&self.parameters))?;
+        for (i, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "{}:", BlockId(i))?;
+            for phi in &block.phis {
+                let incoming = phi.incoming.iter().map(|(block, value)| format!("[{}: {}]", block, value)).collect::<Vec<_>>();
+                writeln!(f, "    {} = phi {}", phi.result, incoming.join(", "))?;
+            }
+            for instruction in &block.instructions {
+                match instruction.result {
+                    Some(result) => writeln!(f, "    {} = {}", result, instruction.op)?,
+                    None => writeln!(f, "    {}", instruction.op)?,
+                }
+            }
+            writeln!(f, "    {}", block.terminator)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl Display for Module {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, function) in self.functions.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "fn {} {}", FunctionId(i), function)?;
+        }
+        Ok(())
+    }
+}
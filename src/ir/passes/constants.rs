@@ -0,0 +1,90 @@
+//! Constant propagation: computes prefix and infix operations on constants, the way the
+//! evaluator would, and turns branches on constants into jumps, dropping the blocks only
+//! the other side reached. Operations that would fail, like dividing by zero, stay to
+//! raise their error at run time.
+
+use super::*;
+use crate::{evaluator::Evaluator, modules::ModuleLoader, object::Integer};
+
+/// Propagates constants through `function` until nothing changes.
+pub fn propagate_constants(function: &mut Function) {
+    let evaluator = Evaluator::with_loader("<optimizer>", ModuleLoader::new());
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let mut constants = vec![None; function.values];
+        for block in &function.blocks {
+            for instruction in &block.instructions {
+                if let (Some(result), Op::Constant(constant)) = (instruction.result, &instruction.op) {
+                    constants[result.0] = Some(constant.clone());
+                }
+            }
+        }
+        let object = |value: &Value| constants.get(value.0).cloned().flatten().map(object);
+
+        for block in &mut function.blocks {
+            for instruction in &mut block.instructions {
+                let folded = match &instruction.op {
+                    Op::Prefix(operator, right) => object(right).and_then(|right| evaluator.eval_prefix_expression(operator, right).ok()),
+                    Op::Infix(operator, left, right) => object(left)
+                        .zip(object(right))
+                        .and_then(|(left, right)| evaluator.eval_infix_expression(operator, left, right).ok()),
+                    _ => None,
+                };
+                if let Some(constant) = folded.and_then(constant) {
+                    instruction.op = Op::Constant(constant);
+                    changed = true;
+                }
+            }
+
+            // A phi picking the same constant from every predecessor is that constant.
+            let mut i = 0;
+            while i < block.phis.len() {
+                let mut incoming = block.phis[i].incoming.iter().map(|(_, value)| constants.get(value.0).cloned().flatten());
+                match incoming.next().flatten().filter(|first| incoming.all(|constant| constant.as_ref() == Some(first))) {
+                    Some(constant) => {
+                        let phi = block.phis.remove(i);
+                        let span = block.instructions.first().map(|instruction| instruction.span).unwrap_or_default();
+                        block.instructions.insert(0, Instruction { result: Some(phi.result), op: Op::Constant(constant), span });
+                        changed = true;
+                    },
+                    None => i += 1,
+                }
+            }
+
+            if let Terminator::Branch(condition, then, otherwise) = block.terminator {
+                if let Some(condition) = object(&condition) {
+                    block.terminator = Terminator::Jump(if condition.is_truthy() { then } else { otherwise });
+                    changed = true;
+                }
+            }
+        }
+        clean_up(function);
+    }
+}
+
+fn object(constant: Constant) -> Object {
+    match constant {
+        Constant::Null => Object::Null,
+        Constant::Integer(value) => Object::Integer(Integer::I64(value)),
+        Constant::Float(value) => Object::Float(Float::F64(value)),
+        Constant::Boolean(value) => Object::Boolean(value),
+        Constant::String(value) => Object::String(value),
+    }
+}
+
+/// The constant for `object`, if the IR has one for its type.
+fn constant(object: Object) -> Option<Constant> {
+    match object {
+        Object::Null => Some(Constant::Null),
+        Object::Integer(Integer::I64(value)) => Some(Constant::Integer(value)),
+        Object::Float(Float::F64(value)) => Some(Constant::Float(value)),
+        Object::Boolean(value) => Some(Constant::Boolean(value)),
+        Object::String(value) => Some(Constant::String(value)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+#[path = "./constants_tests.rs"]
+mod tests;
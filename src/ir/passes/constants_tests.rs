@@ -0,0 +1,137 @@
+use super::*;
+
+use test_case::test_case;
+
+#[test_case("let x = 2 * 3; let y = if (x > 5) { x + 1 } else { x / 0 }; y - 1", "fn @0 <main>() {
+b0:
+    %0 = const 2
+    %1 = const 3
+    %2 = mul %0, %1
+    %3 = const 5
+    %4 = gt %2, %3
+    branch %4, b1, b2
+b1:
+    %5 = const 1
+    %6 = add %2, %5
+    jump b3
+b2:
+    %7 = const 0
+    %8 = div %2, %7
+    jump b3
+b3:
+    %9 = phi [b1: %6], [b2: %8]
+    %10 = const 1
+    %11 = sub %9, %10
+    return %11
+}
+", "fn @0 <main>() {
+b0:
+    %0 = const 2
+    %1 = const 3
+    %2 = const 6
+    %3 = const 5
+    %4 = const true
+    %5 = const 1
+    %6 = const 7
+    %7 = const 1
+    %8 = const 6
+    return %8
+}
+"; "operations and branches on constants")]
+#[test_case("let a = 1 / 0; let b = \"a\" - 1; -true", "fn @0 <main>() {
+b0:
+    %0 = const 1
+    %1 = const 0
+    %2 = div %0, %1
+    %3 = const \"a\"
+    %4 = const 1
+    %5 = sub %3, %4
+    %6 = const true
+    %7 = neg %6
+    return %7
+}
+", "fn @0 <main>() {
+b0:
+    %0 = const 1
+    %1 = const 0
+    %2 = div %0, %1
+    %3 = const \"a\"
+    %4 = const 1
+    %5 = sub %3, %4
+    %6 = const true
+    %7 = neg %6
+    return %7
+}
+"; "failing operations stay")]
+#[test_case("let b = if (c) { 1 } else { 1 }; b + 2", "fn @0 <main>() {
+b0:
+    %0 = load c
+    branch %0, b1, b2
+b1:
+    %1 = const 1
+    jump b3
+b2:
+    %2 = const 1
+    jump b3
+b3:
+    %3 = phi [b1: %1], [b2: %2]
+    %4 = const 2
+    %5 = add %3, %4
+    return %5
+}
+", "fn @0 <main>() {
+b0:
+    %0 = load c
+    branch %0, b1, b2
+b1:
+    %1 = const 1
+    jump b3
+b2:
+    %2 = const 1
+    jump b3
+b3:
+    %3 = const 1
+    %4 = const 2
+    %5 = const 3
+    return %5
+}
+"; "phi of one constant")]
+#[test_case("if (c) { 1 + 1 } else { 2 }", "fn @0 <main>() {
+b0:
+    %0 = load c
+    branch %0, b1, b2
+b1:
+    %1 = const 1
+    %2 = const 1
+    %3 = add %1, %2
+    jump b3
+b2:
+    %4 = const 2
+    jump b3
+b3:
+    %5 = phi [b1: %3], [b2: %4]
+    return %5
+}
+", "fn @0 <main>() {
+b0:
+    %0 = load c
+    branch %0, b1, b2
+b1:
+    %1 = const 1
+    %2 = const 1
+    %3 = const 2
+    jump b3
+b2:
+    %4 = const 2
+    jump b3
+b3:
+    %5 = const 2
+    return %5
+}
+"; "branches on unknown values stay")]
+fn test_propagate_constants(source: &str, before: &str, after: &str) {
+    let mut module = lowered(source);
+    assert_eq!(module.to_string(), before);
+    PassManager::new(vec![Pass::Constants]).run(&mut module);
+    assert_eq!(module.to_string(), after);
+}
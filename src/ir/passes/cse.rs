@@ -0,0 +1,55 @@
+//! Common-subexpression elimination: an operation identical to one that ran on every path
+//! to it reads that one's result instead. Only operations whose result depends on nothing
+//! but their operands take part, so not `load`, `call` or `import`. One that could fail
+//! can still be removed, as the first would have raised the error already.
+
+use std::collections::HashMap;
+
+use super::*;
+
+/// Removes the operations in `function` that repeat an earlier one.
+pub fn eliminate_common_subexpressions(function: &mut Function) {
+    let dominators = function.dominators();
+    let mut replaced = HashMap::new();
+    let mut available: HashMap<String, Vec<(usize, Value)>> = HashMap::new();
+    for block in reverse_postorder(function) {
+        for instruction in &function.blocks[block].instructions {
+            let (Some(result), true) = (instruction.result, is_deterministic(&instruction.op)) else {
+                continue;
+            };
+            // Compare the operation as it reads once earlier duplicates are gone.
+            let mut op = instruction.op.clone();
+            for operand in op.operands_mut() {
+                *operand = replaced.get(operand).copied().unwrap_or(*operand);
+            }
+            let candidates = available.entry(op.to_string()).or_default();
+            match candidates.iter().find(|(defined, _)| dominators[block][*defined]) {
+                Some((_, earlier)) => {
+                    replaced.insert(result, *earlier);
+                },
+                None => candidates.push((block, result)),
+            }
+        }
+    }
+    if replaced.is_empty() {
+        return;
+    }
+
+    for block in &mut function.blocks {
+        block.instructions.retain(|instruction| instruction.result.is_none_or(|result| !replaced.contains_key(&result)));
+    }
+    for operand in function.operands_mut() {
+        *operand = replaced.get(operand).copied().unwrap_or(*operand);
+    }
+    simplify_phis(function);
+}
+
+/// Whether running the operation twice on the same operands gives the same result, and
+/// changes nothing.
+fn is_deterministic(op: &Op) -> bool {
+    !matches!(op, Op::Call(..) | Op::Spawn(..) | Op::Select(_) | Op::Await(_) | Op::Load(_) | Op::Store(..) | Op::Import(_))
+}
+
+#[cfg(test)]
+#[path = "./cse_tests.rs"]
+mod tests;
@@ -0,0 +1,125 @@
+use super::*;
+
+use test_case::test_case;
+
+#[test_case("fn(x, c) { let a = x + 1; let b = x + 1; if (c) { [x + 1, a] } else { b * 2 } }", "fn @0 <main>() {
+b0:
+    %0 = closure @1
+    return %0
+}
+
+fn @1 <anonymous fn>(%0, %1) {
+b0:
+    %2 = const 1
+    %3 = add %0, %2
+    %4 = const 1
+    %5 = add %0, %4
+    branch %1, b1, b2
+b1:
+    %6 = const 1
+    %7 = add %0, %6
+    %8 = array [%7, %3]
+    jump b3
+b2:
+    %9 = const 2
+    %10 = mul %5, %9
+    jump b3
+b3:
+    %11 = phi [b1: %8], [b2: %10]
+    return %11
+}
+", "fn @0 <main>() {
+b0:
+    %0 = closure @1
+    return %0
+}
+
+fn @1 <anonymous fn>(%0, %1) {
+b0:
+    %2 = const 1
+    %3 = add %0, %2
+    branch %1, b1, b2
+b1:
+    %4 = array [%3, %3]
+    jump b3
+b2:
+    %5 = const 2
+    %6 = mul %3, %5
+    jump b3
+b3:
+    %7 = phi [b1: %4], [b2: %6]
+    return %7
+}
+"; "repeats on every path")]
+#[test_case("fn(x, c) { let a = if (c) { -x } else { 0 }; -x + a }", "fn @0 <main>() {
+b0:
+    %0 = closure @1
+    return %0
+}
+
+fn @1 <anonymous fn>(%0, %1) {
+b0:
+    branch %1, b1, b2
+b1:
+    %2 = neg %0
+    jump b3
+b2:
+    %3 = const 0
+    jump b3
+b3:
+    %4 = phi [b1: %2], [b2: %3]
+    %5 = neg %0
+    %6 = add %5, %4
+    return %6
+}
+", "fn @0 <main>() {
+b0:
+    %0 = closure @1
+    return %0
+}
+
+fn @1 <anonymous fn>(%0, %1) {
+b0:
+    branch %1, b1, b2
+b1:
+    %2 = neg %0
+    jump b3
+b2:
+    %3 = const 0
+    jump b3
+b3:
+    %4 = phi [b1: %2], [b2: %3]
+    %5 = neg %0
+    %6 = add %5, %4
+    return %6
+}
+"; "only dominating operations are reused")]
+#[test_case("f(x) + f(x)", "fn @0 <main>() {
+b0:
+    %0 = load f
+    %1 = load x
+    %2 = call %0(%1)
+    %3 = load f
+    %4 = load x
+    %5 = call %3(%4)
+    %6 = add %2, %5
+    return %6
+}
+", "fn @0 <main>() {
+b0:
+    %0 = load f
+    %1 = load x
+    %2 = call %0(%1)
+    %3 = load f
+    %4 = load x
+    %5 = call %3(%4)
+    %6 = add %2, %5
+    return %6
+}
+"; "loads and calls repeat")]
+fn test_eliminate_common_subexpressions(source: &str, before: &str, after: &str) {
+    let mut module = lowered(source);
+    assert_eq!(module.to_string(), before);
+    PassManager::new(vec![Pass::Cse]).run(&mut module);
+    assert_eq!(module.to_string(), after);
+}
@@ -0,0 +1,28 @@
+//! Dead-code elimination: removes the operations and phi nodes whose results nothing
+//! reads, as long as they can neither fail nor change anything, and the blocks control
+//! never reaches.
+
+use super::*;
+
+/// Removes the dead code in `function`, including code only dead code read.
+pub fn eliminate_dead_code(function: &mut Function) {
+    clean_up(function);
+    loop {
+        let uses = function.uses();
+        let live = |result: Option<Value>| result.is_none_or(|result| uses[result.0] > 0);
+        let mut removed = false;
+        for block in &mut function.blocks {
+            let (phis, instructions) = (block.phis.len(), block.instructions.len());
+            block.phis.retain(|phi| live(Some(phi.result)));
+            block.instructions.retain(|instruction| live(instruction.result) || !instruction.op.is_pure());
+            removed |= block.phis.len() != phis || block.instructions.len() != instructions;
+        }
+        if !removed {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./dce_tests.rs"]
+mod tests;
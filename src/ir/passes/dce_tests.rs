@@ -0,0 +1,95 @@
+use super::*;
+
+use test_case::test_case;
+
+#[test_case("let a = [1, 2]; let b = !a; fn(x) { x }; 3", "fn @0 <main>() {
+b0:
+    %0 = const 1
+    %1 = const 2
+    %2 = array [%0, %1]
+    %3 = not %2
+    %4 = closure @1
+    %5 = const 3
+    return %5
+}
+
+fn @1 <anonymous fn>(%0) {
+b0:
+    return %0
+}
+", "fn @0 <main>() {
+b0:
+    %0 = const 3
+    return %0
+}
+
+fn @1 <anonymous fn>(%0) {
+b0:
+    return %0
+}
+"; "unused pure operations")]
+#[test_case("let a = x + 1; let b = -a; f(); 3", "fn @0 <main>() {
+b0:
+    %0 = load x
+    %1 = const 1
+    %2 = add %0, %1
+    %3 = neg %2
+    %4 = load f
+    %5 = call %4()
+    %6 = const 3
+    return %6
+}
+", "fn @0 <main>() {
+b0:
+    %0 = load x
+    %1 = const 1
+    %2 = add %0, %1
+    %3 = neg %2
+    %4 = load f
+    %5 = call %4()
+    %6 = const 3
+    return %6
+}
+"; "operations that could fail stay")]
+#[test_case("fn(c) { let a = if (c) { 1 } else { 2 }; c }", "fn @0 <main>() {
+b0:
+    %0 = closure @1
+    return %0
+}
+
+fn @1 <anonymous fn>(%0) {
+b0:
+    branch %0, b1, b2
+b1:
+    %1 = const 1
+    jump b3
+b2:
+    %2 = const 2
+    jump b3
+b3:
+    %3 = phi [b1: %1], [b2: %2]
+    return %0
+}
+", "fn @0 <main>() {
+b0:
+    %0 = closure @1
+    return %0
+}
+
+fn @1 <anonymous fn>(%0) {
+b0:
+    branch %0, b1, b2
+b1:
+    jump b3
+b2:
+    jump b3
+b3:
+    return %0
+}
+"; "unused phis")]
+fn test_eliminate_dead_code(source: &str, before: &str, after: &str) {
+    let mut module = lowered(source);
+    assert_eq!(module.to_string(), before);
+    PassManager::new(vec![Pass::Dce]).run(&mut module);
+    assert_eq!(module.to_string(), after);
+}
@@ -0,0 +1,124 @@
+//! Inlining: a call to a function created by a `closure` in the caller itself runs the
+//! body of that function in place, when it is small and neither binds variables nor
+//! creates closures, so that it never needs an environment of its own. Recursive
+//! functions capture themselves and are called through `load`, so they are never inlined.
+//! Errors raised in inlined code report the caller in stack traces.
+
+use super::*;
+
+/// Functions with more instructions than this are not inlined.
+const MAX_INSTRUCTIONS: usize = 12;
+
+/// Inlines the calls to small functions in every function of `module`.
+pub fn inline_small_functions(module: &mut Module) {
+    for caller in 0..module.functions.len() {
+        let mut inlined = false;
+        while let Some((block, position, callee)) = find_call(module, caller) {
+            let callee = module.functions[callee.0].clone();
+            inline(&mut module.functions[caller], block, position, &callee);
+            inlined = true;
+        }
+        if inlined {
+            clean_up(&mut module.functions[caller]);
+        }
+    }
+}
+
+/// The first call in `caller` that can be inlined, with the function it calls.
+fn find_call(module: &Module, caller: usize) -> Option<(usize, usize, FunctionId)> {
+    let function = &module.functions[caller];
+    let mut closures = vec![None; function.values];
+    for block in &function.blocks {
+        for instruction in &block.instructions {
+            if let (Some(result), Op::Closure(id)) = (instruction.result, &instruction.op) {
+                closures[result.0] = Some(*id);
+            }
+        }
+    }
+    function.blocks.iter().enumerate().find_map(|(i, block)| {
+        block.instructions.iter().enumerate().find_map(|(position, instruction)| match &instruction.op {
+            Op::Call(callee, arguments) => closures
+                .get(callee.0)
+                .copied()
+                .flatten()
+                .filter(|id| id.0 != caller && can_inline(&module.functions[id.0], arguments.len()))
+                .map(|id| (i, position, id)),
+            _ => None,
+        })
+    })
+}
+
+fn can_inline(callee: &Function, arguments: usize) -> bool {
+    let mut instructions = callee.blocks.iter().flat_map(|block| &block.instructions);
+    callee.parameters.len() == arguments
+        && callee.blocks.iter().map(|block| block.instructions.len()).sum::<usize>() <= MAX_INSTRUCTIONS
+        && instructions.all(|instruction| !matches!(instruction.op, Op::Store(..) | Op::Closure(_)))
+}
+
+/// Replaces the call at `position` in `block` of `caller` by the blocks of `callee`. The
+/// block is split after the call, and the callee's returns jump to its second half, where
+/// a phi node takes over the call's result.
+fn inline(caller: &mut Function, block: usize, position: usize, callee: &Function) {
+    let call = caller.blocks[block].instructions.remove(position);
+    let Op::Call(_, arguments) = call.op else {
+        unreachable!("only calls are inlined");
+    };
+    let first_block = caller.blocks.len();
+    let continuation = BlockId(first_block + callee.blocks.len());
+    let first_value = caller.values;
+    caller.values += callee.values;
+
+    let value = |value: &mut Value| match callee.parameters.iter().position(|parameter| parameter == value) {
+        Some(i) => *value = arguments[i],
+        None => value.0 += first_value,
+    };
+    let mut returns = Vec::new();
+    for (i, original) in callee.blocks.iter().enumerate() {
+        let mut block = original.clone();
+        for phi in &mut block.phis {
+            value(&mut phi.result);
+            for (from, incoming) in &mut phi.incoming {
+                from.0 += first_block;
+                value(incoming);
+            }
+        }
+        for instruction in &mut block.instructions {
+            instruction.result.iter_mut().for_each(value);
+            instruction.op.operands_mut().into_iter().for_each(value);
+        }
+        block.terminator = match block.terminator {
+            Terminator::Return(mut result) => {
+                value(&mut result);
+                returns.push((BlockId(first_block + i), result));
+                Terminator::Jump(continuation)
+            },
+            mut terminator => {
+                terminator.successors_mut().into_iter().for_each(|successor| successor.0 += first_block);
+                terminator.operands_mut().into_iter().for_each(value);
+                terminator
+            },
+        };
+        caller.blocks.push(block);
+    }
+
+    let before = &mut caller.blocks[block];
+    let after = Block {
+        phis: vec![Phi { result: call.result.expect("calls have a result"), incoming: returns }],
+        instructions: before.instructions.split_off(position),
+        terminator: std::mem::replace(&mut before.terminator, Terminator::Jump(BlockId(first_block))),
+    };
+    for successor in after.terminator.successors() {
+        for phi in &mut caller.blocks[successor.0].phis {
+            for (from, _) in &mut phi.incoming {
+                if from.0 == block {
+                    *from = continuation;
+                }
+            }
+        }
+    }
+    caller.blocks.push(after);
+}
+
+#[cfg(test)]
+#[path = "./inline_tests.rs"]
+mod tests;
@@ -0,0 +1,224 @@
+use super::*;
+
+use test_case::test_case;
+
+#[test_case("let square = fn(x) { x * x }; square(y) + 1", "fn @0 <main>() {
+b0:
+    %0 = closure @1
+    %1 = load y
+    %2 = call %0(%1)
+    %3 = const 1
+    %4 = add %2, %3
+    return %4
+}
+
+fn @1 square(%0) {
+b0:
+    %1 = mul %0, %0
+    return %1
+}
+", "fn @0 <main>() {
+b0:
+    %0 = closure @1
+    %1 = load y
+    %2 = mul %1, %1
+    %3 = const 1
+    %4 = add %2, %3
+    return %4
+}
+
+fn @1 square(%0) {
+b0:
+    %1 = mul %0, %0
+    return %1
+}
+"; "straight-line function")]
+#[test_case("let abs = fn(x) { if (x < 0) { return -x; } x }; abs(y)", "fn @0 <main>() {
+b0:
+    %0 = closure @1
+    %1 = load y
+    %2 = call %0(%1)
+    return %2
+}
+
+fn @1 abs(%0) {
+b0:
+    %1 = const 0
+    %2 = lt %0, %1
+    branch %2, b1, b2
+b1:
+    %3 = neg %0
+    return %3
+b2:
+    jump b3
+b3:
+    return %0
+}
+", "fn @0 <main>() {
+b0:
+    %0 = closure @1
+    %1 = load y
+    %2 = const 0
+    %3 = lt %1, %2
+    branch %3, b1, b2
+b1:
+    %4 = neg %1
+    jump b3
+b2:
+    jump b3
+b3:
+    %5 = phi [b1: %4], [b2: %1]
+    return %5
+}
+
+fn @1 abs(%0) {
+b0:
+    %1 = const 0
+    %2 = lt %0, %1
+    branch %2, b1, b2
+b1:
+    %3 = neg %0
+    return %3
+b2:
+    jump b3
+b3:
+    return %0
+}
+"; "returns meet in a phi")]
+#[test_case("let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) } }; let g = fn(a, b) { a }; fib(3) + g(1)", "fn @0 <main>() {
+b0:
+    %0 = closure @1
+    store fib, %0
+    %1 = closure @2
+    %2 = load fib
+    %3 = const 3
+    %4 = call %2(%3)
+    %5 = const 1
+    %6 = call %1(%5)
+    %7 = add %4, %6
+    return %7
+}
+
+fn @1 fib(%0) {
+b0:
+    %1 = const 2
+    %2 = lt %0, %1
+    branch %2, b1, b2
+b1:
+    jump b3
+b2:
+    %3 = load fib
+    %4 = const 1
+    %5 = sub %0, %4
+    %6 = call %3(%5)
+    jump b3
+b3:
+    %7 = phi [b1: %0], [b2: %6]
+    return %7
+}
+
+fn @2 g(%0, %1) {
+b0:
+    return %0
+}
+", "fn @0 <main>() {
+b0:
+    %0 = closure @1
+    store fib, %0
+    %1 = closure @2
+    %2 = load fib
+    %3 = const 3
+    %4 = call %2(%3)
+    %5 = const 1
+    %6 = call %1(%5)
+    %7 = add %4, %6
+    return %7
+}
+
+fn @1 fib(%0) {
+b0:
+    %1 = const 2
+    %2 = lt %0, %1
+    branch %2, b1, b2
+b1:
+    jump b3
+b2:
+    %3 = load fib
+    %4 = const 1
+    %5 = sub %0, %4
+    %6 = call %3(%5)
+    jump b3
+b3:
+    %7 = phi [b1: %0], [b2: %6]
+    return %7
+}
+
+fn @2 g(%0, %1) {
+b0:
+    return %0
+}
+"; "recursive and mismatched calls stay")]
+#[test_case("let f = fn(x) { fn() { x } }; f(1)", "fn @0 <main>() {
+b0:
+    %0 = closure @1
+    %1 = const 1
+    %2 = call %0(%1)
+    return %2
+}
+
+fn @1 f(%0) {
+b0:
+    store x, %0
+    %1 = closure @2
+    return %1
+}
+
+fn @2 <anonymous fn>() {
+b0:
+    %0 = load x
+    return %0
+}
+", "fn @0 <main>() {
+b0:
+    %0 = closure @1
+    %1 = const 1
+    %2 = call %0(%1)
+    return %2
+}
+
+fn @1 f(%0) {
+b0:
+    store x, %0
+    %1 = closure @2
+    return %1
+}
+
+fn @2 <anonymous fn>() {
+b0:
+    %0 = load x
+    return %0
+}
+"; "functions that bind or create closures stay")]
+fn test_inline_small_functions(source: &str, before: &str, after: &str) {
+    let mut module = lowered(source);
+    assert_eq!(module.to_string(), before);
+    PassManager::new(vec![Pass::Inline]).run(&mut module);
+    assert_eq!(module.to_string(), after);
+}
+
+#[test]
+fn test_large_functions_stay() {
+    let body = (0..MAX_INSTRUCTIONS).map(|i| format!("x + {}", i)).collect::<Vec<_>>().join(" * ");
+    let mut module = lowered(&format!("let f = fn(x) {{ {} }}; f(1)", body));
+    let before = module.to_string();
+    PassManager::new(vec![Pass::Inline]).run(&mut module);
+    assert_eq!(module.to_string(), before);
+}
+
+#[test]
+fn test_async_functions_stay() {
+    let mut module = lowered("let f = async fn(x) { x + 1 }; await f(1)");
+    let before = module.to_string();
+    PassManager::new(vec![Pass::Inline]).run(&mut module);
+    assert_eq!(module.to_string(), before);
+}
@@ -0,0 +1,67 @@
+//! Loop-invariant code motion: operations in a loop that read nothing defined in it, and
+//! so give the same result on every iteration, move to the block before the loop. Only
+//! operations that can neither fail nor change anything move, since the loop might run
+//! them on no iteration at all.
+//!
+//! A loop is found from a back edge, a jump to a block that dominates the block jumping,
+//! and holds the blocks that reach that jump without going through its target. Keynes has
+//! no loops yet, so lowered programs never have back edges, but the pass works on any
+//! well-formed IR.
+
+use super::*;
+
+/// Hoists the invariant operations of the loops in `function` that are entered from a
+/// single block ending in a jump.
+pub fn hoist_loop_invariants(function: &mut Function) {
+    let dominators = function.dominators();
+    let predecessors = function.predecessors();
+    let order = reverse_postorder(function);
+    for &header in &order {
+        let latches = predecessors[header].iter().filter(|predecessor| dominators[predecessor.0][header]).collect::<Vec<_>>();
+        if latches.is_empty() {
+            continue;
+        }
+        let mut body = vec![false; function.blocks.len()];
+        body[header] = true;
+        let mut stack = latches.iter().map(|latch| latch.0).collect::<Vec<_>>();
+        while let Some(block) = stack.pop() {
+            if !std::mem::replace(&mut body[block], true) {
+                stack.extend(predecessors[block].iter().map(|predecessor| predecessor.0));
+            }
+        }
+        let mut entries = predecessors[header].iter().filter(|predecessor| !body[predecessor.0]);
+        let (Some(preheader), None) = (entries.next(), entries.next()) else {
+            continue;
+        };
+        if function.blocks[preheader.0].terminator != Terminator::Jump(BlockId(header)) {
+            continue;
+        }
+
+        let mut defined_in_loop = vec![false; function.values];
+        for block in (0..function.blocks.len()).filter(|block| body[*block]) {
+            let block = &function.blocks[block];
+            let results = block.phis.iter().map(|phi| Some(phi.result)).chain(block.instructions.iter().map(|instruction| instruction.result));
+            for result in results.flatten() {
+                defined_in_loop[result.0] = true;
+            }
+        }
+        for &block in order.iter().filter(|block| body[**block]) {
+            let mut i = 0;
+            while i < function.blocks[block].instructions.len() {
+                let instruction = &function.blocks[block].instructions[i];
+                let invariant = instruction.op.operands().iter().all(|operand| !defined_in_loop[operand.0]);
+                if !(invariant && instruction.op.is_pure()) {
+                    i += 1;
+                    continue;
+                }
+                let instruction = function.blocks[block].instructions.remove(i);
+                instruction.result.iter().for_each(|result| defined_in_loop[result.0] = false);
+                function.blocks[preheader.0].instructions.push(instruction);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./licm_tests.rs"]
+mod tests;
@@ -0,0 +1,129 @@
+use super::*;
+
+use crate::lexer::Span;
+
+use test_case::test_case;
+
+fn instruction(result: usize, op: Op) -> Instruction {
+    Instruction { result: Some(Value(result)), op, span: Span::default() }
+}
+
+/// A loop counting `%2` from zero up to the parameter `%0`, with `b1` as its header and
+/// `b2` as its body, entered from `entry`.
+fn counting_loop(entry: Terminator) -> Module {
+    let body = vec![
+        instruction(4, Op::Constant(Constant::Integer(1))),
+        instruction(5, Op::Prefix(PrefixOperator::BANG, Value(0))),
+        instruction(6, Op::Array(vec![Value(5), Value(4)])),
+        instruction(7, Op::Infix(InfixOperator::PLUS, Value(2), Value(4))),
+        instruction(8, Op::Array(vec![Value(7)])),
+        instruction(9, Op::Infix(InfixOperator::MULTIPLY, Value(0), Value(0))),
+    ];
+    let blocks = vec![
+        Block { phis: vec![], instructions: vec![instruction(1, Op::Constant(Constant::Integer(0)))], terminator: entry },
+        Block {
+            phis: vec![Phi { result: Value(2), incoming: vec![(BlockId(0), Value(1)), (BlockId(2), Value(7))] }],
+            instructions: vec![instruction(3, Op::Infix(InfixOperator::LESS_THAN, Value(2), Value(0)))],
+            terminator: Terminator::Branch(Value(3), BlockId(2), BlockId(3)),
+        },
+        Block { phis: vec![], instructions: body, terminator: Terminator::Jump(BlockId(1)) },
+        Block { phis: vec![], instructions: vec![], terminator: Terminator::Return(Value(1)) },
+    ];
+    let main = Function { name: "<main>".to_string(), parameters: vec![Value(0)], blocks, values: 10, span: Span::default() };
+    let module = Module { functions: vec![main] };
+    assert_eq!(verify(&module), Ok(()));
+    module
+}
+
+#[test_case(Terminator::Jump(BlockId(1)), "fn @0 <main>(%0) {
+b0:
+    %1 = const 0
+    jump b1
+b1:
+    %2 = phi [b0: %1], [b2: %7]
+    %3 = lt %2, %0
+    branch %3, b2, b3
+b2:
+    %4 = const 1
+    %5 = not %0
+    %6 = array [%5, %4]
+    %7 = add %2, %4
+    %8 = array [%7]
+    %9 = mul %0, %0
+    jump b1
+b3:
+    return %1
+}
+", "fn @0 <main>(%0) {
+b0:
+    %1 = const 0
+    %2 = const 1
+    %3 = not %0
+    %4 = array [%3, %2]
+    jump b1
+b1:
+    %5 = phi [b0: %1], [b2: %7]
+    %6 = lt %5, %0
+    branch %6, b2, b3
+b2:
+    %7 = add %5, %2
+    %8 = array [%7]
+    %9 = mul %0, %0
+    jump b1
+b3:
+    return %1
+}
+"; "invariant operations move before the loop")]
+#[test_case(Terminator::Branch(Value(0), BlockId(1), BlockId(3)), "fn @0 <main>(%0) {
+b0:
+    %1 = const 0
+    branch %0, b1, b3
+b1:
+    %2 = phi [b0: %1], [b2: %7]
+    %3 = lt %2, %0
+    branch %3, b2, b3
+b2:
+    %4 = const 1
+    %5 = not %0
+    %6 = array [%5, %4]
+    %7 = add %2, %4
+    %8 = array [%7]
+    %9 = mul %0, %0
+    jump b1
+b3:
+    return %1
+}
+", "fn @0 <main>(%0) {
+b0:
+    %1 = const 0
+    branch %0, b1, b3
+b1:
+    %2 = phi [b0: %1], [b2: %7]
+    %3 = lt %2, %0
+    branch %3, b2, b3
+b2:
+    %4 = const 1
+    %5 = not %0
+    %6 = array [%5, %4]
+    %7 = add %2, %4
+    %8 = array [%7]
+    %9 = mul %0, %0
+    jump b1
+b3:
+    return %1
+}
+"; "loops entered by a branch stay")]
+fn test_hoist_loop_invariants(entry: Terminator, before: &str, after: &str) {
+    let mut module = counting_loop(entry);
+    assert_eq!(module.to_string(), before);
+    PassManager::new(vec![Pass::Licm]).run(&mut module);
+    assert_eq!(module.to_string(), after);
+}
+
+#[test]
+fn test_programs_without_loops_stay() {
+    let mut module = lowered("let f = fn(x) { if (x) { [1] } else { !x } }; f(1)");
+    let before = module.to_string();
+    PassManager::new(vec![Pass::Licm]).run(&mut module);
+    assert_eq!(module.to_string(), before);
+}
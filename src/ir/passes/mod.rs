@@ -0,0 +1,250 @@
+//! Optimizations of the IR, which a [`PassManager`] runs in order. Each pass keeps what a
+//! program does, errors included: an instruction that could fail is only removed or moved
+//! once its operands show that it cannot.
+//!
+//! `keynes run --ir` runs programs as the passes left them, with [`interpret`](super::interpret).
+
+pub mod constants;
+pub mod cse;
+pub mod dce;
+pub mod inline;
+pub mod licm;
+
+use std::fmt::Display;
+
+use log::*;
+
+use super::*;
+use crate::optimizer::OptLevel;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    /// Inlines calls to small functions created in the caller, see [`inline`].
+    Inline,
+    /// Folds operations and branches on constants, see [`constants`].
+    Constants,
+    /// Reuses the result of an identical operation that always ran before, see [`cse`].
+    Cse,
+    /// Moves operations that give the same result on every iteration out of loops, see [`licm`].
+    Licm,
+    /// Removes operations whose results are unused, see [`dce`].
+    Dce,
+}
+
+impl Pass {
+    /// Every pass, in the order `-O1` runs them.
+    pub const ALL: [Pass; 5] = [Pass::Inline, Pass::Constants, Pass::Cse, Pass::Licm, Pass::Dce];
+
+    /// The name `--passes` knows the pass by.
+    pub fn name(self) -> &'static str {
+        match self {
+            Pass::Inline => "inline",
+            Pass::Constants => "constprop",
+            Pass::Cse => "cse",
+            Pass::Licm => "licm",
+            Pass::Dce => "dce",
+        }
+    }
+
+    fn run(self, module: &mut Module) {
+        match self {
+            Pass::Inline => inline::inline_small_functions(module),
+            Pass::Constants => module.functions.iter_mut().for_each(constants::propagate_constants),
+            Pass::Cse => module.functions.iter_mut().for_each(cse::eliminate_common_subexpressions),
+            Pass::Licm => module.functions.iter_mut().for_each(licm::hoist_loop_invariants),
+            Pass::Dce => module.functions.iter_mut().for_each(dce::eliminate_dead_code),
+        }
+    }
+}
+
+impl TryFrom<&str> for Pass {
+    type Error = String;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        Pass::ALL.into_iter().find(|pass| pass.name() == name).ok_or_else(|| {
+            let names = Pass::ALL.map(Pass::name);
+            format!("unknown pass `{}`, expected one of {}", name, names.join(", "))
+        })
+    }
+}
+
+impl Display for Pass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Runs a list of passes over modules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassManager {
+    passes: Vec<Pass>,
+}
+
+impl PassManager {
+    /// Runs `passes` in the order given, which may repeat passes.
+    pub fn new(passes: Vec<Pass>) -> PassManager {
+        PassManager { passes }
+    }
+
+    /// The passes for `level`: none at `-O0`, and all of them at `-O1`.
+    pub fn for_level(level: OptLevel) -> PassManager {
+        match level {
+            OptLevel::O0 => PassManager::new(vec![]),
+            OptLevel::O1 => PassManager::new(Pass::ALL.to_vec()),
+        }
+    }
+
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    /// Runs the passes over `module`, then numbers the values of each function again.
+    pub fn run(&self, module: &mut Module) {
+        for pass in &self.passes {
+            trace!("run {} pass", pass);
+            pass.run(module);
+            debug_assert_eq!(verify(module), Ok(()), "after the {} pass", pass);
+        }
+        module.functions.iter_mut().for_each(Function::renumber);
+    }
+}
+
+/// Leaves `function` well formed and tidy after a pass changed its terminators: removes
+/// the blocks control can no longer reach and the phi values coming from blocks that no
+/// longer go to theirs, merges each block into its predecessor when that is the only way
+/// to reach it, then [`simplify_phis`].
+fn clean_up(function: &mut Function) {
+    let dominators = function.dominators();
+    let reachable = (0..function.blocks.len()).map(|block| dominators[block][block]).collect::<Vec<_>>();
+    remove_blocks(function, &reachable);
+
+    let predecessors = function.predecessors();
+    for (block, predecessors) in function.blocks.iter_mut().zip(&predecessors) {
+        for phi in &mut block.phis {
+            let mut expected = predecessors.clone();
+            phi.incoming.retain(|(from, _)| match expected.iter().position(|predecessor| predecessor == from) {
+                Some(index) => {
+                    expected.remove(index);
+                    true
+                },
+                None => false,
+            });
+        }
+    }
+
+    // The block each block was merged into, or the block itself.
+    let mut merged = (0..function.blocks.len()).collect::<Vec<_>>();
+    for block in 1..function.blocks.len() {
+        let [predecessor] = predecessors[block][..] else {
+            continue;
+        };
+        let mut into = predecessor.0;
+        while merged[into] != into {
+            into = merged[into];
+        }
+        if into == block || function.blocks[into].terminator != Terminator::Jump(BlockId(block)) {
+            continue;
+        }
+        let Block { phis, instructions, terminator } = std::mem::replace(&mut function.blocks[block], Block {
+            phis: vec![],
+            instructions: vec![],
+            terminator: Terminator::Jump(BlockId(block)),
+        });
+        for successor in terminator.successors() {
+            for (from, _) in function.blocks[successor.0].phis.iter_mut().flat_map(|phi| &mut phi.incoming) {
+                if from.0 == block {
+                    *from = BlockId(into);
+                }
+            }
+        }
+        function.blocks[into].instructions.extend(instructions);
+        function.blocks[into].terminator = terminator;
+        merged[block] = into;
+        for phi in phis {
+            function.replace_uses(phi.result, phi.incoming[0].1);
+        }
+    }
+    let keep = merged.iter().enumerate().map(|(block, into)| block == *into).collect::<Vec<_>>();
+    remove_blocks(function, &keep);
+    simplify_phis(function);
+}
+
+/// Removes the blocks not to `keep`, which nothing may go to, and numbers the rest again.
+fn remove_blocks(function: &mut Function, keep: &[bool]) {
+    if keep.iter().all(|keep| *keep) {
+        return;
+    }
+    let mut numbers = Vec::with_capacity(keep.len());
+    let mut next = 0;
+    for keep in keep {
+        numbers.push(BlockId(next));
+        next += usize::from(*keep);
+    }
+    let mut i = 0;
+    function.blocks.retain(|_| {
+        i += 1;
+        keep[i - 1]
+    });
+    for block in &mut function.blocks {
+        for successor in block.terminator.successors_mut() {
+            *successor = numbers[successor.0];
+        }
+        for phi in &mut block.phis {
+            phi.incoming.retain(|(from, _)| keep[from.0]);
+            phi.incoming.iter_mut().for_each(|(from, _)| *from = numbers[from.0]);
+        }
+    }
+}
+
+/// Replaces each phi node that only ever picks one value, besides itself, by that value.
+fn simplify_phis(function: &mut Function) {
+    loop {
+        let trivial = function.blocks.iter().enumerate().find_map(|(block, Block { phis, .. })| {
+            phis.iter().enumerate().find_map(|(i, phi)| {
+                let mut values = phi.incoming.iter().map(|(_, value)| *value).filter(|value| *value != phi.result);
+                let first = values.next()?;
+                values.all(|value| value == first).then_some((block, i, phi.result, first))
+            })
+        });
+        let Some((block, i, result, value)) = trivial else {
+            return;
+        };
+        function.blocks[block].phis.remove(i);
+        function.replace_uses(result, value);
+    }
+}
+
+/// The reachable blocks in reverse postorder, where each block comes after all of its
+/// dominators.
+fn reverse_postorder(function: &Function) -> Vec<usize> {
+    let mut visited = vec![false; function.blocks.len()];
+    let mut order = Vec::new();
+    let mut stack = vec![(0, false)];
+    while let Some((block, finished)) = stack.pop() {
+        if finished {
+            order.push(block);
+        } else if !std::mem::replace(&mut visited[block], true) {
+            stack.push((block, true));
+            for successor in function.blocks[block].terminator.successors().into_iter().rev() {
+                if !visited[successor.0] {
+                    stack.push((successor.0, false));
+                }
+            }
+        }
+    }
+    order.reverse();
+    order
+}
+
+/// The verified IR of `source`, for the tests of each pass.
+#[cfg(test)]
+fn lowered(source: &str) -> Module {
+    let mut lexer = crate::lexer::Lexer::new(source.to_string());
+    let module = lower(&crate::parser::Parser::new(&mut lexer).parse_program());
+    assert_eq!(verify(&module), Ok(()), "{}", module);
+    module
+}
+
+#[cfg(test)]
+#[path = "./mod_tests.rs"]
+mod tests;
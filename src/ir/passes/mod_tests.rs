@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+
+use super::*;
+
+use crate::{ast::program::Program, generator::Generator, lexer::Lexer, parser::Parser};
+
+use test_case::test_case;
+
+#[test_case("inline", Ok(Pass::Inline))]
+#[test_case("constprop", Ok(Pass::Constants))]
+#[test_case("cse", Ok(Pass::Cse))]
+#[test_case("licm", Ok(Pass::Licm))]
+#[test_case("dce", Ok(Pass::Dce))]
+#[test_case("gvn", Err("unknown pass `gvn`, expected one of inline, constprop, cse, licm, dce".to_string()))]
+fn test_pass_from_name(name: &str, expected: Result<Pass, String>) {
+    assert_eq!(Pass::try_from(name), expected);
+}
+
+#[test]
+fn test_pass_manager_for_level() {
+    assert_eq!(PassManager::for_level(OptLevel::O0).passes(), &[]);
+    assert_eq!(PassManager::for_level(OptLevel::O1).passes(), &Pass::ALL);
+}
+
+#[test]
+fn test_passes_together() {
+    let mut module = lowered("let square = fn(x) { x * x }; let y = square(3) + square(4); if (y == 25) { puts(\"yes\") } else { puts(\"no\") }");
+    PassManager::for_level(OptLevel::O1).run(&mut module);
+    assert_eq!(module.to_string(), "fn @0 <main>() {
+b0:
+    %0 = load puts
+    %1 = const \"yes\"
+    %2 = call %0(%1)
+    return %2
+}
+
+fn @1 square(%0) {
+b0:
+    %1 = mul %0, %0
+    return %1
+}
+");
+}
+
+/// Every pass alone, then all of them, over `program`, checking the result each time.
+fn run_each_pass(program: &Program, name: &str) {
+    let managers = Pass::ALL.map(|pass| PassManager::new(vec![pass])).into_iter().chain([PassManager::for_level(OptLevel::O1)]);
+    for manager in managers {
+        let mut module = lower(program);
+        manager.run(&mut module);
+        assert_eq!(verify(&module), Ok(()), "{} after {:?}\n{}", name, manager.passes(), module);
+    }
+}
+
+fn parse(source: &str) -> Option<Program> {
+    let mut lexer = Lexer::new(source.to_string());
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+    parser.errors.is_empty().then_some(program)
+}
+
+#[test]
+fn test_programs_stay_valid() {
+    let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("programs");
+    for entry in std::fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|extension| extension == "ks") {
+            if let Some(program) = parse(&std::fs::read_to_string(&path).unwrap()) {
+                run_each_pass(&program, &path.display().to_string());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_generated_programs_stay_valid() {
+    for seed in 0..300 {
+        let source = Generator::new(seed, 4).program();
+        if let Some(program) = parse(&source) {
+            run_each_pass(&program, &format!("seed {}\n{}", seed, source));
+        }
+    }
+}
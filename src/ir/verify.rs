@@ -0,0 +1,120 @@
+//! Checks that IR is well formed: every block is reachable and ends by going to blocks
+//! that exist, every value is defined once and before each of its uses on every path
+//! there, and phi nodes have exactly one value for each predecessor of their block.
+
+use super::*;
+
+/// Checks `module`, returning every problem found, each prefixed with its function.
+pub fn verify(module: &Module) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    for (i, function) in module.functions.iter().enumerate() {
+        let mut problems = Vec::new();
+        verify_function(module, function, &mut problems);
+        errors.extend(problems.into_iter().map(|problem| format!("{} {}: {}", FunctionId(i), function.name, problem)));
+    }
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors),
+    }
+}
+
+/// Where a value is defined: its block, and its position in it. Parameters and phi nodes
+/// come first, at position 0, followed by the instructions.
+type Definition = (usize, usize);
+
+fn verify_function(module: &Module, function: &Function, problems: &mut Vec<String>) {
+    if function.blocks.is_empty() {
+        problems.push("has no blocks".to_string());
+        return;
+    }
+    for (i, block) in function.blocks.iter().enumerate() {
+        for target in block.terminator.successors() {
+            if target.0 >= function.blocks.len() {
+                problems.push(format!("{}: goes to {}, which does not exist", BlockId(i), target));
+            }
+        }
+    }
+    if !problems.is_empty() {
+        return;
+    }
+    let predecessors = function.predecessors();
+    if !predecessors[0].is_empty() {
+        problems.push(format!("{}: the entry block has predecessors", BlockId(0)));
+    }
+    let dominators = function.dominators();
+    for (i, dominators) in dominators.iter().enumerate() {
+        if !dominators[i] {
+            problems.push(format!("{}: is unreachable", BlockId(i)));
+        }
+    }
+
+    let mut definitions: Vec<Option<Definition>> = vec![None; function.values];
+    let mut define = |value: Value, definition: Definition, problems: &mut Vec<String>| match definitions.get_mut(value.0) {
+        None => problems.push(format!("{}: {} is out of range for {} values", BlockId(definition.0), value, function.values)),
+        Some(Some(_)) => problems.push(format!("{}: {} is defined more than once", BlockId(definition.0), value)),
+        Some(slot) => *slot = Some(definition),
+    };
+    for parameter in &function.parameters {
+        define(*parameter, (0, 0), problems);
+    }
+    for (i, block) in function.blocks.iter().enumerate() {
+        for phi in &block.phis {
+            define(phi.result, (i, 0), problems);
+        }
+        for (position, instruction) in block.instructions.iter().enumerate() {
+            if matches!(instruction.op, Op::Store(..)) != instruction.result.is_none() {
+                problems.push(format!("{}: only `store` has no result: {}", BlockId(i), instruction.op));
+            }
+            if let Some(result) = instruction.result {
+                define(result, (i, position + 1), problems);
+            }
+        }
+    }
+
+    // Whether `value` is defined on every path to `position` in `block`.
+    let available = |value: Value, block: usize, position: usize| match definitions.get(value.0).copied().flatten() {
+        Some((defined, at)) if defined == block => at < position,
+        Some((defined, _)) => dominators[block][defined],
+        None => false,
+    };
+    for (i, block) in function.blocks.iter().enumerate() {
+        for phi in &block.phis {
+            let mut expected = predecessors[i].clone();
+            for (from, value) in &phi.incoming {
+                match expected.iter().position(|predecessor| predecessor == from) {
+                    Some(index) => {
+                        expected.remove(index);
+                    },
+                    None => problems.push(format!("{}: phi {} has a value for {}, which is not a predecessor", BlockId(i), phi.result, from)),
+                }
+                if from.0 < function.blocks.len() && !available(*value, from.0, usize::MAX) {
+                    problems.push(format!("{}: phi {} takes {} from {}, where it is not defined", BlockId(i), phi.result, value, from));
+                }
+            }
+            for missing in expected {
+                problems.push(format!("{}: phi {} has no value for predecessor {}", BlockId(i), phi.result, missing));
+            }
+        }
+        for (position, instruction) in block.instructions.iter().enumerate() {
+            for operand in instruction.op.operands() {
+                if !available(operand, i, position + 1) {
+                    problems.push(format!("{}: {} is used before it is defined", BlockId(i), operand));
+                }
+            }
+            if let Op::Closure(id) | Op::AsyncClosure(id) = instruction.op {
+                if id.0 >= module.functions.len() {
+                    problems.push(format!("{}: creates {}, which does not exist", BlockId(i), id));
+                }
+            }
+        }
+        for operand in block.terminator.operands() {
+            if !available(operand, i, usize::MAX) {
+                problems.push(format!("{}: {} is used before it is defined", BlockId(i), operand));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./verify_tests.rs"]
+mod tests;
@@ -0,0 +1,52 @@
+use super::*;
+
+use crate::{ir::lower, lexer::Lexer, parser::Parser};
+
+use test_case::test_case;
+
+/// The lowered IR of `source`, which branches on `c` into b1 and b2, meeting again in b3.
+fn lowered(source: &str) -> Module {
+    let mut lexer = Lexer::new(source.to_string());
+    let program = Parser::new(&mut lexer).parse_program();
+    let module = lower(&program);
+    assert_eq!(verify(&module), Ok(()));
+    module
+}
+
+fn main(module: &mut Module) -> &mut Function {
+    &mut module.functions[0]
+}
+
+#[test_case(|module| main(module).blocks[0].instructions[0].op = Op::Prefix(PrefixOperator::MINUS, Value(1)), "@0 <main>: b0: %1 is used before it is defined"; "use before definition")]
+#[test_case(|module| main(module).blocks[3].instructions[0].op = Op::Prefix(PrefixOperator::MINUS, Value(2)), "@0 <main>: b3: %2 is used before it is defined"; "definition on another path")]
+#[test_case(|module| main(module).blocks[3].phis[0].incoming.pop().map(|_| ()).unwrap(), "@0 <main>: b3: phi %4 has no value for predecessor b2"; "phi without a value for a predecessor")]
+#[test_case(|module| main(module).blocks[3].phis[0].incoming[1].0 = BlockId(0), "@0 <main>: b3: phi %4 has a value for b0, which is not a predecessor"; "phi with a value for another block")]
+#[test_case(|module| main(module).blocks[3].phis[0].incoming[0].1 = Value(3), "@0 <main>: b3: phi %4 takes %3 from b1, where it is not defined"; "phi value not defined in predecessor")]
+#[test_case(|module| main(module).blocks[1].terminator = Terminator::Jump(BlockId(9)), "@0 <main>: b1: goes to b9, which does not exist"; "missing block")]
+#[test_case(|module| main(module).blocks[0].terminator = Terminator::Jump(BlockId(1)), "@0 <main>: b2: is unreachable"; "unreachable block")]
+#[test_case(|module| main(module).blocks[1].terminator = Terminator::Jump(BlockId(0)), "@0 <main>: b0: the entry block has predecessors"; "entry with predecessors")]
+#[test_case(|module| main(module).blocks[1].instructions[0].result = Some(Value(0)), "@0 <main>: b1: %0 is defined more than once"; "value defined twice")]
+#[test_case(|module| main(module).values = 4, "@0 <main>: b3: %4 is out of range for 4 values"; "value out of range")]
+#[test_case(|module| main(module).blocks[1].instructions[0].result = None, "@0 <main>: b1: only `store` has no result: const 1"; "missing result")]
+#[test_case(|module| main(module).blocks[1].instructions[0].op = Op::Closure(FunctionId(5)), "@0 <main>: b1: creates @5, which does not exist"; "missing function")]
+fn test_verify_rejects(corrupt: fn(&mut Module), expected: &str) {
+    let mut module = lowered("let a = 0; let x = if (c) { 1 } else { 2 }; -x");
+    corrupt(&mut module);
+    let errors = verify(&module).unwrap_err();
+    assert!(errors.contains(&expected.to_string()), "{:?}", errors);
+}
+
+#[test]
+fn test_verify_reports_every_function() {
+    let mut module = lowered("let f = fn() { 1 }; let g = fn() { 2 };");
+    for function in &mut module.functions[1..] {
+        function.blocks[0].terminator = Terminator::Return(Value(7));
+    }
+    assert_eq!(
+        verify(&module),
+        Err(vec![
+            "@1 f: b0: %7 is used before it is defined".to_string(),
+            "@2 g: b0: %7 is used before it is defined".to_string(),
+        ])
+    );
+}
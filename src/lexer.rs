@@ -1,277 +1,486 @@
-use std::fmt::Display;
-
-use log::*;
-
-
-#[derive(Debug, PartialEq, Clone)]
-pub enum Token {
-    ILLEGAL(String),
-    EOF,
-
-    IDENTIFIER(String),
-    INTEGER(String),
-
-    ASSIGN,
-
-    EQUAL,
-    NOT_EQUAL,
-
-    GREATER_THAN,
-    GREATER_THAN_EQUAL,
-    LESS_THAN,
-    LESS_THAN_EQUAL,
-
-    BANG,
-    TRUE, 
-    FALSE,
-    RANGE,
-
-    PLUS,
-    MINUS,
-    MULTIPLY,
-    DIVIDE,
-
-    COMMA,
-    SEMICOLON,
-
-    LPAREN,
-    RPAREN,
-    LBRACE,
-    RBRACE,
-    LBRACKET,
-    RBRACKET,
-
-    FUNCTION,
-    RETURN,
-    MUT,
-    LET,
-    IF,
-    ELSE,
-
-    RUN,
-    SPAWN,
-}
-
-impl Display for Token {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut token = String::new();
-        match self {
-            Token::IDENTIFIER(ident) => token.push_str(ident),
-            Token::INTEGER(int) => token.push_str(int),
-            Token::ASSIGN => token.push_str("="),
-            Token::EQUAL => token.push_str("=="),
-            Token::NOT_EQUAL => token.push_str("!="),
-            Token::GREATER_THAN => token.push_str(">"),
-            Token::GREATER_THAN_EQUAL => token.push_str(">="),
-            Token::LESS_THAN => token.push_str("<"),
-            Token::LESS_THAN_EQUAL => token.push_str("<="),
-            Token::BANG => token.push_str("!"),
-            Token::RANGE => token.push_str(".."),
-            Token::PLUS => token.push_str("+"),
-            Token::MINUS => token.push_str("-"),
-            Token::MULTIPLY => token.push_str("*"),
-            Token::DIVIDE => token.push_str("/"),
-            Token::COMMA => token.push_str(","),
-            Token::SEMICOLON => token.push_str(";"),
-            Token::LPAREN => token.push_str("("),
-            Token::RPAREN => token.push_str(")"),
-            Token::LBRACE => token.push_str("{"),
-            Token::RBRACE => token.push_str("}"),
-            Token::LBRACKET => token.push_str("["),
-            Token::RBRACKET => token.push_str("]"),
-            Token::FUNCTION => token.push_str("fn"),
-            Token::RETURN => token.push_str("return"),
-            Token::MUT => token.push_str("mut"),
-            Token::LET => token.push_str("let"),
-            Token::IF => token.push_str("if"),
-            Token::ELSE => token.push_str("else"),
-            Token::TRUE => token.push_str("true"),
-            Token::FALSE => token.push_str("false"),
-            Token::RUN => token.push_str("run"),
-            Token::SPAWN => token.push_str("spawn"),
-            
-            _ => token.push_str(format!("{:?}", self).as_str()),
-        }
-        write!(f, "{}", token)
-    }
-}
-
-#[derive(Default, Debug)]
-pub struct Lexer {
-    input: String,
-    position: usize,
-    read_position: usize,
-    ch: char,
-    peek: char,
-}
-
-impl Iterator for Lexer {
-    type Item = Token;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let tok = self.next_token();
-        if tok == Token::EOF {
-            None
-        } else {
-            Some(tok)
-        }
-    }
-}
-
-impl Lexer {
-    pub fn new(input: String) -> Lexer {
-        let mut lex = Lexer {
-            input: input.trim().to_string(),
-            ..Default::default()
-        };
-        trace!("input length {}", lex.input.chars().count());
-        trace!("input {:?}", lex.input.chars());
-        trace!("new ");
-        lex.read_char();
-        lex
-    }
-
-    fn read_char(&mut self) {
-        self.ch = if self.read_position >= self.input.chars().count() {
-            '\0'
-        } else {
-            self.input.chars().nth(self.read_position).unwrap()
-        };
-
-        self.peek = if self.read_position + 1 >= self.input.chars().count() {
-            '\0'
-        } else {
-            self.input.chars().nth(self.read_position + 1).unwrap()
-        };
-
-        trace!("read_char: {} {}", self.ch, self.read_position);
-
-        self.position = self.read_position;
-        self.read_position += 1;
-    }
-
-    fn read_previous_char(&mut self) {
-        self.ch = if self.position == 0 {
-            '\0'
-        } else {
-            self.input.chars().nth(self.position - 1).unwrap()
-        };
-
-        self.peek = if self.position == 1 {
-            '\0'
-        } else {
-            self.input.chars().nth(self.position - 2).unwrap()
-        };
-
-        trace!("read_previous_char: {} {}", self.ch, self.position);
-
-        self.read_position = self.position;
-        self.position -= 1;
-    }
-
-    pub fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
-        let tok = match (self.ch, self.peek) {
-            ('!', '=') => {
-                self.read_char();
-                Token::NOT_EQUAL
-            },
-            ('!', _) => Token::BANG,
-            ('=', '=') => {
-                self.read_char();
-                Token::EQUAL
-            },
-            ('=', _) => Token::ASSIGN,
-
-            ('>', '=') => {
-                self.read_char();
-                Token::GREATER_THAN_EQUAL
-            },
-            ('>', _) => Token::GREATER_THAN,
-
-            ('<', '=') => {
-                self.read_char();
-                Token::LESS_THAN_EQUAL
-            },
-            ('<',_) => Token::LESS_THAN,
-
-            ('.', '.') => {
-                self.read_char();
-                Token::RANGE
-            },
-
-            ('+', _) => Token::PLUS,
-            ('-', _) => Token::MINUS,
-            ('*', _) => Token::MULTIPLY,
-            ('/', _) => Token::DIVIDE,
-
-            (';', _) => Token::SEMICOLON,
-            (',', _) => Token::COMMA,
-
-            ('(', _) => Token::LPAREN,
-            (')', _) => Token::RPAREN,
-            ('{', _) => Token::LBRACE,
-            ('}', _) => Token::RBRACE,
-            ('[', _) => Token::LBRACKET,
-            (']', _) => Token::RBRACKET,
-
-            ('\0', _) => Token::EOF,   
-            _ => if self.ch.is_alphabetic() {
-                lookup_ident(self.read_identifier())
-            } else if self.ch.is_numeric() {
-                Token::INTEGER(self.read_number())
-            } else {
-                Token::ILLEGAL(String::from(self.ch))
-            },
-        };
-        trace!("next_token ");
-        self.read_char();
-        tok
-    }
-
-    fn read_identifier(&mut self) -> String {
-        trace!("read_identifier: {}", self.ch);
-        let start = self.position;
-        while self.ch.is_alphanumeric() {
-            trace!("read_identifier loop ");
-            self.read_char();
-        }
-        let end = self.position;
-        self.read_previous_char();
-        self.input[start..end].to_string()
-    }
-
-    fn read_number(&mut self) -> String {
-        let start = self.position;
-        while self.ch.is_numeric() {
-            trace!("read_number loop ");
-            self.read_char();
-        }
-        let end = self.position;
-        self.read_previous_char();
-        self.input[start..end].to_string()
-    }
-
-    fn skip_whitespace(&mut self) {
-        while self.ch.is_whitespace() {
-            trace!("skip_whitespace loop ");
-            self.read_char();
-        }
-    }
-}
-
-fn lookup_ident(ident: String) -> Token {
-    match ident.as_str() {
-        "fn" => Token::FUNCTION,
-        "let" => Token::LET,
-        "mut" => Token::MUT,
-        "return" => Token::RETURN,
-        "run" => Token::RUN,
-        "spawn" => Token::SPAWN,
-        "true" => Token::TRUE,
-        "false" => Token::FALSE,
-        "if" => Token::IF,
-        "else" => Token::ELSE,
-        _ => Token::IDENTIFIER(ident),
-    }
-}
\ No newline at end of file
+use std::fmt::Display;
+
+use log::*;
+
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Token {
+    ILLEGAL(String),
+    EOF,
+
+    IDENTIFIER(String),
+    INTEGER(String),
+    FLOAT(String),
+    STRING(String),
+
+    ASSIGN,
+
+    EQUAL,
+    NOT_EQUAL,
+
+    FAT_ARROW,
+    GREATER_THAN,
+    GREATER_THAN_EQUAL,
+    LESS_THAN,
+    LESS_THAN_EQUAL,
+
+    BANG,
+    TRUE, 
+    FALSE,
+    RANGE,
+
+    PLUS,
+    MINUS,
+    MULTIPLY,
+    DIVIDE,
+
+    COMMA,
+    SEMICOLON,
+    COLON,
+    DOUBLE_COLON,
+
+    LPAREN,
+    RPAREN,
+    LBRACE,
+    RBRACE,
+    LBRACKET,
+    RBRACKET,
+
+    FUNCTION,
+    RETURN,
+    MUT,
+    LET,
+    IF,
+    ELSE,
+
+    RUN,
+    SPAWN,
+    SELECT,
+    ASYNC,
+    AWAIT,
+
+    IMPORT,
+    AS,
+    USE,
+    PUB,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut token = String::new();
+        match self {
+            Token::IDENTIFIER(ident) => token.push_str(ident),
+            Token::INTEGER(int) => token.push_str(int),
+            Token::FLOAT(float) => token.push_str(float),
+            Token::STRING(string) => token.push_str(&quote_string(string)),
+            Token::ASSIGN => token.push_str("="),
+            Token::EQUAL => token.push_str("=="),
+            Token::NOT_EQUAL => token.push_str("!="),
+            Token::FAT_ARROW => token.push_str("=>"),
+            Token::GREATER_THAN => token.push_str(">"),
+            Token::GREATER_THAN_EQUAL => token.push_str(">="),
+            Token::LESS_THAN => token.push_str("<"),
+            Token::LESS_THAN_EQUAL => token.push_str("<="),
+            Token::BANG => token.push_str("!"),
+            Token::RANGE => token.push_str(".."),
+            Token::PLUS => token.push_str("+"),
+            Token::MINUS => token.push_str("-"),
+            Token::MULTIPLY => token.push_str("*"),
+            Token::DIVIDE => token.push_str("/"),
+            Token::COMMA => token.push_str(","),
+            Token::SEMICOLON => token.push_str(";"),
+            Token::COLON => token.push_str(":"),
+            Token::DOUBLE_COLON => token.push_str("::"),
+            Token::LPAREN => token.push_str("("),
+            Token::RPAREN => token.push_str(")"),
+            Token::LBRACE => token.push_str("{"),
+            Token::RBRACE => token.push_str("}"),
+            Token::LBRACKET => token.push_str("["),
+            Token::RBRACKET => token.push_str("]"),
+            Token::FUNCTION => token.push_str("fn"),
+            Token::RETURN => token.push_str("return"),
+            Token::MUT => token.push_str("mut"),
+            Token::LET => token.push_str("let"),
+            Token::IF => token.push_str("if"),
+            Token::ELSE => token.push_str("else"),
+            Token::TRUE => token.push_str("true"),
+            Token::FALSE => token.push_str("false"),
+            Token::RUN => token.push_str("run"),
+            Token::SPAWN => token.push_str("spawn"),
+            Token::SELECT => token.push_str("select"),
+            Token::ASYNC => token.push_str("async"),
+            Token::AWAIT => token.push_str("await"),
+            Token::IMPORT => token.push_str("import"),
+            Token::AS => token.push_str("as"),
+            Token::USE => token.push_str("use"),
+            Token::PUB => token.push_str("pub"),
+            
+            _ => token.push_str(format!("{:?}", self).as_str()),
+        }
+        write!(f, "{}", token)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Span {
+        Span { start, end }
+    }
+
+    /// Span covering from the start of `self` to the end of `other`.
+    pub fn to(&self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
+        }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.start)
+    }
+}
+
+/// A `//` comment, which the lexer skips but keeps for the formatter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    /// Text after the `//`, without trailing whitespace.
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Default, Debug)]
+pub struct Lexer {
+    input: String,
+    position: usize,
+    read_position: usize,
+    ch: char,
+    peek: char,
+    line_starts: Vec<usize>,
+    span: Span,
+    comments: Vec<Comment>,
+}
+
+impl Iterator for Lexer {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tok = self.next_token();
+        if tok == Token::EOF {
+            None
+        } else {
+            Some(tok)
+        }
+    }
+}
+
+impl Lexer {
+    pub fn new(input: String) -> Lexer {
+        let mut line_starts = vec![0];
+        for (i, c) in input.chars().enumerate() {
+            if c == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        let mut lex = Lexer {
+            input,
+            line_starts,
+            ..Default::default()
+        };
+        trace!("input length {}", lex.input.chars().count());
+        trace!("input {:?}", lex.input.chars());
+        trace!("new ");
+        lex.read_char();
+        lex
+    }
+
+    fn read_char(&mut self) {
+        self.ch = if self.read_position >= self.input.chars().count() {
+            '\0'
+        } else {
+            self.input.chars().nth(self.read_position).unwrap()
+        };
+
+        self.peek = if self.read_position + 1 >= self.input.chars().count() {
+            '\0'
+        } else {
+            self.input.chars().nth(self.read_position + 1).unwrap()
+        };
+
+        trace!("read_char: {} {}", self.ch, self.read_position);
+
+        self.position = self.read_position;
+        self.read_position += 1;
+    }
+
+    fn read_previous_char(&mut self) {
+        self.ch = if self.position == 0 {
+            '\0'
+        } else {
+            self.input.chars().nth(self.position - 1).unwrap()
+        };
+
+        self.peek = if self.position == 1 {
+            '\0'
+        } else {
+            self.input.chars().nth(self.position - 2).unwrap()
+        };
+
+        trace!("read_previous_char: {} {}", self.ch, self.position);
+
+        self.read_position = self.position;
+        self.position -= 1;
+    }
+
+    /// Span of the token most recently returned by `next_token`.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Comments skipped so far, in source order.
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    fn location(&self, index: usize) -> Position {
+        let line = self.line_starts.partition_point(|&start| start <= index);
+        Position {
+            line,
+            column: index - self.line_starts[line - 1] + 1,
+        }
+    }
+
+    pub fn next_token(&mut self) -> Token {
+        self.skip_whitespace();
+        let start = self.position;
+        let tok = match (self.ch, self.peek) {
+            ('!', '=') => {
+                self.read_char();
+                Token::NOT_EQUAL
+            },
+            ('!', _) => Token::BANG,
+            ('=', '=') => {
+                self.read_char();
+                Token::EQUAL
+            },
+            ('=', '>') => {
+                self.read_char();
+                Token::FAT_ARROW
+            },
+            ('=', _) => Token::ASSIGN,
+
+            ('>', '=') => {
+                self.read_char();
+                Token::GREATER_THAN_EQUAL
+            },
+            ('>', _) => Token::GREATER_THAN,
+
+            ('<', '=') => {
+                self.read_char();
+                Token::LESS_THAN_EQUAL
+            },
+            ('<',_) => Token::LESS_THAN,
+
+            ('.', '.') => {
+                self.read_char();
+                Token::RANGE
+            },
+
+            ('+', _) => Token::PLUS,
+            ('-', _) => Token::MINUS,
+            ('*', _) => Token::MULTIPLY,
+            ('/', _) => Token::DIVIDE,
+
+            (';', _) => Token::SEMICOLON,
+            (':', ':') => {
+                self.read_char();
+                Token::DOUBLE_COLON
+            },
+            (':', _) => Token::COLON,
+            (',', _) => Token::COMMA,
+
+            ('(', _) => Token::LPAREN,
+            (')', _) => Token::RPAREN,
+            ('{', _) => Token::LBRACE,
+            ('}', _) => Token::RBRACE,
+            ('[', _) => Token::LBRACKET,
+            (']', _) => Token::RBRACKET,
+
+            ('"', _) => self.read_string(),
+
+            ('\0', _) => Token::EOF,   
+            _ => if self.ch.is_alphabetic() || self.ch == '_' {
+                lookup_ident(self.read_identifier())
+            } else if self.ch.is_numeric() {
+                self.read_number()
+            } else {
+                Token::ILLEGAL(String::from(self.ch))
+            },
+        };
+        trace!("next_token ");
+        let end = if tok == Token::EOF { start } else { self.position + 1 };
+        self.span = Span::new(self.location(start), self.location(end));
+        self.read_char();
+        tok
+    }
+
+    fn read_identifier(&mut self) -> String {
+        trace!("read_identifier: {}", self.ch);
+        let start = self.position;
+        while self.ch.is_alphanumeric() || self.ch == '_' {
+            trace!("read_identifier loop ");
+            self.read_char();
+        }
+        let end = self.position;
+        self.read_previous_char();
+        self.slice(start, end)
+    }
+
+    /// The characters from `start` up to `end`, which like `position` count characters rather than bytes.
+    fn slice(&self, start: usize, end: usize) -> String {
+        self.input.chars().skip(start).take(end - start).collect()
+    }
+
+    fn read_number(&mut self) -> Token {
+        let start = self.position;
+        while self.ch.is_numeric() {
+            trace!("read_number loop ");
+            self.read_char();
+        }
+        // A `.` only continues the number when a digit follows, so `1..5` stays a range.
+        let is_float = self.ch == '.' && self.peek.is_numeric();
+        if is_float {
+            self.read_char();
+            while self.ch.is_numeric() {
+                trace!("read_number fraction loop ");
+                self.read_char();
+            }
+        }
+        let end = self.position;
+        self.read_previous_char();
+        let number = self.slice(start, end);
+        if is_float {
+            Token::FLOAT(number)
+        } else {
+            Token::INTEGER(number)
+        }
+    }
+
+    /// Reads a double quoted string, leaving `ch` on the closing quote.
+    fn read_string(&mut self) -> Token {
+        let mut string = String::new();
+        loop {
+            self.read_char();
+            match self.ch {
+                '"' => return Token::STRING(string),
+                '\0' => return Token::ILLEGAL(format!("\"{}", string)),
+                '\\' => {
+                    self.read_char();
+                    match self.ch {
+                        'n' => string.push('\n'),
+                        't' => string.push('\t'),
+                        'r' => string.push('\r'),
+                        '0' => string.push('\0'),
+                        '"' => string.push('"'),
+                        '\\' => string.push('\\'),
+                        '\0' => return Token::ILLEGAL(format!("\"{}", string)),
+                        other => {
+                            string.push('\\');
+                            string.push(other);
+                        },
+                    }
+                },
+                other => string.push(other),
+            }
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            while self.ch.is_whitespace() {
+                trace!("skip_whitespace loop ");
+                self.read_char();
+            }
+            if self.ch == '/' && self.peek == '/' {
+                self.read_comment();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn read_comment(&mut self) {
+        let start = self.position;
+        self.read_char();
+        self.read_char();
+        let mut text = String::new();
+        while self.ch != '\n' && self.ch != '\0' {
+            text.push(self.ch);
+            self.read_char();
+        }
+        trace!("read_comment: {}", text);
+        self.comments.push(Comment {
+            text: text.trim_end().to_string(),
+            span: Span::new(self.location(start), self.location(self.position)),
+        });
+    }
+}
+
+/// Quotes `value` as a string literal that `read_string` reads back unchanged.
+pub fn quote_string(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            '\0' => quoted.push_str("\\0"),
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn lookup_ident(ident: String) -> Token {
+    match ident.as_str() {
+        "fn" => Token::FUNCTION,
+        "let" => Token::LET,
+        "mut" => Token::MUT,
+        "return" => Token::RETURN,
+        "run" => Token::RUN,
+        "spawn" => Token::SPAWN,
+        "select" => Token::SELECT,
+        "async" => Token::ASYNC,
+        "await" => Token::AWAIT,
+        "import" => Token::IMPORT,
+        "as" => Token::AS,
+        "use" => Token::USE,
+        "pub" => Token::PUB,
+        "true" => Token::TRUE,
+        "false" => Token::FALSE,
+        "if" => Token::IF,
+        "else" => Token::ELSE,
+        _ => Token::IDENTIFIER(ident),
+    }
+}
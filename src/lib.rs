@@ -0,0 +1,40 @@
+//! Keynes is a small expression language. [`Engine`] is the entry point for
+//! embedding it in a Rust application; the remaining modules expose the
+//! individual stages of the interpreter for tooling such as the CLI.
+
+pub mod lexer;
+pub mod ast;
+pub mod ast2;
+pub mod parser;
+pub mod parser2;
+pub mod diagnostics;
+pub mod object;
+pub mod environment;
+pub mod evaluator;
+pub mod builtins;
+pub mod modules;
+pub mod scheduler;
+pub mod channel;
+pub mod race;
+pub mod convert;
+pub mod formatter;
+pub mod checker;
+pub mod optimizer;
+pub mod ir;
+pub mod backend;
+pub mod differential;
+pub mod generator;
+pub mod testing;
+mod engine;
+mod random;
+#[cfg(test)]
+mod test_support;
+
+// Lets the derive macros, which refer to `::keynes`, be used inside this crate.
+extern crate self as keynes;
+
+pub use convert::{FromKeynes, IntoKeynes};
+pub use engine::{Engine, EngineError, TestOutcome};
+pub use keynes_derive::{FromKeynes, IntoKeynes};
+pub use evaluator::RuntimeError;
+pub use object::Object;
@@ -0,0 +1,323 @@
+use std::{collections::HashMap, error::Error};
+
+use keynes::{
+    builtins,
+    checker::{check, Analysis, Definition, DefinitionKind},
+    formatter::format_source,
+    lexer::{Position, Span},
+};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, PublishDiagnostics},
+    request::{DocumentSymbolRequest, Formatting, GotoDefinition, HoverRequest},
+    DocumentFormattingParams, DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, HoverProviderCapability, Location, MarkupContent,
+    MarkupKind, OneOf, PublishDiagnosticsParams, Range, ServerCapabilities, SymbolKind, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextEdit, Url,
+};
+
+use log::*;
+
+pub type LspError = Box<dyn Error + Send + Sync>;
+
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    }
+}
+
+/// Serves the editor on the other end of stdin and stdout until it shuts the server down.
+pub fn stdio() -> Result<(), LspError> {
+    let (connection, io_threads) = Connection::stdio();
+    serve(&connection)?;
+    io_threads.join()?;
+    Ok(())
+}
+
+/// Runs the initialization handshake and then answers requests on `connection`
+/// until the client shuts the server down.
+pub fn serve(connection: &Connection) -> Result<(), LspError> {
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+    Server::default().run(connection)
+}
+
+/// An open file and the analysis of its current text.
+struct Document {
+    text: String,
+    analysis: Analysis,
+}
+
+impl Document {
+    fn new(text: String) -> Document {
+        let analysis = check(&text);
+        Document { text, analysis }
+    }
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<Url, Document>,
+}
+
+impl Server {
+    fn run(&mut self, connection: &Connection) -> Result<(), LspError> {
+        for message in &connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    connection.sender.send(self.request(request).into())?;
+                },
+                Message::Notification(notification) => {
+                    if let Some(diagnostics) = self.notification(notification) {
+                        let notification = Notification::new(
+                            <PublishDiagnostics as lsp_types::notification::Notification>::METHOD.to_string(),
+                            diagnostics,
+                        );
+                        connection.sender.send(notification.into())?;
+                    }
+                },
+                Message::Response(_) => {},
+            }
+        }
+        Ok(())
+    }
+
+    fn request(&mut self, request: Request) -> Response {
+        trace!("lsp request: {}", request.method);
+        match request.method.as_str() {
+            "textDocument/hover" => handle::<HoverRequest>(request, |params| self.hover(params)),
+            "textDocument/definition" => handle::<GotoDefinition>(request, |params| self.definition(params)),
+            "textDocument/documentSymbol" => handle::<DocumentSymbolRequest>(request, |params| self.symbols(params)),
+            "textDocument/formatting" => handle::<Formatting>(request, |params| self.format(params)),
+            method => Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported request: {}", method),
+            ),
+        }
+    }
+
+    /// Updates the open documents, returning the diagnostics to publish if one changed.
+    fn notification(&mut self, notification: Notification) -> Option<PublishDiagnosticsParams> {
+        trace!("lsp notification: {}", notification.method);
+        let uri = match notification.method.as_str() {
+            "textDocument/didOpen" => {
+                let params = extract::<DidOpenTextDocument>(notification)?;
+                let uri = params.text_document.uri;
+                self.documents.insert(uri.clone(), Document::new(params.text_document.text));
+                uri
+            },
+            "textDocument/didChange" => {
+                let params = extract::<DidChangeTextDocument>(notification)?;
+                // Changes are always the whole document since that is the sync kind we ask for.
+                let text = params.content_changes.into_iter().last()?.text;
+                let uri = params.text_document.uri;
+                self.documents.insert(uri.clone(), Document::new(text));
+                uri
+            },
+            "textDocument/didClose" => {
+                let params = extract::<DidCloseTextDocument>(notification)?;
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                return Some(PublishDiagnosticsParams::new(uri, Vec::new(), None));
+            },
+            _ => return None,
+        };
+
+        let document = &self.documents[&uri];
+        let diagnostics = document
+            .analysis
+            .diagnostics
+            .iter()
+            .map(|diagnostic| lsp_types::Diagnostic {
+                range: range(&document.text, diagnostic.span),
+                severity: Some(lsp_types::DiagnosticSeverity::ERROR),
+                source: Some("keynes".to_string()),
+                message: diagnostic.message.clone(),
+                ..Default::default()
+            })
+            .collect();
+        Some(PublishDiagnosticsParams::new(uri, diagnostics, None))
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let params = params.text_document_position_params;
+        let document = self.documents.get(&params.text_document.uri)?;
+        let position = from_lsp(&document.text, params.position);
+
+        let (value, span) = match document.analysis.definition_at(position) {
+            Some(definition) => {
+                let span = match document.analysis.reference_at(position) {
+                    Some(reference) => reference.span,
+                    None => definition.span,
+                };
+                (describe(definition), span)
+            },
+            None => {
+                let reference = document.analysis.reference_at(position)?;
+                builtins::get(&reference.name)?;
+                (format!("builtin {}", reference.name), reference.span)
+            },
+        };
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```keynes\n{}\n```", value),
+            }),
+            range: Some(range(&document.text, span)),
+        })
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let params = params.text_document_position_params;
+        let document = self.documents.get(&params.text_document.uri)?;
+        let definition = document.analysis.definition_at(from_lsp(&document.text, params.position))?;
+        Some(GotoDefinitionResponse::Scalar(Location::new(
+            params.text_document.uri,
+            range(&document.text, definition.span),
+        )))
+    }
+
+    fn symbols(&self, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        let definitions = document
+            .analysis
+            .definitions
+            .iter()
+            .filter(|definition| definition.kind != DefinitionKind::Parameter)
+            .collect::<Vec<_>>();
+        Some(DocumentSymbolResponse::Nested(symbols(&document.text, &definitions)))
+    }
+
+    /// Replaces the whole document with its formatted text, or does nothing if it does not parse.
+    fn format(&self, params: DocumentFormattingParams) -> Option<Vec<TextEdit>> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        let formatted = format_source(&document.text).ok()?;
+        if formatted == document.text {
+            return Some(Vec::new());
+        }
+        let end = to_lsp(&document.text, end_of(&document.text));
+        let whole = Range::new(lsp_types::Position::new(0, 0), end);
+        Some(vec![TextEdit::new(whole, formatted)])
+    }
+}
+
+/// Answers `request` with the result of `handler`, or with an error if its parameters are invalid.
+fn handle<R: lsp_types::request::Request>(request: Request, handler: impl FnOnce(R::Params) -> R::Result) -> Response {
+    let id = request.id.clone();
+    match request.extract::<R::Params>(R::METHOD) {
+        Ok((id, params)) => Response::new_ok(id, handler(params)),
+        Err(err) => Response::new_err(id, ErrorCode::InvalidParams as i32, err.to_string()),
+    }
+}
+
+fn extract<N: lsp_types::notification::Notification>(notification: Notification) -> Option<N::Params> {
+    match notification.extract::<N::Params>(N::METHOD) {
+        Ok(params) => Some(params),
+        Err(err) => {
+            warn!("ignoring notification: {}", err);
+            None
+        },
+    }
+}
+
+fn describe(definition: &Definition) -> String {
+    match definition.kind {
+        DefinitionKind::Variable | DefinitionKind::Function => format!("let {}: {}", definition.name, definition.ty),
+        DefinitionKind::Parameter => format!("(parameter) {}", definition.name),
+        DefinitionKind::Module => format!("import {}", definition.name),
+        DefinitionKind::Use => format!("use {}", definition.name),
+    }
+}
+
+/// Nests the symbols of `definitions`, which are in source order, by the statements they appear in.
+#[allow(deprecated)]
+fn symbols(text: &str, definitions: &[&Definition]) -> Vec<DocumentSymbol> {
+    let mut nested = Vec::new();
+    let mut i = 0;
+    while i < definitions.len() {
+        let definition = definitions[i];
+        let children = definitions[i + 1..]
+            .iter()
+            .take_while(|child| child.full_span != definition.full_span && encloses(definition.full_span, child.full_span))
+            .count();
+        let kind = match definition.kind {
+            DefinitionKind::Function => SymbolKind::FUNCTION,
+            DefinitionKind::Module => SymbolKind::MODULE,
+            _ => SymbolKind::VARIABLE,
+        };
+        nested.push(DocumentSymbol {
+            name: definition.name.clone(),
+            detail: Some(definition.ty.to_string()),
+            kind,
+            tags: None,
+            deprecated: None,
+            range: range(text, definition.full_span),
+            selection_range: range(text, definition.span),
+            children: Some(symbols(text, &definitions[i + 1..i + 1 + children])),
+        });
+        i += 1 + children;
+    }
+    nested
+}
+
+fn encloses(outer: Span, inner: Span) -> bool {
+    outer.start <= inner.start && inner.end <= outer.end
+}
+
+/// Position just past the last character of `text`.
+fn end_of(text: &str) -> Position {
+    let line = text.matches('\n').count() + 1;
+    let last = text.rsplit('\n').next().unwrap_or_default();
+    Position {
+        line,
+        column: last.chars().count() + 1,
+    }
+}
+
+/// Converts a position the client sent, counted from zero in UTF-16 code units,
+/// to a lexer position, counted from one in characters.
+fn from_lsp(text: &str, position: lsp_types::Position) -> Position {
+    let line = text.split('\n').nth(position.line as usize).unwrap_or_default();
+    let mut units = 0;
+    let mut column = 1;
+    for ch in line.chars() {
+        if units >= position.character as usize {
+            break;
+        }
+        units += ch.len_utf16();
+        column += 1;
+    }
+    Position {
+        line: position.line as usize + 1,
+        column,
+    }
+}
+
+fn to_lsp(text: &str, position: Position) -> lsp_types::Position {
+    let line = position.line.saturating_sub(1);
+    let units = text
+        .split('\n')
+        .nth(line)
+        .unwrap_or_default()
+        .chars()
+        .take(position.column.saturating_sub(1))
+        .map(char::len_utf16)
+        .sum::<usize>();
+    lsp_types::Position::new(line as u32, units as u32)
+}
+
+fn range(text: &str, span: Span) -> Range {
+    Range::new(to_lsp(text, span.start), to_lsp(text, span.end))
+}
+
+#[cfg(test)]
+#[path = "./lsp_tests.rs"]
+mod tests;
@@ -0,0 +1,244 @@
+use std::{thread, time::Duration};
+
+use lsp_server::RequestId;
+use lsp_types::{
+    notification::{Exit, Initialized},
+    request::Initialize,
+    DidChangeTextDocumentParams, DidOpenTextDocumentParams, FormattingOptions, InitializeParams, InitializedParams,
+    Position as LspPosition, TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem,
+    TextDocumentPositionParams, VersionedTextDocumentIdentifier,
+};
+use serde_json::Value;
+
+use super::*;
+
+/// The editor side of an in-process connection to a running server.
+struct Client {
+    connection: Connection,
+    server: Option<thread::JoinHandle<Result<(), LspError>>>,
+    next_id: i32,
+}
+
+impl Client {
+    fn start() -> Client {
+        let (server, connection) = Connection::memory();
+        let server = thread::spawn(move || serve(&server));
+        let mut client = Client {
+            connection,
+            server: Some(server),
+            next_id: 0,
+        };
+        client.request::<Initialize>(InitializeParams::default());
+        client.notify::<Initialized>(InitializedParams {});
+        client
+    }
+
+    fn send(&self, message: Message) {
+        self.connection.sender.send(message).unwrap();
+    }
+
+    fn receive(&self) -> Message {
+        self.connection.receiver.recv_timeout(Duration::from_secs(5)).expect("server answered")
+    }
+
+    fn request_value(&mut self, method: &str, params: Value) -> Response {
+        self.next_id += 1;
+        let id = RequestId::from(self.next_id);
+        self.send(Request::new(id.clone(), method.to_string(), params).into());
+        match self.receive() {
+            Message::Response(response) if response.id == id => response,
+            other => panic!("expected response to {}, got {:?}", method, other),
+        }
+    }
+
+    fn request<R: lsp_types::request::Request>(&mut self, params: R::Params) -> R::Result {
+        let response = self.request_value(R::METHOD, serde_json::to_value(params).unwrap());
+        serde_json::from_value(response.result.unwrap_or(Value::Null)).unwrap()
+    }
+
+    fn notify<N: lsp_types::notification::Notification>(&self, params: N::Params) {
+        self.send(Notification::new(N::METHOD.to_string(), params).into());
+    }
+
+    fn diagnostics(&self) -> PublishDiagnosticsParams {
+        match self.receive() {
+            Message::Notification(notification) => notification.extract("textDocument/publishDiagnostics").unwrap(),
+            other => panic!("expected diagnostics, got {:?}", other),
+        }
+    }
+
+    fn open(&self, text: &str) -> PublishDiagnosticsParams {
+        self.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri(), "keynes".to_string(), 1, text.to_string()),
+        });
+        self.diagnostics()
+    }
+
+    fn change(&self, text: &str) -> PublishDiagnosticsParams {
+        self.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri(), 2),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: text.to_string(),
+            }],
+        });
+        self.diagnostics()
+    }
+
+    fn hover(&mut self, line: u32, character: u32) -> Option<(String, Range)> {
+        let hover = self.request::<HoverRequest>(HoverParams {
+            text_document_position_params: at(line, character),
+            work_done_progress_params: Default::default(),
+        })?;
+        match hover.contents {
+            HoverContents::Markup(markup) => Some((markup.value, hover.range.unwrap())),
+            other => panic!("unexpected hover contents {:?}", other),
+        }
+    }
+
+    fn definition(&mut self, line: u32, character: u32) -> Option<Range> {
+        let response = self.request::<GotoDefinition>(GotoDefinitionParams {
+            text_document_position_params: at(line, character),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        })?;
+        match response {
+            GotoDefinitionResponse::Scalar(location) => Some(location.range),
+            other => panic!("unexpected definition {:?}", other),
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            return;
+        }
+        self.request_value("shutdown", Value::Null);
+        self.notify::<Exit>(());
+        self.server.take().unwrap().join().unwrap().unwrap();
+    }
+}
+
+fn uri() -> Url {
+    Url::parse("file:///test.ks").unwrap()
+}
+
+fn at(line: u32, character: u32) -> TextDocumentPositionParams {
+    TextDocumentPositionParams::new(TextDocumentIdentifier::new(uri()), LspPosition::new(line, character))
+}
+
+fn lsp_range(start: (u32, u32), end: (u32, u32)) -> Range {
+    Range::new(LspPosition::new(start.0, start.1), LspPosition::new(end.0, end.1))
+}
+
+#[test]
+fn test_diagnostics_on_open_and_change() {
+    let client = Client::start();
+
+    let published = client.open("let x = 1;\nx + y");
+    assert_eq!(published.uri, uri());
+    assert_eq!(published.diagnostics.len(), 1);
+    assert_eq!(published.diagnostics[0].message, "identifier not found: y");
+    assert_eq!(published.diagnostics[0].range, lsp_range((1, 4), (1, 5)));
+
+    let published = client.change("let x = 1;\nlet y = 2;\nx + y");
+    assert_eq!(published.diagnostics, Vec::new());
+
+    let published = client.change("let = 1;");
+    assert_eq!(published.diagnostics[0].message, "expected next token to be an identifier, got ASSIGN instead");
+}
+
+#[test]
+fn test_hover() {
+    let mut client = Client::start();
+    client.open("let add = fn(a, b) { a + b };\nlet n = add(1, 2) * 2;\nlen(\"é\") + n");
+
+    let (value, range) = client.hover(1, 9).unwrap();
+    assert_eq!(value, "```keynes\nlet add: fn(a, b)\n```");
+    assert_eq!(range, lsp_range((1, 8), (1, 11)));
+
+    assert_eq!(client.hover(0, 21).unwrap().0, "```keynes\n(parameter) a\n```");
+    assert_eq!(client.hover(2, 1).unwrap().0, "```keynes\nbuiltin len\n```");
+    assert_eq!(client.hover(2, 11).unwrap().0, "```keynes\nlet n: unknown\n```");
+    assert_eq!(client.hover(0, 10), None);
+}
+
+#[test]
+fn test_goto_definition() {
+    let mut client = Client::start();
+    client.open("let x = 1;\nlet f = fn(x) {\n    x + 1\n};\nf(x)");
+
+    assert_eq!(client.definition(2, 4), Some(lsp_range((1, 11), (1, 12))));
+    assert_eq!(client.definition(4, 2), Some(lsp_range((0, 4), (0, 5))));
+    assert_eq!(client.definition(4, 0), Some(lsp_range((1, 4), (1, 5))));
+    assert_eq!(client.definition(4, 1), Some(lsp_range((1, 4), (1, 5))));
+}
+
+#[test]
+fn test_document_symbols() {
+    let mut client = Client::start();
+    client.open("import \"m.ks\" as m;\nlet f = fn(a) {\n    let b = a;\n    b\n};\nlet c = 1;");
+
+    let symbols = match client.request::<DocumentSymbolRequest>(DocumentSymbolParams {
+        text_document: TextDocumentIdentifier::new(uri()),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    }) {
+        Some(DocumentSymbolResponse::Nested(symbols)) => symbols,
+        other => panic!("unexpected symbols {:?}", other),
+    };
+    let outline = symbols
+        .iter()
+        .map(|symbol| {
+            let children = symbol.children.iter().flatten().map(|child| child.name.clone()).collect::<Vec<_>>();
+            (symbol.name.clone(), symbol.kind, children)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        outline,
+        vec![
+            ("m".to_string(), SymbolKind::MODULE, vec![]),
+            ("f".to_string(), SymbolKind::FUNCTION, vec!["b".to_string()]),
+            ("c".to_string(), SymbolKind::VARIABLE, vec![]),
+        ]
+    );
+    assert_eq!(symbols[1].range, lsp_range((1, 0), (4, 2)));
+    assert_eq!(symbols[1].selection_range, lsp_range((1, 4), (1, 5)));
+}
+
+#[test]
+fn test_formatting() {
+    let mut client = Client::start();
+    client.open("let x=1;\nx+1");
+    let params = DocumentFormattingParams {
+        text_document: TextDocumentIdentifier::new(uri()),
+        options: FormattingOptions::default(),
+        work_done_progress_params: Default::default(),
+    };
+
+    let edits = client.request::<Formatting>(params.clone()).unwrap();
+    assert_eq!(edits, vec![TextEdit::new(lsp_range((0, 0), (1, 3)), "let x = 1;\nx + 1;\n".to_string())]);
+
+    client.change("let x = 1;\nx + 1;\n");
+    assert_eq!(client.request::<Formatting>(params.clone()), Some(Vec::new()));
+
+    client.change("let x = ;");
+    assert_eq!(client.request::<Formatting>(params), None);
+}
+
+#[test]
+fn test_unsupported_request() {
+    let mut client = Client::start();
+    let response = client.request_value("textDocument/completion", serde_json::json!({}));
+    assert_eq!(response.error.unwrap().code, ErrorCode::MethodNotFound as i32);
+}
+
+#[test]
+fn test_positions_count_utf16_units() {
+    let text = "\"😀\" + x";
+    let position = from_lsp(text, LspPosition::new(0, 6));
+    assert_eq!(position, Position { line: 1, column: 6 });
+    assert_eq!(to_lsp(text, position), LspPosition::new(0, 6));
+}
@@ -1,13 +1,10 @@
 
 use clap::{command, arg};
 use dotenv;
-use parser2::program::parse_program;
+use keynes::{ast::program::Program, backend, diagnostics::Diagnostic, formatter::format_source, ir::{self, passes::{Pass, PassManager}}, lexer::{self, Span}, optimizer::OptLevel, parser, parser2::program::parse_program, scheduler::Mode, testing, Engine, Object};
 
-mod lexer;
-mod ast;
-mod ast2;
-mod parser;
-mod parser2;
+mod lsp;
+mod repl;
 
 
 fn main() {
@@ -18,34 +15,134 @@ fn main() {
             command!("lexer").arg(arg!(<input>)),
             command!("parser").arg(arg!(<input>)),
             command!("parser2").arg(arg!(<input>)),
-            command!("run").arg(arg!(<input>)),
+            command!("run")
+                .arg(arg!(<file>))
+                .arg(arg!(--ir "Run the program from its IR, optimized by the passes of the optimization level or `--passes`"))
+                .arg(passes())
+                .arg(opt_level())
+                .args(scheduling()),
+            command!("repl"),
+            command!("lsp").about("Run the language server over stdio"),
+            command!("fmt")
+                .about("Format files in place")
+                .arg(arg!(--check "Only report files that are not formatted, failing if there are any"))
+                .arg(arg!(<files> ... "Files to format")),
+            command!("test")
+                .about("Run the test blocks of Keynes files")
+                .arg(arg!([path] "File or directory to search for tests").default_value("."))
+                .arg(arg!([filter] "Only run tests whose name contains this"))
+                .arg(arg!(-j --jobs <N> "Number of files to test in parallel").value_parser(clap::value_parser!(usize)).default_value("1"))
+                .arg(opt_level()),
+            command!("build")
+                .about("Compile a Keynes program ahead of time")
+                .arg(arg!(<file>))
+                .arg(arg!(--target <target> "Language to compile to").value_parser(["c", "wat", "x86-64"]).default_value("c"))
+                .arg(arg!(-o --output <path> "Where to write the result"))
+                .arg(
+                    arg!(--emit [what] "Write the generated C or assembly, or with `--emit=ir` the IR, instead of compiling it")
+                        .value_parser(["source", "ir"])
+                        .require_equals(true)
+                        .default_missing_value("source"),
+                )
+                .arg(passes())
+                .arg(opt_level()),
         ]).get_matches();
 
     match matches.subcommand() {
         Some(("lexer", sub_m)) => if let Some(input) = sub_m.get_one::<String>("input") {
             lexer_single(input);
-        } else {
-            lexer_repl();
         },
         Some(("parser", sub_m)) => if let Some(input) = sub_m.get_one::<String>("input") {
             parser_single(input);
-        } else {
-            parser_repl();
         },
          Some(("parser2", sub_m)) => if let Some(input) = sub_m.get_one::<String>("input") {
             parser2_single(input);
-        } else {
-            parser2_repl();
         },
-        Some(("run", sub_m)) => if let Some(input) = sub_m.get_one::<String>("input") {
-            println!("Running {}", input);
+        Some(("run", sub_m)) => if let Some(file) = sub_m.get_one::<String>("file") {
+            let passes = sub_m.get_flag("ir").then(|| passes_of(sub_m));
+            run(file, opt_level_of(sub_m), passes, scheduling_of(sub_m), sub_m.get_flag("race"));
         } else {
             println!("No input file specified");
         },
+        Some(("fmt", sub_m)) => {
+            let files = sub_m.get_many::<String>("files").unwrap_or_default().collect::<Vec<_>>();
+            if !fmt(&files, sub_m.get_flag("check")) {
+                std::process::exit(1);
+            }
+        },
+        Some(("test", sub_m)) => {
+            let path = sub_m.get_one::<String>("path").unwrap();
+            let filter = sub_m.get_one::<String>("filter").map(String::as_str).unwrap_or_default();
+            if !test(path, filter, *sub_m.get_one::<usize>("jobs").unwrap(), opt_level_of(sub_m)) {
+                std::process::exit(1);
+            }
+        },
+        Some(("build", sub_m)) => {
+            let file = sub_m.get_one::<String>("file").unwrap();
+            let output = sub_m.get_one::<String>("output").map(String::as_str);
+            let target = sub_m.get_one::<String>("target").unwrap();
+            let level = opt_level_of(sub_m);
+            if !build(file, target, output, sub_m.get_one::<String>("emit").map(String::as_str), level, &passes_of(sub_m)) {
+                std::process::exit(1);
+            }
+        },
+        Some(("repl", _)) => if let Err(err) = repl::Repl::new().run() {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        },
+        Some(("lsp", _)) => if let Err(err) = lsp::stdio() {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        },
         _ => println!("No subcommand was used"),
     }
 }
 
+/// `-O0` runs programs as parsed, `-O1` (the default) folds constants and removes dead branches first.
+/// It also picks the IR passes for `keynes run --ir` and `keynes build --emit=ir`.
+fn opt_level() -> clap::Arg {
+    arg!(-O <level> "Optimization level").value_parser(["0", "1"]).default_value("1")
+}
+
+fn opt_level_of(matches: &clap::ArgMatches) -> OptLevel {
+    OptLevel::try_from(matches.get_one::<String>("level").unwrap().as_str()).unwrap()
+}
+
+fn passes() -> clap::Arg {
+    arg!(--passes <passes> "IR passes to run, in order, instead of those of the optimization level")
+        .value_parser(Pass::ALL.map(Pass::name))
+        .value_delimiter(',')
+}
+
+/// The IR passes named by `--passes`, or those of the optimization level.
+fn passes_of(matches: &clap::ArgMatches) -> PassManager {
+    match matches.get_many::<String>("passes") {
+        Some(names) => PassManager::new(names.map(|name| Pass::try_from(name.as_str()).unwrap()).collect()),
+        None => PassManager::for_level(opt_level_of(matches)),
+    }
+}
+
+/// Spawned tasks run on `--workers` threads, all cores by default, or with `--deterministic-seed`
+/// on one thread in an order the seed decides, so that a run can be repeated. With `--race`
+/// data races on `let mut` bindings are errors.
+fn scheduling() -> [clap::Arg; 3] {
+    [
+        arg!(--workers <N> "Number of threads running tasks").value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        arg!(--"deterministic-seed" <seed> "Run tasks one at a time, in an order decided by the seed")
+            .value_parser(clap::value_parser!(u64))
+            .conflicts_with("workers"),
+        arg!(--race "Report data races on `let mut` bindings between tasks"),
+    ]
+}
+
+fn scheduling_of(matches: &clap::ArgMatches) -> Mode {
+    match (matches.get_one::<u64>("deterministic-seed"), matches.get_one::<usize>("workers")) {
+        (Some(seed), _) => Mode::Deterministic(*seed),
+        (None, Some(workers)) => Mode::Parallel(*workers),
+        (None, None) => Mode::default(),
+    }
+}
+
 fn lexer_single(input: &str) {
     let lexer = lexer::Lexer::new(input.to_string());
     for tok in lexer {
@@ -53,22 +150,6 @@ fn lexer_single(input: &str) {
     }
 }
 
-fn lexer_repl() {
-    println!("Keynes LEXER REPL");
-    println!("Type in a line of code and press enter to tokenize it.");
-    println!("Press Ctrl-C to exit.");
-    loop {
-        print!(">> ");
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).unwrap();
-        let lexer = lexer::Lexer::new(input);
-        for tok in lexer {
-            println!("{:?}", tok);
-        }
-        println!("====================")
-    }
-}
-
 fn parser_single(input: &str) {
     let mut lexer = lexer::Lexer::new(input.to_string());
     let mut parser = parser::Parser::new(&mut lexer);
@@ -76,43 +157,134 @@ fn parser_single(input: &str) {
     println!("{}", program);
 }
 
-fn parser_repl() {
-    println!("Keynes PARSER REPL");
-    println!("Type in a line of code and press enter to parse it.");
-    println!("Press Ctrl-C to exit.");
-    loop {
-        print!(">> ");
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).unwrap();
-        let mut lexer = lexer::Lexer::new(input);
-        let mut parser = parser::Parser::new(&mut lexer);
-        let program = parser.parse_program();
-        println!("{}", program);
-        println!("====================")
-    }
-}
-
 fn parser2_single(input: &str) {
     let result = parse_program(input);
     match result {
-        Ok((_,program)) => println!("{:?}", program),
-        Err(err) => println!("{}", err),
+        Ok(program) => println!("{:?}", program),
+        Err(errors) => errors.iter().for_each(|error| eprint!("{}", error.render("<input>", input))),
     }
 }
 
-fn parser2_repl() {
-    println!("Keynes PARSER2 REPL");
-    println!("Type in a line of code and press enter to parse it.");
-    println!("Press Ctrl-C to exit.");
-    loop {
-        print!(">> ");
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).unwrap();
-        let result = parse_program(input.as_str());
-        match result {
-            Ok((_,program)) => println!("{:?}", program),
-            Err(err) => println!("{}", err),
+/// Runs `file`, from its IR optimized by `passes` if they are given.
+fn run(file: &str, level: OptLevel, passes: Option<PassManager>, mode: Mode, race: bool) {
+    let mut engine = Engine::new();
+    engine.set_opt_level(level);
+    engine.set_ir_passes(passes);
+    engine.set_scheduling(mode);
+    engine.set_race_detection(race);
+    match engine.eval_file(file) {
+        Ok(Object::Null) => {},
+        Ok(result) => println!("{}", result),
+        Err(err) => {
+            eprint!("{}", engine.render_error(&err));
+            std::process::exit(1);
+        },
+    }
+}
+
+/// Formats `files`, or with `check` only lists the ones that would change. Returns whether all went well.
+fn fmt(files: &[&String], check: bool) -> bool {
+    let mut ok = true;
+    for file in files {
+        let source = match std::fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("error: could not read {}: {}", file, err);
+                ok = false;
+                continue;
+            },
+        };
+        let formatted = match format_source(&source) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                for error in &errors {
+                    eprint!("{}", error.render(file, &source));
+                }
+                ok = false;
+                continue;
+            },
+        };
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{} is not formatted", file);
+            ok = false;
+        } else if let Err(err) = std::fs::write(file, formatted) {
+            eprintln!("error: could not write {}: {}", file, err);
+            ok = false;
         }
-        println!("====================")
     }
-}
\ No newline at end of file
+    ok
+}
+
+/// Runs the tests found under `path` and prints a report. Returns whether they all passed.
+fn test(path: &str, filter: &str, jobs: usize, level: OptLevel) -> bool {
+    let files = match testing::discover(std::path::Path::new(path)) {
+        Ok(files) => files,
+        Err(err) => {
+            eprintln!("error: {}: {}", path, err);
+            return false;
+        },
+    };
+    let (report, ok) = testing::render(&testing::run(&files, filter, jobs, level));
+    print!("{}", report);
+    ok
+}
+
+/// Compiles `file` for `target` and writes the result to `output`, next to `file` by default.
+/// C and assembly are compiled on to an executable unless `emit` is set, and `emit` set to
+/// `ir` writes the IR, optimized by `passes`, instead of compiling for `target`. Returns
+/// whether it succeeded.
+fn build(file: &str, target: &str, output: Option<&str>, emit: Option<&str>, level: OptLevel, passes: &PassManager) -> bool {
+    let source = match std::fs::read_to_string(file) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("error: could not read {}: {}", file, err);
+            return false;
+        },
+    };
+    let compile = |program: Program| match (emit, target) {
+        (Some("ir"), _) => emit_ir(&program, passes),
+        (_, "wat") => backend::wat::compile(file, &program),
+        (_, "x86-64") => backend::x86_64::compile(file, &program),
+        _ => backend::c::compile(file, &program),
+    };
+    let compiled = match backend::front_end(&source, level).and_then(compile) {
+        Ok(compiled) => compiled,
+        Err(diagnostics) => {
+            for diagnostic in &diagnostics {
+                eprint!("{}", diagnostic.render(file, &source));
+            }
+            return false;
+        },
+    };
+    let executable = target != "wat" && emit.is_none();
+    let extension = match (emit, target) {
+        _ if executable => "",
+        (Some("ir"), _) => "ir",
+        (_, "x86-64") => "s",
+        (_, target) => target,
+    };
+    let default = std::path::Path::new(file).with_extension(extension);
+    let output = output.map(std::path::PathBuf::from).unwrap_or(default);
+    let result = match (executable, target) {
+        (true, "x86-64") => backend::x86_64::build(&compiled, &output),
+        (true, _) => backend::c::build(&compiled, &output),
+        (false, _) => std::fs::write(&output, compiled).map_err(|err| format!("could not write {}: {}", output.display(), err)),
+    };
+    if let Err(err) = &result {
+        eprintln!("error: {}", err);
+    }
+    result.is_ok()
+}
+
+/// The IR of `program` after `passes`, which must pass the verifier.
+fn emit_ir(program: &Program, passes: &PassManager) -> Result<String, Vec<Diagnostic>> {
+    let mut module = ir::lower(program);
+    passes.run(&mut module);
+    match ir::verify(&module) {
+        Ok(()) => Ok(module.to_string()),
+        Err(errors) => Err(errors.into_iter().map(|error| Diagnostic::new(format!("invalid IR: {}", error), Span::default())).collect()),
+    }
+}
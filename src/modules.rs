@@ -0,0 +1,188 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::{Arc, Mutex}};
+
+use crate::{
+    ast::{program::Program, statements::LetStatement},
+    environment::Env,
+    evaluator::RuntimeError,
+    lexer::{Lexer, Token},
+    object::Object,
+    optimizer::{optimize, OptLevel},
+    parser::Parser,
+    scheduler::Waker,
+};
+
+use log::*;
+
+pub type Loader = Arc<Mutex<ModuleLoader>>;
+
+/// An evaluated module: the `pub` bindings of a file's top level.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub name: Arc<str>,
+    pub exports: Arc<HashMap<String, Object>>,
+}
+
+impl Module {
+    /// Collects the values of the top level `pub let` bindings of `program`.
+    pub fn from_program(name: Arc<str>, program: &Program, env: &Env) -> Module {
+        let mut exports = HashMap::new();
+        for statement in &program.statements {
+            let let_statement = match statement.as_any().downcast_ref::<LetStatement>() {
+                Some(let_statement) if let_statement.public => let_statement,
+                _ => continue,
+            };
+            if let Token::IDENTIFIER(name) = &let_statement.name.token {
+                if let Some(value) = env.lock().unwrap().get(name) {
+                    exports.insert(name.clone(), value);
+                }
+            }
+        }
+        Module {
+            name,
+            exports: Arc::new(exports),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Result<Object, RuntimeError> {
+        match self.exports.get(name) {
+            Some(value) => Ok(value.clone()),
+            None => Err(RuntimeError::new(format!("module {} has no public binding `{}`", self.name, name))),
+        }
+    }
+}
+
+impl PartialEq for Module {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.exports, &other.exports)
+    }
+}
+
+/// The files whose evaluation an import is part of, outermost first, as canonical paths
+/// and the names they were imported by.
+pub type ImportChain = Arc<Vec<(PathBuf, Arc<str>)>>;
+
+/// What [`ModuleLoader::enter`] found a module to be in.
+pub enum Entered {
+    /// Evaluated already.
+    Loaded(Module),
+    /// Parsed, and now to be evaluated by the importer, which must then
+    /// [`leave`](ModuleLoader::leave) it.
+    Started(Arc<Program>),
+}
+
+/// Resolves, parses and caches modules, and tracks the modules being evaluated so that
+/// other tasks importing them wait for them.
+#[derive(Debug, Default)]
+pub struct ModuleLoader {
+    programs: HashMap<PathBuf, Arc<Program>>,
+    modules: HashMap<PathBuf, Module>,
+    sources: HashMap<Arc<str>, String>,
+    /// Modules being evaluated, with the tasks waiting for them.
+    loading: HashMap<PathBuf, Vec<Waker>>,
+    level: OptLevel,
+}
+
+impl ModuleLoader {
+    pub fn new() -> Loader {
+        Arc::new(Mutex::new(ModuleLoader::default()))
+    }
+
+    /// Resolves an import path relative to the directory of the importing file.
+    pub fn resolve(importer: &str, path: &str) -> PathBuf {
+        let directory = Path::new(importer).parent().unwrap_or(Path::new(""));
+        directory.join(path)
+    }
+
+    /// Sets how modules parsed from now on are optimized.
+    pub fn set_opt_level(&mut self, level: OptLevel) {
+        self.level = level;
+    }
+
+    pub fn canonicalize(path: &Path) -> Result<PathBuf, RuntimeError> {
+        path.canonicalize()
+            .map_err(|err| RuntimeError::new(format!("could not read module {}: {}", path.display(), err)))
+    }
+
+    /// The import chain of the entry file, so that imports of it are reported as cycles.
+    /// It is empty if `file` is not a file, such as the source given to `Engine::eval`.
+    pub fn main_chain(file: &str) -> ImportChain {
+        match Path::new(file).canonicalize() {
+            Ok(key) => Arc::new(vec![(key, file.into())]),
+            Err(_) => ImportChain::default(),
+        }
+    }
+
+    pub fn source(&self, file: &str) -> Option<&str> {
+        self.sources.get(file).map(|source| source.as_str())
+    }
+
+    /// Remembers the source of a file that was evaluated outside of an import, for error rendering.
+    pub fn add_source(&mut self, file: Arc<str>, source: String) {
+        self.sources.insert(file, source);
+    }
+
+    /// Imports the module at `key` as `name`, from a file whose evaluation is part of
+    /// `chain`. Gives `None` while another task evaluates the module, after arranging for
+    /// `waker` to be woken once it is done, as for [`block_until`](crate::scheduler::block_until).
+    pub fn enter(&mut self, key: &Path, name: &Arc<str>, chain: &[(PathBuf, Arc<str>)], waker: &Waker) -> Option<Result<Entered, RuntimeError>> {
+        if let Some(module) = self.modules.get(key) {
+            return Some(Ok(Entered::Loaded(module.clone())));
+        }
+        if let Some(position) = chain.iter().position(|(loading, _)| loading == key) {
+            let mut chain = chain[position..].iter().map(|(_, name)| name.to_string()).collect::<Vec<_>>();
+            chain.push(name.to_string());
+            return Some(Err(RuntimeError::new(format!("import cycle detected: {}", chain.join(" -> ")))));
+        }
+        if let Some(waiting) = self.loading.get_mut(key) {
+            waiting.push(waker.clone());
+            return None;
+        }
+
+        let program = self.parse(key, name.clone());
+        if program.is_ok() {
+            self.loading.insert(key.to_path_buf(), Vec::new());
+        }
+        Some(program.map(Entered::Started))
+    }
+
+    /// Finishes evaluating the module at `key`, caching it if evaluation succeeded. The tasks
+    /// waiting for it otherwise evaluate it themselves.
+    pub fn leave(&mut self, key: &Path, module: Option<Module>) {
+        if let Some(module) = module {
+            self.modules.insert(key.to_path_buf(), module);
+        }
+        for waker in self.loading.remove(key).unwrap_or_default() {
+            waker.wake();
+        }
+    }
+
+    fn parse(&mut self, key: &Path, name: Arc<str>) -> Result<Arc<Program>, RuntimeError> {
+        if let Some(program) = self.programs.get(key) {
+            return Ok(program.clone());
+        }
+
+        trace!("parse module: {}", name);
+        let source = std::fs::read_to_string(key)
+            .map_err(|err| RuntimeError::new(format!("could not read module {}: {}", name, err)))?;
+        let mut lexer = Lexer::new(source.clone());
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program();
+        if !parser.errors.is_empty() {
+            let diagnostics = parser
+                .errors
+                .iter()
+                .map(|error| error.render(&name, &source))
+                .collect::<String>();
+            return Err(RuntimeError::new(format!("could not parse module {}:\n{}", name, diagnostics.trim_end())));
+        }
+
+        let program = Arc::new(optimize(program, self.level));
+        self.sources.insert(name, source);
+        self.programs.insert(key.to_path_buf(), program.clone());
+        Ok(program)
+    }
+}
+
+#[cfg(test)]
+#[path = "./modules_tests.rs"]
+mod tests;
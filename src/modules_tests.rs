@@ -0,0 +1,132 @@
+use std::path::PathBuf;
+
+use crate::{environment::Environment, evaluator::Evaluator, test_support};
+
+use super::*;
+
+fn write_files(files: &[(&str, &str)]) -> PathBuf {
+    test_support::write_files("keynes-modules", files)
+}
+
+fn run_file(path: PathBuf) -> Result<Object, RuntimeError> {
+    let file = path.to_string_lossy().to_string();
+    let input = std::fs::read_to_string(&path).unwrap();
+    let mut lexer = Lexer::new(input);
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+    assert_eq!(parser.errors, vec![]);
+    Evaluator::new(&file).eval_program(&program, &Environment::new())
+}
+
+#[test]
+fn test_import_exposes_public_bindings_through_path() {
+    let dir = write_files(&[
+        ("main.ks", "import \"lib/math.ks\" as math;\nmath::double(math::base);"),
+        ("lib/math.ks", "pub let base = 21;\npub let double = fn(x) { x * 2 };"),
+    ]);
+    assert_eq!(run_file(dir.join("main.ks")), Ok(Object::from(42)));
+}
+
+#[test]
+fn test_use_brings_names_into_scope() {
+    let dir = write_files(&[
+        ("main.ks", "import \"math.ks\" as m;\nuse m::{double, triple};\nuse m::one;\ndouble(triple(one))"),
+        ("math.ks", "pub let one = 1;\npub let double = fn(x) { x * 2 };\npub let triple = fn(x) { x * 3 };"),
+    ]);
+    assert_eq!(run_file(dir.join("main.ks")), Ok(Object::from(6)));
+}
+
+#[test]
+fn test_imports_resolve_relative_to_importing_file() {
+    let dir = write_files(&[
+        ("main.ks", "import \"lib/outer.ks\" as outer;\nouter::value"),
+        ("lib/outer.ks", "import \"inner/inner.ks\" as inner;\npub let value = inner::value + 1;"),
+        ("lib/inner/inner.ks", "pub let value = 41;"),
+    ]);
+    assert_eq!(run_file(dir.join("main.ks")), Ok(Object::from(42)));
+}
+
+#[test]
+fn test_private_bindings_are_not_exposed() {
+    let dir = write_files(&[
+        ("main.ks", "import \"lib.ks\" as lib;\nlib::secret"),
+        ("lib.ks", "let secret = 1;\npub let get = fn() { secret };"),
+    ]);
+    let err = run_file(dir.join("main.ks")).unwrap_err();
+    assert!(err.message.starts_with("module "), "{}", err.message);
+    assert!(err.message.ends_with("lib.ks has no public binding `secret`"), "{}", err.message);
+
+    let dir = write_files(&[
+        ("main.ks", "import \"lib.ks\" as lib;\nlib::get()"),
+        ("lib.ks", "let secret = 1;\npub let get = fn() { secret };"),
+    ]);
+    assert_eq!(run_file(dir.join("main.ks")), Ok(Object::from(1)));
+}
+
+#[test]
+fn test_module_is_evaluated_once() {
+    let dir = write_files(&[
+        ("main.ks", "import \"lib.ks\" as a;\nimport \"./lib.ks\" as b;\n[a, b]"),
+        ("lib.ks", "pub let value = 1;"),
+    ]);
+    match run_file(dir.join("main.ks")) {
+        Ok(Object::Array(modules)) => assert_eq!(modules[0], modules[1]),
+        other => panic!("expected two modules, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_import_cycle_is_reported_with_chain() {
+    let dir = write_files(&[
+        ("a.ks", "import \"b.ks\" as b;"),
+        ("b.ks", "import \"c.ks\" as c;"),
+        ("c.ks", "import \"b.ks\" as b;"),
+    ]);
+    let err = run_file(dir.join("a.ks")).unwrap_err();
+    let d = dir.to_string_lossy();
+    assert_eq!(
+        err.message,
+        format!("import cycle detected: {d}/b.ks -> {d}/c.ks -> {d}/b.ks")
+    );
+    let frames = err.trace.iter().map(|frame| frame.function.as_str()).collect::<Vec<_>>();
+    assert_eq!(frames, vec!["<module>", "<module>", "<main>"]);
+}
+
+#[test]
+fn test_import_of_entry_file_is_a_cycle() {
+    let dir = write_files(&[
+        ("main.ks", "import \"lib.ks\" as lib;"),
+        ("lib.ks", "import \"main.ks\" as main;"),
+    ]);
+    let err = run_file(dir.join("main.ks")).unwrap_err();
+    assert!(err.message.starts_with("import cycle detected: "), "{}", err.message);
+    assert!(err.message.ends_with("main.ks"), "{}", err.message);
+}
+
+#[test]
+fn test_errors_in_modules_have_module_locations() {
+    let dir = write_files(&[
+        ("main.ks", "import \"lib.ks\" as lib;\nlib::divide(1, 0);"),
+        ("lib.ks", "pub let divide = fn(a, b) {\n  a / b\n};"),
+    ]);
+    let err = run_file(dir.join("main.ks")).unwrap_err();
+    assert_eq!(err.message, "division by zero");
+    assert!(err.trace[0].file.ends_with("lib.ks"));
+    assert_eq!(err.trace[0].span.unwrap().start, crate::lexer::Position { line: 2, column: 3 });
+    assert!(err.trace[1].file.ends_with("main.ks"));
+    assert_eq!(err.trace[1].span.unwrap().start, crate::lexer::Position { line: 2, column: 1 });
+}
+
+#[test]
+fn test_missing_and_unparsable_modules() {
+    let dir = write_files(&[("main.ks", "import \"missing.ks\" as m;")]);
+    let err = run_file(dir.join("main.ks")).unwrap_err();
+    assert!(err.message.starts_with("could not read module "), "{}", err.message);
+
+    let dir = write_files(&[
+        ("main.ks", "import \"broken.ks\" as m;"),
+        ("broken.ks", "let = 5;"),
+    ]);
+    let err = run_file(dir.join("main.ks")).unwrap_err();
+    assert!(err.message.starts_with("could not parse module "), "{}", err.message);
+}
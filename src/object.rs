@@ -0,0 +1,318 @@
+use std::{collections::BTreeMap, fmt::{Debug, Display}, sync::Arc};
+
+use crate::{ast::{expressions::IdentifierLiteral, statements::BlockStatement}, channel::{Receiver, Sender}, environment::Env, evaluator::RuntimeError, ir::interpret::Closure, lexer::Span, modules::Module, scheduler::{Future, Task}};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    Null,
+    Integer(Integer),
+    Float(Float),
+    Boolean(bool),
+    String(String),
+    Array(Vec<Object>),
+    Hash(BTreeMap<HashKey, Object>),
+    Function(Function),
+    /// A function of a program run from its IR.
+    Closure(Closure),
+    Builtin(Builtin),
+    Module(Module),
+    Task(Task),
+    Future(Future),
+    Sender(Sender),
+    Receiver(Receiver),
+    ReturnValue(Box<Object>),
+}
+
+impl Object {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Null => "NULL",
+            Object::Integer(_) => "INTEGER",
+            Object::Float(_) => "FLOAT",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::String(_) => "STRING",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::Function(_) | Object::Closure(_) => "FUNCTION",
+            Object::Builtin(_) => "BUILTIN",
+            Object::Module(_) => "MODULE",
+            Object::Task(_) => "TASK",
+            Object::Future(_) => "FUTURE",
+            Object::Sender(_) => "SENDER",
+            Object::Receiver(_) => "RECEIVER",
+            Object::ReturnValue(value) => value.type_name(),
+        }
+    }
+
+    /// Type name including the width for numbers, e.g. `i32` or `STRING`.
+    pub fn describe_type(&self) -> String {
+        match self {
+            Object::Integer(value) => value.width().to_string(),
+            Object::Float(value) => value.width().to_string(),
+            other => other.type_name().to_string(),
+        }
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Object::Null | Object::Boolean(false))
+    }
+
+    /// Like `Display`, but strings are quoted so they can be told apart inside collections.
+    pub fn inspect(&self) -> String {
+        match self {
+            Object::String(value) => format!("{:?}", value),
+            other => other.to_string(),
+        }
+    }
+}
+
+impl From<i64> for Object {
+    fn from(value: i64) -> Self {
+        Object::Integer(Integer::I64(value))
+    }
+}
+
+impl Display for Object {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Object::Null => write!(f, "null"),
+            Object::Integer(value) => write!(f, "{}", value),
+            Object::Float(value) => write!(f, "{}", value),
+            Object::Boolean(value) => write!(f, "{}", value),
+            Object::String(value) => write!(f, "{}", value),
+            Object::Array(elements) => {
+                let elements = elements.iter().map(|e| e.inspect()).collect::<Vec<_>>();
+                write!(f, "[{}]", elements.join(", "))
+            },
+            Object::Hash(pairs) => {
+                let pairs = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", Object::from(key.clone()).inspect(), value.inspect()))
+                    .collect::<Vec<_>>();
+                write!(f, "{{{}}}", pairs.join(", "))
+            },
+            Object::Function(function) => write!(f, "{}", function),
+            Object::Closure(closure) => write!(f, "{}", closure),
+            Object::Builtin(builtin) => write!(f, "<builtin {}>", builtin.name),
+            Object::Module(module) => write!(f, "<module {}>", module.name),
+            Object::Task(task) => write!(f, "{}", task),
+            Object::Future(future) => write!(f, "{}", future),
+            Object::Sender(sender) => write!(f, "{}", sender),
+            Object::Receiver(receiver) => write!(f, "{}", receiver),
+            Object::ReturnValue(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// An integer of one of the widths expressible with literal suffixes (`5i8` .. `5i128`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Integer {
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    I128(i128),
+}
+
+impl Integer {
+    pub fn width(&self) -> &'static str {
+        match self {
+            Integer::I8(_) => "i8",
+            Integer::I16(_) => "i16",
+            Integer::I32(_) => "i32",
+            Integer::I64(_) => "i64",
+            Integer::I128(_) => "i128",
+        }
+    }
+
+    pub fn to_i128(self) -> i128 {
+        match self {
+            Integer::I8(value) => value as i128,
+            Integer::I16(value) => value as i128,
+            Integer::I32(value) => value as i128,
+            Integer::I64(value) => value as i128,
+            Integer::I128(value) => value,
+        }
+    }
+
+    /// Builds an integer of the named width, or `None` if `value` does not fit.
+    pub fn from_i128(width: &str, value: i128) -> Option<Integer> {
+        match width {
+            "i8" => i8::try_from(value).ok().map(Integer::I8),
+            "i16" => i16::try_from(value).ok().map(Integer::I16),
+            "i32" => i32::try_from(value).ok().map(Integer::I32),
+            "i64" => i64::try_from(value).ok().map(Integer::I64),
+            "i128" => Some(Integer::I128(value)),
+            _ => None,
+        }
+    }
+
+    /// Applies a checked operation to two integers of the same width.
+    pub fn checked(self, other: Integer, verb: &str, op: impl FnOnce(i128, i128) -> Option<i128>) -> Result<Integer, RuntimeError> {
+        if self.width() != other.width() {
+            return Err(RuntimeError::new(format!(
+                "integer width mismatch: cannot {} {} and {}",
+                verb,
+                self.width(),
+                other.width()
+            )));
+        }
+        op(self.to_i128(), other.to_i128())
+            .and_then(|value| Integer::from_i128(self.width(), value))
+            .ok_or_else(|| RuntimeError::new(format!("attempt to {} with overflow", verb)))
+    }
+}
+
+impl Display for Integer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_i128())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Float {
+    F32(f32),
+    F64(f64),
+}
+
+impl Float {
+    pub fn width(&self) -> &'static str {
+        match self {
+            Float::F32(_) => "f32",
+            Float::F64(_) => "f64",
+        }
+    }
+
+    pub fn to_f64(self) -> f64 {
+        match self {
+            Float::F32(value) => value as f64,
+            Float::F64(value) => value,
+        }
+    }
+
+    /// Builds a float of the same width as `self`.
+    pub fn with_value(self, value: f64) -> Float {
+        match self {
+            Float::F32(_) => Float::F32(value as f32),
+            Float::F64(_) => Float::F64(value),
+        }
+    }
+}
+
+impl Display for Float {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Float::F32(value) => write!(f, "{:?}", value),
+            Float::F64(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+/// The subset of objects usable as hash keys.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HashKey {
+    Boolean(bool),
+    Integer(Integer),
+    String(String),
+}
+
+impl TryFrom<Object> for HashKey {
+    type Error = RuntimeError;
+    fn try_from(object: Object) -> Result<Self, Self::Error> {
+        match object {
+            Object::Boolean(value) => Ok(HashKey::Boolean(value)),
+            Object::Integer(value) => Ok(HashKey::Integer(value)),
+            Object::String(value) => Ok(HashKey::String(value)),
+            other => Err(RuntimeError::new(format!("unusable as hash key: {}", other.type_name()))),
+        }
+    }
+}
+
+impl From<HashKey> for Object {
+    fn from(key: HashKey) -> Self {
+        match key {
+            HashKey::Boolean(value) => Object::Boolean(value),
+            HashKey::Integer(value) => Object::Integer(value),
+            HashKey::String(value) => Object::String(value),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Function {
+    /// Name of the binding the function was first assigned to, if any.
+    pub name: Option<String>,
+    /// Whether calls give a future of the result, computed in a task of its own.
+    pub is_async: bool,
+    pub parameters: Vec<IdentifierLiteral>,
+    pub body: Arc<BlockStatement>,
+    pub env: Env,
+    pub file: Arc<str>,
+    pub span: Span,
+}
+
+impl Function {
+    pub fn display_name(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => "<anonymous fn>".to_string(),
+        }
+    }
+}
+
+impl Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.name)
+            .field("is_async", &self.is_async)
+            .field("parameters", &self.parameters)
+            .field("body", &self.body)
+            .field("file", &self.file)
+            .field("span", &self.span)
+            .finish()
+    }
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.body, &other.body) && Arc::ptr_eq(&self.env, &other.env)
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parameters = self.parameters.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        let keyword = if self.is_async { "async fn" } else { "fn" };
+        write!(f, "{}({}) {}", keyword, parameters.join(", "), self.body)
+    }
+}
+
+pub type NativeFunction = Arc<dyn Fn(Vec<Object>) -> Result<Object, RuntimeError> + Send + Sync>;
+
+/// A function implemented in Rust and callable from Keynes code.
+#[derive(Clone)]
+pub struct Builtin {
+    pub name: Arc<str>,
+    pub function: NativeFunction,
+}
+
+impl Builtin {
+    pub fn new(name: &str, function: impl Fn(Vec<Object>) -> Result<Object, RuntimeError> + Send + Sync + 'static) -> Builtin {
+        Builtin {
+            name: name.into(),
+            function: Arc::new(function),
+        }
+    }
+}
+
+impl Debug for Builtin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Builtin").field("name", &self.name).finish()
+    }
+}
+
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
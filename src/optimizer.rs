@@ -0,0 +1,268 @@
+//! An AST-to-AST pass that folds constant expressions and removes dead `if` branches.
+//!
+//! Folding goes through the evaluator's own operators, so a folded expression has
+//! exactly the value it would have had at runtime. Expressions that would fail,
+//! such as `i64` overflow or division by zero, are left in place to fail when run.
+
+use crate::{
+    ast::{expressions::*, program::Program, statements::*},
+    evaluator::Evaluator,
+    lexer::Token,
+    modules::ModuleLoader,
+    object::{Float, Integer, Object},
+};
+
+use log::*;
+
+/// How much [`optimize`] does, as chosen by `-O0` and `-O1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptLevel {
+    /// Runs the program as parsed.
+    O0,
+    /// Folds constants and removes dead branches.
+    #[default]
+    O1,
+}
+
+impl TryFrom<&str> for OptLevel {
+    type Error = String;
+
+    fn try_from(level: &str) -> Result<Self, Self::Error> {
+        match level {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            _ => Err(format!("unknown optimization level `{}`, expected 0 or 1", level)),
+        }
+    }
+}
+
+/// Optimizes `program` at `level`.
+pub fn optimize(program: Program, level: OptLevel) -> Program {
+    match level {
+        OptLevel::O0 => program,
+        OptLevel::O1 => {
+            trace!("optimize program");
+            let optimizer = Optimizer::new();
+            Program {
+                statements: program.statements.iter().map(|statement| optimizer.statement(statement.as_ref())).collect(),
+            }
+        },
+    }
+}
+
+/// The value of `expression` if it is a literal.
+fn constant(expression: &dyn Expression) -> Option<Object> {
+    let any = expression.as_any();
+    if let Some(integer) = any.downcast_ref::<IntegerLiteral>() {
+        Some(Object::Integer(Integer::I64(integer.value)))
+    } else if let Some(float) = any.downcast_ref::<FloatLiteral>() {
+        Some(Object::Float(Float::F64(float.value)))
+    } else if let Some(string) = any.downcast_ref::<StringLiteral>() {
+        Some(Object::String(string.value.clone()))
+    } else {
+        any.downcast_ref::<BooleanLiteral>().map(|boolean| Object::Boolean(boolean.value))
+    }
+}
+
+/// A literal for `value`, if the language has one for its type.
+fn literal(value: Object) -> Option<Box<dyn Expression>> {
+    match value {
+        Object::Integer(Integer::I64(value)) => Some(Box::new(IntegerLiteral {
+            token: Token::INTEGER(value.to_string()),
+            value,
+        })),
+        Object::Float(Float::F64(value)) => Some(Box::new(FloatLiteral {
+            token: Token::FLOAT(value.to_string()),
+            value,
+        })),
+        Object::String(value) => Some(Box::new(StringLiteral {
+            token: Token::STRING(value.clone()),
+            value,
+        })),
+        Object::Boolean(value) => Some(Box::new(BooleanLiteral {
+            token: if value { Token::TRUE } else { Token::FALSE },
+            value,
+        })),
+        _ => None,
+    }
+}
+
+/// The branch an `if` with a literal condition always takes, or `None` if the condition is not a literal.
+/// A missing `else` is taken as an empty block, which evaluates to null just the same.
+fn live_branch(if_expression: &IfExpression) -> Option<BlockStatement> {
+    let condition = constant(if_expression.condition.as_ref())?;
+    match condition.is_truthy() {
+        true => Some(if_expression.consequence.clone()),
+        false => Some(if_expression.alternative.clone().unwrap_or_else(|| BlockStatement {
+            token: Token::LBRACE,
+            statements: Vec::new(),
+            span: if_expression.span,
+        })),
+    }
+}
+
+struct Optimizer {
+    evaluator: Evaluator,
+}
+
+impl Optimizer {
+    fn new() -> Optimizer {
+        Optimizer {
+            evaluator: Evaluator::with_loader("<optimizer>", ModuleLoader::new()),
+        }
+    }
+
+    fn statement(&self, statement: &(dyn Statement + 'static)) -> Box<dyn Statement> {
+        let any = statement.as_any();
+        if let Some(statement) = any.downcast_ref::<LetStatement>() {
+            Box::new(LetStatement {
+                value: self.expression(statement.value.as_ref()),
+                ..statement.clone()
+            })
+        } else if let Some(statement) = any.downcast_ref::<LetTupleStatement>() {
+            Box::new(LetTupleStatement {
+                value: self.expression(statement.value.as_ref()),
+                ..statement.clone()
+            })
+        } else if let Some(statement) = any.downcast_ref::<ReturnStatement>() {
+            Box::new(ReturnStatement {
+                expression: self.expression(statement.expression.as_ref()),
+                ..statement.clone()
+            })
+        } else if let Some(statement) = any.downcast_ref::<ExpressionStatement>() {
+            let expression = self.expression(statement.expression.as_ref());
+            // Blocks do not open a scope, so a statement that is a decided `if` can be its live branch alone.
+            match expression.as_any().downcast_ref::<IfExpression>().and_then(live_branch) {
+                Some(block) => Box::new(block),
+                None => Box::new(ExpressionStatement {
+                    expression,
+                    ..statement.clone()
+                }),
+            }
+        } else if let Some(block) = any.downcast_ref::<BlockStatement>() {
+            Box::new(self.block(block))
+        } else if let Some(test) = any.downcast_ref::<TestStatement>() {
+            Box::new(TestStatement {
+                body: self.block(&test.body),
+                ..test.clone()
+            })
+        } else {
+            dyn_clone::clone_box(statement)
+        }
+    }
+
+    fn block(&self, block: &BlockStatement) -> BlockStatement {
+        BlockStatement {
+            statements: block.statements.iter().map(|statement| self.statement(statement.as_ref())).collect(),
+            ..block.clone()
+        }
+    }
+
+    fn expressions(&self, expressions: &[Box<dyn Expression>]) -> Vec<Box<dyn Expression>> {
+        expressions.iter().map(|expression| self.expression(expression.as_ref())).collect()
+    }
+
+    fn expression(&self, expression: &(dyn Expression + 'static)) -> Box<dyn Expression> {
+        let any = expression.as_any();
+        if let Some(prefix) = any.downcast_ref::<PrefixExpression>() {
+            let right = self.expression(prefix.right.as_ref());
+            let folded = constant(right.as_ref())
+                .and_then(|right| self.evaluator.eval_prefix_expression(&prefix.operator, right).ok())
+                .and_then(literal);
+            folded.unwrap_or_else(|| Box::new(PrefixExpression { right, ..prefix.clone() }))
+        } else if let Some(infix) = any.downcast_ref::<InfixExpression>() {
+            let left = self.expression(infix.left.as_ref());
+            let right = self.expression(infix.right.as_ref());
+            let folded = constant(left.as_ref())
+                .zip(constant(right.as_ref()))
+                .and_then(|(l, r)| self.evaluator.eval_infix_expression(&infix.operator, l, r).ok())
+                .and_then(literal);
+            folded.unwrap_or_else(|| Box::new(InfixExpression { left, right, ..infix.clone() }))
+        } else if let Some(if_expression) = any.downcast_ref::<IfExpression>() {
+            self.if_expression(if_expression)
+        } else if let Some(function) = any.downcast_ref::<FunctionLiteral>() {
+            Box::new(FunctionLiteral {
+                body: self.block(&function.body),
+                ..function.clone()
+            })
+        } else if let Some(call) = any.downcast_ref::<CallExpression>() {
+            Box::new(CallExpression {
+                function: self.expression(call.function.as_ref()),
+                arguments: self.expressions(&call.arguments),
+                ..call.clone()
+            })
+        } else if let Some(array) = any.downcast_ref::<ArrayLiteral>() {
+            Box::new(ArrayLiteral {
+                elements: self.expressions(&array.elements),
+                ..array.clone()
+            })
+        } else if let Some(hash) = any.downcast_ref::<HashLiteral>() {
+            let pairs = hash
+                .pairs
+                .iter()
+                .map(|(key, value)| (self.expression(key.as_ref()), self.expression(value.as_ref())))
+                .collect();
+            Box::new(HashLiteral { pairs, ..hash.clone() })
+        } else if let Some(index) = any.downcast_ref::<IndexExpression>() {
+            Box::new(IndexExpression {
+                left: self.expression(index.left.as_ref()),
+                index: self.expression(index.index.as_ref()),
+                ..index.clone()
+            })
+        } else if let Some(spawn) = any.downcast_ref::<SpawnExpression>() {
+            let call = CallExpression {
+                function: self.expression(spawn.call.function.as_ref()),
+                arguments: self.expressions(&spawn.call.arguments),
+                ..spawn.call.clone()
+            };
+            Box::new(SpawnExpression { call, ..spawn.clone() })
+        } else if let Some(await_expression) = any.downcast_ref::<AwaitExpression>() {
+            Box::new(AwaitExpression {
+                value: self.expression(await_expression.value.as_ref()),
+                ..await_expression.clone()
+            })
+        } else if let Some(select) = any.downcast_ref::<SelectExpression>() {
+            let arms = select
+                .arms
+                .iter()
+                .map(|arm| SelectArm {
+                    receiver: self.expression(arm.receiver.as_ref()),
+                    binding: arm.binding.clone(),
+                    body: self.block(&arm.body),
+                })
+                .collect();
+            Box::new(SelectExpression { arms, ..select.clone() })
+        } else {
+            dyn_clone::clone_box(expression)
+        }
+    }
+
+    /// Folds the condition, then keeps only the branch it selects. A branch that is a lone
+    /// expression replaces the whole `if`; a longer one stays in an `if (true)` of its own.
+    fn if_expression(&self, if_expression: &IfExpression) -> Box<dyn Expression> {
+        let folded = IfExpression {
+            condition: self.expression(if_expression.condition.as_ref()),
+            consequence: self.block(&if_expression.consequence),
+            alternative: if_expression.alternative.as_ref().map(|alternative| self.block(alternative)),
+            ..if_expression.clone()
+        };
+        let Some(live) = live_branch(&folded) else {
+            return Box::new(folded);
+        };
+        if let [statement] = live.statements.as_slice() {
+            if let Some(statement) = statement.as_any().downcast_ref::<ExpressionStatement>() {
+                return statement.expression.clone();
+            }
+        }
+        Box::new(IfExpression {
+            condition: literal(Object::Boolean(true)).unwrap(),
+            consequence: live,
+            alternative: None,
+            ..folded
+        })
+    }
+}
+
+#[cfg(test)]
+#[path = "./optimizer_tests.rs"]
+mod tests;
@@ -0,0 +1,81 @@
+use super::*;
+
+use crate::{engine::Engine, generator::Generator, lexer::Lexer, parser::Parser};
+
+use test_case::test_case;
+
+fn optimized(source: &str) -> String {
+    let mut lexer = Lexer::new(source.to_string());
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+    assert_eq!(parser.errors, vec![]);
+    optimize(program, OptLevel::O1).to_string()
+}
+
+/// The value of `source`, or its error message, when run at `level`. Functions print
+/// their optimized bodies, so only their type is compared.
+fn run(source: &str, level: OptLevel) -> Result<String, String> {
+    let mut engine = Engine::new();
+    engine.set_opt_level(level);
+    match engine.eval(source) {
+        Ok(Object::Function(_)) => Ok("function".to_string()),
+        Ok(value) => Ok(value.to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[test_case("2 * (5 + 5)", "20"; "arithmetic")]
+#[test_case("let x = 1 + 2 * 3 - 4 / 2;", "let x = 5;"; "let value")]
+#[test_case("-(2 - 5)", "3"; "prefix")]
+#[test_case("!(1 < 2)", "false"; "comparison")]
+#[test_case("1 <= 1 == (2 >= 3)", "false"; "comparisons of comparisons")]
+#[test_case("\"a\" + \"b\" == \"ab\"", "true"; "strings")]
+#[test_case("1.5 * 2.0", "3.0"; "floats")]
+#[test_case("x + 2 * 3", "(x + 6)"; "partly constant")]
+#[test_case("fn(x) { return x * (60 * 60); }", "fn(x) { return (x * 3600); }"; "function body")]
+#[test_case("f([1 + 1, {\"k\": 2 - 1}][0 + 0])", "f(([2, {\"k\": 1}][0]))"; "nested in other expressions")]
+#[test_case("9223372036854775807 + 1", "(9223372036854775807 + 1)"; "overflow is left to fail at runtime")]
+#[test_case("1 / (2 - 2)", "(1 / 0)"; "division by zero is left to fail at runtime")]
+#[test_case("1 + true", "(1 + true)"; "type mismatch is left to fail at runtime")]
+#[test_case("1 + 1.0", "(1 + 1.0)"; "width mismatch is left to fail at runtime")]
+fn test_constant_folding(source: &str, expected: &str) {
+    assert_eq!(optimized(source), expected);
+}
+
+#[test_case("let x = if (true) { a } else { b };", "let x = a;"; "true condition")]
+#[test_case("let x = if (1 > 2) { a } else { b };", "let x = b;"; "folded condition")]
+#[test_case("let x = if (1 == 2) { a };", "let x = if true {  };"; "missing else")]
+#[test_case("let x = if (\"\") { a; b } else { c };", "let x = if true { ab };"; "longer branch")]
+#[test_case("if (y) { 1 + 1 } else { 2 }", "if y { 2 } else { 2 }"; "unknown condition")]
+#[test_case("if (true) { let a = 1; a } else { b }", "{ let a = 1;a }"; "statement becomes block")]
+#[test_case("if (false) { a }", "{  }"; "statement without live branch")]
+#[test_case("fn() { if (2 > 1) { return 1; } 2 }", "fn() { { return 1; }2 }"; "return in live branch")]
+fn test_dead_branch_elimination(source: &str, expected: &str) {
+    assert_eq!(optimized(source), expected);
+}
+
+#[test]
+fn test_o0_keeps_program() {
+    let mut lexer = Lexer::new("if (true) { 1 + 1 }".to_string());
+    let program = Parser::new(&mut lexer).parse_program();
+    assert_eq!(optimize(program, OptLevel::O0).to_string(), "if true { (1 + 1) }");
+}
+
+#[test_case("let f = fn() { if (true) { return 1; } 2 }; f()", "1"; "return from live branch")]
+#[test_case("let x = 1; if (false) { 2 }", "null"; "dead branch value")]
+#[test_case("let x = 2 * (5 + 5); x", "20"; "folded value")]
+#[test_case("i8(100) + 100", "integer width mismatch: cannot add i8 and i64"; "width mismatch")]
+#[test_case("9223372036854775807 + 1", "attempt to add with overflow"; "overflow")]
+fn test_same_result_at_each_level(source: &str, expected: &str) {
+    let o0 = run(source, OptLevel::O0);
+    assert_eq!(o0.clone().unwrap_or_else(|err| err), expected);
+    assert_eq!(run(source, OptLevel::O1), o0);
+}
+
+#[test]
+fn test_generated_programs_same_result_at_each_level() {
+    for seed in 0..200 {
+        let source = Generator::new(seed, 4).program();
+        assert_eq!(run(&source, OptLevel::O1), run(&source, OptLevel::O0), "seed {} differs when optimized\n{}", seed, source);
+    }
+}
@@ -1,525 +1,1056 @@
-use crate::{lexer::{Lexer, Token}, ast::{statements::*, expressions::*, program::Program}};
-
-use log::*;
-
-#[derive(Debug)]
-pub struct Parser<'a> {
-    lexer: &'a mut Lexer,
-    cur_token: Token,
-    peek_token: Token,
-    pub errors: Vec<String>,
-}
-
-impl <'a> Parser<'a> {
-    pub fn new(lexer: &mut Lexer) -> Parser {
-        let mut parser = Parser {
-            lexer,
-            cur_token: Token::EOF,
-            peek_token: Token::EOF,
-            errors: Vec::new(),
-        };
-        parser.next_token();
-        parser.next_token();
-        parser
-    }
-
-    fn next_token(&mut self) {
-        self.cur_token = self.peek_token.clone();
-        self.peek_token = self.lexer.next_token();
-    }
-
-    pub fn parse_program(&mut self) -> Program {
-        trace!("parse_program");
-        let mut program = Program::new();
-        while self.cur_token != Token::EOF {
-            let stmt = self.parse_statement();
-            if stmt.is_some() {
-                program.statements.push(stmt.unwrap());
-            }
-            self.next_token();
-        }
-        program
-    }
-
-    fn parse_statement(&mut self) -> Option<Box<dyn Statement>> {
-        trace!("parse_statement: {:?}", self.cur_token);
-        let statment = match self.cur_token {
-            Token::RETURN => self.parse_return_statement(),
-            Token::LET => self.parse_let_statement(),
-            Token::RUN => todo!(),
-            Token::SPAWN => todo!(),
-            _ => self.parse_expression_statement(),
-        };
-
-        trace!("parse_statement completed: {:?}", statment);
-        statment
-    }
-
-    fn parse_return_statement(&mut self) -> Option<Box<dyn Statement>> {
-        trace!("parse_return_statement",);
-        let token = self.cur_token.clone();
-        self.next_token();
-        let expression = self.parse_expression(Precedence::LOWEST);
-        if expression.is_none() {
-            trace!("parse_return_statement: parse_expression failed");
-            return None;
-        }
-        trace!("parse_return_statement: expression {:?}", expression);
-        if self.peek_token_is(&Token::SEMICOLON) {
-            self.next_token();
-        }
-        Some(Box::new(ReturnStatement {
-            token,
-            expression: expression.unwrap(),
-        }))
-    }
-
-    fn parse_let_statement(&mut self) -> Option<Box<dyn Statement>> {
-        trace!("parse_let_statement",);
-        let token = self.cur_token.clone();
-    
-        let mutable = self.optional_peek(Token::MUT);
-
-        trace!("parse_let_statement: mutable {:?}", mutable);
-
-        if self.expect_peek_ident().is_none() {
-            trace!("parse_let_statement: expect_peek_ident for name failed");
-            return None;
-        }
-        let name = self.cur_token.clone();
-
-        trace!("parse_let_statement: name {:?}", name);
-
-        if !self.expect_peek(Token::ASSIGN) {
-            trace!("parse_let_statement: expect_peek for assign failed");
-            return None;
-        }
-
-        trace!("parse_let_statement: assign");
-        self.next_token();
-
-        let expression = self.parse_expression(Precedence::LOWEST);
-        if expression.is_none() {
-            trace!("parse_let_statement: parse_expression failed");
-            return None;
-        }
-
-        if self.peek_token_is(&Token::SEMICOLON) {
-            self.next_token();
-        }
-
-        trace!("parse_let_statement: expression {:?}", expression);
-
-        Some(Box::new(LetStatement {
-            token,
-            name: name.into(),
-            mutable,
-            value: expression.unwrap(),
-        }))
-    }
-
-    fn parse_expression_statement(&mut self) -> Option<Box<dyn Statement>> {
-        trace!("parse_expression_statement",);
-        let token = self.cur_token.clone();
-        let expression = self.parse_expression(Precedence::LOWEST);
-        if expression.is_none() {
-            trace!("parse_expression_statement: parse_expression failed");
-            return None;
-        }
-        trace!("parse_expression_statement: expression {:?}", expression);
-        if self.peek_token_is(&Token::SEMICOLON) {
-            self.next_token();
-        }
-        Some(Box::new(ExpressionStatement {
-            token,
-            expression: expression.unwrap(),
-        }))
-    }
-
-    fn parse_expression(&mut self, precedence: Precedence) -> Option<Box<dyn Expression>> {
-        trace!("parse_expression: {:?} {:?} {:?}", precedence, self.cur_token, self.peek_token);
-
-        let prefix_w = match self.cur_token {
-            Token::IDENTIFIER(_) => self.parse_identifier_expression(),
-            Token::INTEGER(_) => self.parse_integer_literal(),
-            Token::LPAREN => self.parse_grouped_expression(),
-            Token::IF => self.parse_if_expression(),
-            Token::BANG | Token::MINUS => self.parse_prefix_expression(),
-            Token::FUNCTION => self.parse_function_literial(),
-            Token::TRUE | Token::FALSE => self.parse_boolean_literal(),
-            _ => None,
-        };
-        if prefix_w.is_none() {
-            trace!("parse_expression: prefix failed for {:?}", self.cur_token);
-            self.errors.push(format!("unhandled prefix parse for {:?}", self.cur_token));
-            return None;
-        }
-
-        let prefix = prefix_w.unwrap();
-
-        let mut left_exp = Some(prefix.clone());
-
-        while !self.peek_token_is(&Token::SEMICOLON) && precedence < self.peek_token.clone().into() {
-            let infix = match self.peek_token.clone() {
-                Token::PLUS | 
-                Token::MINUS | 
-                Token::DIVIDE | 
-                Token::MULTIPLY | 
-                Token::EQUAL | 
-                Token::NOT_EQUAL | 
-                Token::LESS_THAN | 
-                Token::GREATER_THAN => {
-                    self.next_token();
-                    self.parse_infix_expression(left_exp.clone().unwrap())
-                }
-                Token::LPAREN => {
-                    self.parse_call_expression(left_exp.clone().unwrap())
-                },
-                _ => None,
-            };
-
-            if infix.is_none() {
-                return left_exp;
-            }
-
-            left_exp = Some(infix.unwrap());
-        }
-        trace!("parse_expression: end infix {:?}", self.peek_token);
-        trace!("parse_expression: end infix {:?}", left_exp);
-
-        left_exp
-    }
-
-
-    fn cur_token_is(&self, t: &Token) -> bool {
-        self.cur_token == *t
-    }
-
-    fn peek_token_is(&self, t: &Token) -> bool {
-        self.peek_token == *t
-    }
-
-    fn peek_error(&mut self, t: Token) {
-        self.errors.push(format!("expected next token to be {:?}, got {:?} instead", t, self.peek_token));
-    }
-
-    fn expect_peek(&mut self, t: Token) -> bool {
-        if self.peek_token_is(&t) {
-            self.next_token();
-            true
-        } else {
-            self.peek_error(t);
-            false
-        }
-    }
-
-    fn optional_peek(&mut self, t: Token) -> bool {
-        let r = self.peek_token_is(&t);
-        if r {
-            self.next_token();
-        }
-        r
-    }
-
-    fn expect_peek_ident(&mut self) -> Option<Token> {
-        match self.peek_token {
-            Token::IDENTIFIER(_) => {
-                self.next_token();
-                Some(self.cur_token.clone())
-            },
-            _ => None,
-        }
-    }
-
-    fn parse_integer_literal(&mut self) -> Option<Box<dyn Expression>> { 
-        trace!("parse_integer_literal: {:?}", self.cur_token);
-        let value = match self.cur_token.clone() {
-            Token::INTEGER(val) => Some(val),
-            _ => return None,
-        };
-        if value.is_none() {
-            self.errors.push(format!("could not parse {:?} as integer", self.cur_token));
-            return None;
-        }
-
-        let parsed_val = value.unwrap().parse::<i64>();
-        if parsed_val.is_err() {
-            self.errors.push(format!("could not parse {:?} as integer", self.cur_token));
-            return None;
-        }
-        Some(Box::new(IntegerLiteral {
-            token: self.cur_token.clone(),
-            value: parsed_val.unwrap(),
-        }))
-    }
-
-    fn parse_identifier_expression(&mut self) -> Option<Box<dyn Expression>> {
-        trace!("parse_identifier_expression: {:?}", self.cur_token);
-        let val = match self.cur_token.clone() {
-            Token::IDENTIFIER(val) => Some(val),
-            _ => return None,
-        };
-        if val.is_none() {
-            self.errors.push(format!("could not parse {:?} as identifier", self.cur_token));
-            return None;
-        }
-
-        Some(Box::new(IdentifierLiteral {
-            token: self.cur_token.clone(),
-        }))
-    }
-
-    fn parse_prefix_expression(&mut self) -> Option<Box<dyn Expression>> {
-        trace!("parse_prefix_expression: {:?}", self.cur_token);
-        let token = self.cur_token.clone();
-
-        self.next_token();
-
-        let r_exp = self.parse_expression(Precedence::PREFIX);
-        if r_exp.is_none() {
-            self.errors.push(format!("expected expression after {:?}", token));
-            return None;
-        }
-
-        Some(Box::new(PrefixExpression {
-            token: token.clone(),
-            operator: token.into(),
-            right: r_exp.unwrap(),
-        }))
-    }
-
-    fn parse_infix_expression(&mut self, left: Box<dyn Expression>) -> Option<Box<dyn Expression>> {
-        trace!("parse_infix_expression: operator {:?}", self.cur_token);
-        let token = self.cur_token.clone();
-        let precedence: Precedence = token.clone().into();
-        
-        let token_as_infix = token.clone().try_into();
-        if token_as_infix.is_err() {
-            self.errors.push(format!("expected infix operator, got {:?}", token));
-            return None;
-        }
-
-        self.next_token();
-        trace!("parse_infix_expression: right {:?}", self.cur_token);
-
-        let right = self.parse_expression(precedence);
-        if right.is_none() {
-            self.errors.push(format!("expected expression after {:?}", token));
-            return None;
-        }
-        Some(Box::new(InfixExpression {
-            token: token.clone(),
-            operator: token_as_infix.unwrap(),
-            left,
-            right: right.unwrap(),
-        }))
-    }
-
-    fn parse_grouped_expression(&mut self) -> Option<Box<dyn Expression>> {
-        self.next_token();
-
-        trace!("parse_grouped_expression: {:?}", self.cur_token);
-        let exp = self.parse_expression(Precedence::LOWEST);
-        if !self.expect_peek(Token::RPAREN) {
-            return None;
-        }
-        exp
-    }
-
-    fn parse_if_expression(&mut self) -> Option<Box<dyn Expression>> {
-        trace!("parse_if_expression: {:?}", self.cur_token);
-        let token = self.cur_token.clone();
-        if !self.expect_peek(Token::LPAREN) {
-            return None;
-        }
-        self.next_token();
-        let condition = self.parse_expression(Precedence::LOWEST);
-        if condition.is_none() {
-            return None;
-        }
-        if !self.expect_peek(Token::RPAREN) {
-            return None;
-        }
-        if !self.expect_peek(Token::LBRACE) {
-            return None;
-        }
-        let consequence = self.parse_block_statement();
-        if consequence.is_none() {
-            return None;
-        }
-        let alternative = if self.peek_token_is(&Token::ELSE) {
-            self.next_token();
-            if !self.expect_peek(Token::LBRACE) {
-                return None;
-            }
-            self.parse_block_statement()
-        } else {
-            None
-        };
-        Some(Box::new(IfExpression {
-            token,
-            condition: condition.unwrap(),
-            consequence: consequence.unwrap(),
-            alternative,
-        }))
-    }
-
-    fn parse_block_statement(&mut self) -> Option<BlockStatement> {
-        trace!("parse_block_statement: {:?}", self.cur_token);
-        let token = self.cur_token.clone();
-        let mut statements = Vec::new();
-        self.next_token();
-        while !self.cur_token_is(&Token::RBRACE) && !self.cur_token_is(&Token::EOF) {
-            let stmt = self.parse_statement();
-            if stmt.is_some() {
-                statements.push(stmt.unwrap());
-            }
-            self.next_token();
-        }
-        Some(BlockStatement {
-            token,
-            statements,
-        })
-    }
-
-    fn parse_function_literial(&mut self) -> Option<Box<dyn Expression>> {
-        trace!("parse_function_literial: {:?}", self.cur_token);
-        let token = self.cur_token.clone();
-        if !self.expect_peek(Token::LPAREN) {
-            return None;
-        }
-        let parameters = self.parse_function_parameters();
-        if parameters.is_none() {
-            return None;
-        }
-        if !self.expect_peek(Token::LBRACE) {
-            return None;
-        }
-        let body = self.parse_block_statement();
-        if body.is_none() {
-            return None;
-        }
-        Some(Box::new(FunctionLiteral {
-            token,
-            parameters: parameters.unwrap(),
-            body: body.unwrap(),
-        }))
-    }
-
-    fn parse_function_parameters(&mut self) -> Option<Vec<IdentifierLiteral>> {
-        trace!("parse_function_parameters: {:?}", self.cur_token);
-        let mut identifiers = Vec::new();
-        if self.peek_token_is(&Token::RPAREN) {
-            self.next_token();
-            return Some(identifiers);
-        }
-        self.next_token();
-        let ident = match self.cur_token.clone() {
-            Token::IDENTIFIER(val) => Some(val),
-            _ => return None,
-        };
-        if ident.is_none() {
-            self.errors.push(format!("could not parse {:?} as identifier", self.cur_token));
-            return None;
-        }
-        identifiers.push(IdentifierLiteral {
-            token: self.cur_token.clone(),
-        });
-        while self.peek_token_is(&Token::COMMA) {
-            self.next_token();
-            self.next_token();
-            let ident = match self.cur_token.clone() {
-                Token::IDENTIFIER(val) => Some(val),
-                _ => return None,
-            };
-            if ident.is_none() {
-                self.errors.push(format!("could not parse {:?} as identifier", self.cur_token));
-                return None;
-            }
-            identifiers.push(IdentifierLiteral {
-                token: self.cur_token.clone(),
-            });
-        }
-        if !self.expect_peek(Token::RPAREN) {
-            return None;
-        }
-        Some(identifiers)
-    }
-
-    fn parse_call_expression(&mut self, function: Box<dyn Expression>) -> Option<Box<dyn Expression>> {
-        trace!("parse_call_expression: {:?}", self.cur_token);
-        let token = self.cur_token.clone();
-        self.next_token();
-        let arguments = self.parse_call_arguments();
-        
-        trace!("parse_call_expression: completed arguments {:?}", arguments);
-        Some(Box::new(CallExpression {
-            token,
-            function,
-            arguments: arguments.unwrap_or_default(),
-        }))
-    }
-
-    fn parse_call_arguments(&mut self) -> Option<Vec<Box<dyn Expression>>> {
-        trace!("parse_call_arguments: {:?}", self.cur_token);
-        let mut arguments = Vec::new();
-        if self.peek_token_is(&Token::RPAREN) {
-            self.next_token();
-            return Some(arguments);
-        }
-        self.next_token();
-        let exp = self.parse_expression(Precedence::LOWEST);
-        if exp.is_none() {
-            return None;
-        }
-        arguments.push(exp.unwrap());
-        while self.peek_token_is(&Token::COMMA) {
-            self.next_token();
-            self.next_token();
-            let exp = self.parse_expression(Precedence::LOWEST);
-            if exp.is_none() {
-                return None;
-            }
-            arguments.push(exp.unwrap());
-        }
-        if !self.expect_peek(Token::RPAREN) {
-            return None;
-        }
-        trace!("parse_call_arguments: completed arguments {:?}", arguments);
-        Some(arguments)
-    }
-
-    fn parse_boolean_literal(&mut self) -> Option<Box<dyn Expression>> {
-        trace!("parse_boolean_literal: {:?}", self.cur_token);
-        let token = self.cur_token.clone();
-        let value = match self.cur_token.clone() {
-            Token::TRUE => Some(true),
-            Token::FALSE => Some(false),
-            _ => None,
-        };
-        if value.is_none() {
-            self.errors.push(format!("could not parse {:?} as boolean", self.cur_token));
-            return None;
-        }
-        Some(Box::new(BooleanLiteral {
-            token,
-            value: value.unwrap(),
-        }))
-        
-    }
-
-    fn check_parser_errors(&mut self) {
-        if self.errors.len() == 0 {
-            return;
-        }
-        error!("parser has {} errors", self.errors.len());
-        for error in &self.errors {
-            error!("{}", error);
-        }
-        panic!("parser has {} errors", self.errors.len());
-    }
-}
-
-#[cfg(test)]
-#[path = "./parser_tests.rs"]
+use crate::{lexer::{Lexer, Token, Span}, ast::{statements::*, expressions::*, program::Program}, diagnostics::Diagnostic};
+
+use log::*;
+
+/// Parsing stops once this many errors have been reported; past that point
+/// they are almost always caused by the earlier ones.
+pub const MAX_ERRORS: usize = 20;
+
+/// Most expressions that can be nested in one another, so that parsing, and everything
+/// that walks the tree later, cannot overflow the stack on input like `((((…`.
+pub const MAX_NESTING: usize = 128;
+
+#[derive(Debug)]
+pub struct Parser<'a> {
+    lexer: &'a mut Lexer,
+    cur_token: Token,
+    peek_token: Token,
+    prev_span: Span,
+    cur_span: Span,
+    peek_span: Span,
+    pub errors: Vec<Diagnostic>,
+    /// Set by the first error in a statement; later errors in the same
+    /// statement are consequences of it and are not reported.
+    panicking: bool,
+    /// Set when recovering from an error consumed the `}` closing the enclosing block.
+    closed_block: bool,
+    /// Expressions the one being parsed is nested in.
+    depth: usize,
+}
+
+impl <'a> Parser<'a> {
+    pub fn new(lexer: &mut Lexer) -> Parser {
+        let mut parser = Parser {
+            lexer,
+            cur_token: Token::EOF,
+            peek_token: Token::EOF,
+            prev_span: Span::default(),
+            cur_span: Span::default(),
+            peek_span: Span::default(),
+            errors: Vec::new(),
+            panicking: false,
+            closed_block: false,
+            depth: 0,
+        };
+        parser.next_token();
+        parser.next_token();
+        parser
+    }
+
+    fn next_token(&mut self) {
+        self.cur_token = self.peek_token.clone();
+        self.prev_span = self.cur_span;
+        self.cur_span = self.peek_span;
+        self.peek_token = self.lexer.next_token();
+        self.peek_span = self.lexer.span();
+    }
+
+    fn error(&mut self, span: Span, message: String) {
+        if self.panicking {
+            trace!("error suppressed while panicking: {}", message);
+            return;
+        }
+        self.panicking = true;
+        match self.errors.len() {
+            count if count < MAX_ERRORS => self.errors.push(Diagnostic::new(message, span)),
+            MAX_ERRORS => self.errors.push(Diagnostic::new("too many errors, giving up".to_string(), span)),
+            _ => {},
+        }
+    }
+
+    pub fn parse_program(&mut self) -> Program {
+        trace!("parse_program");
+        let mut program = Program::new();
+        while self.cur_token != Token::EOF && self.errors.len() <= MAX_ERRORS {
+            program.statements.push(self.parse_statement_or_error());
+            // A `}` consumed while recovering at the top level has no block to close.
+            self.closed_block = false;
+            self.next_token();
+        }
+        program
+    }
+
+    /// Parses a statement. If that fails, reports an error, skips to the end
+    /// of the statement and returns an `ErrorStatement` in its place.
+    fn parse_statement_or_error(&mut self) -> Box<dyn Statement> {
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+        self.panicking = false;
+        let statement = self.parse_statement();
+        if let Some(statement) = statement {
+            if !self.panicking {
+                return statement;
+            }
+        }
+
+        self.error(start, format!("could not parse statement starting with {:?}", token));
+        self.synchronize();
+        self.panicking = false;
+        let end = if self.closed_block { self.prev_span } else { self.cur_span };
+        Box::new(ErrorStatement {
+            token,
+            span: start.to(end),
+        })
+    }
+
+    /// Panic-mode recovery: skips tokens up to the `;` or `}` that ends the
+    /// current statement, stepping over nested blocks. Stops before the `}`
+    /// closing the enclosing block, or sets `closed_block` if it was already
+    /// consumed by the failed statement.
+    fn synchronize(&mut self) {
+        trace!("synchronize: {:?}", self.cur_token);
+        let mut depth = 0usize;
+        loop {
+            match self.cur_token {
+                Token::EOF => return,
+                Token::SEMICOLON if depth == 0 => return,
+                Token::LBRACE => depth += 1,
+                Token::RBRACE if depth == 0 => {
+                    self.closed_block = true;
+                    return;
+                },
+                Token::RBRACE => {
+                    depth -= 1;
+                    if depth == 0 {
+                        if self.peek_token_is(&Token::SEMICOLON) {
+                            self.next_token();
+                        }
+                        return;
+                    }
+                },
+                _ => {},
+            }
+            if depth == 0 && (self.peek_token_is(&Token::RBRACE) || self.peek_token_is(&Token::EOF)) {
+                return;
+            }
+            self.next_token();
+        }
+    }
+
+    fn parse_statement(&mut self) -> Option<Box<dyn Statement>> {
+        trace!("parse_statement: {:?}", self.cur_token);
+        let statment = match self.cur_token {
+            Token::RETURN => self.parse_return_statement(),
+            Token::LET => self.parse_let_statement(false, self.cur_span),
+            Token::PUB => self.parse_public_statement(),
+            Token::IMPORT => self.parse_import_statement(),
+            Token::USE => self.parse_use_statement(),
+            // `test` is only a keyword in front of a test name, so it stays usable as an identifier.
+            Token::IDENTIFIER(ref name) if name == "test" && matches!(self.peek_token, Token::STRING(_)) => {
+                self.parse_test_statement()
+            },
+            Token::RUN => {
+                self.error(self.cur_span, format!("`{}` is not supported yet", self.cur_token));
+                None
+            },
+            _ => self.parse_expression_statement(),
+        };
+
+        trace!("parse_statement completed: {:?}", statment);
+        statment
+    }
+
+    fn parse_return_statement(&mut self) -> Option<Box<dyn Statement>> {
+        trace!("parse_return_statement",);
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+        self.next_token();
+        let expression = self.parse_expression(Precedence::LOWEST);
+        if expression.is_none() {
+            trace!("parse_return_statement: parse_expression failed");
+            return None;
+        }
+        trace!("parse_return_statement: expression {:?}", expression);
+        if self.peek_token_is(&Token::SEMICOLON) {
+            self.next_token();
+        }
+        Some(Box::new(ReturnStatement {
+            token,
+            expression: expression.unwrap(),
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_public_statement(&mut self) -> Option<Box<dyn Statement>> {
+        trace!("parse_public_statement",);
+        let start = self.cur_span;
+        if !self.expect_peek(Token::LET) {
+            return None;
+        }
+        self.parse_let_statement(true, start)
+    }
+
+    fn parse_let_statement(&mut self, public: bool, start: Span) -> Option<Box<dyn Statement>> {
+        trace!("parse_let_statement",);
+        let token = self.cur_token.clone();
+        if !public && self.peek_token_is(&Token::LPAREN) {
+            return self.parse_let_tuple_statement(start);
+        }
+    
+        let mutable = self.optional_peek(Token::MUT);
+
+        trace!("parse_let_statement: mutable {:?}", mutable);
+
+        let name = match self.expect_peek_ident() {
+            Some(name) => name,
+            None => {
+                trace!("parse_let_statement: expect_peek_ident for name failed");
+                self.peek_ident_error();
+                return None;
+            }
+        };
+
+        trace!("parse_let_statement: name {:?}", name);
+
+        if !self.expect_peek(Token::ASSIGN) {
+            trace!("parse_let_statement: expect_peek for assign failed");
+            return None;
+        }
+
+        trace!("parse_let_statement: assign");
+        self.next_token();
+
+        let expression = self.parse_expression(Precedence::LOWEST);
+        if expression.is_none() {
+            trace!("parse_let_statement: parse_expression failed");
+            return None;
+        }
+
+        if self.peek_token_is(&Token::SEMICOLON) {
+            self.next_token();
+        }
+
+        trace!("parse_let_statement: expression {:?}", expression);
+
+        Some(Box::new(LetStatement {
+            token,
+            name,
+            public,
+            mutable,
+            value: expression.unwrap(),
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_let_tuple_statement(&mut self, start: Span) -> Option<Box<dyn Statement>> {
+        trace!("parse_let_tuple_statement",);
+        let token = self.cur_token.clone();
+        self.next_token();
+        let names = self.parse_function_parameters()?;
+        if names.is_empty() {
+            self.error(start.to(self.cur_span), "expected names to bind".to_string());
+            return None;
+        }
+        if !self.expect_peek(Token::ASSIGN) {
+            return None;
+        }
+        self.next_token();
+        let value = self.parse_expression(Precedence::LOWEST)?;
+        if self.peek_token_is(&Token::SEMICOLON) {
+            self.next_token();
+        }
+        Some(Box::new(LetTupleStatement {
+            token,
+            names,
+            value,
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_import_statement(&mut self) -> Option<Box<dyn Statement>> {
+        trace!("parse_import_statement",);
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+
+        self.next_token();
+        let path = match self.cur_token.clone() {
+            Token::STRING(path) => path,
+            _ => {
+                self.error(self.cur_span, format!("expected module path string, got {:?} instead", self.cur_token));
+                return None;
+            }
+        };
+
+        if !self.expect_peek(Token::AS) {
+            return None;
+        }
+        let alias = self.expect_peek_ident();
+        if alias.is_none() {
+            self.peek_ident_error();
+            return None;
+        }
+
+        if self.peek_token_is(&Token::SEMICOLON) {
+            self.next_token();
+        }
+
+        Some(Box::new(ImportStatement {
+            token,
+            path,
+            alias: alias.unwrap(),
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_use_statement(&mut self) -> Option<Box<dyn Statement>> {
+        trace!("parse_use_statement",);
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+
+        let module = self.expect_peek_ident();
+        if module.is_none() {
+            self.peek_ident_error();
+            return None;
+        }
+        if !self.expect_peek(Token::DOUBLE_COLON) {
+            return None;
+        }
+
+        let mut names = Vec::new();
+        if self.optional_peek(Token::LBRACE) {
+            loop {
+                let name = self.expect_peek_ident();
+                if name.is_none() {
+                    self.peek_ident_error();
+                    return None;
+                }
+                names.push(name.unwrap());
+                if !self.optional_peek(Token::COMMA) {
+                    break;
+                }
+            }
+            if !self.expect_peek(Token::RBRACE) {
+                return None;
+            }
+        } else {
+            let name = self.expect_peek_ident();
+            if name.is_none() {
+                self.peek_ident_error();
+                return None;
+            }
+            names.push(name.unwrap());
+        }
+
+        if self.peek_token_is(&Token::SEMICOLON) {
+            self.next_token();
+        }
+
+        Some(Box::new(UseStatement {
+            token,
+            module: module.unwrap(),
+            names,
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_test_statement(&mut self) -> Option<Box<dyn Statement>> {
+        trace!("parse_test_statement",);
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+
+        self.next_token();
+        let name = match self.cur_token.clone() {
+            Token::STRING(name) => name,
+            _ => return None,
+        };
+        if !self.expect_peek(Token::LBRACE) {
+            return None;
+        }
+        let body = self.parse_block_statement()?;
+
+        Some(Box::new(TestStatement {
+            token,
+            name,
+            body,
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_expression_statement(&mut self) -> Option<Box<dyn Statement>> {
+        trace!("parse_expression_statement",);
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+        let expression = self.parse_expression(Precedence::LOWEST);
+        if expression.is_none() {
+            trace!("parse_expression_statement: parse_expression failed");
+            return None;
+        }
+        trace!("parse_expression_statement: expression {:?}", expression);
+        if self.peek_token_is(&Token::SEMICOLON) {
+            self.next_token();
+        }
+        Some(Box::new(ExpressionStatement {
+            token,
+            expression: expression.unwrap(),
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_expression(&mut self, precedence: Precedence) -> Option<Box<dyn Expression>> {
+        if self.depth == MAX_NESTING {
+            self.error(self.cur_span, format!("expression nested more than {} levels deep", MAX_NESTING));
+            return None;
+        }
+        self.depth += 1;
+        let expression = self.parse_nested_expression(precedence);
+        self.depth -= 1;
+        expression
+    }
+
+    fn parse_nested_expression(&mut self, precedence: Precedence) -> Option<Box<dyn Expression>> {
+        trace!("parse_expression: {:?} {:?} {:?}", precedence, self.cur_token, self.peek_token);
+        let start = self.cur_span;
+
+        let prefix_w = match self.cur_token {
+            Token::IDENTIFIER(_) => self.parse_identifier_expression(),
+            Token::INTEGER(_) => self.parse_integer_literal(),
+            Token::FLOAT(_) => self.parse_float_literal(),
+            Token::STRING(_) => self.parse_string_literal(),
+            Token::LPAREN => self.parse_grouped_expression(),
+            Token::IF => self.parse_if_expression(),
+            Token::BANG | Token::MINUS => self.parse_prefix_expression(),
+            Token::FUNCTION => self.parse_function_literial(),
+            Token::ASYNC => self.parse_async_function_literal(),
+            Token::AWAIT => self.parse_await_expression(),
+            Token::SPAWN => self.parse_spawn_expression(),
+            Token::SELECT => self.parse_select_expression(),
+            Token::TRUE | Token::FALSE => self.parse_boolean_literal(),
+            Token::LBRACKET => self.parse_array_literal(),
+            Token::LBRACE => self.parse_hash_literal(),
+            _ => None,
+        };
+        if prefix_w.is_none() {
+            trace!("parse_expression: prefix failed for {:?}", self.cur_token);
+            self.error(self.cur_span, format!("unhandled prefix parse for {:?}", self.cur_token));
+            return None;
+        }
+
+        let prefix = prefix_w.unwrap();
+
+        let mut left_exp = Some(prefix.clone());
+
+        while !self.peek_token_is(&Token::SEMICOLON) && precedence < self.peek_token.clone().into() {
+            let infix = match self.peek_token.clone() {
+                Token::PLUS | 
+                Token::MINUS | 
+                Token::DIVIDE | 
+                Token::MULTIPLY | 
+                Token::EQUAL | 
+                Token::NOT_EQUAL | 
+                Token::LESS_THAN | 
+                Token::LESS_THAN_EQUAL | 
+                Token::GREATER_THAN | 
+                Token::GREATER_THAN_EQUAL => {
+                    self.next_token();
+                    self.parse_infix_expression(start, left_exp.clone().unwrap())
+                }
+                Token::LPAREN => {
+                    self.parse_call_expression(start, left_exp.clone().unwrap())
+                },
+                Token::LBRACKET => {
+                    self.parse_index_expression(start, left_exp.clone().unwrap())
+                },
+                _ => None,
+            };
+
+            // An operand that failed to parse leaves nothing worth keeping.
+            if self.panicking {
+                return None;
+            }
+            if infix.is_none() {
+                return left_exp;
+            }
+
+            left_exp = Some(infix.unwrap());
+        }
+        trace!("parse_expression: end infix {:?}", self.peek_token);
+        trace!("parse_expression: end infix {:?}", left_exp);
+
+        left_exp
+    }
+
+
+    fn cur_token_is(&self, t: &Token) -> bool {
+        self.cur_token == *t
+    }
+
+    fn peek_token_is(&self, t: &Token) -> bool {
+        self.peek_token == *t
+    }
+
+    fn peek_error(&mut self, t: Token) {
+        self.error(self.peek_span, format!("expected next token to be {:?}, got {:?} instead", t, self.peek_token));
+    }
+
+    fn peek_ident_error(&mut self) {
+        self.error(self.peek_span, format!("expected next token to be an identifier, got {:?} instead", self.peek_token));
+    }
+
+    fn expect_peek(&mut self, t: Token) -> bool {
+        if self.peek_token_is(&t) {
+            self.next_token();
+            true
+        } else {
+            self.peek_error(t);
+            false
+        }
+    }
+
+    fn optional_peek(&mut self, t: Token) -> bool {
+        let r = self.peek_token_is(&t);
+        if r {
+            self.next_token();
+        }
+        r
+    }
+
+    fn expect_peek_ident(&mut self) -> Option<IdentifierLiteral> {
+        match self.peek_token {
+            Token::IDENTIFIER(_) => {
+                self.next_token();
+                Some(IdentifierLiteral {
+                    token: self.cur_token.clone(),
+                    span: self.cur_span,
+                })
+            },
+            _ => None,
+        }
+    }
+
+    fn parse_integer_literal(&mut self) -> Option<Box<dyn Expression>> { 
+        trace!("parse_integer_literal: {:?}", self.cur_token);
+        let value = match self.cur_token.clone() {
+            Token::INTEGER(val) => Some(val),
+            _ => return None,
+        };
+        if value.is_none() {
+            self.error(self.cur_span, format!("could not parse {:?} as integer", self.cur_token));
+            return None;
+        }
+
+        let parsed_val = value.unwrap().parse::<i64>();
+        if parsed_val.is_err() {
+            self.error(self.cur_span, format!("could not parse {:?} as integer", self.cur_token));
+            return None;
+        }
+        Some(Box::new(IntegerLiteral {
+            token: self.cur_token.clone(),
+            value: parsed_val.unwrap(),
+        }))
+    }
+
+    fn parse_float_literal(&mut self) -> Option<Box<dyn Expression>> {
+        trace!("parse_float_literal: {:?}", self.cur_token);
+        let value = match self.cur_token.clone() {
+            Token::FLOAT(val) => val,
+            _ => return None,
+        };
+        match value.parse::<f64>() {
+            Ok(value) => Some(Box::new(FloatLiteral {
+                token: self.cur_token.clone(),
+                value,
+            })),
+            Err(_) => {
+                self.error(self.cur_span, format!("could not parse {:?} as float", self.cur_token));
+                None
+            }
+        }
+    }
+
+    fn parse_string_literal(&mut self) -> Option<Box<dyn Expression>> {
+        trace!("parse_string_literal: {:?}", self.cur_token);
+        match self.cur_token.clone() {
+            Token::STRING(value) => Some(Box::new(StringLiteral {
+                token: self.cur_token.clone(),
+                value,
+            })),
+            _ => None,
+        }
+    }
+
+    fn parse_identifier_expression(&mut self) -> Option<Box<dyn Expression>> {
+        trace!("parse_identifier_expression: {:?}", self.cur_token);
+        let val = match self.cur_token.clone() {
+            Token::IDENTIFIER(val) => Some(val),
+            _ => return None,
+        };
+        if val.is_none() {
+            self.error(self.cur_span, format!("could not parse {:?} as identifier", self.cur_token));
+            return None;
+        }
+
+        let identifier = IdentifierLiteral {
+            token: self.cur_token.clone(),
+            span: self.cur_span,
+        };
+        if !self.peek_token_is(&Token::DOUBLE_COLON) {
+            return Some(Box::new(identifier));
+        }
+
+        let start = self.cur_span;
+        self.next_token();
+        let token = self.cur_token.clone();
+        let member = self.expect_peek_ident();
+        if member.is_none() {
+            self.peek_ident_error();
+            return None;
+        }
+        Some(Box::new(PathExpression {
+            token,
+            module: identifier,
+            member: member.unwrap(),
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_prefix_expression(&mut self) -> Option<Box<dyn Expression>> {
+        trace!("parse_prefix_expression: {:?}", self.cur_token);
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+
+        self.next_token();
+
+        let r_exp = self.parse_expression(Precedence::PREFIX);
+        if r_exp.is_none() {
+            self.error(self.cur_span, format!("expected expression after {:?}", token));
+            return None;
+        }
+
+        Some(Box::new(PrefixExpression {
+            token: token.clone(),
+            operator: token.into(),
+            right: r_exp.unwrap(),
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_spawn_expression(&mut self) -> Option<Box<dyn Expression>> {
+        trace!("parse_spawn_expression: {:?}", self.cur_token);
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+        self.next_token();
+        let expression = self.parse_expression(Precedence::PREFIX)?;
+        let Some(call) = expression.as_any().downcast_ref::<CallExpression>() else {
+            self.error(start.to(self.cur_span), "expected a function call after `spawn`".to_string());
+            return None;
+        };
+        Some(Box::new(SpawnExpression {
+            token,
+            call: call.clone(),
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_async_function_literal(&mut self) -> Option<Box<dyn Expression>> {
+        trace!("parse_async_function_literal: {:?}", self.cur_token);
+        let start = self.cur_span;
+        if !self.expect_peek(Token::FUNCTION) {
+            return None;
+        }
+        let function = self.parse_function_literial()?;
+        let function = function.as_any().downcast_ref::<FunctionLiteral>()?;
+        Some(Box::new(FunctionLiteral {
+            is_async: true,
+            span: start.to(function.span),
+            ..function.clone()
+        }))
+    }
+
+    fn parse_await_expression(&mut self) -> Option<Box<dyn Expression>> {
+        trace!("parse_await_expression: {:?}", self.cur_token);
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+        self.next_token();
+        let Some(value) = self.parse_expression(Precedence::PREFIX) else {
+            self.error(self.cur_span, "expected expression after `await`".to_string());
+            return None;
+        };
+        Some(Box::new(AwaitExpression {
+            token,
+            value,
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_select_expression(&mut self) -> Option<Box<dyn Expression>> {
+        trace!("parse_select_expression: {:?}", self.cur_token);
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+        if !self.expect_peek(Token::LBRACE) {
+            return None;
+        }
+        if self.peek_token_is(&Token::RBRACE) {
+            // Reported before the `}` so that recovery skips the whole braces.
+            self.error(start.to(self.peek_span), "expected at least one arm in `select`".to_string());
+            return None;
+        }
+        let mut arms = Vec::new();
+        while !self.peek_token_is(&Token::RBRACE) {
+            self.next_token();
+            arms.push(self.parse_select_arm()?);
+            if !self.peek_token_is(&Token::RBRACE) && !self.expect_peek(Token::COMMA) {
+                return None;
+            }
+        }
+        if !self.expect_peek(Token::RBRACE) {
+            return None;
+        }
+        Some(Box::new(SelectExpression {
+            token,
+            arms,
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    /// `receiver [as name] => body`, where the body is a block or a single expression.
+    fn parse_select_arm(&mut self) -> Option<SelectArm> {
+        trace!("parse_select_arm: {:?}", self.cur_token);
+        let receiver = self.parse_expression(Precedence::LOWEST)?;
+        let binding = if self.optional_peek(Token::AS) {
+            let Some(binding) = self.expect_peek_ident() else {
+                self.peek_ident_error();
+                return None;
+            };
+            Some(binding)
+        } else {
+            None
+        };
+        if !self.expect_peek(Token::FAT_ARROW) {
+            return None;
+        }
+        let body = if self.optional_peek(Token::LBRACE) {
+            self.parse_block_statement()?
+        } else {
+            let token = self.cur_token.clone();
+            self.next_token();
+            let start = self.cur_span;
+            let expression = self.parse_expression(Precedence::LOWEST)?;
+            let span = start.to(self.cur_span);
+            BlockStatement {
+                token: token.clone(),
+                statements: vec![Box::new(ExpressionStatement { token, expression, span })],
+                span,
+            }
+        };
+        Some(SelectArm { receiver, binding, body })
+    }
+
+    fn parse_infix_expression(&mut self, start: Span, left: Box<dyn Expression>) -> Option<Box<dyn Expression>> {
+        trace!("parse_infix_expression: operator {:?}", self.cur_token);
+        let token = self.cur_token.clone();
+        let precedence: Precedence = token.clone().into();
+        
+        let token_as_infix = token.clone().try_into();
+        if token_as_infix.is_err() {
+            self.error(self.cur_span, format!("expected infix operator, got {:?}", token));
+            return None;
+        }
+
+        self.next_token();
+        trace!("parse_infix_expression: right {:?}", self.cur_token);
+
+        let right = self.parse_expression(precedence);
+        if right.is_none() {
+            self.error(self.cur_span, format!("expected expression after {:?}", token));
+            return None;
+        }
+        Some(Box::new(InfixExpression {
+            token: token.clone(),
+            operator: token_as_infix.unwrap(),
+            left,
+            right: right.unwrap(),
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_grouped_expression(&mut self) -> Option<Box<dyn Expression>> {
+        self.next_token();
+
+        trace!("parse_grouped_expression: {:?}", self.cur_token);
+        let exp = self.parse_expression(Precedence::LOWEST);
+        if !self.expect_peek(Token::RPAREN) {
+            return None;
+        }
+        exp
+    }
+
+    fn parse_if_expression(&mut self) -> Option<Box<dyn Expression>> {
+        trace!("parse_if_expression: {:?}", self.cur_token);
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+        if !self.expect_peek(Token::LPAREN) {
+            return None;
+        }
+        self.next_token();
+        let condition = self.parse_expression(Precedence::LOWEST);
+        if condition.is_none() {
+            return None;
+        }
+        if !self.expect_peek(Token::RPAREN) {
+            return None;
+        }
+        if !self.expect_peek(Token::LBRACE) {
+            return None;
+        }
+        let consequence = self.parse_block_statement();
+        if consequence.is_none() {
+            return None;
+        }
+        let alternative = if self.peek_token_is(&Token::ELSE) {
+            self.next_token();
+            if !self.expect_peek(Token::LBRACE) {
+                return None;
+            }
+            self.parse_block_statement()
+        } else {
+            None
+        };
+        Some(Box::new(IfExpression {
+            token,
+            condition: condition.unwrap(),
+            consequence: consequence.unwrap(),
+            alternative,
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_block_statement(&mut self) -> Option<BlockStatement> {
+        trace!("parse_block_statement: {:?}", self.cur_token);
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+        let mut statements = Vec::new();
+        self.next_token();
+        while !self.cur_token_is(&Token::RBRACE) && !self.cur_token_is(&Token::EOF) {
+            statements.push(self.parse_statement_or_error());
+            if std::mem::take(&mut self.closed_block) {
+                break;
+            }
+            self.next_token();
+        }
+        Some(BlockStatement {
+            token,
+            statements,
+            span: start.to(self.cur_span),
+        })
+    }
+
+    fn parse_function_literial(&mut self) -> Option<Box<dyn Expression>> {
+        trace!("parse_function_literial: {:?}", self.cur_token);
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+        if !self.expect_peek(Token::LPAREN) {
+            return None;
+        }
+        let parameters = self.parse_function_parameters();
+        if parameters.is_none() {
+            return None;
+        }
+        if !self.expect_peek(Token::LBRACE) {
+            return None;
+        }
+        let body = self.parse_block_statement();
+        if body.is_none() {
+            return None;
+        }
+        Some(Box::new(FunctionLiteral {
+            token,
+            is_async: false,
+            parameters: parameters.unwrap(),
+            body: body.unwrap(),
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_function_parameters(&mut self) -> Option<Vec<IdentifierLiteral>> {
+        trace!("parse_function_parameters: {:?}", self.cur_token);
+        let mut identifiers = Vec::new();
+        if self.peek_token_is(&Token::RPAREN) {
+            self.next_token();
+            return Some(identifiers);
+        }
+        self.next_token();
+        let ident = match self.cur_token.clone() {
+            Token::IDENTIFIER(val) => Some(val),
+            _ => return None,
+        };
+        if ident.is_none() {
+            self.error(self.cur_span, format!("could not parse {:?} as identifier", self.cur_token));
+            return None;
+        }
+        identifiers.push(IdentifierLiteral {
+            token: self.cur_token.clone(),
+            span: self.cur_span,
+        });
+        while self.peek_token_is(&Token::COMMA) {
+            self.next_token();
+            self.next_token();
+            let ident = match self.cur_token.clone() {
+                Token::IDENTIFIER(val) => Some(val),
+                _ => return None,
+            };
+            if ident.is_none() {
+                self.error(self.cur_span, format!("could not parse {:?} as identifier", self.cur_token));
+                return None;
+            }
+            identifiers.push(IdentifierLiteral {
+                token: self.cur_token.clone(),
+                span: self.cur_span,
+            });
+        }
+        if !self.expect_peek(Token::RPAREN) {
+            return None;
+        }
+        Some(identifiers)
+    }
+
+    fn parse_call_expression(&mut self, start: Span, function: Box<dyn Expression>) -> Option<Box<dyn Expression>> {
+        trace!("parse_call_expression: {:?}", self.cur_token);
+        let token = self.cur_token.clone();
+        self.next_token();
+        let arguments = self.parse_expression_list(Token::RPAREN);
+        
+        trace!("parse_call_expression: completed arguments {:?}", arguments);
+        Some(Box::new(CallExpression {
+            token,
+            function,
+            arguments: arguments.unwrap_or_default(),
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_expression_list(&mut self, end: Token) -> Option<Vec<Box<dyn Expression>>> {
+        trace!("parse_expression_list: {:?}", self.cur_token);
+        let mut arguments = Vec::new();
+        if self.peek_token_is(&end) {
+            self.next_token();
+            return Some(arguments);
+        }
+        self.next_token();
+        let exp = self.parse_expression(Precedence::LOWEST);
+        if exp.is_none() {
+            return None;
+        }
+        arguments.push(exp.unwrap());
+        while self.peek_token_is(&Token::COMMA) {
+            self.next_token();
+            self.next_token();
+            let exp = self.parse_expression(Precedence::LOWEST);
+            if exp.is_none() {
+                return None;
+            }
+            arguments.push(exp.unwrap());
+        }
+        if !self.expect_peek(end) {
+            return None;
+        }
+        trace!("parse_expression_list: completed arguments {:?}", arguments);
+        Some(arguments)
+    }
+
+    fn parse_array_literal(&mut self) -> Option<Box<dyn Expression>> {
+        trace!("parse_array_literal: {:?}", self.cur_token);
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+        let elements = self.parse_expression_list(Token::RBRACKET);
+        if elements.is_none() {
+            return None;
+        }
+        Some(Box::new(ArrayLiteral {
+            token,
+            elements: elements.unwrap(),
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_hash_literal(&mut self) -> Option<Box<dyn Expression>> {
+        trace!("parse_hash_literal: {:?}", self.cur_token);
+        let token = self.cur_token.clone();
+        let start = self.cur_span;
+        let mut pairs = Vec::new();
+        while !self.peek_token_is(&Token::RBRACE) {
+            self.next_token();
+            let key = self.parse_expression(Precedence::LOWEST)?;
+            if !self.expect_peek(Token::COLON) {
+                return None;
+            }
+            self.next_token();
+            let value = self.parse_expression(Precedence::LOWEST)?;
+            pairs.push((key, value));
+            if !self.peek_token_is(&Token::RBRACE) && !self.expect_peek(Token::COMMA) {
+                return None;
+            }
+        }
+        if !self.expect_peek(Token::RBRACE) {
+            return None;
+        }
+        Some(Box::new(HashLiteral {
+            token,
+            pairs,
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_index_expression(&mut self, start: Span, left: Box<dyn Expression>) -> Option<Box<dyn Expression>> {
+        trace!("parse_index_expression: {:?}", self.cur_token);
+        self.next_token();
+        let token = self.cur_token.clone();
+        self.next_token();
+        let index = self.parse_expression(Precedence::LOWEST);
+        if index.is_none() {
+            return None;
+        }
+        if !self.expect_peek(Token::RBRACKET) {
+            return None;
+        }
+        Some(Box::new(IndexExpression {
+            token,
+            left,
+            index: index.unwrap(),
+            span: start.to(self.cur_span),
+        }))
+    }
+
+    fn parse_boolean_literal(&mut self) -> Option<Box<dyn Expression>> {
+        trace!("parse_boolean_literal: {:?}", self.cur_token);
+        let token = self.cur_token.clone();
+        let value = match self.cur_token.clone() {
+            Token::TRUE => Some(true),
+            Token::FALSE => Some(false),
+            _ => None,
+        };
+        if value.is_none() {
+            self.error(self.cur_span, format!("could not parse {:?} as boolean", self.cur_token));
+            return None;
+        }
+        Some(Box::new(BooleanLiteral {
+            token,
+            value: value.unwrap(),
+        }))
+        
+    }
+
+    fn check_parser_errors(&mut self) {
+        if self.errors.len() == 0 {
+            return;
+        }
+        error!("parser has {} errors", self.errors.len());
+        for error in &self.errors {
+            error!("{}", error);
+        }
+        panic!("parser has {} errors", self.errors.len());
+    }
+}
+
+#[cfg(test)]
+#[path = "./parser_tests.rs"]
 mod tests;
\ No newline at end of file
@@ -0,0 +1,110 @@
+use nom::IResult;
+use nom_supreme::error::{BaseErrorKind, ErrorTree, GenericErrorTree, StackContext};
+
+use crate::{
+    diagnostics::Diagnostic,
+    lexer::{Position, Span},
+};
+
+pub type ParseResult<'a, T> = IResult<&'a str, T, ErrorTree<&'a str>>;
+
+/// One way a parse failed, with the contexts it failed in from outermost to innermost.
+struct Leaf<'a> {
+    location: &'a str,
+    expected: Option<String>,
+    /// The message of an error raised by a parser itself, e.g. on nesting too deep.
+    message: Option<String>,
+    contexts: Vec<&'static str>,
+}
+
+/// Turns the error of a failed parse of `source` into a diagnostic at the farthest point any
+/// alternative reached, e.g. `expected ";" in let statement, found end of input`.
+pub fn to_diagnostic(source: &str, error: &ErrorTree<&str>) -> Diagnostic {
+    let mut all = Vec::new();
+    leaves(error, &[], &mut all);
+    let rest = all.iter().map(|leaf| leaf.location.len()).min().unwrap_or_default();
+    let farthest = all.into_iter().filter(|leaf| leaf.location.len() == rest).collect::<Vec<_>>();
+    if let Some(message) = farthest.iter().find_map(|leaf| leaf.message.clone()) {
+        return Diagnostic::new(message, span_at(source, source.len() - rest));
+    }
+
+    let common = farthest
+        .iter()
+        .map(|leaf| leaf.contexts.as_slice())
+        .reduce(|common, contexts| {
+            let shared = common.iter().zip(contexts).take_while(|(a, b)| a == b).count();
+            &common[..shared]
+        })
+        .unwrap_or_default();
+    let expected = farthest.iter().map(|leaf| leaf.expected.clone()).collect::<Option<Vec<_>>>();
+    let (what, within) = match expected {
+        Some(expected) if !expected.is_empty() => {
+            let mut unique = Vec::new();
+            for expectation in expected {
+                if !unique.contains(&expectation) {
+                    unique.push(expectation);
+                }
+            }
+            (unique.join(" or "), common.last())
+        },
+        _ => match common.split_last() {
+            Some((inner, outer)) => (inner.to_string(), outer.last()),
+            None => ("statement".to_string(), None),
+        },
+    };
+
+    let location = &source[source.len() - rest..];
+    let mut message = format!("expected {}", what);
+    if let Some(context) = within {
+        message.push_str(&format!(" in {}", context));
+    }
+    match location.chars().next() {
+        Some(ch) => message.push_str(&format!(", found `{}`", ch.escape_default())),
+        None => message.push_str(", found end of input"),
+    }
+    Diagnostic::new(message, span_at(source, source.len() - rest))
+}
+
+fn leaves<'a>(tree: &ErrorTree<&'a str>, contexts: &[&'static str], out: &mut Vec<Leaf<'a>>) {
+    match tree {
+        GenericErrorTree::Base { location, kind } => out.push(Leaf {
+            location,
+            expected: match kind {
+                BaseErrorKind::Expected(expectation) => Some(expectation.to_string()),
+                _ => None,
+            },
+            message: match kind {
+                BaseErrorKind::External(error) => Some(error.to_string()),
+                _ => None,
+            },
+            contexts: contexts.to_vec(),
+        }),
+        GenericErrorTree::Stack { base, contexts: stack } => {
+            // The stack lists the innermost context first.
+            let mut contexts = contexts.to_vec();
+            contexts.extend(stack.iter().rev().filter_map(|(_, context)| match context {
+                StackContext::Context(context) => Some(*context),
+                StackContext::Kind(_) => None,
+            }));
+            leaves(base, &contexts, out)
+        },
+        GenericErrorTree::Alt(alternatives) => {
+            for alternative in alternatives {
+                leaves(alternative, contexts, out);
+            }
+        },
+    }
+}
+
+/// Span of the character at byte `offset` of `source`, or an empty span at the end.
+pub fn span_at(source: &str, offset: usize) -> Span {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    let start = Position { line, column };
+    let end = match source[offset..].chars().next() {
+        Some(ch) if ch != '\n' => Position { line, column: column + 1 },
+        _ => start,
+    };
+    Span::new(start, end)
+}
@@ -1,23 +1,46 @@
-use super::{literals::*, statements::parse_block_statement};
+use std::cell::Cell;
+
+use super::{error::ParseResult, literals::*, statements::parse_block_statement};
 
 use nom::{
     branch::alt,
-    IResult,
+    character::complete::{multispace0, multispace1, satisfy},
     combinator::{
-        map, fail, opt, 
+        map, fail, not, opt, 
     },
-    bytes::complete::tag,
-    sequence::{delimited, tuple}, multi::many0,
+    sequence::{delimited, terminated, tuple}, multi::{many0, separated_list0}, Parser,
 };
+use nom_supreme::{error::{BaseErrorKind, ErrorTree}, tag::complete::tag, ParserExt};
 use nom_7_precedence::{precedence, binary_op, Assoc, unary_op, Operation};
 
-use crate::ast2::{traits::*, expressions::*};
+use crate::{ast2::{traits::*, expressions::*}, parser::MAX_NESTING};
+
+thread_local! {
+    /// Expressions the one being parsed on this thread is nested in.
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
 
-pub fn parse_expression(input: &str) -> IResult<&str, Box<dyn Expression>> {
+/// Parses an expression, failing the whole parse once expressions nest more than
+/// [`MAX_NESTING`] deep, as `parser` does.
+pub fn parse_expression(input: &str) -> ParseResult<'_, Box<dyn Expression>> {
+    let depth = DEPTH.with(Cell::get);
+    if depth == MAX_NESTING {
+        let message = format!("expression nested more than {} levels deep", MAX_NESTING);
+        return Err(nom::Err::Failure(ErrorTree::Base { location: input, kind: BaseErrorKind::External(message.into()) }));
+    }
+    DEPTH.with(|cell| cell.set(depth + 1));
+    let result = parse_nested_expression(input);
+    DEPTH.with(|cell| cell.set(depth));
+    result
+}
+
+fn parse_nested_expression(input: &str) -> ParseResult<'_, Box<dyn Expression>> {
     precedence(
 alt((
             unary_op(1, tag("-")),
             unary_op(1, tag("!")),
+            // `await` only when it is not the start of a longer identifier; `await(x)` is fine.
+            unary_op(1, terminated(tag("await"), not(satisfy(|c: char| c.is_alphanumeric() || c == '_')))),
         )),
         fail,
         alt((
@@ -31,12 +54,14 @@ alt((
             binary_op(4, Assoc::Left, tag(">=")),
             binary_op(4, Assoc::Left, tag("<")),
             binary_op(4, Assoc::Left, tag("<=")),
-            binary_op(5, Assoc::Left, tag("..")),
+            // `..=` before `..`, which would otherwise take its first two characters.
             binary_op(5, Assoc::Left, tag("..=")),
+            binary_op(5, Assoc::Left, tag("..")),
         )),
         alt((
             delimited(tag("("), parse_expression, tag(")")), 
             parse_if_expression,
+            parse_function_literal,
             map(parse_integer_literal, |i| Box::new(i) as Box<dyn Expression>),
             map(parse_float_literal, |i| Box::new(i) as Box<dyn Expression>),
             map(parse_boolean_literal, |b| Box::new(b) as Box<dyn Expression>),
@@ -44,6 +69,7 @@ alt((
        )),
         |op: Operation<&str, &str, &str, Box<dyn Expression>>| {
             match op {
+                Operation::Prefix("await", o) => Ok(Box::new(AwaitExpression { value: o }) as Box<dyn Expression>),
                 Operation::Prefix(op, o) => Ok(Box::new(PrefixExpression {
                     operator: op.to_string(),
                     right: o,
@@ -56,10 +82,12 @@ alt((
                 _ => Err("Invalid combination"),
             }
           }
-    )(input)
+    )
+    .context("expression")
+    .parse(input)
 }
 
-pub fn parse_if_expression(input: &str) -> IResult<&str, Box<dyn Expression>> {
+pub fn parse_if_expression(input: &str) -> ParseResult<'_, Box<dyn Expression>> {
     map(
         tuple((
             tag("if"),
@@ -87,5 +115,31 @@ pub fn parse_if_expression(input: &str) -> IResult<&str, Box<dyn Expression>> {
 
             exp as Box<dyn Expression>
         },
-    )(input)
+    )
+    .context("if expression")
+    .parse(input)
+}
+
+pub fn parse_function_literal(input: &str) -> ParseResult<'_, Box<dyn Expression>> {
+    map(
+        tuple((
+            multispace0,
+            opt(terminated(tag("async"), multispace1)),
+            tag("fn"),
+            multispace0,
+            tag("("),
+            separated_list0(tag(","), parse_identifier_literal),
+            tag(")"),
+            parse_block_statement,
+        )),
+        |(_, is_async, _, _, _, parameters, _, body)| {
+            Box::new(FunctionLiteral {
+                is_async: is_async.is_some(),
+                parameters,
+                body,
+            }) as Box<dyn Expression>
+        },
+    )
+    .context("function literal")
+    .parse(input)
 }
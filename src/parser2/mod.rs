@@ -1,5 +1,6 @@
-pub mod expressions;
-pub mod statements;
-pub mod literals;
-pub mod program;
+pub mod error;
+pub mod expressions;
+pub mod statements;
+pub mod literals;
+pub mod program;
 pub mod utils;
\ No newline at end of file
@@ -1,16 +1,61 @@
-use super::statements::*;
+use super::{error::*, statements::*};
 
 use nom::{
-    IResult,
-    multi::many1,
+    Err,
+    character::complete::multispace0,
 };
 
-use crate::ast2::program::Program;
+use crate::{ast2::program::Program, diagnostics::Diagnostic};
 
-pub fn parse_program(input: &str) -> IResult<&str, Program> {
-    many1(parse_statment)(input).map(|(input, statements)| {
-        (input, Program {
-            statements
-        })
-    })
-}
\ No newline at end of file
+/// Parses all of `input`. A statement that does not parse is reported and skipped up to the
+/// next `;` outside of braces, so every bad statement in the file is reported, not just the first.
+pub fn parse_program(input: &str) -> Result<Program, Vec<Diagnostic>> {
+    let mut statements = Vec::new();
+    let mut errors = Vec::new();
+    let mut rest = input;
+    loop {
+        rest = multispace0::<_, ()>(rest).map_or(rest, |(rest, _)| rest);
+        if rest.is_empty() {
+            break;
+        }
+        match parse_statment(rest) {
+            Ok((remaining, statement)) => {
+                statements.push(statement);
+                rest = remaining;
+            },
+            Err(Err::Error(error) | Err::Failure(error)) => {
+                errors.push(to_diagnostic(input, &error));
+                rest = skip_statement(rest);
+            },
+            Err(Err::Incomplete(_)) => {
+                errors.push(Diagnostic::new("unexpected end of input".to_string(), span_at(input, input.len())));
+                break;
+            },
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(Program { statements })
+    } else {
+        Err(errors)
+    }
+}
+
+/// Skips past the next `;` that is not nested in braces, or a stray `}`.
+fn skip_statement(input: &str) -> &str {
+    let mut depth = 0usize;
+    for (i, ch) in input.char_indices() {
+        match ch {
+            '{' => depth += 1,
+            '}' if depth == 0 => return &input[i + 1..],
+            '}' => depth -= 1,
+            ';' if depth == 0 => return &input[i + 1..],
+            _ => {},
+        }
+    }
+    ""
+}
+
+#[cfg(test)]
+#[path = "./program_tests.rs"]
+mod tests;
@@ -0,0 +1,59 @@
+use super::*;
+
+use test_case::test_case;
+
+use crate::{lexer::{Position, Span}, parser::MAX_NESTING};
+
+fn messages(input: &str) -> Vec<String> {
+    match parse_program(input) {
+        Ok(program) => panic!("expected errors, parsed {:?}", program),
+        Err(errors) => errors.into_iter().map(|error| error.message).collect(),
+    }
+}
+
+#[test_case("let x = 1i64;"; "let")]
+#[test_case("let mut x = 1i64; x + 2i64;"; "several statements")]
+#[test_case("  return a;\n\n"; "surrounding whitespace")]
+#[test_case(""; "empty")]
+#[test_case("let f = async fn(x, y) { return await x; };"; "async function")]
+#[test_case("let g = fn() { 1i64; }; await g;"; "function and await")]
+fn test_parses(input: &str) {
+    assert!(parse_program(input).is_ok());
+}
+
+#[test_case("let x = 1i64", "expected \";\" in let statement, found end of input"; "missing semicolon")]
+#[test_case("let = 1i64;", "expected identifier in let statement, found `=`"; "missing name")]
+#[test_case("let x = ;", "expected expression in let statement, found `;`"; "missing value")]
+#[test_case("return ;", "expected expression in return statement, found `;`"; "missing return value")]
+#[test_case("a b;", "expected \";\" in expression statement, found `b`"; "two operands")]
+#[test_case(")", "expected statement, found `)`"; "stray parenthesis")]
+fn test_error_messages(input: &str, expected: &str) {
+    assert_eq!(messages(input), vec![expected.to_string()]);
+}
+
+#[test]
+fn test_recovers_at_statement_boundaries() {
+    let errors = parse_program("let = 1i64;\nlet ok = 2i64;\nreturn ;\nif a { let = 1i64; } ;\nok;").unwrap_err();
+    let lines = errors.iter().map(|error| error.span.start.line).collect::<Vec<_>>();
+    assert_eq!(lines, vec![1, 3, 4]);
+}
+
+#[test]
+fn test_error_span() {
+    let errors = parse_program("let x = 1i64;\nlet y = ;").unwrap_err();
+    let position = Position { line: 2, column: 9 };
+    assert_eq!(errors[0].span, Span::new(position, Position { line: 2, column: 10 }));
+}
+
+#[test]
+fn test_nesting_is_limited() {
+    let nested = |parentheses: usize| format!("{}1i64{};", "(".repeat(parentheses), ")".repeat(parentheses));
+    assert!(parse_program(&nested(MAX_NESTING - 1)).is_ok());
+    let expected = format!("expression nested more than {} levels deep", MAX_NESTING);
+    assert_eq!(messages(&nested(MAX_NESTING)), vec![expected]);
+}
+
+#[test]
+fn test_unconsumed_input_is_reported() {
+    assert_eq!(messages("a;\n}"), vec!["expected statement, found `}`".to_string()]);
+}
@@ -1,79 +1,89 @@
-use nom::{
-    IResult,
-    branch::alt,
-    bytes::complete::tag,
-    character::complete::{multispace0, multispace1},
-    combinator::opt,
-    sequence::delimited,
-    sequence::tuple, multi::many0
-};
-
-use crate::ast2::{traits::*, statements::*};
-
-use super::{expressions::*, literals::*};
-
-pub fn parse_statment(input: &str) -> IResult<&str, Box<dyn Statement>> {
-    delimited(multispace0, alt((
-        parse_let_statement,
-        parse_return_statement,
-        parse_expression_statement
-    )), multispace0)(input)
-}
-
-pub fn parse_let_statement(input: &str) -> IResult<&str, Box<dyn Statement>> {
-    tuple((
-        multispace0,
-        tag("let"),
-        multispace1,
-        opt(tag("mut")),
-        multispace0,
-        parse_identifier_literal,
-        multispace0,
-        tag("="),
-        multispace0,
-        parse_expression,
-        multispace0,
-        tag(";"),
-        multispace0
-    ))(input).map(|(input, (_,_, _, mutable, _, name, _, _, _, value, _, _, _))| {   
-        (input, Box::new(LetStatement { mutable: mutable.is_some(), name, value }) as Box<dyn Statement>)
-    })
-}
-
-pub fn parse_return_statement(input: &str) -> IResult<&str, Box<dyn Statement>> {
-    tuple((
-        multispace0,
-        tag("return"),
-        multispace1,
-        parse_expression,
-        multispace0,
-        tag(";"),
-    ))(input).map(|(input, (_, _, _, value, _, _))| {   
-        (input, Box::new(ReturnStatement { value }) as Box<dyn Statement>)
-    })
-}
-
-pub fn parse_expression_statement(input: &str) -> IResult<&str, Box<dyn Statement>> {
-    tuple((
-        multispace0,
-        parse_expression,
-        multispace0,
-        tag(";"),
-    ))(input).map(|(input, (_, expression, _, _))| {   
-        (input, Box::new(ExpressionStatement { expression }) as Box<dyn Statement>)
-    })
-}
-
-pub fn parse_block_statement(input: &str) -> IResult<&str, BlockStatement> {
-    tuple((
-        multispace0,
-        tag("{"),
-        multispace0,
-        many0(parse_statment),
-        multispace0,
-        tag("}"),
-        multispace0
-    ))(input).map(|(input, (_, _, _, statements, _, _, _))| {   
-        (input, BlockStatement { statements })
-    })
-}
\ No newline at end of file
+use nom::{
+    branch::alt,
+    character::complete::{multispace0, multispace1},
+    combinator::{cut, opt},
+    sequence::{delimited, preceded},
+    sequence::tuple, multi::many0, Parser,
+};
+use nom_supreme::{tag::complete::tag, ParserExt};
+
+use crate::ast2::{traits::*, statements::*};
+
+use super::{error::ParseResult, expressions::*, literals::*};
+
+pub fn parse_statment(input: &str) -> ParseResult<'_, Box<dyn Statement>> {
+    delimited(multispace0, alt((
+        parse_let_statement,
+        parse_return_statement,
+        parse_expression_statement
+    )), multispace0)(input)
+}
+
+pub fn parse_let_statement(input: &str) -> ParseResult<'_, Box<dyn Statement>> {
+    // Once `let` is seen the statement can be nothing else, so later errors are not retried as expressions.
+    preceded(tuple((
+        multispace0,
+        tag("let"),
+        multispace1,
+    )), cut(tuple((
+        opt(tag("mut")),
+        multispace0,
+        parse_identifier_literal,
+        multispace0,
+        tag("="),
+        multispace0,
+        parse_expression,
+        multispace0,
+        tag(";"),
+        multispace0
+    ))))
+    .context("let statement")
+    .parse(input).map(|(input, (mutable, _, name, _, _, _, value, _, _, _))| {
+        (input, Box::new(LetStatement { mutable: mutable.is_some(), name, value }) as Box<dyn Statement>)
+    })
+}
+
+pub fn parse_return_statement(input: &str) -> ParseResult<'_, Box<dyn Statement>> {
+    preceded(tuple((
+        multispace0,
+        tag("return"),
+        multispace1,
+    )), cut(tuple((
+        parse_expression,
+        multispace0,
+        tag(";"),
+    ))))
+    .context("return statement")
+    .parse(input).map(|(input, (value, _, _))| {
+        (input, Box::new(ReturnStatement { value }) as Box<dyn Statement>)
+    })
+}
+
+pub fn parse_expression_statement(input: &str) -> ParseResult<'_, Box<dyn Statement>> {
+    tuple((
+        multispace0,
+        parse_expression,
+        multispace0,
+        tag(";"),
+    ))
+    .context("expression statement")
+    .parse(input).map(|(input, (_, expression, _, _))| {
+        (input, Box::new(ExpressionStatement { expression }) as Box<dyn Statement>)
+    })
+}
+
+pub fn parse_block_statement(input: &str) -> ParseResult<'_, BlockStatement> {
+    tuple((
+        multispace0,
+        tag("{"),
+        multispace0,
+        many0(parse_statment),
+        multispace0,
+        tag("}"),
+        multispace0
+    ))
+    .context("block")
+    .parse(input).map(|(input, (_, _, _, statements, _, _, _))| {
+        (input, BlockStatement { statements })
+    })
+}
@@ -0,0 +1,119 @@
+//! Detects data races on `let mut` bindings, when the scheduler was made to. Each task
+//! keeps a vector clock of how far it has seen every task get: spawning a task, joining
+//! it, and passing a value or closing a channel order what happened before in one task
+//! before what happens after in the other. Two accesses to a binding race when one of
+//! them writes and neither is ordered before the other.
+//!
+//! Rebinding a name in the scope holding it is a write, and reading it is a read. Filling
+//! a bounded channel does not order the tasks waiting for room, so code using one as a
+//! semaphore may be reported.
+
+use std::fmt::Display;
+
+use crate::{
+    evaluator::RuntimeError,
+    scheduler::{self, Site},
+};
+
+/// How far a task has seen each task get, by task id.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VectorClock(Vec<u64>);
+
+impl VectorClock {
+    pub fn get(&self, task: usize) -> u64 {
+        self.0.get(task).copied().unwrap_or(0)
+    }
+
+    pub fn set(&mut self, task: usize, time: u64) {
+        if self.0.len() <= task {
+            self.0.resize(task + 1, 0);
+        }
+        self.0[task] = time;
+    }
+
+    /// Moves `task` on, so that what it does next is not ordered before what others
+    /// saw so far.
+    pub fn tick(&mut self, task: usize) {
+        self.set(task, self.get(task) + 1);
+    }
+
+    /// Takes in what `other` has seen.
+    pub fn join(&mut self, other: &VectorClock) {
+        if self.0.len() < other.0.len() {
+            self.0.resize(other.0.len(), 0);
+        }
+        for (mine, theirs) in self.0.iter_mut().zip(&other.0) {
+            *mine = (*mine).max(*theirs);
+        }
+    }
+}
+
+/// The accesses to a `let mut` binding that later ones could race with: the last write,
+/// and the reads since then, the last one of each task.
+#[derive(Debug, Clone, Default)]
+pub struct Accesses {
+    write: Option<Access>,
+    reads: Vec<Access>,
+}
+
+#[derive(Debug, Clone)]
+struct Access {
+    task: usize,
+    /// The time of the task when it made the access.
+    time: u64,
+    site: Site,
+}
+
+impl Access {
+    /// Whether the access is ordered before what the task with `clock` does now.
+    fn happened_before(&self, clock: &VectorClock) -> bool {
+        self.time <= clock.get(self.task)
+    }
+}
+
+impl Display for Access {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at {} in <task {}>", self.site, self.task)
+    }
+}
+
+/// Records a read of the binding `name` at `site` by the current task, failing if it
+/// races with the last write.
+pub fn read(name: &str, accesses: &mut Accesses, site: Site) -> Result<(), RuntimeError> {
+    let race = scheduler::with_clock(|task, clock| {
+        let access = Access { task, time: clock.get(task), site };
+        if let Some(write) = accesses.write.as_ref().filter(|write| !write.happened_before(clock)) {
+            return Err(race(name, ("write", write), ("read", &access)));
+        }
+        accesses.reads.retain(|read| read.task != task);
+        accesses.reads.push(access);
+        Ok(())
+    });
+    race.unwrap_or(Ok(()))
+}
+
+/// Records a write of the binding `name` at `site` by the current task, failing if it
+/// races with the last write or a read since.
+pub fn write(name: &str, accesses: &mut Accesses, site: Site) -> Result<(), RuntimeError> {
+    let race = scheduler::with_clock(|task, clock| {
+        let access = Access { task, time: clock.get(task), site };
+        if let Some(write) = accesses.write.as_ref().filter(|write| !write.happened_before(clock)) {
+            return Err(race(name, ("write", write), ("write", &access)));
+        }
+        if let Some(read) = accesses.reads.iter().find(|read| !read.happened_before(clock)) {
+            return Err(race(name, ("read", read), ("write", &access)));
+        }
+        accesses.write = Some(access);
+        accesses.reads.clear();
+        Ok(())
+    });
+    race.unwrap_or(Ok(()))
+}
+
+fn race(name: &str, (earlier, first): (&str, &Access), (later, second): (&str, &Access)) -> RuntimeError {
+    RuntimeError::new(format!("data race on `{}`: {} {} conflicts with {} {}", name, later, second, earlier, first))
+}
+
+#[cfg(test)]
+#[path = "./race_tests.rs"]
+mod tests;
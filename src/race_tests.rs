@@ -0,0 +1,51 @@
+use super::*;
+
+use crate::{object::Object, scheduler::Mode, Engine, EngineError};
+
+use test_case::test_case;
+
+fn engine(race: bool) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_scheduling(Mode::Deterministic(3));
+    engine.set_race_detection(race);
+    engine
+}
+
+#[test]
+fn test_vector_clock() {
+    let mut clock = VectorClock::default();
+    clock.tick(2);
+    let mut other = VectorClock::default();
+    other.set(0, 3);
+    clock.join(&other);
+    assert_eq!((clock.get(0), clock.get(1), clock.get(2), clock.get(7)), (3, 0, 1, 0));
+}
+
+#[test]
+fn test_race_is_reported() {
+    // Tasks only start once the main task waits, so the write comes first, but nothing
+    // orders it before the read.
+    let source = "let mut x = 1;\nlet f = fn() { x };\nlet t = spawn f();\nlet x = 2;\njoin(t)";
+    match engine(true).eval(source) {
+        Err(EngineError::Runtime(err)) => assert_eq!(
+            err.message,
+            "data race on `x`: read at <eval#1>:2:16 in <task 1> conflicts with write at <eval#1>:4:5 in <task 0>"
+        ),
+        other => panic!("expected a data race, got {:?}", other),
+    }
+}
+
+#[test_case("let mut x = 1; let f = fn() { x }; let t = spawn f(); join(t); let x = 2; x"; "join")]
+#[test_case("let mut x = 1; let f = fn() { x + 1 }; join(spawn f())"; "spawn")]
+#[test_case("let mut x = 1; let (tx, rx) = channel(); let f = fn() { send(tx, x) }; spawn f(); recv(rx); let x = 2; x"; "send")]
+#[test_case("let mut x = 1; let (tx, rx) = channel(); let f = fn() { let y = x; close(tx) }; spawn f(); recv(rx); let x = 2; x"; "close")]
+#[test_case("let x = 1; let f = fn() { x }; let t = spawn f(); let x = 2; join(t)"; "immutable binding")]
+fn test_ordered_accesses(source: &str) {
+    assert!(engine(true).eval(source).is_ok());
+}
+
+#[test]
+fn test_off_by_default() {
+    let source = "let mut x = 1; let f = fn() { x }; let t = spawn f(); let x = 2; join(t)";
+    assert_eq!(engine(false).eval(source), Ok(Object::from(2)));
+}
@@ -0,0 +1,23 @@
+//! The pseudo-random numbers behind the program generator and the deterministic scheduler,
+//! so that a seed gives the same sequence in both.
+
+/// Xorshift: fast, and the same everywhere for the same seed.
+#[derive(Debug)]
+pub(crate) struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub(crate) fn new(seed: u64) -> Random {
+        // Zero is the one state xorshift never leaves.
+        Random { state: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1 }
+    }
+
+    /// A number from 0 up to, but not including, `bound`.
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state % bound as u64) as usize
+    }
+}
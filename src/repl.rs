@@ -0,0 +1,195 @@
+use std::{path::PathBuf, time::Instant};
+
+use keynes::{lexer::{Lexer, Token}, parser::Parser, parser2::program::parse_program, Engine, Object};
+use rustyline::{
+    completion::Completer,
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::{ValidationContext, ValidationResult, Validator},
+    Editor, Helper,
+};
+
+const PROMPT: &str = ">> ";
+
+const HELP: &str = "\
+:tokens <code>  show the tokens of <code>
+:ast <code>     show how <code> parses
+:ast2 <code>    show how <code> parses with the nom parser
+:type <expr>    evaluate <expr> and show the type of its value
+:env            list the bindings of the session
+:load <file>    evaluate <file> in the session
+:reset          forget all bindings
+:time <expr>    evaluate <expr> and show how long it took
+:help           show this message";
+
+/// Returns whether `input` ends inside a string or with unclosed brackets,
+/// meaning the user has more to type.
+pub fn is_incomplete(input: &str) -> bool {
+    let mut depth = 0i64;
+    for token in Lexer::new(input.to_string()) {
+        match token {
+            Token::LPAREN | Token::LBRACE | Token::LBRACKET => depth += 1,
+            Token::RPAREN | Token::RBRACE | Token::RBRACKET => depth -= 1,
+            Token::ILLEGAL(illegal) if illegal.starts_with('"') => return true,
+            _ => {},
+        }
+    }
+    depth > 0
+}
+
+/// Tells the line editor to keep reading lines while the input is incomplete.
+struct InputValidator;
+
+impl Validator for InputValidator {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if is_incomplete(ctx.input()) {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+impl Completer for InputValidator {
+    type Candidate = String;
+}
+
+impl Hinter for InputValidator {
+    type Hint = String;
+}
+
+impl Highlighter for InputValidator {}
+
+impl Helper for InputValidator {}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".keynes_history"))
+}
+
+/// Keeps the interpreter state of an interactive session.
+pub struct Repl {
+    engine: Engine,
+    /// Number of inputs evaluated so far, used to name them in error locations.
+    count: usize,
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Repl {
+            engine: Engine::new(),
+            count: 0,
+        }
+    }
+
+    /// Evaluates one input, which is either code or a meta-command starting
+    /// with `:`, returning the text to print on stdout or the rendered error.
+    pub fn eval(&mut self, input: &str) -> Result<Option<String>, String> {
+        match input.trim_start().strip_prefix(':') {
+            Some(command) => self.command(command),
+            None => self.eval_code(input).map(|value| match value {
+                Object::Null => None,
+                value => Some(value.inspect()),
+            }),
+        }
+    }
+
+    fn eval_code(&mut self, input: &str) -> Result<Object, String> {
+        self.count += 1;
+        let file = format!("<repl:{}>", self.count);
+        self.engine.eval_named(&file, input).map_err(|err| self.engine.render_error(&err))
+    }
+
+    fn command(&mut self, command: &str) -> Result<Option<String>, String> {
+        let (name, argument) = match command.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (command.trim(), ""),
+        };
+        let output = match (name, argument) {
+            ("tokens", code) => Lexer::new(code.to_string())
+                .map(|token| format!("{:?}", token))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            ("ast", code) => {
+                let mut lexer = Lexer::new(code.to_string());
+                let mut parser = Parser::new(&mut lexer);
+                let program = parser.parse_program();
+                if !parser.errors.is_empty() {
+                    return Err(parser.errors.iter().map(|error| error.render("<repl>", code)).collect());
+                }
+                program.to_string()
+            },
+            ("ast2", code) => match parse_program(code) {
+                Ok(program) => format!("{:#?}", program),
+                Err(errors) => return Err(errors.iter().map(|error| error.render("<repl>", code)).collect()),
+            },
+            ("type", code) => self.eval_code(code)?.describe_type(),
+            ("env", "") => self
+                .engine
+                .globals()
+                .iter()
+                .map(|(name, value)| format!("{}: {} = {}", name, value.describe_type(), value.inspect()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            ("load", file) if !file.is_empty() => {
+                self.engine.eval_file(file).map_err(|err| self.engine.render_error(&err))?;
+                return Ok(None);
+            },
+            ("reset", "") => {
+                *self = Repl::new();
+                return Ok(None);
+            },
+            ("time", code) => {
+                let start = Instant::now();
+                let value = self.eval_code(code)?;
+                format!("{}\ntime: {:?}", value.inspect(), start.elapsed())
+            },
+            ("help", "") => HELP.to_string(),
+            _ => return Err(format!("error: unknown command `:{}`, try `:help`\n", command.trim())),
+        };
+        Ok(if output.is_empty() { None } else { Some(output) })
+    }
+
+    pub fn run(&mut self) -> rustyline::Result<()> {
+        let mut editor: Editor<InputValidator, DefaultHistory> = Editor::new()?;
+        editor.set_helper(Some(InputValidator));
+        let history = history_path();
+        if let Some(history) = &history {
+            // A missing history file just means this is the first session.
+            let _ = editor.load_history(history);
+        }
+
+        println!("Keynes {} REPL", env!("CARGO_PKG_VERSION"));
+        println!("Type :help for a list of commands, press Ctrl-D to exit.");
+        loop {
+            match editor.readline(PROMPT) {
+                Ok(line) => {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    editor.add_history_entry(line.as_str())?;
+                    match self.eval(&line) {
+                        Ok(Some(output)) => println!("{}", output),
+                        Ok(None) => {},
+                        Err(err) => eprint!("{}", err),
+                    }
+                },
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        if let Some(history) = &history {
+            if let Err(err) = editor.save_history(history) {
+                eprintln!("warning: could not save history to {}: {}", history.display(), err);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "./repl_tests.rs"]
+mod tests;
@@ -0,0 +1,83 @@
+use super::*;
+
+use test_case::test_case;
+
+#[test_case("let x = 5;", false; "complete statement")]
+#[test_case("let f = fn(x) {", true; "unclosed brace")]
+#[test_case("let f = fn(x) {\n  x\n};", false; "closed over lines")]
+#[test_case("add(1,", true; "unclosed paren")]
+#[test_case("[1, [2,", true; "unclosed brackets")]
+#[test_case("\"abc", true; "unterminated string")]
+#[test_case("\"{\"", false; "brace inside string")]
+#[test_case("}", false; "extra closing brace is left to the parser")]
+fn test_is_incomplete(input: &str, expected: bool) {
+    assert_eq!(is_incomplete(input), expected);
+}
+
+#[test]
+fn test_state_is_kept_between_inputs() {
+    let mut repl = Repl::new();
+    assert_eq!(repl.eval("let x = 2;"), Ok(None));
+    assert_eq!(repl.eval("let f = fn(y) {\n  x * y\n};"), Ok(None));
+    assert_eq!(repl.eval("f(21)"), Ok(Some("42".to_string())));
+    assert_eq!(repl.eval("\"hi\""), Ok(Some("\"hi\"".to_string())));
+}
+
+#[test]
+fn test_errors_point_into_the_input_that_raised_them() {
+    let mut repl = Repl::new();
+    repl.eval("let f = fn() {\n  1 / 0\n};").unwrap();
+    let err = repl.eval("f()").unwrap_err();
+    assert!(err.contains(" --> <repl:1>:2:3\n"), "{}", err);
+    assert!(err.contains("2 |   1 / 0\n"), "{}", err);
+    assert!(err.contains("1: <main> at <repl:2>:1:1\n"), "{}", err);
+}
+
+#[test]
+fn test_pipeline_commands() {
+    let mut repl = Repl::new();
+    assert_eq!(repl.eval(":tokens let x"), Ok(Some("LET\nIDENTIFIER(\"x\")".to_string())));
+    assert_eq!(repl.eval(":ast 1 + 2 * 3"), Ok(Some("(1 + (2 * 3))".to_string())));
+    assert!(repl.eval(":ast let = 1;").unwrap_err().starts_with("error: "));
+    assert!(repl.eval(":ast2 1 + 2;").unwrap().unwrap().contains("Program"));
+}
+
+#[test_case(":type 1", "i64"; "integer")]
+#[test_case(":type i8(1)", "i8"; "narrow integer")]
+#[test_case(":type 1.0", "f64"; "float")]
+#[test_case(":type \"s\"", "STRING"; "string")]
+#[test_case(":type len", "BUILTIN"; "builtin")]
+fn test_type_command(input: &str, expected: &str) {
+    assert_eq!(Repl::new().eval(input), Ok(Some(expected.to_string())));
+}
+
+#[test]
+fn test_env_and_reset() {
+    let mut repl = Repl::new();
+    assert_eq!(repl.eval(":env"), Ok(None));
+    repl.eval("let b = \"x\"; let a = 1;").unwrap();
+    assert_eq!(repl.eval(":env"), Ok(Some("a: i64 = 1\nb: STRING = \"x\"".to_string())));
+    assert_eq!(repl.eval(":reset"), Ok(None));
+    assert_eq!(repl.eval(":env"), Ok(None));
+    assert!(repl.eval("a").is_err());
+}
+
+#[test]
+fn test_load_and_time() {
+    let path = std::env::temp_dir().join(format!("keynes-repl-{}.ks", std::process::id()));
+    std::fs::write(&path, "let loaded = 41;").unwrap();
+    let mut repl = Repl::new();
+    assert_eq!(repl.eval(&format!(":load {}", path.display())), Ok(None));
+    assert_eq!(repl.eval("loaded + 1"), Ok(Some("42".to_string())));
+    std::fs::remove_file(&path).unwrap();
+
+    let output = repl.eval(":time loaded * 2").unwrap().unwrap();
+    assert!(output.starts_with("82\ntime: "), "{}", output);
+}
+
+#[test_case(":nope"; "unknown command")]
+#[test_case(":load"; "load without file")]
+#[test_case(":env x"; "env with argument")]
+fn test_invalid_commands(input: &str) {
+    assert!(Repl::new().eval(input).unwrap_err().starts_with("error: unknown command"));
+}
@@ -0,0 +1,904 @@
+//! Runs the tasks `spawn` creates. Tasks are green threads: each evaluates on a stack of
+//! its own, and gives up its thread when it waits for another task, calls `yield()`, or
+//! has made enough function calls, so that many tasks share a few OS threads.
+//!
+//! In [`Mode::Parallel`] a pool of threads runs the tasks, which makes the order in which
+//! their effects happen vary from run to run. [`Mode::Deterministic`] runs every task on
+//! the thread waiting for a result instead, picking the next task to run and how long it
+//! runs from a seeded generator, so that a run can be repeated exactly with its seed. Its
+//! clock only moves once every task is blocked, to the time the next timer is due.
+//!
+//! Once every task is blocked and neither a timer nor a Rust future is left to wake one,
+//! the program is deadlocked, and the error says where each task blocked.
+//!
+//! A scheduler may also keep the vector clocks [`race`](crate::race) detection needs.
+
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt::{Debug, Display},
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock},
+    task::{Poll, Wake},
+    time::{Duration, Instant},
+};
+
+use corosensei::{stack::DefaultStack, Coroutine, CoroutineResult, Yielder};
+
+use crate::{
+    evaluator::{RuntimeError, MAX_CALL_DEPTH},
+    lexer::Span,
+    object::Object,
+    race::VectorClock,
+    random::Random,
+};
+
+use log::*;
+
+/// Stack a task sets aside for each call of a Keynes function it may nest, about twice what
+/// the frames of a call take in debug builds. Only the pages a task touches are ever allocated, so
+/// the stacks can be sized for the deepest recursion allowed, as a task overflowing its
+/// stack crashes the whole process.
+const STACK_PER_CALL: usize = 128 << 10;
+/// Stack a task sets aside besides its calls.
+const STACK_BASE: usize = 1 << 20;
+/// Function calls a task makes before giving up its thread in parallel mode.
+const SLICE: usize = 256;
+/// Most function calls a task makes before giving up its thread in deterministic mode.
+const DETERMINISTIC_SLICE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Runs tasks on this many threads, counting the one waiting for the program.
+    Parallel(usize),
+    /// Runs tasks one at a time, in an order decided by the seed.
+    Deterministic(u64),
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Mode::Parallel(std::thread::available_parallelism().map_or(1, |threads| threads.get()))
+    }
+}
+
+/// Owns the tasks of an [`Engine`](crate::Engine). Tasks still running when it is dropped
+/// are abandoned, like those still running when a program finishes.
+#[derive(Debug)]
+pub struct Scheduler {
+    shared: Arc<Shared>,
+}
+
+impl Scheduler {
+    pub fn new(mode: Mode) -> Scheduler {
+        Scheduler::with_race_detection(mode, false)
+    }
+
+    /// Creates a scheduler whose tasks report data races on `let mut` bindings if `race`.
+    pub fn with_race_detection(mode: Mode, race: bool) -> Scheduler {
+        Scheduler::with_max_call_depth(mode, race, MAX_CALL_DEPTH)
+    }
+
+    /// Creates a scheduler whose tasks report data races on `let mut` bindings if `race`,
+    /// and fail with an error once they nest `depth` calls of Keynes functions. The stacks
+    /// of its tasks are sized to fit that many calls.
+    pub fn with_max_call_depth(mode: Mode, race: bool, depth: usize) -> Scheduler {
+        let order = match mode {
+            Mode::Parallel(_) => None,
+            Mode::Deterministic(seed) => Some(Random::new(seed)),
+        };
+        let state = State {
+            tasks: HashMap::new(),
+            runnable: VecDeque::new(),
+            running: 0,
+            next_id: 0,
+            generation: 0,
+            timers: BTreeMap::new(),
+            next_timer: 0,
+            clock: Duration::ZERO,
+            outside: VectorClock::default(),
+            polling: 0,
+            order,
+            started: false,
+            shutdown: false,
+        };
+        Scheduler {
+            shared: Arc::new(Shared {
+                mode,
+                race,
+                max_call_depth: depth,
+                start: Instant::now(),
+                state: Mutex::new(state),
+                changed: Condvar::new(),
+            }),
+        }
+    }
+
+    /// The scheduler of tasks spawned outside of any [`Engine`](crate::Engine), such as by
+    /// an [`Evaluator`](crate::evaluator::Evaluator) used on its own.
+    pub fn global() -> &'static Scheduler {
+        static GLOBAL: OnceLock<Scheduler> = OnceLock::new();
+        GLOBAL.get_or_init(|| Scheduler::new(Mode::default()))
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.shared.mode
+    }
+
+    pub fn detects_races(&self) -> bool {
+        self.shared.race
+    }
+
+    pub fn max_call_depth(&self) -> usize {
+        self.shared.max_call_depth
+    }
+
+    /// Runs `main` as a task and waits for it, running other tasks meanwhile. Tasks it
+    /// spawns that are still running when it finishes are left to run on their own.
+    pub fn block_on(&self, main: impl FnOnce() -> Result<Object, RuntimeError> + Send + 'static) -> Result<Object, RuntimeError> {
+        if with_current(|current| current.is_some()) {
+            return main();
+        }
+        self.shared.add(main).join()
+    }
+
+    /// Spawns a task running `f`.
+    pub fn spawn(&self, f: impl FnOnce() -> Result<Object, RuntimeError> + Send + 'static) -> Task {
+        let task = self.shared.add(f);
+        self.shared.start_workers();
+        task
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        let (coroutines, timers) = {
+            let mut state = self.shared.state.lock().unwrap();
+            state.shutdown = true;
+            state.runnable.clear();
+            let coroutines = state.tasks.values_mut().filter_map(|task| task.coroutine.take()).collect::<Vec<_>>();
+            (coroutines, std::mem::take(&mut state.timers))
+        };
+        self.shared.changed.notify_all();
+        // Unwinds the stacks of the abandoned tasks, outside of the lock since that drops
+        // the values on them.
+        drop(coroutines);
+        drop(timers);
+    }
+}
+
+/// The scheduler of the current task, or the [global](Scheduler::global) one outside of tasks.
+fn current_shared() -> Arc<Shared> {
+    with_current(|current| current.as_ref().map(|current| current.shared.clone())).unwrap_or_else(|| Scheduler::global().shared.clone())
+}
+
+/// Spawns a task running `f` on the scheduler of the current task, or on the
+/// [global](Scheduler::global) one outside of tasks.
+pub fn spawn(f: impl FnOnce() -> Result<Object, RuntimeError> + Send + 'static) -> Task {
+    let shared = current_shared();
+    let task = shared.add(f);
+    shared.start_workers();
+    task
+}
+
+/// Blocks the current task, or this thread outside of tasks, until `ready` gives a value.
+/// Whenever it gives none, `ready` must have arranged for the waker it is passed to be
+/// woken once it might give one. `on` names what the task waits in, for deadlock reports.
+pub fn block_until<T>(on: &'static str, mut ready: impl FnMut(&Waker) -> Option<T>) -> Result<T, RuntimeError> {
+    match with_current(|current| current.as_ref().map(|current| (current.shared.clone(), current.task))) {
+        Some((shared, task)) => {
+            let waker = Waker { shared, task: Some(task) };
+            loop {
+                if let Some(value) = ready(&waker) {
+                    return Ok(value);
+                }
+                suspend(Suspend::Block(on));
+            }
+        },
+        None => {
+            let shared = Scheduler::global().shared.clone();
+            let waker = Waker { shared: shared.clone(), task: None };
+            shared.wait(|| ready(&waker))
+        },
+    }
+}
+
+/// Runs `future` to completion, blocking the current task, or this thread outside of
+/// tasks, whenever it is pending, until it wakes the waker it was polled with.
+pub fn block_on_future<F: std::future::Future>(future: F) -> Result<F::Output, RuntimeError> {
+    let shared = current_shared();
+    shared.state.lock().unwrap().polling += 1;
+    let mut future = std::pin::pin!(future);
+    let output = block_until("a native future", |waker| {
+        let waker = std::task::Waker::from(Arc::new(waker.clone()));
+        match future.as_mut().poll(&mut std::task::Context::from_waker(&waker)) {
+            Poll::Ready(output) => Some(output),
+            Poll::Pending => None,
+        }
+    });
+    shared.state.lock().unwrap().polling -= 1;
+    output
+}
+
+/// Runs `fire` once `delay` has passed on the clock of the current scheduler.
+pub fn after(delay: Duration, fire: impl FnOnce() + Send + 'static) {
+    let shared = current_shared();
+    let mut state = shared.state.lock().unwrap();
+    let deadline = shared.now(&state) + delay;
+    let id = state.next_timer;
+    state.next_timer += 1;
+    state.timers.insert((deadline, id), Timer(Box::new(fire)));
+    shared.changed.notify_all();
+}
+
+/// Runs `f` with the current task at `site`, which a deadlock report gives for the task if
+/// it blocks meanwhile.
+pub fn at<T>(site: Site, f: impl FnOnce() -> T) -> T {
+    let outer = with_current(|current| current.as_mut().map(|current| current.context.site.replace(site)));
+    let result = f();
+    if let Some(outer) = outer {
+        with_current(|current| current.as_mut().unwrap().context.site = outer);
+    }
+    result
+}
+
+/// Whether the current task reports data races.
+pub fn detecting_races() -> bool {
+    with_current(|current| current.as_ref().is_some_and(|current| current.shared.race))
+}
+
+/// Most calls of Keynes functions the current task may nest, or those of the
+/// [global](Scheduler::global) scheduler outside of tasks.
+pub fn max_call_depth() -> usize {
+    with_current(|current| current.as_ref().map(|current| current.shared.max_call_depth))
+        .unwrap_or_else(|| Scheduler::global().max_call_depth())
+}
+
+/// Gives `f` the current task and its vector clock, if it reports data races.
+pub fn with_clock<T>(f: impl FnOnce(usize, &VectorClock) -> T) -> Option<T> {
+    with_current(|current| {
+        current
+            .as_ref()
+            .filter(|current| current.shared.race)
+            .map(|current| f(current.task, &current.context.clock))
+    })
+}
+
+/// Gives the vector clock of the current task, for whoever [acquires](acquire) it to be
+/// ordered after what the task did so far, and moves the task on. Gives an empty clock if
+/// the task does not report data races, or outside of tasks.
+pub fn release() -> VectorClock {
+    with_current(|current| match current {
+        Some(current) if current.shared.race => release_clock(&mut current.context.clock, current.task),
+        _ => VectorClock::default(),
+    })
+}
+
+/// Orders what the current task does from now on after what was done before `clock` was
+/// [released](release).
+pub fn acquire(clock: &VectorClock) {
+    with_current(|current| {
+        if let Some(current) = current.as_mut().filter(|current| current.shared.race) {
+            current.context.clock.join(clock);
+        }
+    });
+}
+
+fn release_clock(clock: &mut VectorClock, task: usize) -> VectorClock {
+    let snapshot = clock.clone();
+    clock.tick(task);
+    snapshot
+}
+
+/// Lets other tasks run before the current one goes on. Does nothing outside of tasks.
+pub fn yield_now() {
+    if with_current(|current| current.is_some()) {
+        suspend(Suspend::Yield);
+    }
+}
+
+/// Counts a function call against the slice of the current task, yielding once it is
+/// used up, so that no task keeps its thread from the others for long.
+pub fn preempt() {
+    let used_up = with_current(|current| match current {
+        Some(current) => {
+            current.budget = current.budget.saturating_sub(1);
+            current.budget == 0
+        },
+        None => false,
+    });
+    if used_up {
+        suspend(Suspend::Yield);
+    }
+}
+
+/// A task `spawn` created, which `join` waits for.
+#[derive(Clone)]
+pub struct Task {
+    id: usize,
+    shared: Arc<Shared>,
+}
+
+impl Task {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Waits for the task to finish, giving its result, or raising its error again.
+    pub fn join(&self) -> Result<Object, RuntimeError> {
+        self.wait("join")
+    }
+
+    /// Like [`join`](Task::join), with `on` naming what the waiting task waits in, for
+    /// deadlock reports.
+    fn wait(&self, on: &'static str) -> Result<Object, RuntimeError> {
+        let current = with_current(|current| {
+            current
+                .as_ref()
+                .filter(|current| Arc::ptr_eq(&current.shared, &self.shared))
+                .map(|current| current.task)
+        });
+        let Some(current) = current else {
+            let outcome = self.shared.wait(|| {
+                let mut state = self.shared.state.lock().unwrap();
+                let state = &mut *state;
+                let task = state.tasks.get_mut(&self.id).unwrap();
+                let outcome = task.outcome()?;
+                state.outside.join(&task.context.clock);
+                Some(outcome)
+            });
+            return outcome?.result();
+        };
+        if current == self.id {
+            return Err(RuntimeError::new(format!("deadlock: {} joins itself", self)));
+        }
+        loop {
+            let outcome = {
+                let mut state = self.shared.state.lock().unwrap();
+                let task = state.tasks.get_mut(&self.id).unwrap();
+                let outcome = task.outcome();
+                match outcome {
+                    Some(_) => acquire(&task.context.clock),
+                    None => task.joiners.push(current),
+                }
+                outcome
+            };
+            match outcome {
+                Some(outcome) => return outcome.result(),
+                None => suspend(Suspend::Block(on)),
+            }
+        }
+    }
+}
+
+impl PartialEq for Task {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && Arc::ptr_eq(&self.shared, &other.shared)
+    }
+}
+
+impl Debug for Task {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Task").field("id", &self.id).finish()
+    }
+}
+
+impl Display for Task {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<task {}>", self.id)
+    }
+}
+
+/// The result of a call to an `async` function, or to an async function of the host, which
+/// `await` waits for. A task computes it, so it resolves whether or not it is awaited.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Future {
+    task: Task,
+}
+
+impl Future {
+    /// Runs `f` in a task of the current scheduler, resolving to its result.
+    pub fn spawn(f: impl FnOnce() -> Result<Object, RuntimeError> + Send + 'static) -> Future {
+        Future { task: spawn(f) }
+    }
+
+    /// Polls `future` in a task of the current scheduler, resolving to its output. The task
+    /// gives up its thread while `future` is pending, so `future` must not block.
+    pub fn from_rust(future: impl std::future::Future<Output = Result<Object, RuntimeError>> + Send + 'static) -> Future {
+        Future::spawn(move || block_on_future(future)?)
+    }
+
+    /// Waits for the future to resolve, giving its value, or raising its error again.
+    pub fn wait(&self) -> Result<Object, RuntimeError> {
+        self.task.wait("await")
+    }
+}
+
+impl Display for Future {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<future {}>", self.task.id)
+    }
+}
+
+/// Wakes a task blocked in [`block_until`], or a thread waiting outside of tasks.
+#[derive(Clone)]
+pub struct Waker {
+    shared: Arc<Shared>,
+    task: Option<usize>,
+}
+
+impl Waker {
+    pub fn wake(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+        if let Some(task) = self.task {
+            state.wake(task);
+        }
+        state.generation += 1;
+        self.shared.changed.notify_all();
+    }
+}
+
+impl Wake for Waker {
+    fn wake(self: Arc<Self>) {
+        Waker::wake(&self);
+    }
+}
+
+impl PartialEq for Waker {
+    fn eq(&self, other: &Self) -> bool {
+        self.task == other.task && Arc::ptr_eq(&self.shared, &other.shared)
+    }
+}
+
+impl Debug for Waker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Waker").field("task", &self.task).finish()
+    }
+}
+
+/// Where in a program a task is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Site {
+    pub file: Arc<str>,
+    pub span: Span,
+}
+
+impl Display for Site {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file, self.span.start)
+    }
+}
+
+/// Why a task gave up its thread.
+enum Suspend {
+    /// It can go on right away, after the others had their turn.
+    Yield,
+    /// It waits in what is named until another task wakes it.
+    Block(&'static str),
+}
+
+type TaskCoroutine = Coroutine<(), Suspend, Result<Object, RuntimeError>>;
+
+/// A suspended task, which may be resumed on another thread than the one it ran on.
+struct Parked(TaskCoroutine);
+
+// SAFETY: a task only suspends inside `suspend`, called from the evaluator, whose values
+// are all `Send`, and holds neither locks nor references to thread locals across it:
+// `suspend` reads the current task again after resuming.
+unsafe impl Send for Parked {}
+
+#[derive(Debug)]
+struct Shared {
+    mode: Mode,
+    /// Whether tasks report data races.
+    race: bool,
+    /// Most calls of Keynes functions a task may nest.
+    max_call_depth: usize,
+    /// Time zero of the clock in parallel mode.
+    start: Instant,
+    state: Mutex<State>,
+    /// Signalled when a task finishes or becomes runnable, and on shutdown.
+    changed: Condvar,
+}
+
+#[derive(Debug)]
+struct State {
+    tasks: HashMap<usize, TaskState>,
+    /// Tasks ready to run, in the order they became ready.
+    runnable: VecDeque<usize>,
+    /// Tasks being run by some thread.
+    running: usize,
+    next_id: usize,
+    /// Counts the changes that may let a thread waiting outside of tasks go on.
+    generation: u64,
+    /// What to run when, by due time and then order of creation.
+    timers: BTreeMap<(Duration, usize), Timer>,
+    next_timer: usize,
+    /// The clock in deterministic mode.
+    clock: Duration,
+    /// The vector clock of the code outside of tasks, which tasks spawned from there start
+    /// with, and which joining them there takes in.
+    outside: VectorClock,
+    /// Rust futures being run by [`block_on_future`], which may wake a task any time.
+    polling: usize,
+    /// Picks the next task and its slice in deterministic mode.
+    order: Option<Random>,
+    /// Whether the pool threads have been started.
+    started: bool,
+    shutdown: bool,
+}
+
+struct Timer(Box<dyn FnOnce() + Send>);
+
+impl Debug for Timer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Timer").finish_non_exhaustive()
+    }
+}
+
+struct TaskState {
+    status: Status,
+    /// The task while it is not running.
+    coroutine: Option<Parked>,
+    /// Whether it was woken while still running, so that it should not block.
+    woken: bool,
+    /// Tasks waiting for this one to finish.
+    joiners: Vec<usize>,
+    /// What the task carries over while it is not running.
+    context: Context,
+}
+
+enum Status {
+    Runnable,
+    Running,
+    Blocked(Blocked),
+    Finished(Result<Object, RuntimeError>),
+    Panicked(Option<Box<dyn Any + Send>>),
+}
+
+impl TaskState {
+    /// How the task ended, once it has.
+    fn outcome(&mut self) -> Option<Outcome> {
+        match &mut self.status {
+            Status::Finished(result) => Some(Outcome::Finished(result.clone())),
+            Status::Panicked(payload) => Some(match payload.take() {
+                Some(payload) => Outcome::Panicked(payload),
+                None => Outcome::Finished(Err(RuntimeError::new("task panicked".to_string()))),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// What a blocked task waits in, and where.
+#[derive(Debug)]
+struct Blocked {
+    on: &'static str,
+    site: Option<Site>,
+}
+
+impl Display for Blocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.site {
+            Some(site) => write!(f, "blocked in {} at {}", self.on, site),
+            None => write!(f, "blocked in {}", self.on),
+        }
+    }
+}
+
+enum Outcome {
+    Finished(Result<Object, RuntimeError>),
+    Panicked(Box<dyn Any + Send>),
+}
+
+impl Outcome {
+    /// The result of the task. A panic goes on in the first one to ask, once the state of
+    /// the scheduler is unlocked.
+    fn result(self) -> Result<Object, RuntimeError> {
+        match self {
+            Outcome::Finished(result) => result,
+            Outcome::Panicked(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+impl Debug for TaskState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self.status {
+            Status::Runnable => "runnable",
+            Status::Running => "running",
+            Status::Blocked(_) => "blocked",
+            Status::Finished(_) => "finished",
+            Status::Panicked(_) => "panicked",
+        };
+        f.debug_struct("TaskState").field("status", &status).field("joiners", &self.joiners).finish()
+    }
+}
+
+impl State {
+    /// Takes the next task to run off the queue, with the number of calls it may make.
+    fn pick(&mut self) -> Option<(usize, Parked, Context, usize)> {
+        if self.runnable.is_empty() {
+            return None;
+        }
+        let (index, budget) = match &mut self.order {
+            Some(random) => (random.below(self.runnable.len()), 1 + random.below(DETERMINISTIC_SLICE)),
+            None => (0, SLICE),
+        };
+        let id = self.runnable.remove(index).unwrap();
+        let task = self.tasks.get_mut(&id).unwrap();
+        task.status = Status::Running;
+        self.running += 1;
+        let coroutine = task.coroutine.take().expect("a runnable task is parked");
+        Some((id, coroutine, std::mem::take(&mut task.context), budget))
+    }
+
+    /// Takes the timers due at `now` off the list.
+    fn due(&mut self, now: Duration) -> Vec<Timer> {
+        let later = self.timers.split_off(&(now, usize::MAX));
+        std::mem::replace(&mut self.timers, later).into_values().collect()
+    }
+
+    fn wake(&mut self, id: usize) {
+        let task = self.tasks.get_mut(&id).unwrap();
+        match task.status {
+            Status::Blocked(_) => {
+                task.status = Status::Runnable;
+                self.runnable.push_back(id);
+            },
+            Status::Running => task.woken = true,
+            _ => {},
+        }
+    }
+
+    /// The error for a deadlock, saying where each blocked task waits.
+    fn deadlock(&self) -> RuntimeError {
+        let mut blocked = self
+            .tasks
+            .iter()
+            .filter_map(|(id, task)| match &task.status {
+                Status::Blocked(blocked) => Some((*id, blocked)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        blocked.sort_by_key(|(id, _)| *id);
+        let mut message = "deadlock: all tasks are blocked".to_string();
+        for (id, blocked) in blocked {
+            message.push_str(&format!("\n    <task {}> {}", id, blocked));
+        }
+        RuntimeError::new(message)
+    }
+}
+
+impl Shared {
+    fn add(self: &Arc<Self>, f: impl FnOnce() -> Result<Object, RuntimeError> + Send + 'static) -> Task {
+        let size = self.max_call_depth.saturating_mul(STACK_PER_CALL).saturating_add(STACK_BASE);
+        let stack = DefaultStack::new(size).expect("could not allocate the stack of a task");
+        let coroutine = Coroutine::with_stack(stack, move |yielder: &Yielder<(), Suspend>, ()| {
+            with_current(|current| current.as_mut().unwrap().yielder = yielder);
+            f()
+        });
+        // The new task starts out having seen what the one spawning it did so far.
+        let parent = self.race.then(|| {
+            with_current(|current| match current {
+                Some(current) if Arc::ptr_eq(&current.shared, self) => Some(release_clock(&mut current.context.clock, current.task)),
+                _ => None,
+            })
+        });
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        trace!("spawn task {}", id);
+        let clock = match parent {
+            Some(parent) => {
+                let mut clock = parent.unwrap_or_else(|| state.outside.clone());
+                clock.tick(id);
+                clock
+            },
+            None => VectorClock::default(),
+        };
+        state.tasks.insert(id, TaskState {
+            status: Status::Runnable,
+            coroutine: Some(Parked(coroutine)),
+            woken: false,
+            joiners: Vec::new(),
+            context: Context { clock, site: None },
+        });
+        state.runnable.push_back(id);
+        self.changed.notify_all();
+        Task { id, shared: self.clone() }
+    }
+
+    /// Starts the pool threads on the first spawn in parallel mode.
+    fn start_workers(self: &Arc<Self>) {
+        let Mode::Parallel(threads) = self.mode else {
+            return;
+        };
+        if std::mem::replace(&mut self.state.lock().unwrap().started, true) {
+            return;
+        }
+        for _ in 1..threads {
+            let shared = self.clone();
+            std::thread::Builder::new()
+                .name("keynes-worker".to_string())
+                .spawn(move || shared.work())
+                .expect("could not start a worker thread");
+        }
+    }
+
+    fn now(&self, state: &State) -> Duration {
+        match self.mode {
+            Mode::Parallel(_) => self.start.elapsed(),
+            Mode::Deterministic(_) => state.clock,
+        }
+    }
+
+    /// Runs a slice of a runnable task, or else the timers that are due, giving whether
+    /// there was any to run.
+    fn step<'a>(self: &'a Arc<Self>, mut state: MutexGuard<'a, State>) -> (MutexGuard<'a, State>, bool) {
+        if let Some(task) = state.pick() {
+            drop(state);
+            return (self.resume(task), true);
+        }
+        let now = self.now(&state);
+        let due = state.due(now);
+        if due.is_empty() {
+            return (state, false);
+        }
+        drop(state);
+        due.into_iter().for_each(|Timer(fire)| fire());
+        (self.state.lock().unwrap(), true)
+    }
+
+    /// Waits for a change, or until the next timer is due.
+    fn sleep<'a>(&self, state: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+        match state.timers.keys().next() {
+            Some((deadline, _)) => {
+                let timeout = deadline.saturating_sub(self.now(&state));
+                self.changed.wait_timeout(state, timeout).unwrap().0
+            },
+            None => self.changed.wait(state).unwrap(),
+        }
+    }
+
+    /// Runs tasks as they become runnable, until the scheduler shuts down.
+    fn work(self: &Arc<Self>) {
+        let mut state = self.state.lock().unwrap();
+        while !state.shutdown {
+            let (next, ran) = self.step(state);
+            state = if ran { next } else { self.sleep(next) };
+        }
+    }
+
+    /// Waits outside of any task of this scheduler until `ready` gives a value, running
+    /// tasks meanwhile. Fails once no task is left that could make `ready` give one.
+    fn wait<T>(self: &Arc<Self>, mut ready: impl FnMut() -> Option<T>) -> Result<T, RuntimeError> {
+        loop {
+            let seen = self.state.lock().unwrap().generation;
+            if let Some(value) = ready() {
+                return Ok(value);
+            }
+            let mut state = self.state.lock().unwrap();
+            while state.generation == seen {
+                if state.shutdown {
+                    return Err(RuntimeError::new("the scheduler has shut down".to_string()));
+                }
+                let (next, ran) = self.step(state);
+                state = next;
+                if ran || state.running > 0 {
+                    state = if ran { state } else { self.sleep(state) };
+                    continue;
+                }
+                let deterministic = matches!(self.mode, Mode::Deterministic(_));
+                match state.timers.keys().next() {
+                    // The clock only moves once no Rust future can wake a task first.
+                    _ if deterministic && state.polling > 0 => state = self.changed.wait(state).unwrap(),
+                    None if state.polling == 0 => return Err(state.deadlock()),
+                    Some(&(deadline, _)) if deterministic => state.clock = deadline,
+                    _ => state = self.sleep(state),
+                }
+            }
+        }
+    }
+
+    /// Runs a task for a slice of `budget` function calls or until it suspends.
+    fn resume(self: &Arc<Self>, (id, mut coroutine, context, budget): (usize, Parked, Context, usize)) -> MutexGuard<'_, State> {
+        let outer = with_current(|current| {
+            current.replace(Current {
+                shared: self.clone(),
+                task: id,
+                yielder: std::ptr::null(),
+                budget,
+                context,
+            })
+        });
+        let result = panic::catch_unwind(AssertUnwindSafe(|| coroutine.0.resume(())));
+        let context = with_current(|current| std::mem::replace(current, outer)).unwrap().context;
+
+        let mut state = self.state.lock().unwrap();
+        state.running -= 1;
+        let status = match result {
+            Ok(CoroutineResult::Yield(Suspend::Yield)) => Status::Runnable,
+            Ok(CoroutineResult::Yield(Suspend::Block(on))) => Status::Blocked(Blocked { on, site: context.site.clone() }),
+            Ok(CoroutineResult::Return(result)) => Status::Finished(result),
+            Err(payload) => Status::Panicked(Some(payload)),
+        };
+        let shutdown = state.shutdown;
+        let task = state.tasks.get_mut(&id).unwrap();
+        task.context = context;
+        let finished = match status {
+            Status::Runnable | Status::Blocked(_) if shutdown => Some(coroutine),
+            Status::Runnable | Status::Blocked(_) => {
+                task.coroutine = Some(coroutine);
+                if matches!(status, Status::Runnable) || std::mem::take(&mut task.woken) {
+                    task.status = Status::Runnable;
+                    state.runnable.push_back(id);
+                } else {
+                    task.status = status;
+                }
+                None
+            },
+            status => {
+                trace!("task {} finished", id);
+                task.status = status;
+                for joiner in std::mem::take(&mut task.joiners) {
+                    state.wake(joiner);
+                }
+                Some(coroutine)
+            },
+        };
+        state.generation += 1;
+        self.changed.notify_all();
+        if finished.is_some() {
+            // Frees the stack of the task outside of the lock.
+            drop(state);
+            drop(finished);
+            state = self.state.lock().unwrap();
+        }
+        state
+    }
+}
+
+/// The task running on this thread.
+struct Current {
+    shared: Arc<Shared>,
+    task: usize,
+    yielder: *const Yielder<(), Suspend>,
+    /// Function calls left in its slice.
+    budget: usize,
+    context: Context,
+}
+
+/// What a task carries from one slice to the next.
+#[derive(Debug, Default)]
+struct Context {
+    /// What the task has seen the others do, if it reports data races.
+    clock: VectorClock,
+    /// Where the task is, if in a call to a builtin.
+    site: Option<Site>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Current>> = const { RefCell::new(None) };
+}
+
+/// Gives `f` the task running on this thread. Never inlined, so that the address of the
+/// thread local is looked up anew on every call rather than kept across a suspension,
+/// after which the task may be running on another thread.
+#[inline(never)]
+fn with_current<T>(f: impl FnOnce(&mut Option<Current>) -> T) -> T {
+    CURRENT.with(|current| f(&mut current.borrow_mut()))
+}
+
+/// Gives up the thread of the current task until the scheduler resumes it.
+fn suspend(why: Suspend) {
+    let yielder = with_current(|current| current.as_ref().expect("suspended outside of a task").yielder);
+    // SAFETY: the yielder belongs to the coroutine of the current task, whose stack this
+    // runs on, so it lives at least as long as this call.
+    unsafe { (*yielder).suspend(why) };
+    with_current(|current| current.as_mut().unwrap().yielder = yielder);
+}
+
+#[cfg(test)]
+#[path = "./scheduler_tests.rs"]
+mod tests;
@@ -0,0 +1,204 @@
+use std::sync::{Arc, Mutex};
+
+use super::*;
+
+use crate::{test_support, Engine, EngineError};
+
+use test_case::test_case;
+
+const INTERLEAVED: &str = "
+let count = fn(name, n) { if (n > 0) { record(name + str(n)); count(name, n - 1) } };
+let a = spawn count(\"a\", 5);
+let b = spawn count(\"b\", 5);
+let c = spawn count(\"c\", 5);
+join(a);
+join(b);
+join(c);
+";
+
+fn engine(mode: Mode) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_scheduling(mode);
+    engine
+}
+
+fn runtime_error(result: Result<Object, EngineError>) -> String {
+    match result {
+        Err(EngineError::Runtime(err)) => err.message,
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+/// The order in which the tasks of `INTERLEAVED` ran, with `mode`.
+fn interleaving(mode: Mode) -> Vec<String> {
+    let mut engine = engine(mode);
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let record = log.clone();
+    engine.register_fn("record", move |args| {
+        record.lock().unwrap().push(args[0].to_string());
+        Ok(Object::Null)
+    });
+    engine.eval(INTERLEAVED).unwrap();
+    let log = log.lock().unwrap().clone();
+    log
+}
+
+#[test_case(Mode::Parallel(1); "one thread")]
+#[test_case(Mode::Parallel(4); "four threads")]
+#[test_case(Mode::Deterministic(7); "deterministic")]
+fn test_join_gives_result(mode: Mode) {
+    let mut engine = engine(mode);
+    let source = "let square = fn(x) { x * x }; let tasks = [spawn square(2), spawn square(3)]; join(tasks[0]) + join(tasks[1])";
+    assert_eq!(engine.eval(source), Ok(Object::from(13)));
+}
+
+#[test_case(Mode::Parallel(4); "parallel")]
+#[test_case(Mode::Deterministic(3); "deterministic")]
+fn test_many_tasks(mode: Mode) {
+    let mut engine = engine(mode);
+    let source = "
+let work = fn(n) { if (n == 0) { 0 } else { 1 + work(n - 1) } };
+let start = fn(n) { if (n == 0) { [] } else { push(start(n - 1), spawn work(n)) } };
+let total = fn(tasks, i) { if (i == len(tasks)) { 0 } else { join(tasks[i]) + total(tasks, i + 1) } };
+total(start(100), 0)
+";
+    assert_eq!(engine.eval(source), Ok(Object::from(5050)));
+}
+
+#[test]
+fn test_join_raises_the_error_of_the_task() {
+    let mut engine = engine(Mode::Deterministic(1));
+    let source = "let f = fn() { 1 + true }; let t = spawn f(); join(t)";
+    assert_eq!(runtime_error(engine.eval(source)), "type mismatch: INTEGER + BOOLEAN");
+}
+
+#[test]
+fn test_tasks_outlive_the_eval_spawning_them() {
+    let mut engine = engine(Mode::Deterministic(1));
+    engine.eval("let f = fn(x) { x + 1 }; let t = spawn f(1);").unwrap();
+    assert_eq!(engine.eval("join(t)"), Ok(Object::from(2)));
+    assert_eq!(engine.eval("join(t)"), Ok(Object::from(2)));
+}
+
+#[test]
+fn test_deadlock() {
+    // Tasks only start once the main task waits, so `b` is bound by the time `f` runs.
+    let mut engine = engine(Mode::Deterministic(5));
+    let source = "let f = fn() { join(b) };\nlet g = fn() { join(a) };\nlet a = spawn f();\nlet b = spawn g();\njoin(a)";
+    let expected = "deadlock: all tasks are blocked
+    <task 0> blocked in join at <eval#1>:5:1
+    <task 1> blocked in join at <eval#1>:1:16
+    <task 2> blocked in join at <eval#1>:2:16";
+    assert_eq!(runtime_error(engine.eval(source)), expected);
+}
+
+#[test]
+fn test_task_joining_itself() {
+    let mut engine = engine(Mode::Deterministic(1));
+    let source = "let f = fn() { join(t) }; let t = spawn f(); join(t)";
+    assert_eq!(runtime_error(engine.eval(source)), "deadlock: <task 1> joins itself");
+}
+
+#[test]
+fn test_deterministic_runs_repeat() {
+    for seed in 0..20 {
+        let first = interleaving(Mode::Deterministic(seed));
+        assert_eq!(first.len(), 15);
+        assert_eq!(interleaving(Mode::Deterministic(seed)), first, "seed {}", seed);
+    }
+}
+
+#[test]
+fn test_seeds_change_the_order() {
+    let orders = (0..20).map(|seed| interleaving(Mode::Deterministic(seed))).collect::<Vec<_>>();
+    assert!(orders.iter().any(|order| *order != orders[0]), "{:?}", orders[0]);
+}
+
+#[test]
+fn test_yield() {
+    let mut engine = engine(Mode::Deterministic(1));
+    assert_eq!(engine.eval("yield()"), Ok(Object::Null));
+    assert_eq!(engine.eval("let f = fn() { yield(); 1 }; join(spawn f())"), Ok(Object::from(1)));
+    assert_eq!(runtime_error(engine.eval("yield(1)")), "wrong number of arguments to yield: expected 0, got 1");
+}
+
+#[test]
+fn test_block_on() {
+    let scheduler = Scheduler::new(Mode::Parallel(2));
+    let inner = Arc::new(Mutex::new(None));
+    let task = inner.clone();
+    let result = scheduler.block_on(move || {
+        *task.lock().unwrap() = Some(spawn(|| Ok(Object::from(1))));
+        Ok(Object::from(2))
+    });
+    assert_eq!(result, Ok(Object::from(2)));
+    let task = inner.lock().unwrap().take().unwrap();
+    assert_eq!((task.id(), task.join()), (1, Ok(Object::from(1))));
+}
+
+#[test]
+fn test_spawn_outside_tasks_uses_the_global_scheduler() {
+    let task = spawn(|| Ok(Object::from(3)));
+    assert_eq!(task.join(), Ok(Object::from(3)));
+    assert_eq!(task.to_string(), format!("<task {}>", task.id()));
+}
+
+#[test_case(Mode::Parallel(4); "parallel")]
+#[test_case(Mode::Deterministic(2); "deterministic")]
+fn test_await(mode: Mode) {
+    let mut engine = engine(mode);
+    let source = "let square = async fn(x) { yield(); x * x }; let a = square(3); let b = square(4); [type(a), await a + await b, await a]";
+    engine.register_typed_fn("type", |value: Object| value.type_name());
+    assert_eq!(engine.eval(source).unwrap().to_string(), "[\"FUTURE\", 25, 9]");
+}
+
+#[test_case("let f = async fn() { 1 + true }; let future = f(); await future", "type mismatch: INTEGER + BOOLEAN"; "error of the future")]
+#[test_case("await 1", "cannot await INTEGER, expected FUTURE"; "non future")]
+#[test_case("let f = async fn(x) { x }; f()", "wrong number of arguments to f: expected 1, got 0"; "arguments checked on the call")]
+fn test_await_errors(source: &str, expected: &str) {
+    assert_eq!(runtime_error(engine(Mode::Deterministic(1)).eval(source)), expected);
+}
+
+#[test]
+fn test_deadlock_in_await() {
+    let mut engine = engine(Mode::Deterministic(1));
+    let source = "let (tx, rx) = channel();\nlet f = async fn() { recv(rx) };\nawait f()";
+    let expected = "deadlock: all tasks are blocked
+    <task 0> blocked in await at <eval#1>:3:1
+    <task 1> blocked in recv at <eval#1>:2:22";
+    assert_eq!(runtime_error(engine.eval(source)), expected);
+}
+
+#[test_case(Mode::Parallel(4); "parallel")]
+#[test_case(Mode::Deterministic(1); "seed 1")]
+#[test_case(Mode::Deterministic(2); "seed 2")]
+#[test_case(Mode::Deterministic(3); "seed 3")]
+#[test_case(Mode::Deterministic(4); "seed 4")]
+fn test_tasks_importing_the_same_module(mode: Mode) {
+    let dir = test_support::write_files(
+        "keynes-scheduler",
+        &[
+            ("lib.ks", "let count = fn(n) { if (n == 0) { 0 } else { 1 + count(n - 1) } };\npub let value = count(100);"),
+            ("main.ks", "let f = fn() { import \"lib.ks\" as lib; lib::value };\nlet a = spawn f();\nlet b = spawn f();\njoin(a) + join(b)"),
+        ],
+    );
+    // The module makes enough calls for the second task to run while the first evaluates it.
+    assert_eq!(engine(mode).eval_file(dir.join("main.ks")), Ok(Object::from(200)));
+}
+
+#[test_case(Mode::Parallel(2); "parallel")]
+#[test_case(Mode::Deterministic(1); "deterministic")]
+fn test_deep_recursion_in_a_task_is_an_error(mode: Mode) {
+    let source = "let spin = fn(n) { if (n == 0) { 0 } else { 1 + spin(n - 1) } };\njoin(spawn spin(100000))";
+    assert_eq!(runtime_error(engine(mode).eval(source)), "maximum call depth exceeded");
+}
+
+#[test]
+fn test_deadlock_renders_without_a_location() {
+    let mut engine = engine(Mode::Deterministic(1));
+    let err = engine.eval("let (tx, rx) = channel(1);\nsend(tx, 1);\nsend(tx, 2);").unwrap_err();
+    let expected = "error: deadlock: all tasks are blocked
+    <task 0> blocked in send at <eval#1>:3:1
+";
+    assert_eq!(engine.render_error(&err), expected);
+}
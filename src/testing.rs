@@ -0,0 +1,132 @@
+//! Discovers and runs the `test "name" { ... }` blocks of Keynes files for `keynes test`.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use crate::{engine::Engine, optimizer::OptLevel};
+
+/// The tests of one file. A file that fails to parse or evaluate runs no tests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileReport {
+    pub file: String,
+    /// Each test that ran, with its rendered error if it failed.
+    pub tests: Vec<(String, Result<(), String>)>,
+    pub error: Option<String>,
+}
+
+impl FileReport {
+    pub fn passed(&self) -> usize {
+        self.tests.iter().filter(|(_, result)| result.is_ok()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.tests.len() - self.passed()
+    }
+}
+
+/// The `.ks` files at `path`, which is a file or a directory searched recursively, in name order.
+pub fn discover(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    let mut entries = std::fs::read_dir(path)?.map(|entry| entry.map(|entry| entry.path())).collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            files.extend(discover(&entry)?);
+        } else if entry.extension().is_some_and(|extension| extension == "ks") {
+            files.push(entry);
+        }
+    }
+    Ok(files)
+}
+
+/// Runs the tests of `file` whose name contains `filter` in a fresh engine optimizing at `level`.
+pub fn run_file(file: &Path, filter: &str, level: OptLevel) -> FileReport {
+    let mut engine = Engine::new();
+    engine.set_opt_level(level);
+    let (tests, error) = match engine.eval_tests(file, |name| name.contains(filter)) {
+        Ok(outcomes) => {
+            let tests = outcomes
+                .into_iter()
+                .map(|outcome| (outcome.name, outcome.result.map_err(|err| engine.render_error(&err))))
+                .collect();
+            (tests, None)
+        },
+        Err(err) => (Vec::new(), Some(engine.render_error(&err))),
+    };
+    FileReport {
+        file: file.to_string_lossy().to_string(),
+        tests,
+        error,
+    }
+}
+
+/// Runs the tests of every file on up to `jobs` threads, reporting in the order of `files`.
+pub fn run(files: &[PathBuf], filter: &str, jobs: usize, level: OptLevel) -> Vec<FileReport> {
+    let next = AtomicUsize::new(0);
+    let reports = Mutex::new(vec![None; files.len()]);
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, files.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(file) = files.get(index) else {
+                    break;
+                };
+                let report = run_file(file, filter, level);
+                reports.lock().unwrap()[index] = Some(report);
+            });
+        }
+    });
+    reports.into_inner().unwrap().into_iter().flatten().collect()
+}
+
+/// Renders the reports like `cargo test` does, returning the text and whether everything passed.
+pub fn render(reports: &[FileReport]) -> (String, bool) {
+    let mut out = String::new();
+    let mut failures = Vec::new();
+    for report in reports {
+        if let Some(error) = &report.error {
+            out.push_str(&format!("test {} ... FAILED\n", report.file));
+            failures.push((report.file.clone(), error));
+        }
+        for (name, result) in &report.tests {
+            let label = format!("{}: {}", report.file, name);
+            match result {
+                Ok(()) => out.push_str(&format!("test {} ... ok\n", label)),
+                Err(error) => {
+                    out.push_str(&format!("test {} ... FAILED\n", label));
+                    failures.push((label, error));
+                },
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        out.push_str("\nfailures:\n");
+        for (label, error) in &failures {
+            out.push_str(&format!("\n---- {} ----\n{}", label, error));
+        }
+    }
+
+    let passed = reports.iter().map(FileReport::passed).sum::<usize>();
+    let failed = failures.len();
+    out.push_str(&format!(
+        "\ntest result: {}. {} passed; {} failed\n",
+        if failed == 0 { "ok" } else { "FAILED" },
+        passed,
+        failed
+    ));
+    (out, failed == 0)
+}
+
+#[cfg(test)]
+#[path = "./testing_tests.rs"]
+mod tests;
@@ -0,0 +1,96 @@
+use super::*;
+use crate::test_support;
+
+use test_case::test_case;
+
+fn write_files(files: &[(&str, &str)]) -> PathBuf {
+    test_support::write_files("keynes-testing", files)
+}
+
+fn names(report: &FileReport) -> Vec<(&str, bool)> {
+    report.tests.iter().map(|(name, result)| (name.as_str(), result.is_ok())).collect()
+}
+
+#[test]
+fn test_discover_finds_keynes_files_in_order() {
+    let dir = write_files(&[("b.ks", ""), ("a/c.ks", ""), ("a/notes.txt", ""), ("d.ks", "")]);
+    let files = discover(&dir).unwrap();
+    let relative = files.iter().map(|file| file.strip_prefix(&dir).unwrap().to_path_buf()).collect::<Vec<_>>();
+    assert_eq!(relative, vec![PathBuf::from("a/c.ks"), PathBuf::from("b.ks"), PathBuf::from("d.ks")]);
+    assert_eq!(discover(&dir.join("b.ks")).unwrap(), vec![dir.join("b.ks")]);
+}
+
+#[test]
+fn test_run_file_reports_each_test() {
+    let dir = write_files(&[(
+        "math.ks",
+        "let double = fn(x) { x * 2 };\n\
+         test \"doubles\" { assert_eq(double(2), 4); }\n\
+         test \"fails\" { assert_eq(double(2), 5); }\n\
+         test \"errors\" { double(true); }\n",
+    )]);
+    let report = run_file(&dir.join("math.ks"), "", OptLevel::O1);
+    assert_eq!(report.error, None);
+    assert_eq!(names(&report), vec![("doubles", true), ("fails", false), ("errors", false)]);
+    let failure = report.tests[1].1.clone().unwrap_err();
+    assert!(failure.contains("left: 4\n right: 5"), "{}", failure);
+    assert!(failure.contains("test \"fails\""), "{}", failure);
+    assert_eq!((report.passed(), report.failed()), (1, 2));
+}
+
+#[test]
+fn test_tests_do_not_leak_bindings() {
+    let dir = write_files(&[(
+        "scope.ks",
+        "test \"binds\" { let x = 1; }\ntest \"sees\" { assert(x == 1); }\n",
+    )]);
+    let report = run_file(&dir.join("scope.ks"), "", OptLevel::O1);
+    assert_eq!(names(&report), vec![("binds", true), ("sees", false)]);
+}
+
+#[test_case("", vec!["add one", "add two", "sub"]; "everything")]
+#[test_case("add", vec!["add one", "add two"]; "substring")]
+#[test_case("mul", vec![]; "nothing")]
+fn test_filter(filter: &str, expected: Vec<&str>) {
+    let dir = write_files(&[(
+        "ops.ks",
+        "test \"add one\" { } test \"add two\" { } test \"sub\" { }",
+    )]);
+    let report = run_file(&dir.join("ops.ks"), filter, OptLevel::O1);
+    assert_eq!(report.tests.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn test_file_that_fails_to_load() {
+    let dir = write_files(&[("broken.ks", "let = 1;\ntest \"never\" { }")]);
+    let reports = run(&[dir.join("broken.ks")], "", 1, OptLevel::O1);
+    assert_eq!(reports[0].tests, vec![]);
+    assert!(reports[0].error.is_some());
+    let (text, ok) = render(&reports);
+    assert!(!ok);
+    assert!(text.ends_with("test result: FAILED. 0 passed; 1 failed\n"), "{}", text);
+}
+
+#[test]
+fn test_parallel_run_keeps_file_order() {
+    let files = (0..12).map(|i| (format!("t{:02}.ks", i), format!("test \"n{}\" {{ assert({} / 3 * 3 != {}); }}", i, i, i))).collect::<Vec<_>>();
+    let dir = write_files(&files.iter().map(|(name, source)| (name.as_str(), source.as_str())).collect::<Vec<_>>());
+    let files = discover(&dir).unwrap();
+    let reports = run(&files, "", 4, OptLevel::O1);
+    assert_eq!(reports, run(&files, "", 1, OptLevel::O0));
+    assert_eq!(reports.iter().map(|report| report.tests[0].0.clone()).collect::<Vec<_>>(), (0..12).map(|i| format!("n{}", i)).collect::<Vec<_>>());
+
+    let (text, ok) = render(&reports);
+    assert!(!ok);
+    assert!(text.contains("failures:\n"), "{}", text);
+    assert!(text.ends_with("test result: FAILED. 8 passed; 4 failed\n"), "{}", text);
+}
+
+#[test]
+fn test_render_passing() {
+    let dir = write_files(&[("ok.ks", "test \"fine\" { assert(true); }")]);
+    let file = dir.join("ok.ks");
+    let (text, ok) = render(&run(std::slice::from_ref(&file), "", 1, OptLevel::O1));
+    assert!(ok);
+    assert_eq!(text, format!("test {}: fine ... ok\n\ntest result: ok. 1 passed; 0 failed\n", file.display()));
+}
@@ -0,0 +1,123 @@
+//! Golden tests for the programs in `tests/programs`.
+//!
+//! Every `name.ks` is run through each stage and the result compared with its
+//! siblings: `name.tokens` for the lexer, `name.ast` for the parser, and
+//! `name.out` and `name.err` for what `keynes run` writes to stdout and stderr.
+//! A missing expectation means the stage should produce nothing.
+//!
+//! `cargo test --test programs -- --bless` rewrites the expectations after an
+//! intended change. Any other argument only runs the programs whose name contains it.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, ExitCode},
+};
+
+use keynes::{
+    lexer::{Lexer, Token},
+    parser::Parser,
+};
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let bless = args.iter().any(|arg| arg == "--bless");
+    let filters = args.iter().filter(|arg| !arg.starts_with('-')).collect::<Vec<_>>();
+
+    let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("programs");
+    let mut programs = fs::read_dir(&directory)
+        .expect("tests/programs exists")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ks"))
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            filters.is_empty() || filters.iter().any(|filter| name.contains(filter.as_str()))
+        })
+        .collect::<Vec<_>>();
+    programs.sort();
+
+    let mut failed = 0;
+    for program in &programs {
+        let mismatches = check(program, bless);
+        let name = program.file_name().unwrap().to_string_lossy();
+        if mismatches.is_empty() {
+            println!("program {} ... ok", name);
+        } else {
+            failed += 1;
+            println!("program {} ... FAILED", name);
+            for mismatch in mismatches {
+                print!("{}", mismatch);
+            }
+        }
+    }
+
+    println!("\n{} programs, {} passed, {} failed{}", programs.len(), programs.len() - failed, failed, if bless { " (blessed)" } else { "" });
+    if failed > 0 {
+        println!("rerun with `cargo test --test programs -- --bless` if the changes are intended");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// Runs every stage on `program`, returning a description of each expectation it did not meet.
+/// When blessing, the expectations are rewritten instead and nothing fails.
+fn check(program: &Path, bless: bool) -> Vec<String> {
+    let source = fs::read_to_string(program).unwrap();
+    let (out, err) = run(program);
+    let stages = [("tokens", tokens(&source)), ("ast", ast(&source)), ("out", out), ("err", err)];
+
+    let mut mismatches = Vec::new();
+    for (extension, actual) in stages {
+        let path = program.with_extension(extension);
+        if bless {
+            if actual.is_empty() {
+                let _ = fs::remove_file(&path);
+            } else {
+                fs::write(&path, &actual).unwrap();
+            }
+            continue;
+        }
+        let expected = fs::read_to_string(&path).unwrap_or_default().replace("\r\n", "\n");
+        if expected != actual {
+            mismatches.push(format!("--- expected {}\n{}--- actual {}\n{}", extension, expected, extension, actual));
+        }
+    }
+    mismatches
+}
+
+fn tokens(source: &str) -> String {
+    let mut lexer = Lexer::new(source.to_string());
+    let mut tokens = String::new();
+    loop {
+        let token = lexer.next_token();
+        if token == Token::EOF {
+            return tokens;
+        }
+        tokens.push_str(&format!("{} {:?}\n", lexer.span(), token));
+    }
+}
+
+/// One statement per line, followed by any parse errors.
+fn ast(source: &str) -> String {
+    let mut lexer = Lexer::new(source.to_string());
+    let mut parser = Parser::new(&mut lexer);
+    let program = parser.parse_program();
+    let statements = program.statements.iter().map(|statement| format!("{}\n", statement));
+    let errors = parser.errors.iter().map(|error| format!("error: {}\n", error));
+    statements.chain(errors).collect()
+}
+
+/// What the interpreter prints for `program`, run from its directory as a user would.
+fn run(program: &Path) -> (String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_keynes"))
+        .arg("run")
+        .arg(program.file_name().unwrap())
+        .current_dir(program.parent().unwrap())
+        .env("RUST_LOG", "off")
+        .output()
+        .expect("keynes runs");
+    (
+        String::from_utf8_lossy(&output.stdout).replace("\r\n", "\n"),
+        String::from_utf8_lossy(&output.stderr).replace("\r\n", "\n"),
+    )
+}